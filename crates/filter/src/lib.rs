@@ -1,10 +1,26 @@
+//! Label filters, e.g. `color IN (red, blue) AND NOT (size < 10 OR archived
+//! EXISTS)`.
+//!
+//! The query-string syntax:
+//! - comparisons `key=value`, `!=`, `>`, `<`, `>=` and `<=`
+//! - `key IN (a, b)` and `key NOT IN (a, b)`
+//! - `key EXISTS`
+//! - `key STARTS_WITH "prefix"` and `key CONTAINS "text"`
+//! - grouping with `AND`, `OR`, `NOT` and parentheses, `AND` binding tighter
+//!   than `OR`
+//!
+//! Values are JSON literals; anything else is taken as a string, so strings
+//! with spaces or reserved characters have to be quoted. Keys with dots
+//! address nested objects, `a.b` is the key `b` of the object under `a`.
+
 use std::{
     cmp::Ordering,
     collections::HashMap,
     fmt::{self, Display},
+    str::FromStr,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{de::Deserializer, Deserialize, Serialize, Serializer};
 use serde_json::Value;
 
@@ -16,22 +32,49 @@ pub enum Operator {
     Lt,
     GtEq,
     LtEq,
+    In,
+    NotIn,
+    Exists,
+    StartsWith,
+    Contains,
 }
 
-impl Operator {
-    pub fn from_str(operator: &str) -> Result<Self> {
-        match operator {
+impl FromStr for Operator {
+    type Err = anyhow::Error;
+
+    fn from_str(operator: &str) -> Result<Self> {
+        match operator.to_ascii_uppercase().as_str() {
             "=" => Ok(Self::Eq),
             "!=" => Ok(Self::Neq),
             ">" => Ok(Self::Gt),
             "<" => Ok(Self::Lt),
             ">=" => Ok(Self::GtEq),
             "<=" => Ok(Self::LtEq),
-            _ => Err(anyhow::anyhow!("Invalid filter operator: {}", operator)),
+            "IN" => Ok(Self::In),
+            "NOT IN" => Ok(Self::NotIn),
+            "EXISTS" => Ok(Self::Exists),
+            "STARTS_WITH" => Ok(Self::StartsWith),
+            "CONTAINS" => Ok(Self::Contains),
+            _ => Err(anyhow!("Invalid filter operator: {}", operator)),
         }
     }
 }
 
+impl Operator {
+    /// Whether the operator compares the label with a single value.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Operator::Eq |
+                Operator::Neq |
+                Operator::Gt |
+                Operator::Lt |
+                Operator::GtEq |
+                Operator::LtEq
+        )
+    }
+}
+
 impl Display for Operator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -44,11 +87,18 @@ impl Display for Operator {
                 Operator::Lt => "<",
                 Operator::GtEq => ">=",
                 Operator::LtEq => "<=",
+                Operator::In => "IN",
+                Operator::NotIn => "NOT IN",
+                Operator::Exists => "EXISTS",
+                Operator::StartsWith => "STARTS_WITH",
+                Operator::Contains => "CONTAINS",
             }
         )
     }
 }
 
+/// A condition on a single label. The value is an array for `In` and `NotIn`
+/// and null for `Exists`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Expression {
     pub key: String,
@@ -71,12 +121,29 @@ impl<'de> Deserialize<'de> for Expression {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Expression::from_str(&s).map_err(serde::de::Error::custom)
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(str: &str) -> Result<Self> {
+        match str.parse::<Filter>()? {
+            Filter::Expression(expression) => Ok(expression),
+            _ => Err(anyhow!(
+                "Invalid filter: {}, expected a single condition",
+                str
+            )),
+        }
     }
 }
 
 impl Expression {
-    pub fn from_str(str: &str) -> Result<Self> {
+    /// The original parser, splitting the string at the first operator. Kept
+    /// for expressions with unquoted strings written before values had to
+    /// be quoted.
+    fn from_str_unquoted(str: &str) -> Result<Self> {
         // This parser must start with the longest operators first.
         let operators = vec!["!=", ">=", "<=", "=", ">", "<"];
         for operator in operators {
@@ -86,24 +153,88 @@ impl Expression {
             }
 
             let key = parts[0].to_string();
+            let reserved = |c: char| c.is_whitespace() || "()\"".contains(c);
+            if key.is_empty() || key.contains(reserved) {
+                break;
+            }
             let value = serde_json::from_str(parts[1]).unwrap_or(serde_json::json!(parts[1]));
-            let operator = Operator::from_str(operator)?;
+            let operator = operator.parse::<Operator>()?;
             return Ok(Self {
                 key,
                 value,
                 operator,
             });
         }
-        Err(anyhow::anyhow!("Invalid filter: {}", str))
+        Err(anyhow!("Invalid filter: {}", str))
+    }
+
+    pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
+        let Some(value) = lookup(values, &self.key) else {
+            return false;
+        };
+        let ordering = || partial_cmp(value, &self.value);
+        let contains = |candidates: &Value| {
+            candidates.as_array().is_some_and(|candidates| {
+                candidates
+                    .iter()
+                    .any(|c| partial_cmp(value, c) == Some(Ordering::Equal))
+            })
+        };
+        match self.operator {
+            Operator::Eq => ordering() == Some(Ordering::Equal),
+            Operator::Neq => ordering().is_some_and(|o| o != Ordering::Equal),
+            Operator::Gt => ordering() == Some(Ordering::Greater),
+            Operator::Lt => ordering() == Some(Ordering::Less),
+            Operator::GtEq => ordering().is_some_and(|o| o != Ordering::Less),
+            Operator::LtEq => ordering().is_some_and(|o| o != Ordering::Greater),
+            Operator::In => contains(&self.value),
+            Operator::NotIn => !contains(&self.value),
+            Operator::Exists => !value.is_null(),
+            Operator::StartsWith => match (value, &self.value) {
+                (Value::String(s), Value::String(prefix)) => s.starts_with(prefix.as_str()),
+                _ => false,
+            },
+            Operator::Contains => match (value, &self.value) {
+                (Value::String(s), Value::String(part)) => s.contains(part.as_str()),
+                (Value::Array(items), item) => items
+                    .iter()
+                    .any(|i| partial_cmp(i, item) == Some(Ordering::Equal)),
+                _ => false,
+            },
+        }
     }
 }
 
 impl Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}{}", self.key, self.operator, self.value)
+        match self.operator {
+            Operator::In | Operator::NotIn => {
+                let values: Vec<String> = self
+                    .value
+                    .as_array()
+                    .map(|values| values.iter().map(|v| v.to_string()).collect())
+                    .unwrap_or_default();
+                write!(f, "{} {} ({})", self.key, self.operator, values.join(", "))
+            }
+            Operator::Exists => write!(f, "{} {}", self.key, self.operator),
+            Operator::StartsWith | Operator::Contains => {
+                write!(f, "{} {} {}", self.key, self.operator, self.value)
+            }
+            _ => write!(f, "{}{}{}", self.key, self.operator, self.value),
+        }
     }
 }
 
+/// Looks up a label, falling back to walking nested objects for dotted keys.
+pub fn lookup<'a>(values: &'a HashMap<String, Value>, key: &str) -> Option<&'a Value> {
+    if let Some(value) = values.get(key) {
+        return Some(value);
+    }
+    let mut parts = key.split('.');
+    let first = values.get(parts.next()?)?;
+    parts.try_fold(first, |value, part| value.get(part))
+}
+
 fn partial_cmp(lhs: &Value, rhs: &Value) -> Option<Ordering> {
     match (lhs, rhs) {
         (Value::Number(n), Value::Number(m)) => n.as_f64()?.partial_cmp(&m.as_f64()?),
@@ -113,46 +244,338 @@ fn partial_cmp(lhs: &Value, rhs: &Value) -> Option<Ordering> {
     }
 }
 
+/// A label filter expression tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Expression(Expression),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl From<Expression> for Filter {
+    fn from(expression: Expression) -> Self {
+        Filter::Expression(expression)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(str: &str) -> Result<Self> {
+        match Parser::new(str).and_then(|mut parser| parser.parse()) {
+            Ok(filter) => Ok(filter),
+            Err(e) => Expression::from_str_unquoted(str)
+                .map(Filter::Expression)
+                .map_err(|_| e),
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
+        match self {
+            Filter::Expression(expression) => expression.matches(values),
+            Filter::And(filters) => filters.iter().all(|f| f.matches(values)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(values)),
+            Filter::Not(filter) => !filter.matches(values),
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, parent: &Filter) -> fmt::Result {
+        let grouped = matches!(
+            (parent, self),
+            (Filter::And(_), Filter::Or(_)) | (Filter::Not(_), Filter::And(_) | Filter::Or(_))
+        );
+        if grouped {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Expression(expression) => write!(f, "{}", expression),
+            Filter::And(filters) | Filter::Or(filters) => {
+                let separator = if matches!(self, Filter::And(_)) {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                for (i, filter) in filters.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{}", separator)?;
+                    }
+                    filter.fmt_operand(f, self)?;
+                }
+                Ok(())
+            }
+            Filter::Not(filter) => {
+                write!(f, "NOT ")?;
+                filter.fmt_operand(f, self)
+            }
+        }
+    }
+}
+
+impl Serialize for Filter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D>(deserializer: D) -> Result<Filter, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Comma,
+    Operator(String),
+    Word(String),
+    Quoted(String),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            position: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(anyhow!("expected {:?}, found {:?}", expected, token)),
+        }
+    }
+
+    fn parse(&mut self) -> Result<Filter> {
+        let filter = self.parse_or()?;
+        match self.peek() {
+            None => Ok(filter),
+            Some(token) => Err(anyhow!("unexpected {:?}", token)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter> {
+        let mut filters = vec![self.parse_and()?];
+        while self.peek_keyword("OR") {
+            self.next();
+            filters.push(self.parse_and()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::Or(filters)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Filter> {
+        let mut filters = vec![self.parse_unary()?];
+        while self.peek_keyword("AND") {
+            self.next();
+            filters.push(self.parse_unary()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::And(filters)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter> {
+        if self.peek_keyword("NOT") {
+            self.next();
+            return Ok(Filter::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.next();
+            let filter = self.parse_or()?;
+            self.expect(Token::RParen)?;
+            return Ok(filter);
+        }
+        self.parse_expression().map(Filter::Expression)
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression> {
+        let key = match self.next() {
+            Some(Token::Word(key)) => key,
+            Some(Token::Quoted(key)) => key,
+            token => return Err(anyhow!("expected a label key, found {:?}", token)),
+        };
+        let (operator, value) = match self.next() {
+            Some(Token::Operator(operator)) => (operator.parse::<Operator>()?, self.parse_value()?),
+            Some(Token::Word(word)) => match word.to_ascii_uppercase().as_str() {
+                "IN" => (Operator::In, self.parse_list()?),
+                "NOT" if self.peek_keyword("IN") => {
+                    self.next();
+                    (Operator::NotIn, self.parse_list()?)
+                }
+                "EXISTS" => (Operator::Exists, Value::Null),
+                "STARTS_WITH" => (Operator::StartsWith, self.parse_value()?),
+                "CONTAINS" => (Operator::Contains, self.parse_value()?),
+                _ => return Err(anyhow!("unknown filter operator {}", word)),
+            },
+            token => {
+                return Err(anyhow!(
+                    "expected an operator after {}, found {:?}",
+                    key,
+                    token
+                ))
+            }
+        };
+        Ok(Expression {
+            key,
+            value,
+            operator,
+        })
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Quoted(s)) => Ok(Value::String(s)),
+            Some(Token::Word(word)) => {
+                Ok(serde_json::from_str(&word).unwrap_or(Value::String(word)))
+            }
+            token => Err(anyhow!("expected a value, found {:?}", token)),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Value> {
+        self.expect(Token::LParen)?;
+        let mut values = vec![self.parse_value()?];
+        while self.peek() == Some(&Token::Comma) {
+            self.next();
+            values.push(self.parse_value()?);
+        }
+        self.expect(Token::RParen)?;
+        Ok(Value::Array(values))
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            ',' => tokens.push(Token::Comma),
+            '=' => tokens.push(Token::Operator("=".to_string())),
+            '!' | '<' | '>' => {
+                let mut operator = c.to_string();
+                if let Some((_, '=')) = chars.peek() {
+                    chars.next();
+                    operator.push('=');
+                }
+                if operator == "!" {
+                    return Err(anyhow!("unexpected ! at {}", start));
+                }
+                tokens.push(Token::Operator(operator));
+            }
+            '"' => {
+                let mut end = None;
+                let mut escaped = false;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => escaped = false,
+                    }
+                }
+                let end = end.ok_or(anyhow!("unterminated string at {}", start))?;
+                tokens.push(Token::Quoted(serde_json::from_str(&input[start..=end])?));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.peek() {
+                    if c.is_whitespace() || "(),=!<>\"".contains(*c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(input[start..end].to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Filters which all have to match.
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
-pub struct LabelsFilter(pub Vec<Expression>);
+pub struct LabelsFilter(pub Vec<Filter>);
 
 impl LabelsFilter {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
-    pub fn expressions(&self) -> &[Expression] {
+    pub fn filters(&self) -> &[Filter] {
         &self.0
     }
 
+    /// The filters combined into a single tree.
+    pub fn to_filter(&self) -> Filter {
+        match self.0.as_slice() {
+            [filter] => filter.clone(),
+            filters => Filter::And(filters.to_vec()),
+        }
+    }
+
     pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
-        self.0.iter().all(|expr| {
-            let value = values.get(&expr.key);
-            match value {
-                Some(value) => match partial_cmp(value, &expr.value) {
-                    Some(ordering) => match expr.operator {
-                        Operator::Eq => ordering == std::cmp::Ordering::Equal,
-                        Operator::Neq => ordering != std::cmp::Ordering::Equal,
-                        Operator::Gt => ordering == std::cmp::Ordering::Greater,
-                        Operator::Lt => ordering == std::cmp::Ordering::Less,
-                        Operator::GtEq => ordering != std::cmp::Ordering::Less,
-                        Operator::LtEq => ordering != std::cmp::Ordering::Greater,
-                    },
-                    None => false,
-                },
-                None => false,
-            }
-        })
+        self.0.iter().all(|filter| filter.matches(values))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use super::*;
 
     #[test]
     fn test_from_str() {
-        let filter = Expression::from_str("key=value").unwrap();
+        let filter = "key=value".parse::<Expression>().unwrap();
         assert_eq!(filter.operator, Operator::Eq);
         assert_eq!(filter.key, "key");
         assert_eq!(filter.value, serde_json::json!("value"));
@@ -160,7 +583,7 @@ mod tests {
         let filter_str = filter.to_string();
         assert_eq!(filter_str, "key=\"value\"");
 
-        let filter = Expression::from_str("key>1").unwrap();
+        let filter = "key>1".parse::<Expression>().unwrap();
         assert_eq!(filter.operator, Operator::Gt);
         assert_eq!(filter.key, "key");
         assert_eq!(filter.value, serde_json::json!(1));
@@ -168,13 +591,53 @@ mod tests {
         let filter_str = filter.to_string();
         assert_eq!(filter_str, "key>1");
 
-        let filter = Expression::from_str("key>=\"value\"").unwrap();
+        let filter = "key>=\"value\"".parse::<Expression>().unwrap();
         assert_eq!(filter.operator, Operator::GtEq);
         assert_eq!(filter.key, "key");
         assert_eq!(filter.value, serde_json::json!("value"));
 
         let filter_str = filter.to_string();
         assert_eq!(filter_str, "key>=\"value\"");
+
+        // Unquoted strings with spaces are still read as before
+        let filter = "key=two words".parse::<Expression>().unwrap();
+        assert_eq!(filter.value, serde_json::json!("two words"));
+    }
+
+    #[test]
+    fn test_parse_grouping() {
+        let filter = "color IN (red, \"dark blue\") AND NOT (size < 10 OR archived EXISTS) OR name STARTS_WITH ab"
+            .parse::<Filter>()
+            .unwrap();
+        let expression = |s: &str| Filter::Expression(s.parse::<Expression>().unwrap());
+        assert_eq!(
+            filter,
+            Filter::Or(vec![
+                Filter::And(vec![
+                    expression("color IN (red, \"dark blue\")"),
+                    Filter::Not(Box::new(Filter::Or(vec![
+                        expression("size<10"),
+                        expression("archived EXISTS"),
+                    ]))),
+                ]),
+                expression("name STARTS_WITH \"ab\""),
+            ])
+        );
+        assert_eq!(
+            filter.to_string(),
+            "color IN (\"red\", \"dark blue\") AND NOT (size<10 OR archived EXISTS) OR name STARTS_WITH \"ab\""
+        );
+        assert_eq!(filter.to_string().parse::<Filter>().unwrap(), filter);
+
+        let filter = "(a=1 OR b=2) AND c NOT IN (3, 4)"
+            .parse::<Filter>()
+            .unwrap();
+        assert_eq!(filter.to_string(), "(a=1 OR b=2) AND c NOT IN (3, 4)");
+
+        assert!("a EXISTS AND".parse::<Filter>().is_err());
+        assert!("(a=1".parse::<Filter>().is_err());
+        assert!("a=1 OR (b=2".parse::<Filter>().is_err());
+        assert!("a IN 1".parse::<Filter>().is_err());
     }
 
     #[test]
@@ -184,12 +647,14 @@ mod tests {
                 key: "key1".to_string(),
                 value: serde_json::json!(1),
                 operator: Operator::Eq,
-            },
+            }
+            .into(),
             Expression {
                 key: "key2".to_string(),
                 value: serde_json::json!(2),
                 operator: Operator::Gt,
-            },
+            }
+            .into(),
         ]);
 
         let mut values = HashMap::new();
@@ -202,4 +667,46 @@ mod tests {
         values.insert("key2".to_string(), serde_json::json!(3));
         assert!(filter.matches(&values));
     }
+
+    #[test]
+    fn test_matches_operators() {
+        let values: HashMap<String, Value> = serde_json::from_value(json!({
+            "color": "red",
+            "size": 12,
+            "name": "abcdef",
+            "tags": ["a", "b"],
+            "dims": {"width": 3, "unit": {"name": "cm"}},
+        }))
+        .unwrap();
+        let matches = |s: &str| s.parse::<Filter>().unwrap().matches(&values);
+
+        assert!(matches("color IN (red, blue)"));
+        assert!(!matches("color NOT IN (red, blue)"));
+        assert!(matches("size in (12, 13)"));
+        assert!(matches("color EXISTS"));
+        assert!(!matches("weight EXISTS"));
+        assert!(!matches("weight NOT IN (1)"));
+        assert!(matches("name STARTS_WITH abc"));
+        assert!(!matches("name STARTS_WITH bcd"));
+        assert!(matches("name CONTAINS cde"));
+        assert!(matches("tags CONTAINS b"));
+        assert!(matches("dims.width>=3"));
+        assert!(matches("dims.unit.name=cm"));
+        assert!(!matches("dims.depth EXISTS"));
+        assert!(matches("color=blue OR size>10"));
+        assert!(!matches("color=blue OR NOT size>10"));
+        assert!(matches("NOT (color=blue AND size>10)"));
+    }
+
+    #[test]
+    fn test_serde() {
+        let filter = LabelsFilter(vec!["a=1 OR b EXISTS".parse::<Filter>().unwrap()]);
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(json, "[\"a=1 OR b EXISTS\"]");
+        assert_eq!(serde_json::from_str::<LabelsFilter>(&json).unwrap(), filter);
+        // Filters stored before the grouping syntax still load
+        let old: LabelsFilter = serde_json::from_str("[\"key=value\", \"n>=2\"]").unwrap();
+        let old: Vec<String> = old.filters().iter().map(|f| f.to_string()).collect();
+        assert_eq!(old, vec!["key=\"value\"", "n>=2"]);
+    }
 }
@@ -166,6 +166,54 @@ pub struct Index {
     pub extractor_name: ExtractorName,
     pub graph_name: ExtractionGraphName,
     pub visibility: bool,
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub migration: Option<IndexMigration>,
+}
+
+/// A new version of an index that is being built next to the live one.
+/// Search keeps reading `Index::table_name`, and the extraction policy keeps
+/// writing into it, until every re-embedding task writing into the migration
+/// table has succeeded. Once started, new content is embedded into both.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Deserialize, Default)]
+pub struct IndexMigration {
+    pub version: u64,
+    pub table_name: String,
+    pub schema: String,
+    pub extractor_name: ExtractorName,
+    #[serde(default)]
+    pub started: bool,
+    /// Every content already extracted by the policy has a re-embedding task
+    pub tasks_created: bool,
+    /// Graph content index key the next page of re-embedding tasks is
+    /// created from
+    #[serde(default)]
+    pub tasks_cursor: Option<Vec<u8>>,
+    /// Re-embedding tasks that haven't finished yet
+    #[serde(default)]
+    pub pending_tasks: u64,
+    /// Re-embedding tasks that failed, the migration isn't promoted and has
+    /// to be retried
+    #[serde(default)]
+    pub failed_tasks: u64,
+}
+
+impl IndexMigration {
+    /// Every re-embedding task has finished
+    pub fn finished(&self) -> bool {
+        self.tasks_created && self.pending_tasks == 0
+    }
+
+    pub fn failed(&self) -> bool {
+        self.finished() && self.failed_tasks > 0
+    }
+
+    /// New content is also embedded into the migration table while the
+    /// migration can still be promoted
+    pub fn accepts_new_content(&self) -> bool {
+        self.started && self.failed_tasks == 0
+    }
 }
 
 impl Index {
@@ -194,6 +242,31 @@ impl Index {
             output_name.to_string()
         )
     }
+
+    pub fn build_versioned_table_name(&self, output_name: &String, version: u64) -> String {
+        format!("{}.v{}", self.build_table_name(output_name), version)
+    }
+
+    /// Name of the extractor output this index stores
+    pub fn output_name(&self) -> Option<&str> {
+        self.name
+            .strip_prefix(format!("{}.{}.", self.graph_name, self.extraction_policy_name).as_str())
+    }
+
+    /// Makes the migration table the live table of the index. Returns false
+    /// if there is no migration to promote.
+    pub fn promote_migration(&mut self) -> bool {
+        match self.migration.take() {
+            Some(migration) => {
+                self.version = migration.version;
+                self.table_name = migration.table_name;
+                self.schema = migration.schema;
+                self.extractor_name = migration.extractor_name;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl Hash for Index {
@@ -229,6 +302,8 @@ impl From<indexify_coordinator::Index> for Index {
             namespace: value.namespace,
             graph_name: value.graph_name,
             visibility: false,
+            version: 0,
+            migration: None,
         };
         index.id = index.id();
         index
@@ -239,12 +314,91 @@ impl From<indexify_coordinator::Index> for Index {
 pub struct EmbeddingSchema {
     pub dim: usize,
     pub distance: String,
+    /// Named vectors stored with each embedding, e.g. token embeddings for
+    /// late interaction
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vectors: HashMap<String, VectorSchema>,
+    /// Embeddings are sparse, e.g. SPLADE term weights, and `dim` is the size
+    /// of the vocabulary
+    #[serde(default)]
+    pub sparse: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VectorSchema {
+    pub dim: usize,
+    pub distance: String,
+    /// Each value is a list of vectors, scored against multi-vector queries
+    /// with MaxSim
+    #[serde(default)]
+    pub multivector: bool,
+}
+
+/// A named vector. Multi-vectors hold one vector per token or aspect of the
+/// content.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Vector {
+    Dense(Vec<f32>),
+    Multi(Vec<Vec<f32>>),
+}
+
+impl Vector {
+    /// Dimension of the vectors, `None` for an empty multi-vector
+    pub fn dim(&self) -> Option<usize> {
+        match self {
+            Vector::Dense(values) => Some(values.len()),
+            Vector::Multi(vectors) => vectors.first().map(|values| values.len()),
+        }
+    }
+
+    pub fn is_multi(&self) -> bool {
+        matches!(self, Vector::Multi(_))
+    }
+}
+
+/// A sparse embedding, holding only the non-zero values and their
+/// dimensions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, ToSchema)]
+#[schema(as = internal_api::SparseVector)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    /// Checks that every value has a distinct dimension below `dim`.
+    pub fn validate(&self, dim: usize) -> Result<()> {
+        if self.indices.len() != self.values.len() {
+            return Err(anyhow!(
+                "sparse vector has {} indices and {} values",
+                self.indices.len(),
+                self.values.len()
+            ));
+        }
+        let mut indices = HashSet::new();
+        for index in &self.indices {
+            if *index as usize >= dim {
+                return Err(anyhow!(
+                    "sparse vector index {} is out of range for dimension {}",
+                    index,
+                    dim
+                ));
+            }
+            if !indices.insert(index) {
+                return Err(anyhow!("sparse vector index {} is repeated", index));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Embedding {
     pub values: Vec<f32>,
     pub distance: String,
+    #[serde(default)]
+    pub vectors: HashMap<String, Vector>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -427,6 +581,9 @@ pub enum FeatureType {
     #[strum(serialize = "metadata")]
     #[serde(rename = "metadata")]
     Metadata,
+    #[strum(serialize = "sparse_embedding")]
+    #[serde(rename = "sparse_embedding")]
+    SparseEmbedding,
     #[strum(serialize = "unknown")]
     #[serde(rename = "unknown")]
     Unknown,
@@ -522,6 +679,9 @@ pub struct Task {
     pub index_tables: Vec<String>, // list of index tables that this content may be present in
     #[serde(default = "default_creation_time")]
     pub creation_time: SystemTime,
+    /// Version of the index migration the task re-embeds content for
+    #[serde(default)]
+    pub index_migration: Option<u64>,
 }
 
 impl Task {
@@ -1314,6 +1474,8 @@ pub enum ChangeType {
     AddGraphToContent { extraction_graph: String },
     ExtractionGraphDeleted { start_content_id: Vec<u8> },
     TombstoneContent { is_root: bool },
+    IndexMigrationStarted,
+    IndexMigrationTaskFinished,
 }
 
 impl fmt::Display for ChangeType {
@@ -1338,6 +1500,8 @@ impl fmt::Display for ChangeType {
                 start_content_id
             ),
             ChangeType::TombstoneContent { is_root } => write!(f, "TombstoneContent: {}", is_root),
+            ChangeType::IndexMigrationStarted => write!(f, "IndexMigrationStarted"),
+            ChangeType::IndexMigrationTaskFinished => write!(f, "IndexMigrationTaskFinished"),
         }
     }
 }
@@ -1451,9 +1615,17 @@ impl From<StateChange> for indexify_coordinator::StateChange {
 pub struct ExtractedEmbeddings {
     pub content_id: String,
     pub embedding: Vec<f32>,
+    #[serde(default)]
+    pub vectors: HashMap<String, Vector>,
+    /// Set instead of the embedding for sparse indexes
+    #[serde(default)]
+    pub sparse_embedding: Option<SparseVector>,
     pub metadata: HashMap<String, serde_json::Value>,
     pub root_content_metadata: Option<ContentMetadata>,
     pub content_metadata: ContentMetadata,
+    /// Text of the content, stored for keyword searches
+    #[serde(default)]
+    pub text: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -49,6 +49,7 @@ impl From<ExtractionPolicy> for super::ExtractionPolicy {
                 value: from_str_to_json(v),
                 operator: filter::Operator::Eq,
             })
+            .map(filter::Filter::from)
             .collect();
         super::ExtractionPolicy {
             filter: filter::LabelsFilter(expressions),
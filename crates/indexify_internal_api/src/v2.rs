@@ -44,6 +44,7 @@ impl From<ExtractionPolicy> for super::ExtractionPolicy {
                 value: v,
                 operator: filter::Operator::Eq,
             })
+            .map(filter::Filter::from)
             .collect();
         super::ExtractionPolicy {
             filter: filter::LabelsFilter(expressions),
@@ -1,4 +1,4 @@
-use std::{env, fmt::Debug, sync::Arc};
+use std::{env, fmt::Debug, ops::Range, sync::Arc};
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
@@ -7,9 +7,12 @@ use futures::{stream::BoxStream, StreamExt};
 use object_store::{
     aws::{AmazonS3, AmazonS3Builder},
     local,
+    GetOptions,
+    GetRange,
     ObjectStore,
     WriteMultipart,
 };
+use opentelemetry::metrics::Counter;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::io::AsyncWrite;
@@ -93,6 +96,7 @@ pub trait BlobStorageReader {
 pub struct BlobStorage {
     object_store: Arc<dyn ObjectStore>,
     config: BlobStorageConfig,
+    bytes_written: Counter<u64>,
 }
 
 pub struct StoragePartWriter {
@@ -136,9 +140,14 @@ impl BlobStorage {
             }))?;
             Arc::new(s)
         };
+        let bytes_written = opentelemetry::global::meter("indexify-blob-store")
+            .u64_counter("indexify.blob_store.bytes_written")
+            .with_description("Bytes written to the blob store")
+            .init();
         Ok(Self {
             object_store,
             config,
+            bytes_written,
         })
     }
 
@@ -166,6 +175,7 @@ impl BlobStorage {
             w.write(&chunk);
         }
         w.finish().await?;
+        self.bytes_written.add(size_bytes, &[]);
 
         let hash = format!("{:x}", hasher.finalize());
         Ok(PutResult {
@@ -204,27 +214,53 @@ impl BlobStorage {
         Arc::new(DiskFileReader::new(key))
     }
 
-    pub async fn delete(&self, key: &str) -> Result<()> {
+    // Maps a url returned by `put` back to the path in the object store
+    fn object_path(&self, key: &str) -> Result<object_store::path::Path> {
         if let Some(s3) = &self.config.s3 {
             let (bucket, key) = parse_s3_url(key)
                 .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))?;
             if bucket != s3.bucket {
                 return Err(anyhow!("invalid bucket {}", bucket));
             }
-            let path = object_store::path::Path::from(key);
-            self.object_store.delete(&path).await?;
-            return Ok(());
+            return Ok(object_store::path::Path::from(key));
         } else {
             let prefix = format!("file://{}/", self.config.disk.as_ref().unwrap().path);
             if let Some(key) = key.strip_prefix(prefix.as_str()) {
-                let path = object_store::path::Path::from(key);
-                self.object_store.delete(&path).await?;
-                return Ok(());
+                return Ok(object_store::path::Path::from(key));
             }
         }
         Err(anyhow!("invalid key {}", key))
     }
 
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.object_path(key)?;
+        self.object_store.delete(&path).await?;
+        Ok(())
+    }
+
+    /// Size of the blob in bytes, `None` if it doesn't exist
+    pub async fn size(&self, key: &str) -> Result<Option<usize>> {
+        let path = self.object_path(key)?;
+        match self.object_store.head(&path).await {
+            Ok(meta) => Ok(Some(meta.size)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Reads the bytes of the blob in `range`
+    pub async fn read_range(&self, key: &str, range: Range<usize>) -> Result<Bytes> {
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        let path = self.object_path(key)?;
+        let options = GetOptions {
+            range: Some(GetRange::Bounded(range)),
+            ..Default::default()
+        };
+        Ok(self.object_store.get_opts(&path, options).await?.bytes().await?)
+    }
+
     pub async fn read_bytes(&self, key: &str) -> Result<Bytes> {
         let reader = self.get(key);
         let mut stream = reader.get().await?;
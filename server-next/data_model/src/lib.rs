@@ -1,8 +1,9 @@
 pub mod filter;
+pub mod schema;
 pub mod test_objects;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{self, Display},
     hash::{DefaultHasher, Hash, Hasher},
     time::{SystemTime, UNIX_EPOCH},
@@ -11,7 +12,8 @@ use std::{
 use anyhow::{anyhow, Result};
 use derive_builder::Builder;
 use filter::LabelsFilter;
-use indexify_utils::default_creation_time;
+use indexify_utils::{default_creation_time, get_epoch_time_in_ms};
+use schema::PayloadSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
@@ -33,7 +35,7 @@ impl ExecutorId {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TaskId(String);
 
 impl TaskId {
@@ -68,6 +70,16 @@ pub struct ComputeFn {
     pub description: String,
     pub placement_constraints: LabelsFilter,
     pub fn_name: String,
+    #[serde(default)]
+    pub input: Option<PayloadSchema>,
+    #[serde(default)]
+    pub output: Option<PayloadSchema>,
+    // Upper bound on tasks of this function allocated across the cluster
+    #[serde(default)]
+    pub max_concurrency: Option<u32>,
+    // Names of the namespace secrets handed to the function's tasks
+    #[serde(default)]
+    pub secrets: Vec<String>,
 }
 
 impl ComputeFn {
@@ -76,10 +88,20 @@ impl ComputeFn {
     }
 }
 
+// Invokes another compute graph of the same namespace with the node's input.
+// The outputs of the child graph's leaf functions feed the node's edges.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubgraphNode {
+    pub name: String,
+    pub description: String,
+    pub compute_graph_name: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Node {
     Router(DynamicEdgeRouter),
     Compute(ComputeFn),
+    Subgraph(SubgraphNode),
 }
 
 impl Node {
@@ -87,6 +109,7 @@ impl Node {
         match self {
             Node::Router(router) => &router.name,
             Node::Compute(compute) => &compute.name,
+            Node::Subgraph(subgraph) => &subgraph.name,
         }
     }
 
@@ -94,6 +117,22 @@ impl Node {
         match self {
             Node::Router(_) => true,
             Node::Compute(compute) => compute.matches_executor(executor),
+            // Subgraphs run as child invocations, never on an executor
+            Node::Subgraph(_) => false,
+        }
+    }
+
+    pub fn max_concurrency(&self) -> Option<u32> {
+        match self {
+            Node::Compute(compute) => compute.max_concurrency,
+            _ => None,
+        }
+    }
+
+    pub fn secrets(&self) -> &[String] {
+        match self {
+            Node::Compute(compute) => &compute.secrets,
+            _ => &[],
         }
     }
 }
@@ -109,6 +148,12 @@ impl Node {
         let name = match self {
             Node::Router(router) => router.name.clone(),
             Node::Compute(compute) => compute.name.clone(),
+            Node::Subgraph(subgraph) => {
+                return Err(anyhow!(
+                    "subgraph node {} is invoked, not run as a task",
+                    subgraph.name
+                ))
+            }
         };
         let task = TaskBuilder::default()
             .namespace(namespace.to_string())
@@ -128,6 +173,30 @@ pub struct ComputeGraphCode {
     pub sha256_hash: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookTarget {
+    pub url: String,
+    // Used to sign the payloads delivered to the target. Only set on requests,
+    // the state store seals it before the graph is written.
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub sealed_secret: Option<SealedValue>,
+}
+
+impl WebhookTarget {
+    /// Associated data of the sealed secret, which binds it to the target
+    pub fn secret_key(&self, namespace: &str, compute_graph: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, self.url)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SealedValue {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ComputeGraph {
     pub namespace: String,
@@ -138,13 +207,121 @@ pub struct ComputeGraph {
     pub tomb_stoned: bool,
     pub start_fn: Node,
     pub nodes: HashMap<String, Node>,
-    pub edges: HashMap<String, Vec<String>>,
+    pub edges: HashMap<String, Vec<Edge>>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookTarget>,
+}
+
+// An edge to another node of the graph. Conditional edges are only taken for
+// outputs whose metadata matches the condition, which lets the server branch
+// without running a router on an executor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Edge {
+    Unconditional(String),
+    Conditional {
+        target: String,
+        condition: LabelsFilter,
+    },
+}
+
+impl Edge {
+    pub fn target(&self) -> &str {
+        match self {
+            Edge::Unconditional(target) => target,
+            Edge::Conditional { target, .. } => target,
+        }
+    }
+
+    pub fn matches(&self, metadata: &HashMap<String, serde_json::Value>) -> bool {
+        match self {
+            Edge::Unconditional(_) => true,
+            Edge::Conditional { condition, .. } => condition.matches(metadata),
+        }
+    }
+}
+
+impl From<&str> for Edge {
+    fn from(target: &str) -> Self {
+        Edge::Unconditional(target.to_string())
+    }
 }
 
 impl ComputeGraph {
     pub fn key(&self) -> String {
         format!("{}|{}", self.namespace, self.name)
     }
+
+    /// Returns the function and every function reachable from it, following
+    /// both static edges and router targets.
+    pub fn downstream_fns(&self, fn_name: &str) -> Result<HashSet<String>> {
+        if !self.nodes.contains_key(fn_name) {
+            return Err(anyhow!("compute fn not found: {}", fn_name));
+        }
+        let mut visited = HashSet::new();
+        let mut queue = vec![fn_name.to_string()];
+        while let Some(name) = queue.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            if let Some(edges) = self.edges.get(&name) {
+                queue.extend(edges.iter().map(|edge| edge.target().to_string()));
+            }
+            if let Some(Node::Router(router)) = self.nodes.get(&name) {
+                queue.extend(router.target_functions.iter().cloned());
+            }
+        }
+        Ok(visited)
+    }
+
+    /// Checks the declared payload schemas and that every edge connects a
+    /// producer to a consumer that accepts its output. Routers forward their
+    /// input, so their targets are checked against the router's producers.
+    pub fn validate_schemas(&self) -> Result<()> {
+        for node in self.nodes.values().chain([&self.start_fn]) {
+            let Node::Compute(compute_fn) = node else {
+                continue;
+            };
+            for (kind, payload) in [("input", &compute_fn.input), ("output", &compute_fn.output)] {
+                if let Some(payload) = payload {
+                    payload
+                        .check()
+                        .map_err(|e| anyhow!("{} {} schema: {}", compute_fn.name, kind, e))?;
+                }
+            }
+        }
+        for (from, edges) in &self.edges {
+            let Some(Node::Compute(producer)) = self.nodes.get(from) else {
+                continue;
+            };
+            let Some(output) = &producer.output else {
+                continue;
+            };
+            for edge in edges {
+                let consumers = match self.nodes.get(edge.target()) {
+                    Some(Node::Router(router)) => router.target_functions.clone(),
+                    _ => vec![edge.target().to_string()],
+                };
+                for consumer in consumers {
+                    let Some(Node::Compute(consumer)) = self.nodes.get(&consumer) else {
+                        continue;
+                    };
+                    let Some(input) = &consumer.input else {
+                        continue;
+                    };
+                    input.accepts(output).map_err(|e| {
+                        anyhow!(
+                            "output of {} is not accepted by {}: {}",
+                            producer.name,
+                            consumer.name,
+                            e
+                        )
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -174,6 +351,9 @@ pub struct NodeOutput {
     pub compute_fn_name: String,
     pub invocation_id: String,
     pub payload: OutputPayload,
+    // Reported by the function, and matched against conditional edges
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 impl NodeOutput {
@@ -237,10 +417,30 @@ impl NodeOutputBuilder {
             invocation_id,
             compute_fn_name: fn_name,
             payload,
+            metadata: self.metadata.clone().unwrap_or_default(),
         })
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    // Lower ranks are scheduled first
+    pub fn rank(&self) -> u8 {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct InvocationPayload {
@@ -248,6 +448,18 @@ pub struct InvocationPayload {
     pub namespace: String,
     pub compute_graph_name: String,
     pub payload: DataPayload,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub parent: Option<ParentInvocation>,
+}
+
+// The invocation and subgraph node that started a child invocation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParentInvocation {
+    pub compute_graph_name: String,
+    pub invocation_id: String,
+    pub node_name: String,
 }
 
 impl InvocationPayload {
@@ -280,16 +492,69 @@ impl InvocationPayloadBuilder {
         cg_name.hash(&mut hasher);
         payload.sha256_hash.hash(&mut hasher);
         payload.path.hash(&mut hasher);
+        // The same output can feed several subgraph nodes
+        let parent = self.parent.clone().flatten();
+        if let Some(parent) = &parent {
+            parent.invocation_id.hash(&mut hasher);
+            parent.node_name.hash(&mut hasher);
+        }
         let id = format!("{:x}", hasher.finish());
         Ok(InvocationPayload {
             id,
             namespace: ns,
             compute_graph_name: cg_name,
             payload,
+            priority: self.priority.unwrap_or_default(),
+            parent,
         })
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationBatch {
+    pub id: String,
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub invocation_ids: Vec<String>,
+    #[serde(default = "get_epoch_time_in_ms")]
+    pub created_at: u64,
+}
+
+impl InvocationBatch {
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.compute_graph_name, &self.id)
+    }
+
+    pub fn key_from(ns: &str, cg: &str, id: &str) -> String {
+        format!("{}|{}|{}", ns, cg, id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IdempotencyKey {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub key: String,
+    pub invocation_id: String,
+    // Hash of the request payload, replays must match it
+    pub payload_sha256: String,
+    pub expires_at: u64,
+}
+
+impl IdempotencyKey {
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.compute_graph_name, &self.key)
+    }
+
+    pub fn key_from(ns: &str, cg: &str, key: &str) -> String {
+        format!("{}|{}|{}", ns, cg, key)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= get_epoch_time_in_ms()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct GraphInvocationCtx {
@@ -297,7 +562,6 @@ pub struct GraphInvocationCtx {
     pub compute_graph_name: String,
     pub invocation_id: String,
     pub completed: bool,
-    pub outstanding_tasks: u16,
     pub fn_task_analytics: HashMap<String, TaskAnalytics>,
 }
 
@@ -312,6 +576,26 @@ impl GraphInvocationCtx {
     pub fn key_from(ns: &str, cg: &str, id: &str) -> String {
         format!("{}|{}|{}", ns, cg, id)
     }
+
+    /// Tasks and child invocations of the invocation that haven't finished
+    pub fn pending_tasks(&self) -> u64 {
+        self.fn_task_analytics
+            .values()
+            .map(|analytics| analytics.pending_tasks)
+            .sum()
+    }
+
+    pub fn outcome(&self) -> InvocationOutcome {
+        let failed = self
+            .fn_task_analytics
+            .values()
+            .any(|analytics| analytics.failed_tasks > 0);
+        if failed {
+            InvocationOutcome::Failure
+        } else {
+            InvocationOutcome::Success
+        }
+    }
 }
 
 impl GraphInvocationCtxBuilder {
@@ -333,12 +617,69 @@ impl GraphInvocationCtxBuilder {
             compute_graph_name: cg_name,
             invocation_id,
             completed: false,
-            outstanding_tasks: 0,
             fn_task_analytics: HashMap::new(),
         })
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum InvocationOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookOutputRef {
+    pub compute_fn: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationFinishedPayload {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub outcome: InvocationOutcome,
+    pub outputs: Vec<WebhookOutputRef>,
+    pub finished_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub target: WebhookTarget,
+    pub payload: InvocationFinishedPayload,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub created_at: u64,
+}
+
+impl WebhookDelivery {
+    pub fn new(target: WebhookTarget, payload: InvocationFinishedPayload) -> Self {
+        let mut hasher = DefaultHasher::new();
+        payload.namespace.hash(&mut hasher);
+        payload.compute_graph.hash(&mut hasher);
+        payload.invocation_id.hash(&mut hasher);
+        target.url.hash(&mut hasher);
+        let id = format!("{:x}", hasher.finish());
+        Self {
+            id,
+            target,
+            payload,
+            attempts: 0,
+            next_attempt_at: 0,
+            created_at: get_epoch_time_in_ms(),
+        }
+    }
+
+    pub fn key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}",
+            self.payload.namespace, self.payload.compute_graph, self.payload.invocation_id, self.id
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TaskOutcome {
     Unknown,
@@ -358,6 +699,10 @@ pub struct Task {
     pub outcome: TaskOutcome,
     #[serde(default = "default_creation_time")]
     pub creation_time: SystemTime,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub diagnostics: TaskDiagnostics,
 }
 
 impl Task {
@@ -381,12 +726,41 @@ impl Task {
         format!("{}|{}|{}", self.namespace, self.id, output_id)
     }
 
-    pub fn make_allocation_key(&self, executor_id: &ExecutorId) -> Vec<u8> {
+    fn creation_time_ns(&self) -> u128 {
         let duration = self.creation_time.duration_since(UNIX_EPOCH).unwrap();
         let secs = duration.as_secs() as u128;
         let nsecs = duration.subsec_nanos() as u128;
-        let nsecs = secs * 1_000_000_000 + nsecs;
-        format!("{}|{}|{}", executor_id, nsecs, self.key(),).into()
+        secs * 1_000_000_000 + nsecs
+    }
+
+    // <executor_id>|<priority_rank><creation_time_ns:020>|<task_key>, padded
+    // so that executors are sent their tasks by priority and then by age
+    pub fn make_allocation_key(&self, executor_id: &ExecutorId) -> Vec<u8> {
+        format!(
+            "{}|{}{:020}|{}",
+            executor_id,
+            self.priority.rank(),
+            self.creation_time_ns(),
+            self.key()
+        )
+        .into()
+    }
+
+    // Key of tasks allocated before priorities were introduced
+    pub fn make_legacy_allocation_key(&self, executor_id: &ExecutorId) -> Vec<u8> {
+        format!("{}|{}|{}", executor_id, self.creation_time_ns(), self.key(),).into()
+    }
+
+    // <priority_rank>|<creation_time_ns:020>|<task_key>, padded so that
+    // unallocated tasks are iterated by priority and then by age
+    pub fn make_unallocated_key(&self) -> Vec<u8> {
+        format!(
+            "{}|{:020}|{}",
+            self.priority.rank(),
+            self.creation_time_ns(),
+            self.key()
+        )
+        .into()
     }
 
     pub fn key_from_allocation_key(allocation_key: &[u8]) -> Result<Vec<u8>> {
@@ -412,6 +786,76 @@ impl Display for Task {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskDiagnostics {
+    pub error_message: Option<String>,
+    pub stdout: Option<DataPayload>,
+    pub stderr: Option<DataPayload>,
+    // Structured log lines, one JSON object per line
+    pub logs: Option<DataPayload>,
+}
+
+// A task which finished with a failure, kept until it's requeued. The
+// diagnostics of the failure are kept on the task it refers to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetter {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub invocation_id: String,
+    pub compute_fn_name: String,
+    pub task_id: TaskId,
+    pub executor_id: ExecutorId,
+    pub failed_at: u64,
+}
+
+impl DeadLetter {
+    pub fn new(task: &Task, executor_id: ExecutorId, failed_at: u64) -> Self {
+        Self {
+            namespace: task.namespace.clone(),
+            compute_graph_name: task.compute_graph_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            compute_fn_name: task.compute_fn_name.clone(),
+            task_id: task.id.clone(),
+            executor_id,
+            failed_at,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        Self::key_from(
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
+            &self.task_id.to_string(),
+        )
+    }
+
+    pub fn key_from(ns: &str, cg: &str, invocation_id: &str, task_id: &str) -> String {
+        format!("{}|{}|{}|{}", ns, cg, invocation_id, task_id)
+    }
+
+    // Key of the failed task, see `Task::key`
+    pub fn task_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.namespace,
+            self.compute_graph_name,
+            self.invocation_id,
+            self.compute_fn_name,
+            self.task_id
+        )
+    }
+}
+
+impl TaskDiagnostics {
+    /// Blobs of the captured output
+    pub fn payloads(&self) -> impl Iterator<Item = &DataPayload> {
+        [&self.stdout, &self.stderr, &self.logs]
+            .into_iter()
+            .flatten()
+    }
+}
+
 impl TaskBuilder {
     pub fn build(&self) -> Result<Task> {
         let namespace = self
@@ -450,6 +894,8 @@ impl TaskBuilder {
             namespace,
             outcome: TaskOutcome::Unknown,
             creation_time: SystemTime::now(),
+            priority: self.priority.unwrap_or_default(),
+            diagnostics: TaskDiagnostics::default(),
         };
         Ok(task)
     }
@@ -489,6 +935,9 @@ pub struct ExecutorMetadata {
     pub runner_name: String,
     pub addr: String,
     pub labels: HashMap<String, serde_json::Value>,
+    // Draining executors keep their tasks but don't get new ones
+    #[serde(default)]
+    pub draining: bool,
 }
 
 impl ExecutorMetadata {
@@ -497,6 +946,22 @@ impl ExecutorMetadata {
     }
 }
 
+// Periodic report from an executor about what it's running and how much room
+// it has left
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExecutorHeartbeat {
+    // Tasks the executor is running or has queued locally
+    pub running_task_ids: Vec<TaskId>,
+    pub free_slots: u32,
+    // Fraction of the host's CPU in use, 0.0 - 1.0
+    pub cpu_utilization: f64,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    // compute fn name -> installed version
+    pub function_versions: HashMap<String, String>,
+    pub received_at: u64,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct InvokeComputeGraphEvent {
     pub invocation_id: String,
@@ -531,6 +996,7 @@ pub enum ChangeType {
     TombstoneComputeGraph,
     ExecutorAdded,
     ExecutorRemoved,
+    ExecutorDraining,
     TaskCreated,
 }
 
@@ -543,6 +1009,7 @@ impl fmt::Display for ChangeType {
             ChangeType::TombstoneComputeGraph => write!(f, "TombstoneComputeGraph"),
             ChangeType::ExecutorAdded => write!(f, "ExecutorAdded"),
             ChangeType::ExecutorRemoved => write!(f, "ExecutorRemoved"),
+            ChangeType::ExecutorDraining => write!(f, "ExecutorDraining"),
             ChangeType::TaskCreated => write!(f, "TaskCreated"),
         }
     }
@@ -592,3 +1059,32 @@ pub struct Namespace {
     pub name: String,
     pub created_at: u64,
 }
+
+/// A namespace scoped secret. Only the sealed value is stored, see
+/// `state_store::secrets`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Secret {
+    pub namespace: String,
+    pub name: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    // Executors allowed to receive the value, any executor when empty
+    #[serde(default)]
+    pub executor_labels: LabelsFilter,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Secret {
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.name)
+    }
+
+    pub fn key_from(namespace: &str, name: &str) -> String {
+        format!("{}|{}", namespace, name)
+    }
+
+    pub fn authorizes(&self, executor: &ExecutorMetadata) -> bool {
+        self.executor_labels.matches(&executor.labels)
+    }
+}
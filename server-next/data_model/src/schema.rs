@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use jsonschema::JSONSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Payload a compute fn consumes or produces. The JSON Schema is optional
+/// and only meaningful for JSON content types.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PayloadSchema {
+    pub content_type: String,
+    #[serde(default)]
+    pub json_schema: Option<Value>,
+}
+
+impl PayloadSchema {
+    pub fn is_json(&self) -> bool {
+        let essence = essence(&self.content_type);
+        essence == "application/json" || essence.ends_with("+json")
+    }
+
+    /// Checks that the declaration itself is usable.
+    pub fn check(&self) -> Result<()> {
+        if self.content_type.trim().is_empty() {
+            return Err(anyhow!("content type is empty"));
+        }
+        let Some(schema) = &self.json_schema else {
+            return Ok(());
+        };
+        if !self.is_json() {
+            return Err(anyhow!(
+                "json schema declared for non json content type {}",
+                self.content_type
+            ));
+        }
+        compile(schema)?;
+        Ok(())
+    }
+
+    /// Validates a value against the JSON Schema, if one is declared.
+    pub fn validate(&self, value: &Value) -> Result<()> {
+        self.validator()?.validate(value)
+    }
+
+    /// Compiles the JSON Schema, if one is declared, to validate many values
+    pub fn validator(&self) -> Result<PayloadValidator> {
+        let schema = match &self.json_schema {
+            Some(schema) => Some(compile(schema)?),
+            None => None,
+        };
+        Ok(PayloadValidator { schema })
+    }
+
+    /// Checks that payloads described by `producer` can be fed to a consumer
+    /// described by `self`. Content types must match. When both sides carry a
+    /// schema, their types must overlap and every property the consumer
+    /// requires must also be required by the producer.
+    pub fn accepts(&self, producer: &PayloadSchema) -> Result<()> {
+        if essence(&self.content_type) != essence(&producer.content_type) {
+            return Err(anyhow!(
+                "content type {} does not match {}",
+                producer.content_type,
+                self.content_type
+            ));
+        }
+        let (Some(consumer), Some(producer)) = (&self.json_schema, &producer.json_schema) else {
+            return Ok(());
+        };
+        if let (Some(consumer_types), Some(producer_types)) =
+            (schema_types(consumer), schema_types(producer))
+        {
+            let overlaps = producer_types.iter().any(|t| {
+                consumer_types.contains(t) || (*t == "integer" && consumer_types.contains("number"))
+            });
+            if !overlaps {
+                return Err(anyhow!(
+                    "schema type {:?} does not match {:?}",
+                    producer_types,
+                    consumer_types
+                ));
+            }
+        }
+        let produced = required(producer);
+        let missing: Vec<_> = required(consumer)
+            .into_iter()
+            .filter(|property| !produced.contains(property))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("required properties not produced: {:?}", missing));
+        }
+        Ok(())
+    }
+}
+
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn schema_types(schema: &Value) -> Option<HashSet<&str>> {
+    match schema.get("type")? {
+        Value::String(t) => Some(HashSet::from([t.as_str()])),
+        Value::Array(types) => Some(types.iter().filter_map(Value::as_str).collect()),
+        _ => None,
+    }
+}
+
+fn required(schema: &Value) -> HashSet<&str> {
+    schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+/// Compiled JSON Schema of a payload
+pub struct PayloadValidator {
+    schema: Option<JSONSchema>,
+}
+
+impl PayloadValidator {
+    pub fn validate(&self, value: &Value) -> Result<()> {
+        let Some(schema) = &self.schema else {
+            return Ok(());
+        };
+        schema.validate(value).map_err(|errors| {
+            let errors: Vec<String> = errors
+                .map(|e| format!("{}: {}", e.instance_path, e))
+                .collect();
+            anyhow!("{}", errors.join(", "))
+        })
+    }
+}
+
+// Compiling checks the schema against the JSON Schema meta-schema
+fn compile(schema: &Value) -> Result<JSONSchema> {
+    JSONSchema::compile(schema).map_err(|e| anyhow!("invalid json schema: {}", e))
+}
+
+/// Validates `value` against a JSON Schema.
+pub fn validate(schema: &Value, value: &Value) -> Result<()> {
+    PayloadValidator {
+        schema: Some(compile(schema)?),
+    }
+    .validate(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{test_objects::tests::mock_graph_b, ComputeGraph, Node};
+
+    fn json_payload(schema: Value) -> PayloadSchema {
+        PayloadSchema {
+            content_type: "application/json".to_string(),
+            json_schema: Some(schema),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": {"type": "string", "minLength": 1},
+                "depth": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"type": "string"}},
+            },
+            "additionalProperties": false,
+        });
+        assert!(validate(&schema, &json!({"url": "a", "depth": 2, "tags": ["x"]})).is_ok());
+        assert!(validate(&schema, &json!({"depth": 2})).is_err());
+        assert!(validate(&schema, &json!({"url": ""})).is_err());
+        assert!(validate(&schema, &json!({"url": "a", "depth": -1})).is_err());
+        assert!(validate(&schema, &json!({"url": "a", "tags": [1]})).is_err());
+        assert!(validate(&schema, &json!({"url": "a", "other": 1})).is_err());
+        assert!(validate(&schema, &json!("a")).is_err());
+
+        let schema = json!({
+            "$defs": {"id": {"type": "string", "pattern": "^[a-z]+$"}},
+            "oneOf": [
+                {"type": "object", "required": ["id"], "properties": {"id": {"$ref": "#/$defs/id"}}},
+                {"type": "array", "items": {"$ref": "#/$defs/id"}},
+            ],
+        });
+        assert!(validate(&schema, &json!({"id": "abc"})).is_ok());
+        assert!(validate(&schema, &json!(["abc", "def"])).is_ok());
+        assert!(validate(&schema, &json!({"id": "ABC"})).is_err());
+        assert!(validate(&schema, &json!(["abc", 1])).is_err());
+        assert!(validate(&schema, &json!("abc")).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(json_payload(json!({"type": "object"})).check().is_ok());
+        assert!(json_payload(json!({"type": "float"})).check().is_err());
+        assert!(json_payload(json!({"required": "url"})).check().is_err());
+        let binary = PayloadSchema {
+            content_type: "application/octet-stream".to_string(),
+            json_schema: Some(json!({"type": "string"})),
+        };
+        assert!(binary.check().is_err());
+    }
+
+    #[test]
+    fn test_accepts() {
+        let producer = json_payload(json!({"type": "object", "required": ["url", "depth"]}));
+        let consumer = json_payload(json!({"type": "object", "required": ["url"]}));
+        assert!(consumer.accepts(&producer).is_ok());
+        assert!(producer.accepts(&consumer).is_err());
+        assert!(json_payload(json!({"type": "array"}))
+            .accepts(&producer)
+            .is_err());
+        let text = PayloadSchema {
+            content_type: "text/plain".to_string(),
+            json_schema: None,
+        };
+        assert!(text.accepts(&producer).is_err());
+        let utf8 = PayloadSchema {
+            content_type: "application/json; charset=utf-8".to_string(),
+            json_schema: None,
+        };
+        assert!(utf8.accepts(&producer).is_ok());
+    }
+
+    #[test]
+    fn test_graph_wiring() {
+        // fn_a -> router_x -> fn_b, fn_c
+        let mut graph = mock_graph_b();
+        let set = |graph: &mut ComputeGraph, name: &str, input, output| {
+            if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut(name) {
+                compute_fn.input = input;
+                compute_fn.output = output;
+            }
+        };
+        let url = json_payload(json!({"type": "object", "required": ["url"]}));
+        set(&mut graph, "fn_a", None, Some(url.clone()));
+        set(&mut graph, "fn_b", Some(url), None);
+        assert!(graph.validate_schemas().is_ok());
+
+        let depth = json_payload(json!({"type": "object", "required": ["depth"]}));
+        set(&mut graph, "fn_c", Some(depth), None);
+        assert!(graph.validate_schemas().is_err());
+    }
+}
@@ -82,18 +82,30 @@ pub mod tests {
             description: "description fn_a".to_string(),
             fn_name: "fn_a".to_string(),
             placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
         };
         let fn_b = ComputeFn {
             name: "fn_b".to_string(),
             description: "description fn_b".to_string(),
             fn_name: "fn_b".to_string(),
             placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
         };
         let fn_c = ComputeFn {
             name: "fn_c".to_string(),
             description: "description fn_c".to_string(),
             fn_name: "fn_c".to_string(),
             placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
         };
         ComputeGraph {
             namespace: TEST_NAMESPACE.to_string(),
@@ -103,10 +115,7 @@ pub mod tests {
                 ("fn_c".to_string(), Node::Compute(fn_c)),
                 ("fn_a".to_string(), Node::Compute(fn_a.clone())),
             ]),
-            edges: HashMap::from([(
-                "fn_a".to_string(),
-                vec!["fn_b".to_string(), "fn_c".to_string()],
-            )]),
+            edges: HashMap::from([("fn_a".to_string(), vec!["fn_b".into(), "fn_c".into()])]),
             description: "description graph_A".to_string(),
             code: ComputeGraphCode {
                 path: "cg_path".to_string(),
@@ -116,6 +125,7 @@ pub mod tests {
             create_at: 5,
             tomb_stoned: false,
             start_fn: Compute(fn_a),
+            webhooks: vec![],
         }
     }
 
@@ -125,6 +135,10 @@ pub mod tests {
             description: "description fn_a".to_string(),
             fn_name: "fn_a".to_string(),
             placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
         };
         let router_x = DynamicEdgeRouter {
             name: "router_x".to_string(),
@@ -137,12 +151,20 @@ pub mod tests {
             description: "description fn_b".to_string(),
             fn_name: "fn_b".to_string(),
             placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
         };
         let fn_c = ComputeFn {
             name: "fn_c".to_string(),
             description: "description fn_c".to_string(),
             fn_name: "fn_c".to_string(),
             placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
         };
         ComputeGraph {
             namespace: TEST_NAMESPACE.to_string(),
@@ -153,7 +175,7 @@ pub mod tests {
                 ("router_x".to_string(), Node::Router(router_x)),
                 ("fn_a".to_string(), Node::Compute(fn_a.clone())),
             ]),
-            edges: HashMap::from([("fn_a".to_string(), vec!["router_x".to_string()])]),
+            edges: HashMap::from([("fn_a".to_string(), vec!["router_x".into()])]),
             description: "description graph_B".to_string(),
             code: ComputeGraphCode {
                 path: "cg_path".to_string(),
@@ -163,6 +185,7 @@ pub mod tests {
             create_at: 5,
             tomb_stoned: false,
             start_fn: Compute(fn_a),
+            webhooks: vec![],
         }
     }
 
@@ -176,6 +199,7 @@ pub mod tests {
             runner_name: "test_runner".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            draining: false,
         }
     }
 }
@@ -13,6 +13,26 @@ pub struct ServerConfig {
     pub state_store_path: String,
     pub listen_addr: String,
     pub blob_storage: BlobStorageConfig,
+    // Key used to seal namespace secrets, generated next to the state store
+    // when not set
+    #[serde(default)]
+    pub secrets_key_path: Option<String>,
+    // Batch invocations with larger bodies are rejected with 413, other
+    // requests aren't limited
+    #[serde(default = "default_max_batch_body_bytes")]
+    pub max_batch_body_bytes: usize,
+    // Objects invoked on graphs whose start function declares a JSON input
+    // are read into memory to be validated, larger ones are rejected with 413
+    #[serde(default = "default_max_object_body_bytes")]
+    pub max_object_body_bytes: usize,
+}
+
+fn default_max_batch_body_bytes() -> usize {
+    1024 * 1024 * 1024
+}
+
+fn default_max_object_body_bytes() -> usize {
+    100 * 1024 * 1024
 }
 
 impl Default for ServerConfig {
@@ -22,6 +42,9 @@ impl Default for ServerConfig {
             state_store_path: state_store_path.to_str().unwrap().to_string(),
             listen_addr: "0.0.0.0:8900".to_string(),
             blob_storage: Default::default(),
+            secrets_key_path: None,
+            max_batch_body_bytes: default_max_batch_body_bytes(),
+            max_object_body_bytes: default_max_object_body_bytes(),
         }
     }
 }
@@ -1,10 +1,12 @@
 use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
-use data_model::{ExecutorId, ExecutorMetadata};
+use data_model::{ExecutorHeartbeat, ExecutorId, ExecutorMetadata};
 use state_store::{
     requests::{
         DeregisterExecutorRequest,
+        DrainExecutorRequest,
+        ReconcileExecutorTasksRequest,
         RegisterExecutorRequest,
         RequestPayload,
         StateMachineUpdateRequest,
@@ -40,6 +42,56 @@ impl ExecutorManager {
             })
             .await
     }
+
+    pub async fn drain_executor(&self, executor_id: ExecutorId, evict: bool) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DrainExecutor(DrainExecutorRequest {
+                    executor_id,
+                    draining: true,
+                    evict,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Records a heartbeat and returns the tasks the executor has stopped
+    /// reporting to the queue so they can be placed elsewhere.
+    pub async fn heartbeat(
+        &self,
+        executor_id: ExecutorId,
+        heartbeat: ExecutorHeartbeat,
+    ) -> Result<()> {
+        let lost_tasks = self
+            .indexify_state
+            .record_heartbeat(&executor_id, heartbeat)?;
+        if lost_tasks.is_empty() {
+            return Ok(());
+        }
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::ReconcileExecutorTasks(ReconcileExecutorTasksRequest {
+                    executor_id,
+                    tasks: lost_tasks,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    pub async fn resume_executor(&self, executor_id: ExecutorId) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DrainExecutor(DrainExecutorRequest {
+                    executor_id,
+                    draining: false,
+                    evict: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
 }
 
 pub fn schedule_deregister(ex: Arc<ExecutorManager>, executor_id: ExecutorId, duration: Duration) {
@@ -72,6 +124,7 @@ mod tests {
             runner_name: "test".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            draining: false,
         };
         ex.register_executor(executor).await?;
 
@@ -92,6 +145,7 @@ mod tests {
             runner_name: "test".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            draining: false,
         };
         ex.register_executor(executor.clone()).await?;
 
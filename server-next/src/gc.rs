@@ -1,14 +1,25 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
 use blob_store::BlobStorage;
-use state_store::IndexifyState;
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::time::Instant;
+
+use crate::metrics;
+
+// Expired idempotency keys are only ignored by the API, so they are removed
+// periodically
+const IDEMPOTENCY_KEY_SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 pub struct Gc {
     state: Arc<IndexifyState>,
     storage: Arc<BlobStorage>,
     rx: tokio::sync::watch::Receiver<()>,
     shutdown_rx: tokio::sync::watch::Receiver<()>,
+    metrics: metrics::gc::Metrics,
 }
 
 impl Gc {
@@ -23,22 +34,31 @@ impl Gc {
             storage,
             rx,
             shutdown_rx,
+            metrics: metrics::gc::Metrics::new(),
         }
     }
 
     pub async fn start(&mut self) -> Result<()> {
         let state = self.state.clone();
         let storage = self.storage.clone();
+        let mut next_sweep = Instant::now();
         loop {
             if self.shutdown_rx.has_changed().unwrap_or(false) {
                 println!("Shutdown signal received.");
                 return Ok(());
             }
+            if Instant::now() >= next_sweep {
+                if let Err(e) = self.remove_expired_idempotency_keys().await {
+                    tracing::error!("Error removing expired idempotency keys: {:?}", e);
+                }
+                next_sweep = Instant::now() + IDEMPOTENCY_KEY_SWEEP_INTERVAL;
+            }
 
             let urls = state.reader().get_gc_urls(Some(10))?;
             if urls.is_empty() {
                 tokio::select! {
                     _ = self.rx.changed() => { self.rx.borrow_and_update(); }
+                    _ = tokio::time::sleep_until(next_sweep) => {}
                     _ = self.shutdown_rx.changed() => {
                         println!("Shutdown signal received.");
                         return Ok(());
@@ -47,19 +67,39 @@ impl Gc {
             } else {
                 for url in urls.iter() {
                     tracing::debug!("Deleting url {:?}", url);
-                    if let Err(e) = storage.delete(&url).await {
-                        tracing::error!("Error deleting url {:?}: {:?}", url, e);
+                    match storage.delete(url).await {
+                        Ok(()) => self.metrics.urls_deleted.add(1, &[]),
+                        Err(e) => {
+                            tracing::error!("Error deleting url {:?}: {:?}", url, e);
+                            self.metrics.urls_failed.add(1, &[]);
+                        }
                     }
                 }
                 self.state
-                    .write(state_store::requests::StateMachineUpdateRequest {
-                        payload: state_store::requests::RequestPayload::RemoveGcUrls(urls),
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::RemoveGcUrls(urls),
                         state_changes_processed: vec![],
                     })
                     .await?;
             }
         }
     }
+
+    async fn remove_expired_idempotency_keys(&self) -> Result<()> {
+        loop {
+            let keys = self.state.reader().expired_idempotency_keys(Some(100))?;
+            if keys.is_empty() {
+                return Ok(());
+            }
+            tracing::debug!("Removing {} expired idempotency keys", keys.len());
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::RemoveExpiredIdempotencyKeys(keys),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -130,6 +170,7 @@ mod tests {
                 size: res.size_bytes,
                 sha256_hash: res.sha256_hash,
             }),
+            metadata: Default::default(),
         };
         let key = output.key(&output.invocation_id);
         let serialized_output = JsonEncoder::encode(&output)?;
@@ -4,7 +4,10 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use data_model::ComputeGraphCode;
+use data_model::{
+    filter::{Expression, LabelsFilter},
+    ComputeGraphCode,
+};
 use indexify_utils::get_epoch_time_in_ms;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -42,6 +45,14 @@ impl IndexifyAPIError {
     pub fn bad_request(message: &str) -> Self {
         Self::new(StatusCode::BAD_REQUEST, message)
     }
+
+    pub fn conflict(message: &str) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    pub fn payload_too_large(message: &str) -> Self {
+        Self::new(StatusCode::PAYLOAD_TOO_LARGE, message)
+    }
 }
 
 impl IntoResponse for IndexifyAPIError {
@@ -63,6 +74,13 @@ pub struct ListParams {
     pub cursor: Option<Vec<u8>>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct DrainParams {
+    // Return the tasks allocated to the executor to the queue immediately
+    #[serde(default)]
+    pub evict: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Namespace {
     name: String,
@@ -83,31 +101,66 @@ pub struct NamespaceList {
     pub namespaces: Vec<Namespace>,
 }
 
+/// Content type, and optionally the JSON Schema, of a function's payloads
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct PayloadSchema {
+    pub content_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+}
+
+impl From<PayloadSchema> for data_model::schema::PayloadSchema {
+    fn from(val: PayloadSchema) -> Self {
+        Self {
+            content_type: val.content_type,
+            json_schema: val.json_schema,
+        }
+    }
+}
+
+impl From<data_model::schema::PayloadSchema> for PayloadSchema {
+    fn from(val: data_model::schema::PayloadSchema) -> Self {
+        Self {
+            content_type: val.content_type,
+            json_schema: val.json_schema,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct ComputeFn {
     pub name: String,
     pub fn_name: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<PayloadSchema>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PayloadSchema>,
+    /// Most tasks of this function allocated at once across all executors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+    /// Names of the namespace secrets passed to the function's tasks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
 }
 
 impl From<&ComputeFn> for data_model::ComputeFn {
     fn from(val: &ComputeFn) -> Self {
-        data_model::ComputeFn {
-            name: val.name.clone(),
-            fn_name: val.fn_name.clone(),
-            description: val.description.clone(),
-            placement_constraints: Default::default(),
-        }
+        val.clone().into()
     }
 }
 
 impl From<ComputeFn> for data_model::ComputeFn {
     fn from(val: ComputeFn) -> Self {
         data_model::ComputeFn {
-            name: val.name.clone(),
-            fn_name: val.fn_name.clone(),
-            description: val.description.clone(),
+            name: val.name,
+            fn_name: val.fn_name,
+            description: val.description,
             placement_constraints: Default::default(),
+            input: val.input.map(Into::into),
+            output: val.output.map(Into::into),
+            max_concurrency: val.max_concurrency,
+            secrets: val.secrets,
         }
     }
 }
@@ -118,6 +171,38 @@ impl From<data_model::ComputeFn> for ComputeFn {
             name: c.name,
             fn_name: c.fn_name,
             description: c.description,
+            input: c.input.map(Into::into),
+            output: c.output.map(Into::into),
+            max_concurrency: c.max_concurrency,
+            secrets: c.secrets,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct Subgraph {
+    pub name: String,
+    pub description: String,
+    // Compute graph of the same namespace to invoke
+    pub compute_graph_name: String,
+}
+
+impl From<Subgraph> for data_model::SubgraphNode {
+    fn from(val: Subgraph) -> Self {
+        data_model::SubgraphNode {
+            name: val.name,
+            description: val.description,
+            compute_graph_name: val.compute_graph_name,
+        }
+    }
+}
+
+impl From<data_model::SubgraphNode> for Subgraph {
+    fn from(s: data_model::SubgraphNode) -> Self {
+        Self {
+            name: s.name,
+            description: s.description,
+            compute_graph_name: s.compute_graph_name,
         }
     }
 }
@@ -158,6 +243,8 @@ pub enum Node {
     DynamicRouter(DynamicRouter),
     #[serde(rename = "compute_fn")]
     ComputeFn(ComputeFn),
+    #[serde(rename = "subgraph")]
+    Subgraph(Subgraph),
 }
 
 impl Node {
@@ -165,6 +252,7 @@ impl Node {
         match self {
             Node::DynamicRouter(d) => d.name.clone(),
             Node::ComputeFn(c) => c.name.clone(),
+            Node::Subgraph(s) => s.name.clone(),
         }
     }
 }
@@ -174,6 +262,7 @@ impl From<Node> for data_model::Node {
         match val {
             Node::DynamicRouter(d) => data_model::Node::Router(d.into()),
             Node::ComputeFn(c) => data_model::Node::Compute(c.into()),
+            Node::Subgraph(s) => data_model::Node::Subgraph(s.into()),
         }
     }
 }
@@ -183,6 +272,84 @@ impl From<data_model::Node> for Node {
         match node {
             data_model::Node::Router(d) => Node::DynamicRouter(d.into()),
             data_model::Node::Compute(c) => Node::ComputeFn(c.into()),
+            data_model::Node::Subgraph(s) => Node::Subgraph(s.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookTarget {
+    pub url: String,
+    // Never returned by the API once the graph is registered
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+}
+
+impl From<WebhookTarget> for data_model::WebhookTarget {
+    fn from(target: WebhookTarget) -> Self {
+        data_model::WebhookTarget {
+            url: target.url,
+            secret: target.secret,
+            sealed_secret: None,
+        }
+    }
+}
+
+impl From<data_model::WebhookTarget> for WebhookTarget {
+    fn from(target: data_model::WebhookTarget) -> Self {
+        WebhookTarget {
+            url: target.url,
+            secret: None,
+        }
+    }
+}
+
+/// Either the name of the target node, or a target with a condition over the
+/// source's output metadata, e.g. `{"target": "fn_b", "condition":
+/// ["score>0.5"]}`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum Edge {
+    Unconditional(String),
+    Conditional {
+        target: String,
+        condition: Vec<String>,
+    },
+}
+
+impl TryFrom<Edge> for data_model::Edge {
+    type Error = IndexifyAPIError;
+
+    fn try_from(edge: Edge) -> Result<Self, Self::Error> {
+        match edge {
+            Edge::Unconditional(target) => Ok(data_model::Edge::Unconditional(target)),
+            Edge::Conditional { target, condition } => {
+                let expressions = condition
+                    .iter()
+                    .map(|expr| Expression::from_str(expr))
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+                Ok(data_model::Edge::Conditional {
+                    target,
+                    condition: LabelsFilter(expressions),
+                })
+            }
+        }
+    }
+}
+
+impl From<data_model::Edge> for Edge {
+    fn from(edge: data_model::Edge) -> Self {
+        match edge {
+            data_model::Edge::Unconditional(target) => Edge::Unconditional(target),
+            data_model::Edge::Conditional { target, condition } => Edge::Conditional {
+                target,
+                condition: condition
+                    .expressions()
+                    .iter()
+                    .map(|expr| expr.to_string())
+                    .collect(),
+            },
         }
     }
 }
@@ -194,9 +361,11 @@ pub struct ComputeGraph {
     pub description: String,
     pub start_node: Node,
     pub nodes: HashMap<String, Node>,
-    pub edges: HashMap<String, Vec<String>>,
+    pub edges: HashMap<String, Vec<Edge>>,
     #[serde(default = "get_epoch_time_in_ms")]
     pub created_at: u64,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookTarget>,
 }
 
 impl ComputeGraph {
@@ -208,9 +377,27 @@ impl ComputeGraph {
     ) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
         let mut nodes = HashMap::new();
         for (name, node) in self.nodes {
+            if let Node::ComputeFn(ComputeFn {
+                max_concurrency: Some(0),
+                ..
+            }) = node
+            {
+                return Err(IndexifyAPIError::bad_request(&format!(
+                    "max_concurrency of {} must be at least 1",
+                    name
+                )));
+            }
             nodes.insert(name, node.into());
         }
         let start_fn: data_model::Node = self.start_node.into();
+        let mut edges = HashMap::new();
+        for (source, targets) in self.edges {
+            let targets = targets
+                .into_iter()
+                .map(data_model::Edge::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            edges.insert(source, targets);
+        }
         let compute_graph = data_model::ComputeGraph {
             name: self.name,
             namespace: self.namespace,
@@ -222,9 +409,10 @@ impl ComputeGraph {
                 path: code_path.to_string(),
             },
             nodes,
-            edges: self.edges.clone(),
+            edges,
             create_at: 0,
             tomb_stoned: false,
+            webhooks: self.webhooks.into_iter().map(|w| w.into()).collect(),
         };
         Ok(compute_graph)
     }
@@ -232,10 +420,7 @@ impl ComputeGraph {
 
 impl From<data_model::ComputeGraph> for ComputeGraph {
     fn from(compute_graph: data_model::ComputeGraph) -> Self {
-        let start_fn = match compute_graph.start_fn {
-            data_model::Node::Router(d) => Node::DynamicRouter(d.into()),
-            data_model::Node::Compute(c) => Node::ComputeFn(c.into()),
-        };
+        let start_fn = compute_graph.start_fn.into();
         let mut nodes = HashMap::new();
         for (k, v) in compute_graph.nodes.into_iter() {
             nodes.insert(k, v.into());
@@ -246,8 +431,17 @@ impl From<data_model::ComputeGraph> for ComputeGraph {
             description: compute_graph.description,
             start_node: start_fn,
             nodes,
-            edges: compute_graph.edges,
+            edges: compute_graph
+                .edges
+                .into_iter()
+                .map(|(source, targets)| (source, targets.into_iter().map(Into::into).collect()))
+                .collect(),
             created_at: compute_graph.create_at,
+            webhooks: compute_graph
+                .webhooks
+                .into_iter()
+                .map(|w| w.into())
+                .collect(),
         }
     }
 }
@@ -357,16 +551,127 @@ impl From<data_model::Task> for Task {
     }
 }
 
+/// A task as streamed to the executor it's allocated to, with the values of
+/// the secrets its function references
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutorTask {
+    #[serde(flatten)]
+    pub task: Task,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub secrets: HashMap<String, String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Tasks {
     pub tasks: Vec<Task>,
     pub cursor: Option<Vec<u8>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetter {
+    pub task: Task,
+    pub executor_id: String,
+    pub error_message: Option<String>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub failed_at: u64,
+}
+
+impl DeadLetter {
+    // The diagnostics are read from the failed task
+    pub fn new(dead_letter: data_model::DeadLetter, task: data_model::Task) -> Self {
+        let diagnostics = task.diagnostics.clone();
+        Self {
+            task: task.into(),
+            executor_id: dead_letter.executor_id.to_string(),
+            error_message: diagnostics.error_message,
+            stdout: diagnostics.stdout.map(|p| p.path),
+            stderr: diagnostics.stderr.map(|p| p.path),
+            failed_at: dead_letter.failed_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetters {
+    pub dead_letters: Vec<DeadLetter>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RequeueDeadLetters {
+    // Only requeue the dead letters of this invocation
+    #[serde(default)]
+    pub invocation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RerunInvocation {
+    // The function to rerun from
+    pub compute_fn: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SetSecret {
+    pub value: String,
+    /// Label expressions an executor must match to receive the secret
+    #[serde(default)]
+    pub executor_labels: Vec<String>,
+}
+
+impl SetSecret {
+    pub fn executor_labels(&self) -> Result<LabelsFilter, IndexifyAPIError> {
+        let expressions = self
+            .executor_labels
+            .iter()
+            .map(|expr| Expression::from_str(expr))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        Ok(LabelsFilter(expressions))
+    }
+}
+
+/// A secret without its value, which is never returned by the API
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SecretMetadata {
+    pub name: String,
+    pub executor_labels: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl From<data_model::Secret> for SecretMetadata {
+    fn from(secret: data_model::Secret) -> Self {
+        Self {
+            name: secret.name,
+            executor_labels: secret
+                .executor_labels
+                .expressions()
+                .iter()
+                .map(|expr| expr.to_string())
+                .collect(),
+            created_at: secret.created_at,
+            updated_at: secret.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SecretsList {
+    pub secrets: Vec<SecretMetadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RequeuedTasks {
+    pub task_ids: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct FnOutput {
     pub compute_fn: String,
     pub id: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 impl From<data_model::NodeOutput> for FnOutput {
@@ -374,6 +679,7 @@ impl From<data_model::NodeOutput> for FnOutput {
         Self {
             compute_fn: output.compute_fn_name,
             id: output.id.to_string(),
+            metadata: output.metadata,
         }
     }
 }
@@ -384,11 +690,52 @@ pub struct FnOutputs {
     pub cursor: Option<Vec<u8>>,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl From<Priority> for data_model::Priority {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::High => data_model::Priority::High,
+            Priority::Normal => data_model::Priority::Normal,
+            Priority::Low => data_model::Priority::Low,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct InvokeParams {
+    #[serde(default)]
+    pub priority: Priority,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct InvocationId {
     pub id: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationBatchResult {
+    pub batch_id: String,
+    pub invocation_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationBatchProgress {
+    pub id: String,
+    pub total: usize,
+    pub pending: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub invocation_ids: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ExecutorMetadata {
     pub address: String,
@@ -396,6 +743,104 @@ pub struct ExecutorMetadata {
     pub labels: HashMap<String, serde_json::Value>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorHeartbeat {
+    // Tasks the executor is running or has queued locally
+    #[serde(default)]
+    pub running_task_ids: Vec<String>,
+    #[serde(default)]
+    pub free_slots: u32,
+    #[serde(default)]
+    pub cpu_utilization: f64,
+    #[serde(default)]
+    pub memory_used_bytes: u64,
+    #[serde(default)]
+    pub memory_total_bytes: u64,
+    #[serde(default)]
+    pub function_versions: HashMap<String, String>,
+    // Set by the server when the heartbeat arrives
+    #[serde(default, skip_deserializing)]
+    pub received_at: u64,
+}
+
+impl From<ExecutorHeartbeat> for data_model::ExecutorHeartbeat {
+    fn from(heartbeat: ExecutorHeartbeat) -> Self {
+        Self {
+            running_task_ids: heartbeat
+                .running_task_ids
+                .into_iter()
+                .map(data_model::TaskId::new)
+                .collect(),
+            free_slots: heartbeat.free_slots,
+            cpu_utilization: heartbeat.cpu_utilization,
+            memory_used_bytes: heartbeat.memory_used_bytes,
+            memory_total_bytes: heartbeat.memory_total_bytes,
+            function_versions: heartbeat.function_versions,
+            received_at: get_epoch_time_in_ms(),
+        }
+    }
+}
+
+impl From<data_model::ExecutorHeartbeat> for ExecutorHeartbeat {
+    fn from(heartbeat: data_model::ExecutorHeartbeat) -> Self {
+        Self {
+            running_task_ids: heartbeat
+                .running_task_ids
+                .into_iter()
+                .map(|id| id.to_string())
+                .collect(),
+            free_slots: heartbeat.free_slots,
+            cpu_utilization: heartbeat.cpu_utilization,
+            memory_used_bytes: heartbeat.memory_used_bytes,
+            memory_total_bytes: heartbeat.memory_total_bytes,
+            function_versions: heartbeat.function_versions,
+            received_at: heartbeat.received_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutorHealth {
+    Healthy,
+    // No heartbeat within the heartbeat timeout
+    Unhealthy,
+    // The executor hasn't sent a heartbeat yet
+    Unknown,
+}
+
+impl ExecutorHealth {
+    pub fn from_heartbeat(heartbeat: Option<&data_model::ExecutorHeartbeat>, now: u64) -> Self {
+        match heartbeat {
+            None => ExecutorHealth::Unknown,
+            Some(heartbeat)
+                if now.saturating_sub(heartbeat.received_at) >
+                    state_store::HEARTBEAT_TIMEOUT.as_millis() as u64 =>
+            {
+                ExecutorHealth::Unhealthy
+            }
+            Some(_) => ExecutorHealth::Healthy,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Executor {
+    pub id: String,
+    pub address: String,
+    pub runner_name: String,
+    pub labels: HashMap<String, serde_json::Value>,
+    pub draining: bool,
+    pub health: ExecutorHealth,
+    pub allocated_tasks: usize,
+    pub last_heartbeat: Option<ExecutorHeartbeat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorsList {
+    pub executors: Vec<Executor>,
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -416,4 +861,27 @@ mod tests {
         json_value["namespace"] = serde_json::Value::String("test".to_string());
         let _: super::ComputeGraph = serde_json::from_value(json_value).unwrap();
     }
+
+    #[test]
+    fn test_executor_health() {
+        use super::ExecutorHealth;
+
+        let heartbeat = data_model::ExecutorHeartbeat {
+            received_at: 1_000,
+            ..Default::default()
+        };
+        let timeout = state_store::HEARTBEAT_TIMEOUT.as_millis() as u64;
+        assert_eq!(
+            ExecutorHealth::from_heartbeat(None, 1_000),
+            ExecutorHealth::Unknown
+        );
+        assert_eq!(
+            ExecutorHealth::from_heartbeat(Some(&heartbeat), 1_000 + timeout),
+            ExecutorHealth::Healthy
+        );
+        assert_eq!(
+            ExecutorHealth::from_heartbeat(Some(&heartbeat), 1_001 + timeout),
+            ExecutorHealth::Unhealthy
+        );
+    }
 }
@@ -9,10 +9,12 @@ mod config;
 mod executors;
 mod gc;
 mod http_objects;
+mod metrics;
 mod routes;
 mod scheduler;
 mod server;
 mod service;
+mod webhooks;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
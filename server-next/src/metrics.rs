@@ -0,0 +1,152 @@
+use anyhow::Result;
+use opentelemetry_sdk::metrics::{new_view, Aggregation, Instrument, SdkMeterProvider, Stream};
+
+/// Buckets, in seconds, for latencies from sub-millisecond writes up to
+/// hour long tasks.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0,
+];
+
+/// Installs the global meter provider and returns the registry its
+/// measurements are exported to. Instruments created before this is called
+/// are not exported.
+pub fn init_provider() -> Result<prometheus::Registry> {
+    let registry = prometheus::Registry::new();
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_registry(registry.clone())
+        .build()?;
+    let latency_view = new_view(
+        Instrument::new().name("*latency"),
+        Stream::new().aggregation(Aggregation::ExplicitBucketHistogram {
+            boundaries: LATENCY_BUCKETS.to_vec(),
+            record_min_max: true,
+        }),
+    )?;
+    let provider = SdkMeterProvider::builder()
+        .with_reader(exporter)
+        .with_view(latency_view)
+        .build();
+    opentelemetry::global::set_meter_provider(provider);
+    Ok(registry)
+}
+
+/// Renders the registry in the Prometheus text format.
+pub fn render(registry: &prometheus::Registry) -> Result<String> {
+    let encoder = prometheus::TextEncoder::new();
+    Ok(encoder.encode_to_string(&registry.gather())?)
+}
+
+pub mod executors {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use indexify_utils::get_epoch_time_in_ms;
+    use opentelemetry::{metrics::ObservableGauge, KeyValue};
+    use state_store::IndexifyState;
+
+    use crate::http_objects::ExecutorHealth;
+
+    pub struct Metrics {
+        // Only held so the gauge lives as long as the service
+        _executors: ObservableGauge<u64>,
+    }
+
+    impl Metrics {
+        pub fn new(indexify_state: Arc<IndexifyState>) -> Metrics {
+            let meter = opentelemetry::global::meter("indexify-server");
+            let executors = meter
+                .u64_observable_gauge("indexify.executors")
+                .with_callback(move |observer| {
+                    // Counted from the in-memory executor states, scrapes don't
+                    // read the executors column family
+                    let now = get_epoch_time_in_ms();
+                    let mut counts = BTreeMap::new();
+                    for state in indexify_state.executor_states.read().unwrap().values() {
+                        let health = match ExecutorHealth::from_heartbeat(
+                            state.last_heartbeat.as_ref(),
+                            now,
+                        ) {
+                            ExecutorHealth::Healthy => "healthy",
+                            ExecutorHealth::Unhealthy => "unhealthy",
+                            ExecutorHealth::Unknown => "unknown",
+                        };
+                        *counts.entry((health, state.draining)).or_insert(0) += 1;
+                    }
+                    for ((health, draining), count) in counts {
+                        observer.observe(
+                            count,
+                            &[
+                                KeyValue::new("health", health),
+                                KeyValue::new("draining", draining),
+                            ],
+                        );
+                    }
+                })
+                .with_description("Registered executors by health")
+                .init();
+            Metrics {
+                _executors: executors,
+            }
+        }
+    }
+}
+
+pub mod gc {
+    use opentelemetry::metrics::Counter;
+
+    pub struct Metrics {
+        pub urls_deleted: Counter<u64>,
+        pub urls_failed: Counter<u64>,
+    }
+
+    impl Default for Metrics {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Metrics {
+        pub fn new() -> Metrics {
+            let meter = opentelemetry::global::meter("indexify-server");
+            let urls_deleted = meter
+                .u64_counter("indexify.gc.urls_deleted")
+                .with_description("Blobs deleted by the garbage collector")
+                .init();
+            let urls_failed = meter
+                .u64_counter("indexify.gc.urls_failed")
+                .with_description("Blobs the garbage collector failed to delete")
+                .init();
+            Metrics {
+                urls_deleted,
+                urls_failed,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use state_store::test_state_store::tests::TestStateStore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_render_metrics() -> Result<()> {
+        // The provider has to be installed before the state store creates
+        // its instruments
+        let registry = init_provider()?;
+        let state_store = TestStateStore::new().await?;
+        state_store.with_simple_graph().await;
+
+        let metrics = render(&registry)?;
+        assert!(metrics.contains("# TYPE indexify_invocations_created_total counter"));
+        assert!(metrics.contains("namespace=\"test_ns\""));
+        assert!(metrics.contains("compute_graph=\"graph_A\""));
+        assert!(metrics.contains("# TYPE indexify_state_store_write_latency_seconds histogram"));
+        assert!(metrics.contains("request=\"InvokeComputeGraph\""));
+        assert!(metrics.contains("le=\"0.001\""));
+        assert!(metrics.contains("# TYPE indexify_unallocated_tasks gauge"));
+        assert!(metrics
+            .contains("indexify_gc_pending_urls{otel_scope_name=\"indexify-state-store\"} 0"));
+        Ok(())
+    }
+}
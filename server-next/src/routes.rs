@@ -1,34 +1,42 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Result;
 use axum::{
     body::Body,
     extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, Query, Request, State},
-    http::{Method, Response},
+    http::{header, Method, Response},
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Json,
     Router,
 };
 use blob_store::PutResult;
 use data_model::ExecutorId;
 use futures::StreamExt;
-use indexify_utils::GuardStreamExt;
+use indexify_utils::{get_epoch_time_in_ms, GuardStreamExt};
 use nanoid::nanoid;
 use state_store::{
     requests::{
         CreateComputeGraphRequest,
         DeleteComputeGraphRequest,
         DeleteInvocationRequest,
+        FinalizeTaskRequest,
         NamespaceRequest,
         RequestPayload,
+        RerunInvocationRequest,
         StateMachineUpdateRequest,
     },
     IndexifyState,
+    StateMachineError,
     EXECUTOR_TIMEOUT,
 };
 use tower_http::{
     cors::{Any, CorsLayer},
+    limit::RequestBodyLimitLayer,
     trace::TraceLayer,
 };
 use tracing::info;
@@ -37,16 +45,22 @@ use utoipa_swagger_ui::SwaggerUi;
 
 use crate::executors;
 
+mod dead_letters;
 mod download;
 mod internal_ingest;
 mod invoke;
+mod logs;
+mod secrets;
+use dead_letters::{get_dead_letter, list_dead_letters, requeue_dead_letter, requeue_dead_letters};
 use download::{
     download_fn_output_by_key,
     download_fn_output_payload,
     download_invocation_payload,
 };
 use internal_ingest::ingest_files_from_executor;
-use invoke::{invoke_with_file, invoke_with_object};
+use invoke::{batch_progress, discard_blobs, invoke_batch, invoke_with_file, invoke_with_object};
+use logs::{download_task_logs, upload_live_task_logs};
+use secrets::{delete_secret, list_secrets, set_secret};
 
 use crate::{
     executors::ExecutorManager,
@@ -56,19 +70,41 @@ use crate::{
         ComputeGraphsList,
         CreateNamespace,
         DataObject,
+        DeadLetter,
+        DeadLetters,
+        DrainParams,
         DynamicRouter,
+        Edge,
+        Executor,
+        ExecutorHealth,
+        ExecutorHeartbeat,
         ExecutorMetadata,
+        ExecutorTask,
+        ExecutorsList,
         FnOutputs,
         GraphInvocations,
         IndexifyAPIError,
+        InvocationBatchProgress,
+        InvocationBatchResult,
         InvocationResult,
+        InvokeParams,
         ListParams,
         Namespace,
         NamespaceList,
         Node,
+        PayloadSchema,
+        Priority,
+        RequeueDeadLetters,
+        RequeuedTasks,
+        RerunInvocation,
+        SecretMetadata,
+        SecretsList,
+        SetSecret,
+        Subgraph,
         Task,
         TaskOutcome,
         Tasks,
+        WebhookTarget,
     },
 };
 
@@ -77,8 +113,22 @@ use crate::{
         paths(
             create_namespace,
             namespaces,
+            metrics,
+            secrets::set_secret,
+            secrets::list_secrets,
+            secrets::delete_secret,
             invoke::invoke_with_file,
             invoke::invoke_with_object,
+            invoke::invoke_batch,
+            invoke::batch_progress,
+            dead_letters::list_dead_letters,
+            dead_letters::get_dead_letter,
+            dead_letters::requeue_dead_letter,
+            dead_letters::requeue_dead_letters,
+            logs::download_task_logs,
+            list_executors,
+            drain_executor,
+            resume_executor,
             graph_invocations,
             create_compute_graph,
             list_compute_graphs,
@@ -87,6 +137,7 @@ use crate::{
             list_tasks,
             list_outputs,
             delete_invocation,
+            rerun_invocation,
         ),
         components(
             schemas(
@@ -94,18 +145,39 @@ use crate::{
                 NamespaceList,
                 IndexifyAPIError,
                 Namespace,
+                SetSecret,
+                SecretMetadata,
+                SecretsList,
                 ComputeGraph,
                 Node,
+                Edge,
                 DynamicRouter,
+                Subgraph,
                 ComputeFn,
+                PayloadSchema,
                 ComputeGraphCreateType,
                 ComputeGraphsList,
                 InvocationResult,
+                InvocationBatchResult,
+                InvocationBatchProgress,
+                InvokeParams,
+                Priority,
+                DeadLetter,
+                DeadLetters,
+                RequeueDeadLetters,
+                RequeuedTasks,
+                logs::LogStream,
                 Task,
                 TaskOutcome,
                 Tasks,
                 GraphInvocations,
                 DataObject,
+                WebhookTarget,
+                RerunInvocation,
+                Executor,
+                ExecutorHealth,
+                ExecutorHeartbeat,
+                ExecutorsList,
             )
         ),
         tags(
@@ -120,17 +192,22 @@ pub struct RouteState {
     pub indexify_state: Arc<IndexifyState>,
     pub blob_storage: Arc<blob_store::BlobStorage>,
     pub executor_manager: Arc<ExecutorManager>,
+    pub registry: Arc<prometheus::Registry>,
+    /// Largest object read into memory to be validated against the start
+    /// function's input schema
+    pub max_object_body_bytes: usize,
 }
 
-pub fn create_routes(route_state: RouteState) -> Router {
+pub fn create_routes(route_state: RouteState, max_batch_body_bytes: usize) -> Router {
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_origin(Any)
         .allow_headers(Any);
 
     Router::new()
         .merge(SwaggerUi::new("/docs/swagger").url("/docs/openapi.json", ApiDoc::openapi()))
         .route("/", get(index))
+        .route("/metrics", get(metrics).with_state(route_state.clone()))
         .route(
             "/namespaces",
             get(namespaces).with_state(route_state.clone()),
@@ -139,6 +216,18 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces",
             post(create_namespace).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/secrets",
+            get(list_secrets).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/secrets/:name",
+            put(set_secret).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/secrets/:name",
+            delete(delete_secret).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs",
             post(create_compute_graph).with_state(route_state.clone()),
@@ -159,6 +248,10 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/tasks",
             get(list_tasks).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/logs/:stream",
+            get(download_task_logs).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/outputs",
             get(list_outputs).with_state(route_state.clone()),
@@ -175,10 +268,42 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
             post(invoke_with_object).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_batch",
+            post(invoke_batch)
+                .with_state(route_state.clone())
+                // Streamed bodies aren't checked by the extractors' default limit
+                .layer(DefaultBodyLimit::max(max_batch_body_bytes))
+                .layer(RequestBodyLimitLayer::new(max_batch_body_bytes)),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/batches/:batch_id",
+            get(batch_progress).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id",
             delete(delete_invocation).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/rerun",
+            post(rerun_invocation).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/dead_letters",
+            get(list_dead_letters).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/dead_letters/requeue",
+            post(requeue_dead_letters).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/dead_letters/:task_id",
+            get(get_dead_letter).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/dead_letters/:task_id/requeue",
+            post(requeue_dead_letter).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/notify",
             get(notify_on_change).with_state(route_state.clone()),
@@ -199,10 +324,30 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/internal/ingest_files",
             post(ingest_files_from_executor).with_state(route_state.clone()),
         )
+        .route(
+            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/logs/:stream",
+            post(upload_live_task_logs).with_state(route_state.clone()),
+        )
+        .route(
+            "/executors",
+            get(list_executors).with_state(route_state.clone()),
+        )
+        .route(
+            "/executors/:id/drain",
+            post(drain_executor).with_state(route_state.clone()),
+        )
+        .route(
+            "/executors/:id/resume",
+            post(resume_executor).with_state(route_state.clone()),
+        )
         .route(
             "/internal/executors/:id/tasks",
             post(executor_tasks).with_state(route_state.clone()),
         )
+        .route(
+            "/internal/executors/:id/heartbeat",
+            post(executor_heartbeat).with_state(route_state.clone()),
+        )
         .route(
             "/internal/fn_outputs/:input_key",
             get(download_fn_output_by_key).with_state(route_state.clone()),
@@ -230,6 +375,25 @@ async fn index() -> &'static str {
     "Indexify Server"
 }
 
+/// Server metrics in the Prometheus text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Prometheus metrics", content_type = "text/plain"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn metrics(State(state): State<RouteState>) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let metrics =
+        crate::metrics::render(&state.registry).map_err(IndexifyAPIError::internal_error)?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics,
+    ))
+}
+
 /// Create a new namespace
 #[utoipa::path(
     post,
@@ -287,6 +451,36 @@ struct ComputeGraphCreateType {
     code: String,
 }
 
+// Whether `graph` starts `target` through its subgraphs, at any depth
+fn reaches_compute_graph(
+    state: &RouteState,
+    graph: &data_model::ComputeGraph,
+    target: &str,
+) -> Result<bool> {
+    let reader = state.indexify_state.reader();
+    let mut visited = HashSet::new();
+    let mut graphs = vec![graph.clone()];
+    while let Some(graph) = graphs.pop() {
+        for node in graph.nodes.values() {
+            let data_model::Node::Subgraph(subgraph) = node else {
+                continue;
+            };
+            if subgraph.compute_graph_name == target {
+                return Ok(true);
+            }
+            if !visited.insert(subgraph.compute_graph_name.clone()) {
+                continue;
+            }
+            if let Some(child) =
+                reader.get_compute_graph(&graph.namespace, &subgraph.compute_graph_name)?
+            {
+                graphs.push(child);
+            }
+        }
+    }
+    Ok(false)
+}
+
 /// Create compute graph
 #[utoipa::path(
     post,
@@ -301,14 +495,46 @@ struct ComputeGraphCreateType {
 async fn create_compute_graph(
     Path(namespace): Path<String>,
     State(state): State<RouteState>,
-    mut compute_graph_code: Multipart,
+    compute_graph_code: Multipart,
 ) -> Result<(), IndexifyAPIError> {
+    let mut put_result = None;
+    let result =
+        register_compute_graph(&state, namespace, compute_graph_code, &mut put_result).await;
+    // The code is uploaded while the request is read, it's garbage collected
+    // when the graph isn't created
+    if result.is_err() {
+        if let Some(put_result) = put_result {
+            discard_blobs(&state, vec![put_result.url]).await;
+        }
+    }
+    let name = result?;
+    info!("compute graph created: {}", name);
+    Ok(())
+}
+
+/// Uploads the code of a compute graph, validates the graph and writes it.
+/// Returns the name of the graph, `put_result` is set once the code is
+/// uploaded.
+async fn register_compute_graph(
+    state: &RouteState,
+    namespace: String,
+    mut compute_graph_code: Multipart,
+    put_result: &mut Option<PutResult>,
+) -> Result<String, IndexifyAPIError> {
     let mut compute_graph_definition: Option<ComputeGraph> = Option::None;
-    let mut put_result: Option<PutResult> = None;
-    while let Some(field) = compute_graph_code.next_field().await.unwrap() {
+    while let Some(field) = compute_graph_code
+        .next_field()
+        .await
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?
+    {
         let name = field.name();
         if let Some(name) = name {
             if name == "code" {
+                if put_result.is_some() {
+                    return Err(IndexifyAPIError::bad_request(
+                        "Code is given more than once",
+                    ));
+                }
                 let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
                 let file_name = format!("{}_{}", namespace, nanoid!());
                 let result = state
@@ -316,7 +542,7 @@ async fn create_compute_graph(
                     .put(&file_name, stream)
                     .await
                     .map_err(IndexifyAPIError::internal_error)?;
-                put_result = Some(result);
+                *put_result = Some(result);
             } else if name == "compute_graph" {
                 let text = field
                     .text()
@@ -335,16 +561,71 @@ async fn create_compute_graph(
         ));
     }
 
-    if put_result.is_none() {
+    let Some(put_result) = put_result.as_ref() else {
         return Err(IndexifyAPIError::bad_request("Code is required"));
-    }
-    let put_result = put_result.unwrap();
+    };
     let compute_graph_definition = compute_graph_definition.unwrap();
     let compute_graph = compute_graph_definition.into_data_model(
         &put_result.url,
         &put_result.sha256_hash,
         put_result.size_bytes,
     )?;
+    compute_graph
+        .validate_schemas()
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    // Secrets are resolved when tasks are handed to executors, but must
+    // exist when the graph is registered
+    for node in compute_graph.nodes.values() {
+        let data_model::Node::Compute(compute_fn) = node else {
+            continue;
+        };
+        for secret in &compute_fn.secrets {
+            let exists = state
+                .indexify_state
+                .reader()
+                .get_secret(&namespace, secret)
+                .map_err(IndexifyAPIError::internal_error)?
+                .is_some();
+            if !exists {
+                return Err(IndexifyAPIError::bad_request(&format!(
+                    "{} references unknown secret {}",
+                    compute_fn.name, secret
+                )));
+            }
+        }
+    }
+    // Subgraphs must already be registered, and a graph can't start itself,
+    // directly or through the subgraphs of its subgraphs
+    for node in compute_graph.nodes.values() {
+        let data_model::Node::Subgraph(subgraph) = node else {
+            continue;
+        };
+        if subgraph.compute_graph_name == compute_graph.name {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "subgraph {} references its own compute graph",
+                subgraph.name
+            )));
+        }
+        let child = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(&namespace, &subgraph.compute_graph_name)
+            .map_err(IndexifyAPIError::internal_error)?;
+        let Some(child) = child else {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "subgraph {} references unknown compute graph {}",
+                subgraph.name, subgraph.compute_graph_name
+            )));
+        };
+        if reaches_compute_graph(state, &child, &compute_graph.name)
+            .map_err(IndexifyAPIError::internal_error)?
+        {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "subgraph {} creates a cycle: {} starts {}",
+                subgraph.name, subgraph.compute_graph_name, compute_graph.name
+            )));
+        }
+    }
     let name = compute_graph.name.clone();
     let request = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
         namespace,
@@ -358,8 +639,7 @@ async fn create_compute_graph(
         })
         .await
         .map_err(IndexifyAPIError::internal_error)?;
-    info!("compute graph created: {}", name);
-    Ok(())
+    Ok(name)
 }
 
 /// Delete compute graph
@@ -495,27 +775,39 @@ async fn executor_tasks(
     Json(payload): Json<ExecutorMetadata>,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
     const TASK_LIMIT: usize = 10;
+    let executor = data_model::ExecutorMetadata {
+        id: executor_id.clone(),
+        runner_name: payload.runner_name.clone(),
+        addr: payload.address.clone(),
+        labels: payload.labels.clone(),
+        draining: false,
+    };
     state
         .executor_manager
-        .register_executor(data_model::ExecutorMetadata {
-            id: executor_id.clone(),
-            runner_name: payload.runner_name.clone(),
-            addr: payload.address.clone(),
-            labels: payload.labels.clone(),
-        })
+        .register_executor(executor.clone())
         .await
         .map_err(|e| IndexifyAPIError::internal_error(e))?;
+    let indexify_state = state.indexify_state.clone();
     let stream = state_store::task_stream(state.indexify_state, executor_id.clone(), TASK_LIMIT);
     let executor_manager = state.executor_manager.clone();
     let stream = stream
-        .map(|item| match item {
-            Ok(item) => {
-                let item: Vec<Task> = item.into_iter().map(Into::into).collect();
-                axum::response::sse::Event::default().json_data(item)
-            }
-            Err(e) => {
-                tracing::error!("error in task stream: {}", e);
-                Err(axum::Error::new(e))
+        .then(move |item| {
+            let indexify_state = indexify_state.clone();
+            let executor = executor.clone();
+            async move {
+                let tasks = match item {
+                    Ok(tasks) => {
+                        executor_tasks_with_secrets(&indexify_state, &executor, tasks).await
+                    }
+                    Err(e) => Err(e),
+                };
+                match tasks {
+                    Ok(item) => axum::response::sse::Event::default().json_data(item),
+                    Err(e) => {
+                        tracing::error!("error in task stream: {}", e);
+                        Err(axum::Error::new(e))
+                    }
+                }
             }
         })
         .guard(|| executors::schedule_deregister(executor_manager, executor_id, EXECUTOR_TIMEOUT));
@@ -526,6 +818,191 @@ async fn executor_tasks(
     ))
 }
 
+// Attaches the secrets of the tasks' functions. Placement only picks
+// executors the secrets authorize, so a task whose secrets can't be resolved
+// was placed before a secret was deleted or relabeled. It's failed instead of
+// running without them.
+async fn executor_tasks_with_secrets(
+    indexify_state: &IndexifyState,
+    executor: &data_model::ExecutorMetadata,
+    tasks: Vec<data_model::Task>,
+) -> Result<Vec<ExecutorTask>> {
+    let mut executor_tasks = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let secrets = match task_secrets(indexify_state, executor, &task) {
+            Ok(secrets) => secrets,
+            Err(e) => match e.downcast_ref::<StateMachineError>() {
+                Some(StateMachineError::SecretUnavailable { .. }) => {
+                    tracing::error!("failing task {}: {}", task.id, e);
+                    fail_task(indexify_state, executor, task).await?;
+                    continue;
+                }
+                _ => return Err(e),
+            },
+        };
+        executor_tasks.push(ExecutorTask {
+            task: task.into(),
+            secrets,
+        });
+    }
+    Ok(executor_tasks)
+}
+
+fn task_secrets(
+    indexify_state: &IndexifyState,
+    executor: &data_model::ExecutorMetadata,
+    task: &data_model::Task,
+) -> Result<HashMap<String, String>> {
+    let secret_names = indexify_state
+        .reader()
+        .get_compute_graph(&task.namespace, &task.compute_graph_name)?
+        .and_then(|graph| {
+            graph
+                .nodes
+                .get(&task.compute_fn_name)
+                .map(|node| node.secrets().to_vec())
+        })
+        .unwrap_or_default();
+    if secret_names.is_empty() {
+        return Ok(HashMap::new());
+    }
+    indexify_state.resolve_secrets(&task.namespace, &secret_names, executor)
+}
+
+async fn fail_task(
+    indexify_state: &IndexifyState,
+    executor: &data_model::ExecutorMetadata,
+    task: data_model::Task,
+) -> Result<()> {
+    indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                namespace: task.namespace,
+                compute_graph: task.compute_graph_name,
+                compute_fn: task.compute_fn_name,
+                invocation_id: task.invocation_id,
+                task_id: task.id,
+                node_outputs: vec![],
+                task_outcome: data_model::TaskOutcome::Failure,
+                executor_id: executor.id.clone(),
+                diagnostics: Default::default(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+}
+
+async fn executor_heartbeat(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    Json(heartbeat): Json<ExecutorHeartbeat>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .executor_manager
+        .heartbeat(executor_id, heartbeat.into())
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// List registered executors with their health and last reported capacity
+#[utoipa::path(
+    get,
+    path = "/executors",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List of executors", body = ExecutorsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn list_executors(
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorsList>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let executors = reader
+        .get_all_executors()
+        .map_err(IndexifyAPIError::internal_error)?;
+    let heartbeats = state.indexify_state.executor_heartbeats();
+    let now = get_epoch_time_in_ms();
+    let allocation_counts = state.indexify_state.allocation_counts.read().unwrap();
+    let mut list = Vec::new();
+    for executor in executors {
+        let allocated_tasks = allocation_counts.executor(&executor.id);
+        let heartbeat = heartbeats.get(&executor.id);
+        list.push(Executor {
+            id: executor.id.to_string(),
+            address: executor.addr,
+            runner_name: executor.runner_name,
+            labels: executor.labels,
+            draining: executor.draining,
+            health: ExecutorHealth::from_heartbeat(heartbeat, now),
+            allocated_tasks,
+            last_heartbeat: heartbeat.cloned().map(Into::into),
+        });
+    }
+    Ok(Json(ExecutorsList { executors: list }))
+}
+
+fn executor_exists(state: &RouteState, executor_id: &ExecutorId) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_executor(executor_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("executor not found"))?;
+    Ok(())
+}
+
+/// Stop placing new tasks on an executor, optionally evicting its allocated
+/// tasks. The executor stays drained until it's resumed or deregistered.
+#[utoipa::path(
+    post,
+    path = "/executors/{id}/drain",
+    params(
+        ("evict" = Option<bool>, Query, description = "Return the executor's allocated tasks to the queue"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Executor is draining"),
+        (status = NOT_FOUND, description = "Executor not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn drain_executor(
+    Path(executor_id): Path<ExecutorId>,
+    Query(params): Query<DrainParams>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    executor_exists(&state, &executor_id)?;
+    state
+        .executor_manager
+        .drain_executor(executor_id, params.evict)
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Put a drained executor back in rotation
+#[utoipa::path(
+    post,
+    path = "/executors/{id}/resume",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Executor is accepting tasks"),
+        (status = NOT_FOUND, description = "Executor not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn resume_executor(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    executor_exists(&state, &executor_id)?;
+    state
+        .executor_manager
+        .resume_executor(executor_id)
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
 /// List tasks for a compute graph invocation
 #[utoipa::path(
     get,
@@ -619,6 +1096,67 @@ async fn delete_invocation(
     Ok(())
 }
 
+/// Rerun an invocation from a function. The function's outputs and everything
+/// downstream of it are discarded and recomputed from the stored upstream
+/// outputs.
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/rerun",
+    request_body = RerunInvocation,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Invocation is running again from the function"),
+        (status = BAD_REQUEST, description = "Function is a subgraph node"),
+        (status = NOT_FOUND, description = "Invocation or function not found"),
+        (status = CONFLICT, description = "Invocation has tasks or child invocations in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn rerun_invocation(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<RerunInvocation>,
+) -> Result<(), IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let graph = reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    match graph.nodes.get(&request.compute_fn) {
+        None => return Err(IndexifyAPIError::not_found("Compute Fn not found")),
+        Some(data_model::Node::Subgraph(_)) => {
+            return Err(IndexifyAPIError::bad_request(
+                "Subgraph nodes can't be rerun, rerun the function feeding them",
+            ))
+        }
+        Some(data_model::Node::Compute(_)) | Some(data_model::Node::Router(_)) => {}
+    }
+    let ctx = reader
+        .invocation_ctx(&namespace, &compute_graph, &invocation_id)
+        .map_err(|_| IndexifyAPIError::not_found("Invocation not found"))?;
+    if !ctx.completed || ctx.pending_tasks() > 0 {
+        return Err(IndexifyAPIError::conflict("Invocation is still running"));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RerunInvocation(RerunInvocationRequest {
+                namespace,
+                compute_graph,
+                invocation_id,
+                compute_fn: request.compute_fn,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(|e| match e.downcast_ref::<StateMachineError>() {
+            Some(e @ StateMachineError::InvocationInProgress(_)) => {
+                IndexifyAPIError::conflict(&e.to_string())
+            }
+            _ => IndexifyAPIError::internal_error(e),
+        })
+}
+
 async fn get_code(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use state_store::requests::{RequestPayload, StateMachineUpdateRequest};
+
+use super::RouteState;
+use crate::http_objects::{
+    DeadLetter,
+    DeadLetters,
+    IndexifyAPIError,
+    ListParams,
+    RequeueDeadLetters,
+    RequeuedTasks,
+};
+
+async fn requeue(
+    state: &RouteState,
+    dead_letters: Vec<data_model::DeadLetter>,
+) -> Result<Json<RequeuedTasks>, IndexifyAPIError> {
+    let task_ids = dead_letters.iter().map(|d| d.task_id.to_string()).collect();
+    let keys = dead_letters.iter().map(|d| d.key()).collect();
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RequeueDeadLetters(keys),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(RequeuedTasks { task_ids }))
+}
+
+fn find_dead_letter(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+    task_id: &str,
+) -> Result<data_model::DeadLetter, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .dead_letter(namespace, compute_graph, invocation_id, task_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("dead letter not found"))
+}
+
+fn with_task(
+    state: &RouteState,
+    dead_letter: data_model::DeadLetter,
+) -> Result<DeadLetter, IndexifyAPIError> {
+    let task = state
+        .indexify_state
+        .reader()
+        .dead_letter_task(&dead_letter)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::internal_error_str(&format!(
+            "task of dead letter not found: {}",
+            dead_letter.key()
+        )))?;
+    Ok(DeadLetter::new(dead_letter, task))
+}
+
+/// List the failed tasks of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/dead_letters",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List dead lettered tasks", body = DeadLetters),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_dead_letters(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<DeadLetters>, IndexifyAPIError> {
+    let (dead_letters, cursor) = state
+        .indexify_state
+        .reader()
+        .list_dead_letters(
+            &namespace,
+            &compute_graph,
+            params.cursor.as_deref(),
+            params.limit,
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    let dead_letters = dead_letters
+        .into_iter()
+        .map(|dead_letter| with_task(&state, dead_letter))
+        .collect::<Result<_, _>>()?;
+    Ok(Json(DeadLetters {
+        dead_letters,
+        cursor,
+    }))
+}
+
+/// Get a dead lettered task
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/dead_letters/{task_id}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Dead lettered task", body = DeadLetter),
+        (status = 404, description = "dead letter not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_dead_letter(
+    Path((namespace, compute_graph, invocation_id, task_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+) -> Result<Json<DeadLetter>, IndexifyAPIError> {
+    let dead_letter =
+        find_dead_letter(&state, &namespace, &compute_graph, &invocation_id, &task_id)?;
+    Ok(Json(with_task(&state, dead_letter)?))
+}
+
+/// Requeue a dead lettered task into its invocation
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/dead_letters/{task_id}/requeue",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Task requeued", body = RequeuedTasks),
+        (status = 404, description = "dead letter not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn requeue_dead_letter(
+    Path((namespace, compute_graph, invocation_id, task_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+) -> Result<Json<RequeuedTasks>, IndexifyAPIError> {
+    let dead_letter =
+        find_dead_letter(&state, &namespace, &compute_graph, &invocation_id, &task_id)?;
+    requeue(&state, vec![dead_letter]).await
+}
+
+/// Requeue all dead lettered tasks of a compute graph, optionally limited to
+/// one invocation
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/dead_letters/requeue",
+    request_body = RequeueDeadLetters,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Tasks requeued", body = RequeuedTasks),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn requeue_dead_letters(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<RequeueDeadLetters>,
+) -> Result<Json<RequeuedTasks>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let dead_letters = match request.invocation_id {
+        Some(invocation_id) => {
+            reader.dead_letters_by_invocation(&namespace, &compute_graph, &invocation_id)
+        }
+        None => reader
+            .list_dead_letters(&namespace, &compute_graph, None, None)
+            .map(|(dead_letters, _)| dead_letters),
+    }
+    .map_err(IndexifyAPIError::internal_error)?;
+    if dead_letters.is_empty() {
+        return Ok(Json(RequeuedTasks { task_ids: vec![] }));
+    }
+    requeue(&state, dead_letters).await
+}
@@ -3,7 +3,14 @@ use std::{collections::HashMap, vec};
 use anyhow::{anyhow, Result};
 use axum::extract::{Multipart, State};
 use blob_store::PutResult;
-use data_model::{ExecutorId, NodeOutput, NodeOutputBuilder, OutputPayload, TaskId};
+use data_model::{
+    ExecutorId,
+    NodeOutput,
+    NodeOutputBuilder,
+    OutputPayload,
+    TaskDiagnostics,
+    TaskId,
+};
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use state_store::requests::{FinalizeTaskRequest, RequestPayload, StateMachineUpdateRequest};
@@ -11,7 +18,7 @@ use tracing::info;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::RouteState;
+use super::{logs::delete_live_logs, RouteState};
 use crate::http_objects::IndexifyAPIError;
 
 #[derive(Serialize, Deserialize)]
@@ -49,6 +56,11 @@ pub struct TaskResult {
     task_id: String,
     invocation_id: String,
     executor_id: String,
+    #[serde(default)]
+    error_message: Option<String>,
+    // Metadata of each node output, in upload order
+    #[serde(default)]
+    output_metadata: Vec<HashMap<String, serde_json::Value>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -88,6 +100,7 @@ pub async fn ingest_files_from_executor(
 ) -> Result<(), IndexifyAPIError> {
     let mut output_objects: Vec<PutResult> = vec![];
     let mut task_result: Option<TaskResult> = None;
+    let mut diagnostics = TaskDiagnostics::default();
     while let Some(field) = files.next_field().await.unwrap() {
         if let Some(name) = field.name() {
             if name == "node_outputs" {
@@ -106,6 +119,29 @@ pub async fn ingest_files_from_executor(
                     ))
                 })?;
                 output_objects.push(res.clone());
+            } else if name == "stdout" || name == "stderr" || name == "logs" {
+                let name = name.to_string();
+                let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+                let res = state
+                    .blob_storage
+                    .put(&Uuid::new_v4().to_string(), stream)
+                    .await
+                    .map_err(|e| {
+                        IndexifyAPIError::internal_error(anyhow!(
+                            "failed to write to blob store: {}",
+                            e
+                        ))
+                    })?;
+                let payload = data_model::DataPayload {
+                    path: res.url,
+                    size: res.size_bytes,
+                    sha256_hash: res.sha256_hash,
+                };
+                match name.as_str() {
+                    "stdout" => diagnostics.stdout = Some(payload),
+                    "stderr" => diagnostics.stderr = Some(payload),
+                    _ => diagnostics.logs = Some(payload),
+                }
             } else if name == "task_result" {
                 let text = field
                     .text()
@@ -117,7 +153,9 @@ pub async fn ingest_files_from_executor(
     }
     let task_result =
         task_result.ok_or(IndexifyAPIError::bad_request("task_result is required"))?;
+    diagnostics.error_message = task_result.error_message.clone();
     let mut node_outputs: Vec<NodeOutput> = vec![];
+    let mut output_metadata = task_result.output_metadata.clone().into_iter();
     for put_result in output_objects {
         let data_payload = data_model::DataPayload {
             path: put_result.url,
@@ -130,6 +168,7 @@ pub async fn ingest_files_from_executor(
             .invocation_id(task_result.invocation_id.to_string())
             .compute_fn_name(task_result.compute_fn.to_string())
             .payload(OutputPayload::Fn(data_payload))
+            .metadata(output_metadata.next().unwrap_or_default())
             .build()
             .map_err(|e| {
                 IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
@@ -160,6 +199,7 @@ pub async fn ingest_files_from_executor(
         node_outputs,
         task_outcome: task_result.outcome.clone().into(),
         executor_id: ExecutorId::new(task_result.executor_id.clone()),
+        diagnostics,
     });
     state
         .indexify_state
@@ -171,5 +211,14 @@ pub async fn ingest_files_from_executor(
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
+    delete_live_logs(
+        &state.blob_storage,
+        &task_result.namespace,
+        &task_result.compute_graph,
+        &task_result.invocation_id,
+        &task_result.compute_fn,
+        &task_result.task_id,
+    )
+    .await;
     Ok(())
 }
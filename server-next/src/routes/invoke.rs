@@ -1,21 +1,202 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::anyhow;
 use axum::{
-    body::Body,
-    extract::{Multipart, Path, State},
+    body::{Body, Bytes},
+    extract::{multipart::Field, FromRequest, Multipart, Path, Query, Request, State},
+    http::{header::CONTENT_TYPE, HeaderMap},
     Json,
 };
 use blob_store::PutResult;
-use data_model::InvocationPayloadBuilder;
-use futures::{stream, StreamExt};
-use state_store::requests::{InvokeComputeGraphRequest, RequestPayload, StateMachineUpdateRequest};
+use bytes::BytesMut;
+use data_model::{
+    schema::{PayloadSchema, PayloadValidator},
+    IdempotencyKey,
+    InvocationBatch,
+    InvocationOutcome,
+    InvocationPayload,
+    InvocationPayloadBuilder,
+    Node,
+};
+use futures::{stream, stream::BoxStream, StreamExt};
+use indexify_utils::get_epoch_time_in_ms;
+use sha2::{Digest, Sha256};
+use state_store::requests::{
+    InvokeComputeGraphBatchRequest,
+    InvokeComputeGraphRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+};
 use tracing::info;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
 use super::RouteState;
-use crate::http_objects::{GraphInputFile, IndexifyAPIError, InvocationId};
+use crate::http_objects::{
+    GraphInputFile,
+    IndexifyAPIError,
+    InvocationBatchProgress,
+    InvocationBatchResult,
+    InvocationId,
+    InvokeParams,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+// How long a replayed request returns the original invocation
+const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn idempotency_key_header(headers: &HeaderMap) -> Result<Option<String>, IndexifyAPIError> {
+    headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .map(|key| key.to_string())
+                .map_err(|_| IndexifyAPIError::bad_request("invalid idempotency key"))
+        })
+        .transpose()
+}
+
+/// Looks up the idempotency key of the request, if it was already used
+fn existing_idempotency_key(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    idempotency_key: Option<&String>,
+) -> Result<Option<IdempotencyKey>, IndexifyAPIError> {
+    let Some(idempotency_key) = idempotency_key else {
+        return Ok(None);
+    };
+    state
+        .indexify_state
+        .reader()
+        .idempotency_key(namespace, compute_graph, idempotency_key)
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Returns the invocation created with an existing idempotency key. Reusing a
+/// key with a different payload is a conflict.
+fn replayed_id(existing: IdempotencyKey, payload_sha256: &str) -> Result<String, IndexifyAPIError> {
+    if existing.payload_sha256 != payload_sha256 {
+        return Err(IndexifyAPIError::conflict(
+            "idempotency key was already used with a different payload",
+        ));
+    }
+    Ok(existing.invocation_id)
+}
+
+/// Returns the invocation created by an earlier request with the same
+/// idempotency key. Reusing a key with a different payload is a conflict.
+fn replayed_invocation(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    idempotency_key: Option<&String>,
+    payload_sha256: &str,
+) -> Result<Option<String>, IndexifyAPIError> {
+    existing_idempotency_key(state, namespace, compute_graph, idempotency_key)?
+        .map(|existing| replayed_id(existing, payload_sha256))
+        .transpose()
+}
+
+/// Hashes a payload the way the blob store does, for replays which aren't
+/// uploaded again
+async fn payload_sha256(
+    mut payload: BoxStream<'_, anyhow::Result<Bytes>>,
+) -> Result<String, IndexifyAPIError> {
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        hasher.update(&chunk);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hash of a request that idempotency keys are checked against, the payload's
+/// hash and the parameters of the invocation. Replaying a key with another
+/// priority is a conflict like replaying it with another payload.
+fn request_sha256(payload_sha256: &str, params: &InvokeParams) -> Result<String, IndexifyAPIError> {
+    Ok(format!(
+        "{:x}",
+        Sha256::new()
+            .chain_update(payload_sha256)
+            .chain_update(serde_json::to_vec(params)?)
+            .finalize()
+    ))
+}
+
+fn new_idempotency_key(
+    namespace: &str,
+    compute_graph: &str,
+    idempotency_key: Option<String>,
+    invocation_id: &str,
+    payload_sha256: &str,
+) -> Option<IdempotencyKey> {
+    idempotency_key.map(|key| IdempotencyKey {
+        namespace: namespace.to_string(),
+        compute_graph_name: compute_graph.to_string(),
+        key,
+        invocation_id: invocation_id.to_string(),
+        payload_sha256: payload_sha256.to_string(),
+        expires_at: get_epoch_time_in_ms() + IDEMPOTENCY_KEY_TTL.as_millis() as u64,
+    })
+}
+
+/// Hands the blobs of a request which didn't create its invocations to the
+/// garbage collector
+pub(super) async fn discard_blobs(state: &RouteState, urls: Vec<String>) {
+    let result = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::AddGcUrls(urls.clone()),
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(e) = result {
+        tracing::error!("failed to collect unused uploads {:?}: {:?}", urls, e);
+    }
+}
+
+/// Writes the invocation and returns its id. A concurrent request with the
+/// same idempotency key can create its invocation first, in which case that
+/// invocation is replayed and the blobs of this request are deleted.
+async fn write_invocation(
+    state: &RouteState,
+    request: InvokeComputeGraphRequest,
+    payload_sha256: &str,
+    blob_urls: Vec<String>,
+) -> Result<String, IndexifyAPIError> {
+    let id = request.invocation_payload.id.clone();
+    let namespace = request.namespace.clone();
+    let compute_graph = request.compute_graph_name.clone();
+    let idempotency_key = request.idempotency_key.as_ref().map(|key| key.key.clone());
+    let Err(err) = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::InvokeComputeGraph(request),
+            state_changes_processed: vec![],
+        })
+        .await
+    else {
+        return Ok(id);
+    };
+    let replayed = replayed_invocation(
+        state,
+        &namespace,
+        &compute_graph,
+        idempotency_key.as_ref(),
+        payload_sha256,
+    );
+    discard_blobs(state, blob_urls).await;
+    match replayed? {
+        Some(id) => Ok(id),
+        None => Err(IndexifyAPIError::internal_error(anyhow!(
+            "failed to upload content: {}",
+            err
+        ))),
+    }
+}
 
 #[allow(dead_code)]
 #[derive(ToSchema)]
@@ -31,27 +212,46 @@ pub struct InvokeWithFile {
     post,
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_file",
     request_body(content_type = "multipart/form-data", content = inline(InvokeWithFile)),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays with the same key return the original invocation"),
+        ("priority" = Option<Priority>, Query, description = "Scheduling priority of the invocation, defaults to normal"),
+    ),
     tag = "ingestion",
     responses(
         (status = 200, description = "upload successful"),
-        (status = 400, description = "bad request"),
+        (status = 400, description = "bad request, or the file doesn't match the start function's input"),
+        (status = 409, description = "idempotency key reused with a different payload"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 pub async fn invoke_with_file(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
+    Query(params): Query<InvokeParams>,
+    headers: HeaderMap,
     mut files: Multipart,
 ) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    let idempotency_key = idempotency_key_header(&headers)?;
+    let existing =
+        existing_idempotency_key(&state, &namespace, &compute_graph, idempotency_key.as_ref())?;
+    let input = start_fn_input(&state, &namespace, &compute_graph)?;
     let mut metadata: Option<serde_json::Value> = None;
     let mut put_result: Option<PutResult> = None;
+    let mut replayed_file_sha256: Option<String> = None;
 
     while let Some(field) = files.next_field().await.unwrap() {
         if let Some(name) = field.name() {
             if name == "file" {
+                let stream = checked_part(input.as_ref(), field)
+                    .await
+                    .map_err(|e| IndexifyAPIError::bad_request(&format!("invalid file: {}", e)))?;
+                // Replays are answered without uploading the file again
+                if existing.is_some() {
+                    replayed_file_sha256 = Some(payload_sha256(stream).await?);
+                    continue;
+                }
                 let name = Uuid::new_v4().to_string();
                 info!("writing to blob store, file name = {:?}", name);
-                let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
                 let res = state.blob_storage.put(&name, stream).await.map_err(|e| {
                     IndexifyAPIError::internal_error(anyhow!(
                         "failed to write to blob store: {}",
@@ -69,12 +269,28 @@ pub async fn invoke_with_file(
             }
         }
     }
-    if put_result.is_none() {
-        return Err(IndexifyAPIError::bad_request("file is required"));
+    let file_sha256 = match (&put_result, replayed_file_sha256) {
+        (Some(put_result), _) => put_result.sha256_hash.clone(),
+        (None, Some(file_sha256)) => file_sha256,
+        (None, None) => return Err(IndexifyAPIError::bad_request("file is required")),
+    };
+    let metadata = metadata.unwrap_or_default();
+    let file_and_metadata_sha256 = format!(
+        "{:x}",
+        Sha256::new()
+            .chain_update(&file_sha256)
+            .chain_update(serde_json::to_vec(&metadata)?)
+            .finalize()
+    );
+    let payload_sha256 = request_sha256(&file_and_metadata_sha256, &params)?;
+    if let Some(existing) = existing {
+        let id = replayed_id(existing, &payload_sha256)?;
+        return Ok(Json(InvocationId { id }));
     }
-    let put_result = put_result.unwrap();
+    let put_result = put_result.ok_or(IndexifyAPIError::bad_request("file is required"))?;
+    let file_url = put_result.url.clone();
     let payload = GraphInputFile {
-        metadata: metadata.unwrap_or_default(),
+        metadata,
         url: put_result.url.clone(),
         sha_256: put_result.sha256_hash.clone(),
         size: put_result.size_bytes,
@@ -84,13 +300,21 @@ pub async fn invoke_with_file(
         let payload_json = serde_json::to_string(&payload)?.as_bytes().to_vec().clone();
         Ok(payload_json.into())
     });
-    let put_result = state
+    let put_result = match state
         .blob_storage
         .put(&payload_key, Box::pin(payload_stream))
         .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+    {
+        Ok(put_result) => put_result,
+        Err(e) => {
+            discard_blobs(&state, vec![file_url]).await;
+            return Err(IndexifyAPIError::internal_error(anyhow!(
+                "failed to upload content: {}",
+                e
+            )));
+        }
+    };
+    let blob_urls = vec![file_url, put_result.url.clone()];
     let data_payload = data_model::DataPayload {
         path: put_result.url,
         size: put_result.size_bytes,
@@ -100,58 +324,185 @@ pub async fn invoke_with_file(
         .namespace(namespace.clone())
         .compute_graph_name(compute_graph.clone())
         .payload(data_payload)
+        .priority(params.priority.into())
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
 
-    let id = invocation_payload.id.clone();
-    let request = RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
-        namespace: namespace.clone(),
-        compute_graph_name: compute_graph.clone(),
+    let idempotency_key = new_idempotency_key(
+        &namespace,
+        &compute_graph,
+        idempotency_key,
+        &invocation_payload.id,
+        &payload_sha256,
+    );
+    let request = InvokeComputeGraphRequest {
+        namespace,
+        compute_graph_name: compute_graph,
         invocation_payload,
-    });
-    state
-        .indexify_state
-        .write(StateMachineUpdateRequest {
-            payload: request,
-            state_changes_processed: vec![],
-        })
-        .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+        idempotency_key,
+    };
+    let id = write_invocation(&state, request, &payload_sha256, blob_urls).await?;
     Ok(Json(InvocationId { id }))
 }
 
+/// Input declaration of the graph's start function, with its schema
+/// compiled once per request
+struct StartFnInput {
+    schema: PayloadSchema,
+    validator: PayloadValidator,
+}
+
+impl StartFnInput {
+    fn require_json(&self) -> Result<(), IndexifyAPIError> {
+        if self.schema.is_json() {
+            return Ok(());
+        }
+        Err(IndexifyAPIError::bad_request(&format!(
+            "start function expects {}",
+            self.schema.content_type
+        )))
+    }
+
+    fn validate_json(&self, input: &[u8]) -> anyhow::Result<()> {
+        let value: serde_json::Value =
+            serde_json::from_slice(input).map_err(|e| anyhow!("invalid json: {}", e))?;
+        self.validator
+            .validate(&value)
+            .map_err(|e| anyhow!("input does not match schema: {}", e))
+    }
+}
+
+/// Input declaration of the graph's start function, if it has one
+fn start_fn_input(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<Option<StartFnInput>, IndexifyAPIError> {
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(namespace, compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let Some(Node::Compute(compute_fn)) = graph.map(|graph| graph.start_fn) else {
+        return Ok(None);
+    };
+    let Some(schema) = compute_fn.input else {
+        return Ok(None);
+    };
+    let validator = schema
+        .validator()
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Some(StartFnInput { schema, validator }))
+}
+
+/// Reads an object to validate it against the start function's input.
+/// Objects larger than `limit` bytes are rejected instead of being held in
+/// memory.
+async fn validated_object(
+    input: &StartFnInput,
+    body: Body,
+    limit: usize,
+) -> Result<Bytes, IndexifyAPIError> {
+    input.require_json()?;
+    let mut body = body.into_data_stream();
+    let mut object = BytesMut::new();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        if object.len() + chunk.len() > limit {
+            return Err(IndexifyAPIError::payload_too_large(&format!(
+                "object is larger than {} bytes",
+                limit
+            )));
+        }
+        object.extend_from_slice(&chunk);
+    }
+    let object = object.freeze();
+    input
+        .validate_json(&object)
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    Ok(object)
+}
+
+/// Checks a multipart input against the start function's declaration. JSON
+/// inputs are read into memory to be validated, others are streamed once
+/// their content type is checked.
+async fn checked_part<'a>(
+    input: Option<&StartFnInput>,
+    part: Field<'a>,
+) -> anyhow::Result<BoxStream<'a, anyhow::Result<Bytes>>> {
+    let Some(input) = input else {
+        return Ok(part.map(|res| res.map_err(|err| anyhow!(err))).boxed());
+    };
+    if !input.schema.is_json() {
+        if let Some(content_type) = part.content_type() {
+            input.schema.accepts(&PayloadSchema {
+                content_type: content_type.to_string(),
+                json_schema: None,
+            })?;
+        }
+        return Ok(part.map(|res| res.map_err(|err| anyhow!(err))).boxed());
+    }
+    let bytes = part.bytes().await?;
+    input.validate_json(&bytes)?;
+    Ok(stream::once(async move { Ok(bytes) }).boxed())
+}
+
 /// Upload JSON serialized object to a compute graph
 #[utoipa::path(
     post,
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_object",
     request_body(content_type = "application/json", content = inline(serde_json::Value)),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays with the same key return the original invocation"),
+        ("priority" = Option<Priority>, Query, description = "Scheduling priority of the invocation, defaults to normal"),
+    ),
     tag = "ingestion",
     responses(
         (status = 200, description = "invocation successful"),
-        (status = 400, description = "bad request"),
+        (status = 400, description = "bad request, or the object doesn't match the start function's input schema"),
+        (status = 409, description = "idempotency key reused with a different payload"),
+        (status = 413, description = "the object is validated against the start function's input schema and is larger than the configured limit"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 pub async fn invoke_with_object(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
+    Query(params): Query<InvokeParams>,
+    headers: HeaderMap,
     body: Body,
 ) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    let idempotency_key = idempotency_key_header(&headers)?;
+    let existing =
+        existing_idempotency_key(&state, &namespace, &compute_graph, idempotency_key.as_ref())?;
     let payload_key = Uuid::new_v4().to_string();
-    let payload_stream = body
-        .into_data_stream()
-        .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+    let payload_stream = match start_fn_input(&state, &namespace, &compute_graph)? {
+        Some(input) => {
+            let object = validated_object(&input, body, state.max_object_body_bytes).await?;
+            stream::once(async move { Ok(object) }).boxed()
+        }
+        None => body
+            .into_data_stream()
+            .map(|res| res.map_err(|err| anyhow::anyhow!(err)))
+            .boxed(),
+    };
+    // Replays are answered without uploading the payload again
+    if let Some(existing) = existing {
+        let payload_sha256 = request_sha256(&payload_sha256(payload_stream).await?, &params)?;
+        let id = replayed_id(existing, &payload_sha256)?;
+        return Ok(Json(InvocationId { id }));
+    }
     let put_result = state
         .blob_storage
-        .put(&payload_key, Box::pin(payload_stream))
+        .put(&payload_key, payload_stream)
         .await
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
+    let payload_sha256 = request_sha256(&put_result.sha256_hash, &params)?;
+    let blob_urls = vec![put_result.url.clone()];
     let data_payload = data_model::DataPayload {
         path: put_result.url,
         size: put_result.size_bytes,
@@ -161,25 +512,294 @@ pub async fn invoke_with_object(
         .namespace(namespace.clone())
         .compute_graph_name(compute_graph.clone())
         .payload(data_payload)
+        .priority(params.priority.into())
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
-    let id = invocation_payload.id.clone();
-    let request = RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
-        namespace: namespace.clone(),
-        compute_graph_name: compute_graph.clone(),
+    let idempotency_key = new_idempotency_key(
+        &namespace,
+        &compute_graph,
+        idempotency_key,
+        &invocation_payload.id,
+        &payload_sha256,
+    );
+    let request = InvokeComputeGraphRequest {
+        namespace,
+        compute_graph_name: compute_graph,
         invocation_payload,
+        idempotency_key,
+    };
+    let id = write_invocation(&state, request, &payload_sha256, blob_urls).await?;
+    Ok(Json(InvocationId { id }))
+}
+
+async fn put_batch_input(
+    state: &RouteState,
+    input: impl futures::Stream<Item = anyhow::Result<Bytes>> + Send + Unpin,
+    inputs: &mut Vec<data_model::DataPayload>,
+) -> Result<(), IndexifyAPIError> {
+    let put_result = state
+        .blob_storage
+        .put(&Uuid::new_v4().to_string(), input)
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
+        })?;
+    inputs.push(data_model::DataPayload {
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
     });
-    state
+    Ok(())
+}
+
+async fn put_ndjson_line(
+    state: &RouteState,
+    input: Option<&StartFnInput>,
+    line: Bytes,
+    line_number: usize,
+    inputs: &mut Vec<data_model::DataPayload>,
+) -> Result<(), IndexifyAPIError> {
+    if line.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(());
+    }
+    let checked = match input {
+        Some(input) => input.validate_json(&line),
+        None => serde_json::from_slice::<serde::de::IgnoredAny>(&line)
+            .map(|_| ())
+            .map_err(|e| anyhow!(e)),
+    };
+    checked.map_err(|e| {
+        IndexifyAPIError::bad_request(&format!("invalid input on line {}: {}", line_number, e))
+    })?;
+    put_batch_input(
+        state,
+        Box::pin(stream::once(async move { Ok(line) })),
+        inputs,
+    )
+    .await
+}
+
+/// Uploads the inputs of a batch while the request is read, so only one
+/// input is held in memory at a time. Inputs are one JSON document per line
+/// for application/x-ndjson, or the parts of a multipart/form-data bundle.
+/// Every input is checked against the start function's declaration. Inputs
+/// uploaded before an error are left in `inputs`.
+async fn put_batch_inputs(
+    state: &RouteState,
+    input: Option<&StartFnInput>,
+    request: Request,
+    inputs: &mut Vec<data_model::DataPayload>,
+) -> Result<(), IndexifyAPIError> {
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if content_type.starts_with("multipart/form-data") {
+        let mut parts = Multipart::from_request(request, state)
+            .await
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        while let Some(part) = parts
+            .next_field()
+            .await
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?
+        {
+            let part = checked_part(input, part).await.map_err(|e| {
+                IndexifyAPIError::bad_request(&format!(
+                    "invalid input in part {}: {}",
+                    inputs.len() + 1,
+                    e
+                ))
+            })?;
+            put_batch_input(state, part, inputs).await?;
+        }
+    } else if content_type.starts_with("application/x-ndjson") {
+        if let Some(input) = input {
+            input.require_json()?;
+        }
+        let mut body = request.into_body().into_data_stream();
+        let mut buffer = BytesMut::new();
+        let mut line_number = 0;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            let mut search_from = buffer.len();
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer[search_from..].iter().position(|b| *b == b'\n') {
+                let mut line = buffer.split_to(search_from + end + 1).freeze();
+                line.truncate(line.len() - 1);
+                search_from = 0;
+                line_number += 1;
+                put_ndjson_line(state, input, line, line_number, inputs).await?;
+            }
+        }
+        put_ndjson_line(state, input, buffer.freeze(), line_number + 1, inputs).await?;
+    } else {
+        return Err(IndexifyAPIError::bad_request(
+            "batch must be sent as application/x-ndjson or multipart/form-data",
+        ));
+    }
+    if inputs.is_empty() {
+        return Err(IndexifyAPIError::bad_request("batch has no inputs"));
+    }
+    Ok(())
+}
+
+/// Invoke a compute graph with many inputs at once
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_batch",
+    request_body(content_type = "application/x-ndjson", content = inline(String), description = "One JSON input per line, or a multipart/form-data bundle with one input per part"),
+    params(
+        ("priority" = Option<Priority>, Query, description = "Scheduling priority of the invocations, defaults to normal"),
+    ),
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "invocations created", body = InvocationBatchResult),
+        (status = 400, description = "bad request, or an input doesn't match the start function's input"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn invoke_batch(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Query(params): Query<InvokeParams>,
+    request: Request,
+) -> Result<Json<InvocationBatchResult>, IndexifyAPIError> {
+    let input = start_fn_input(&state, &namespace, &compute_graph)?;
+    let mut inputs = Vec::new();
+    if let Err(e) = put_batch_inputs(&state, input.as_ref(), request, &mut inputs).await {
+        discard_blobs(&state, inputs.into_iter().map(|input| input.path).collect()).await;
+        return Err(e);
+    }
+    let mut invocation_payloads: Vec<InvocationPayload> = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .namespace(namespace.clone())
+            .compute_graph_name(compute_graph.clone())
+            .payload(input)
+            .priority(params.priority.into())
+            .build()
+            .map_err(IndexifyAPIError::internal_error)?;
+        invocation_payloads.push(invocation_payload);
+    }
+    let batch = InvocationBatch {
+        id: Uuid::new_v4().to_string(),
+        namespace: namespace.clone(),
+        compute_graph_name: compute_graph.clone(),
+        invocation_ids: invocation_payloads.iter().map(|p| p.id.clone()).collect(),
+        created_at: get_epoch_time_in_ms(),
+    };
+    let result = InvocationBatchResult {
+        batch_id: batch.id.clone(),
+        invocation_ids: batch.invocation_ids.clone(),
+    };
+    let blob_urls = invocation_payloads
+        .iter()
+        .map(|payload| payload.payload.path.clone())
+        .collect();
+    let written = state
         .indexify_state
         .write(StateMachineUpdateRequest {
-            payload: request,
+            payload: RequestPayload::InvokeComputeGraphBatch(InvokeComputeGraphBatchRequest {
+                batch,
+                invocation_payloads,
+            }),
             state_changes_processed: vec![],
         })
-        .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
-    Ok(Json(InvocationId { id }))
+        .await;
+    if let Err(e) = written {
+        discard_blobs(&state, blob_urls).await;
+        return Err(IndexifyAPIError::internal_error(anyhow!(
+            "failed to invoke batch: {}",
+            e
+        )));
+    }
+    Ok(Json(result))
+}
+
+/// Get the aggregate progress of an invocation batch
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/batches/{batch_id}",
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "batch progress", body = InvocationBatchProgress),
+        (status = 404, description = "batch not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn batch_progress(
+    Path((namespace, compute_graph, batch_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationBatchProgress>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let batch = reader
+        .invocation_batch(&namespace, &compute_graph, &batch_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("batch not found"))?;
+    let mut progress = InvocationBatchProgress {
+        id: batch.id,
+        total: batch.invocation_ids.len(),
+        pending: 0,
+        succeeded: 0,
+        failed: 0,
+        invocation_ids: batch.invocation_ids,
+    };
+    for invocation_id in &progress.invocation_ids {
+        // Invocations deleted since the batch was submitted are not counted
+        let Some(ctx) = reader
+            .get_invocation_ctx(&namespace, &compute_graph, invocation_id)
+            .map_err(IndexifyAPIError::internal_error)?
+        else {
+            continue;
+        };
+        match (ctx.completed, ctx.outcome()) {
+            (false, _) => progress.pending += 1,
+            (true, InvocationOutcome::Success) => progress.succeeded += 1,
+            (true, InvocationOutcome::Failure) => progress.failed += 1,
+        }
+    }
+    Ok(Json(progress))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    use super::*;
+    use crate::http_objects::Priority;
+
+    #[test]
+    fn test_replay_with_different_priority() {
+        let payload_sha256 = format!("{:x}", Sha256::digest(b"{\"a\": 1}"));
+        let high = InvokeParams {
+            priority: Priority::High,
+        };
+        let existing = || {
+            let request_sha256 = request_sha256(&payload_sha256, &high).unwrap();
+            new_idempotency_key(
+                "namespace",
+                "graph",
+                Some("key".to_string()),
+                "invocation",
+                &request_sha256,
+            )
+            .unwrap()
+        };
+
+        let replayed =
+            replayed_id(existing(), &request_sha256(&payload_sha256, &high).unwrap()).unwrap();
+        assert_eq!(replayed, "invocation");
+
+        // Same payload, different priority
+        let low = InvokeParams {
+            priority: Priority::Low,
+        };
+        let err =
+            replayed_id(existing(), &request_sha256(&payload_sha256, &low).unwrap()).unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+    }
 }
@@ -0,0 +1,462 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    response::Response,
+};
+use blob_store::BlobStorage;
+use bytes::Bytes;
+use data_model::{DataPayload, Task};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+
+use super::RouteState;
+use crate::http_objects::IndexifyAPIError;
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    // Structured log lines
+    Logs,
+}
+
+impl LogStream {
+    fn name(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::Logs => "logs",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            LogStream::Stdout | LogStream::Stderr => "text/plain; charset=utf-8",
+            LogStream::Logs => "application/x-ndjson",
+        }
+    }
+
+    fn payload(&self, task: &Task) -> Option<DataPayload> {
+        match self {
+            LogStream::Stdout => task.diagnostics.stdout.clone(),
+            LogStream::Stderr => task.diagnostics.stderr.clone(),
+            LogStream::Logs => task.diagnostics.logs.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct LogParams {
+    /// Only return the last N lines
+    pub tail: Option<usize>,
+    /// Block until the task finishes if its logs haven't been uploaded yet
+    #[serde(default)]
+    pub wait: bool,
+    /// How long to wait for the task to finish, in seconds
+    pub timeout: Option<u64>,
+    /// Keep streaming the output of a running task as the executor uploads
+    /// it, until the task finishes
+    #[serde(default)]
+    pub follow: bool,
+}
+
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_WAIT_TIMEOUT: Duration = Duration::from_secs(300);
+
+// How often a followed log is checked for new output
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Tails are read from the end of the log in chunks of this size, doubled until
+// they hold the requested number of lines
+const TAIL_CHUNK_BYTES: usize = 64 * 1024;
+
+fn find_task(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+    compute_fn: &str,
+    task_id: &str,
+) -> Result<Task, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_task(namespace, compute_graph, invocation_id, compute_fn, task_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("task not found"))
+}
+
+/// Blob the executor uploads the output of a running task to. Every upload
+/// replaces the output uploaded before, and the output uploaded when the task
+/// finishes starts with it.
+fn live_log_key(
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+    compute_fn: &str,
+    task_id: &str,
+    stream: LogStream,
+) -> String {
+    format!(
+        "task_logs/{}/{}/{}/{}/{}/{}",
+        namespace,
+        compute_graph,
+        invocation_id,
+        compute_fn,
+        task_id,
+        stream.name()
+    )
+}
+
+fn live_log_url(blob_storage: &BlobStorage, task: &Task, stream: LogStream) -> String {
+    let key = live_log_key(
+        &task.namespace,
+        &task.compute_graph_name,
+        &task.invocation_id,
+        &task.compute_fn_name,
+        &task.id.to_string(),
+        stream,
+    );
+    blob_storage.path_url(&object_store::path::Path::from(key))
+}
+
+/// Removes the output uploaded while the task ran, once the task finished
+/// and uploaded all of it
+pub async fn delete_live_logs(
+    blob_storage: &BlobStorage,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+    compute_fn: &str,
+    task_id: &str,
+) {
+    for stream in [LogStream::Stdout, LogStream::Stderr, LogStream::Logs] {
+        let key = live_log_key(
+            namespace,
+            compute_graph,
+            invocation_id,
+            compute_fn,
+            task_id,
+            stream,
+        );
+        let url = blob_storage.path_url(&object_store::path::Path::from(key));
+        if let Ok(Some(_)) = blob_storage.size(&url).await {
+            if let Err(e) = blob_storage.delete(&url).await {
+                warn!("unable to delete live log {}: {}", url, e);
+            }
+        }
+    }
+}
+
+/// Returns the last `lines` lines of the log, or None if the log has fewer
+/// lines than that and the part before it might be needed.
+fn tail(log: &[u8], lines: usize) -> Option<&[u8]> {
+    if lines == 0 {
+        return Some(&[]);
+    }
+    let log = log.strip_suffix(b"\n").unwrap_or(log);
+    log.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, b)| **b == b'\n')
+        .nth(lines - 1)
+        .map(|(pos, _)| &log[pos + 1..])
+}
+
+// Reads the end of the first `size` bytes of the log instead of downloading
+// all of it
+async fn read_tail(
+    blob_storage: &BlobStorage,
+    path: &str,
+    size: usize,
+    lines: usize,
+) -> Result<Bytes, IndexifyAPIError> {
+    let mut len = TAIL_CHUNK_BYTES;
+    loop {
+        let log = blob_storage
+            .read_range(path, size.saturating_sub(len)..size)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        if let Some(tail) = tail(&log, lines) {
+            return Ok(log.slice_ref(tail));
+        }
+        // The whole log was read and has fewer lines than requested
+        if len >= size {
+            return Ok(log.slice_ref(log.strip_suffix(b"\n").unwrap_or(&log)));
+        }
+        len *= 2;
+    }
+}
+
+/// Streams the log of the task from `offset`, first from the output uploaded
+/// while it runs, then from its final logs once it finished. Ends when the
+/// task finished and all of its log was sent.
+fn follow_log(
+    state: RouteState,
+    task: Task,
+    stream: LogStream,
+    offset: usize,
+) -> impl Stream<Item = anyhow::Result<Bytes>> {
+    futures::stream::try_unfold((task, offset), move |(task, offset)| {
+        let state = state.clone();
+        async move {
+            let mut task = task;
+            loop {
+                let finished = task.terminal_state();
+                let url = if finished {
+                    stream.payload(&task).map(|payload| payload.path)
+                } else {
+                    Some(live_log_url(&state.blob_storage, &task, stream))
+                };
+                if let Some(url) = url {
+                    let size = state.blob_storage.size(&url).await?.unwrap_or_default();
+                    if size > offset {
+                        let bytes = state.blob_storage.read_range(&url, offset..size).await?;
+                        return anyhow::Ok(Some((bytes, (task, size))));
+                    }
+                }
+                if finished {
+                    return Ok(None);
+                }
+                tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+                // The task is gone if its graph or invocation was deleted
+                task = match state.indexify_state.reader().get_task(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.invocation_id,
+                    &task.compute_fn_name,
+                    &task.id.to_string(),
+                )? {
+                    Some(task) => task,
+                    None => return Ok(None),
+                };
+            }
+        }
+    })
+}
+
+/// Download the stdout, stderr or structured logs of a task
+///
+/// Executors upload logs once a task finishes. With `wait` the request blocks
+/// until the task finishes or the timeout expires, then returns the uploaded
+/// logs. With `follow` the output of a running task is streamed as the
+/// executor uploads it, and the response ends once the task finished.
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fn/{fn_name}/tasks/{task_id}/logs/{stream}",
+    params(LogParams),
+    tag = "operations",
+    responses(
+        (status = 200, description = "task logs"),
+        (status = 404, description = "task or logs not found, or the task didn't finish before the timeout"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn download_task_logs(
+    Path((namespace, compute_graph, invocation_id, compute_fn, task_id, stream)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+        LogStream,
+    )>,
+    Query(params): Query<LogParams>,
+    State(state): State<RouteState>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let mut state_changes = state.indexify_state.get_state_change_watcher();
+    let timeout = params
+        .timeout
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_WAIT_TIMEOUT)
+        .min(MAX_WAIT_TIMEOUT);
+    let deadline = tokio::time::Instant::now() + timeout;
+    let task = loop {
+        let task = find_task(
+            &state,
+            &namespace,
+            &compute_graph,
+            &invocation_id,
+            &compute_fn,
+            &task_id,
+        )?;
+        if !params.wait || params.follow || task.terminal_state() {
+            break task;
+        }
+        match tokio::time::timeout_at(deadline, state_changes.changed()).await {
+            Ok(changed) => {
+                changed.map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))?
+            }
+            Err(_) => {
+                return Err(IndexifyAPIError::not_found(
+                    "task didn't finish before the timeout, logs not uploaded yet",
+                ))
+            }
+        }
+    };
+    let response = Response::builder().header("Content-Type", stream.content_type());
+    if params.follow {
+        let url = if task.terminal_state() {
+            stream.payload(&task).map(|payload| payload.path)
+        } else {
+            Some(live_log_url(&state.blob_storage, &task, stream))
+        };
+        let size = match &url {
+            Some(url) => state
+                .blob_storage
+                .size(url)
+                .await
+                .map_err(IndexifyAPIError::internal_error)?
+                .unwrap_or_default(),
+            None => 0,
+        };
+        // Only the tail of the output so far, then what's uploaded next. The
+        // tail doesn't include the last newline, it's sent with the rest.
+        let (tail, offset) = match (params.tail, url) {
+            (Some(lines), Some(url)) => {
+                let tail = read_tail(&state.blob_storage, &url, size, lines).await?;
+                let last = state
+                    .blob_storage
+                    .read_range(&url, size.saturating_sub(1)..size)
+                    .await
+                    .map_err(IndexifyAPIError::internal_error)?;
+                let offset = if last.as_ref() == b"\n" { size - 1 } else { size };
+                (Some(tail), offset)
+            }
+            _ => (None, 0),
+        };
+        let log_stream = futures::stream::iter(tail.map(anyhow::Ok))
+            .chain(follow_log(state.clone(), task, stream, offset));
+        return response
+            .body(Body::from_stream(log_stream))
+            .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()));
+    }
+    let payload = stream
+        .payload(&task)
+        .ok_or(IndexifyAPIError::not_found("logs not found"))?;
+
+    let body = match params.tail {
+        Some(lines) => Body::from(
+            read_tail(
+                &state.blob_storage,
+                &payload.path,
+                payload.size as usize,
+                lines,
+            )
+            .await?,
+        ),
+        None => {
+            let log_stream = state
+                .blob_storage
+                .get(&payload.path)
+                .get()
+                .await
+                .map_err(IndexifyAPIError::internal_error)?;
+            Body::from_stream(log_stream)
+        }
+    };
+    response
+        .body(body)
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+/// Upload the output of a running task, replacing what was uploaded before,
+/// so it can be followed. Executors upload all of it again with the task's
+/// outcome when it finishes.
+pub async fn upload_live_task_logs(
+    Path((namespace, compute_graph, invocation_id, compute_fn, task_id, stream)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+        LogStream,
+    )>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<(), IndexifyAPIError> {
+    let task = find_task(
+        &state,
+        &namespace,
+        &compute_graph,
+        &invocation_id,
+        &compute_fn,
+        &task_id,
+    )?;
+    if task.terminal_state() {
+        return Err(IndexifyAPIError::bad_request("task already finished"));
+    }
+    let key = live_log_key(
+        &namespace,
+        &compute_graph,
+        &invocation_id,
+        &compute_fn,
+        &task_id,
+        stream,
+    );
+    let log_stream = body
+        .into_data_stream()
+        .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+    state
+        .blob_storage
+        .put(&key, log_stream)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use blob_store::{BlobStorage, BlobStorageConfig};
+    use bytes::Bytes;
+
+    use super::{read_tail, tail, TAIL_CHUNK_BYTES};
+
+    #[test]
+    fn test_tail() {
+        let log = b"one\ntwo\nthree\n";
+        assert_eq!(tail(log, 2), Some(&b"two\nthree"[..]));
+        assert_eq!(tail(log, 1), Some(&b"three"[..]));
+        assert_eq!(tail(log, 0), Some(&b""[..]));
+        // The first line might be cut off, more of the log is needed
+        assert_eq!(tail(log, 3), None);
+        assert_eq!(tail(log, 10), None);
+        assert_eq!(tail(b"", 3), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_tail_of_large_log() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let blob_storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        // Spans several chunks so the tail needs more than one read
+        let lines: Vec<String> = (0..3 * TAIL_CHUNK_BYTES / 10)
+            .map(|i| format!("line {:04}", i % 10_000))
+            .collect();
+        let log = Bytes::from(lines.join("\n") + "\n");
+        let put = blob_storage
+            .put("log", Box::pin(futures::stream::once(async { Ok(log) })))
+            .await?;
+
+        let size = put.size_bytes as usize;
+        let last = read_tail(&blob_storage, &put.url, size, 2).await.unwrap();
+        assert_eq!(last, lines[lines.len() - 2..].join("\n"));
+        let all = read_tail(&blob_storage, &put.url, size, lines.len() + 1)
+            .await
+            .unwrap();
+        assert_eq!(all, lines.join("\n"));
+        // Output appended after the tail was taken isn't part of it
+        let first = read_tail(&blob_storage, &put.url, "line 0000\n".len(), 1)
+            .await
+            .unwrap();
+        assert_eq!(first, "line 0000");
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use state_store::requests::{
+    DeleteSecretRequest,
+    RequestPayload,
+    SetSecretRequest,
+    StateMachineUpdateRequest,
+};
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, SecretsList, SetSecret};
+
+/// Create or replace a secret of the namespace
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/secrets/{name}",
+    request_body = SetSecret,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Secret stored"),
+        (status = 400, description = "Invalid executor label expression"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn set_secret(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(secret): Json<SetSecret>,
+) -> Result<(), IndexifyAPIError> {
+    if name.is_empty() || name.contains('|') {
+        return Err(IndexifyAPIError::bad_request("invalid secret name"));
+    }
+    let executor_labels = secret.executor_labels()?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::SetSecret(SetSecretRequest {
+                namespace,
+                name,
+                value: secret.value,
+                executor_labels,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// List the secrets of the namespace, without their values
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/secrets",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List of secrets", body = SecretsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_secrets(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<SecretsList>, IndexifyAPIError> {
+    let secrets = state
+        .indexify_state
+        .reader()
+        .list_secrets(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(SecretsList {
+        secrets: secrets.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Delete a secret of the namespace
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/secrets/{name}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Secret deleted"),
+        (status = 404, description = "Secret not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn delete_secret(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    let secret = state
+        .indexify_state
+        .reader()
+        .get_secret(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if secret.is_none() {
+        return Err(IndexifyAPIError::not_found("secret not found"));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteSecret(DeleteSecretRequest { namespace, name }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
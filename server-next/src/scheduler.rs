@@ -1,13 +1,27 @@
-use std::{sync::Arc, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    vec,
+};
 
 use anyhow::{anyhow, Result};
 use data_model::{
     ChangeType,
+    DataPayload,
+    Edge,
+    GraphInvocationCtx,
+    InvocationOutcome,
+    InvocationPayload,
+    InvocationPayloadBuilder,
     InvokeComputeGraphEvent,
+    Node,
     OutputPayload,
+    ParentInvocation,
+    Priority,
     StateChangeId,
     Task,
     TaskFinishedEvent,
+    TaskOutcome,
 };
 use state_store::{
     requests::{
@@ -15,12 +29,13 @@ use state_store::{
         RequestPayload,
         SchedulerUpdateRequest,
         StateMachineUpdateRequest,
+        SubgraphOutcome,
     },
     IndexifyState,
 };
 use task_scheduler::TaskScheduler;
 use tokio::{self, sync::watch::Receiver};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Debug)]
 struct TaskCreationResult {
@@ -29,6 +44,157 @@ struct TaskCreationResult {
     tasks: Vec<Task>,
     invocation_finished: bool,
     invocation_id: String,
+    child_invocations: Vec<InvocationPayload>,
+    subgraph_outcome: Option<SubgraphOutcome>,
+    failed_subgraph_nodes: Vec<String>,
+}
+
+impl TaskCreationResult {
+    fn new(namespace: &str, compute_graph: &str, invocation_id: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            compute_graph: compute_graph.to_string(),
+            invocation_id: invocation_id.to_string(),
+            tasks: vec![],
+            invocation_finished: false,
+            child_invocations: vec![],
+            subgraph_outcome: None,
+            failed_subgraph_nodes: vec![],
+        }
+    }
+
+    fn finished(mut self) -> Self {
+        self.invocation_finished = true;
+        self
+    }
+
+    /// Finishes the invocation only once none of its other tasks are running
+    fn finished_if_idle(self, pending_tasks: u64) -> Self {
+        if pending_tasks == 0 {
+            self.finished()
+        } else {
+            self
+        }
+    }
+
+    /// A subgraph node whose child invocation can't be started fails the
+    /// invocation, so the work created alongside it is dropped
+    fn fail_on_failed_subgraph(mut self, pending_tasks: u64) -> Self {
+        if self.failed_subgraph_nodes.is_empty() {
+            return self;
+        }
+        self.tasks.clear();
+        self.child_invocations.clear();
+        self.finished_if_idle(pending_tasks)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.child_invocations.is_empty()
+    }
+}
+
+enum AnalyticsChange {
+    Pending,
+    Success,
+    Failure,
+}
+
+/// Work created and subgraph outcomes recorded by the results of the current
+/// batch. They aren't in the stored invocation contexts until the batch is
+/// written, so they're applied to the contexts the handlers read. Otherwise a
+/// finished branch could finish an invocation whose other branch created
+/// tasks earlier in the batch.
+#[derive(Default)]
+struct BatchChanges {
+    // Invocation context key -> node name and change, in the order the state
+    // machine applies them
+    analytics: HashMap<String, Vec<(String, AnalyticsChange)>>,
+    finished: HashSet<String>,
+}
+
+impl BatchChanges {
+    fn record(&mut self, result: &TaskCreationResult) {
+        let key = GraphInvocationCtx::key_from(
+            &result.namespace,
+            &result.compute_graph,
+            &result.invocation_id,
+        );
+        if result.invocation_finished {
+            self.finished.insert(key.clone());
+        }
+        let changes = self.analytics.entry(key).or_default();
+        for task in &result.tasks {
+            changes.push((task.compute_fn_name.clone(), AnalyticsChange::Pending));
+        }
+        for child in &result.child_invocations {
+            if let Some(parent) = &child.parent {
+                changes.push((parent.node_name.clone(), AnalyticsChange::Pending));
+            }
+        }
+        if let Some(subgraph_outcome) = &result.subgraph_outcome {
+            match subgraph_outcome.outcome {
+                TaskOutcome::Success => {
+                    changes.push((subgraph_outcome.node_name.clone(), AnalyticsChange::Success))
+                }
+                TaskOutcome::Failure => {
+                    changes.push((subgraph_outcome.node_name.clone(), AnalyticsChange::Failure))
+                }
+                TaskOutcome::Unknown => {}
+            }
+        }
+        for node_name in &result.failed_subgraph_nodes {
+            changes.push((node_name.clone(), AnalyticsChange::Pending));
+            changes.push((node_name.clone(), AnalyticsChange::Failure));
+        }
+    }
+
+    /// Reads the invocation's context as it will be once the batch is written
+    fn invocation_ctx(
+        &self,
+        indexify_state: &IndexifyState,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<GraphInvocationCtx> {
+        self.get_invocation_ctx(indexify_state, namespace, compute_graph, invocation_id)?
+            .ok_or(anyhow!("invocation ctx not found"))
+    }
+
+    /// Like `invocation_ctx`, `None` if the invocation was deleted
+    fn get_invocation_ctx(
+        &self,
+        indexify_state: &IndexifyState,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<GraphInvocationCtx>> {
+        let Some(mut ctx) =
+            indexify_state
+                .reader()
+                .get_invocation_ctx(namespace, compute_graph, invocation_id)?
+        else {
+            return Ok(None);
+        };
+        let key = ctx.key();
+        if self.finished.contains(&key) {
+            ctx.completed = true;
+        }
+        for (node_name, change) in self.analytics.get(&key).into_iter().flatten() {
+            let analytics = ctx.fn_task_analytics.entry(node_name.clone()).or_default();
+            match change {
+                AnalyticsChange::Pending => analytics.pending(),
+                AnalyticsChange::Success => analytics.success(),
+                AnalyticsChange::Failure => analytics.fail(),
+            }
+        }
+        Ok(Some(ctx))
+    }
+}
+
+fn has_conditions(edges: &[Edge]) -> bool {
+    edges
+        .iter()
+        .any(|edge| matches!(edge, Edge::Conditional { .. }))
 }
 pub struct Scheduler {
     indexify_state: Arc<IndexifyState>,
@@ -57,34 +223,247 @@ impl Scheduler {
                 "compute graph not found: {:?} {:?}",
                 event.namespace, event.compute_graph
             );
-            return Ok(TaskCreationResult {
-                tasks: vec![],
-                namespace: event.namespace.clone(),
-                invocation_id: event.invocation_id.clone(),
-                compute_graph: event.compute_graph.clone(),
-                invocation_finished: false,
-            });
+            return Ok(TaskCreationResult::new(
+                &event.namespace,
+                &event.compute_graph,
+                &event.invocation_id,
+            ));
         }
         let compute_graph = compute_graph.unwrap();
+        let priority = self
+            .indexify_state
+            .reader()
+            .invocation_payload(&event.namespace, &event.compute_graph, &event.invocation_id)
+            .map(|invocation| invocation.priority)
+            .unwrap_or_default();
         // Crate a task for the compute graph
-        let task = compute_graph.start_fn.create_task(
-            &event.namespace,
-            &event.compute_graph,
-            &event.invocation_id,
+        let mut result =
+            TaskCreationResult::new(&event.namespace, &event.compute_graph, &event.invocation_id);
+        self.create_work(
+            &compute_graph.start_fn,
             &event.invocation_id,
+            priority,
+            &mut result,
         )?;
-        Ok(TaskCreationResult {
-            namespace: event.namespace.clone(),
-            compute_graph: event.compute_graph.clone(),
-            invocation_id: event.invocation_id.clone(),
-            tasks: vec![task],
-            invocation_finished: false,
-        })
+        Ok(result.fail_on_failed_subgraph(0))
+    }
+
+    /// Creates the work for a node reached with `input_key`: a task for
+    /// functions and routers, or a child invocation for subgraphs.
+    fn create_work(
+        &self,
+        node: &Node,
+        input_key: &str,
+        priority: Priority,
+        result: &mut TaskCreationResult,
+    ) -> Result<()> {
+        let Node::Subgraph(subgraph) = node else {
+            let mut task = node.create_task(
+                &result.namespace,
+                &result.compute_graph,
+                &result.invocation_id,
+                input_key,
+            )?;
+            task.priority = priority;
+            result.tasks.push(task);
+            return Ok(());
+        };
+        let child_graph = self
+            .indexify_state
+            .reader()
+            .get_compute_graph(&result.namespace, &subgraph.compute_graph_name)?;
+        if child_graph.is_none() {
+            error!(
+                "subgraph compute graph not found, failing invocation {}: {:?} {:?}",
+                result.invocation_id, result.namespace, subgraph.compute_graph_name
+            );
+            result.failed_subgraph_nodes.push(subgraph.name.clone());
+            return Ok(());
+        }
+        let payload = self.input_payload(result, input_key)?;
+        let child = InvocationPayloadBuilder::default()
+            .namespace(result.namespace.clone())
+            .compute_graph_name(subgraph.compute_graph_name.clone())
+            .payload(payload)
+            .priority(priority)
+            .parent(Some(ParentInvocation {
+                compute_graph_name: result.compute_graph.clone(),
+                invocation_id: result.invocation_id.clone(),
+                node_name: subgraph.name.clone(),
+            }))
+            .build()?;
+        info!(
+            "starting child invocation {} of {} for {}",
+            child.id, subgraph.compute_graph_name, result.invocation_id
+        );
+        result.child_invocations.push(child);
+        Ok(())
+    }
+
+    // Input keys are either the invocation id, for the start node, or the key
+    // of a function output
+    fn input_payload(&self, result: &TaskCreationResult, input_key: &str) -> Result<DataPayload> {
+        let reader = self.indexify_state.reader();
+        if input_key == result.invocation_id {
+            return Ok(reader
+                .invocation_payload(&result.namespace, &result.compute_graph, input_key)?
+                .payload);
+        }
+        match reader.fn_output_payload_by_key(input_key)?.payload {
+            OutputPayload::Fn(payload) => Ok(payload),
+            OutputPayload::Router(_) => Err(anyhow!("input is a router output: {}", input_key)),
+        }
+    }
+
+    /// Continues the parent of a finished child invocation. The outputs of the
+    /// child graph's leaf functions are the inputs of the subgraph node's
+    /// edges, and a failed child fails the parent. The parent's graph can be
+    /// deleted while the child runs, then there's nothing to continue.
+    async fn handle_child_invocation_finished(
+        &self,
+        child_result: &TaskCreationResult,
+        batch: &BatchChanges,
+    ) -> Result<Option<TaskCreationResult>> {
+        let reader = self.indexify_state.reader();
+        let Some(child) = reader.get_invocation_payload(
+            &child_result.namespace,
+            &child_result.compute_graph,
+            &child_result.invocation_id,
+        )?
+        else {
+            warn!(
+                "finished invocation {} of {} not found, it was deleted",
+                child_result.invocation_id, child_result.compute_graph
+            );
+            return Ok(None);
+        };
+        let Some(parent) = &child.parent else {
+            return Ok(None);
+        };
+        let Some(child_ctx) = batch.get_invocation_ctx(
+            &self.indexify_state,
+            &child.namespace,
+            &child.compute_graph_name,
+            &child.id,
+        )?
+        else {
+            warn!(
+                "finished child invocation {} of {} not found, it was deleted",
+                child.id, child.compute_graph_name
+            );
+            return Ok(None);
+        };
+        // A failed grandchild isn't in the child's analytics until this update
+        // is written
+        let failed = child_ctx.outcome() == InvocationOutcome::Failure ||
+            child_result
+                .subgraph_outcome
+                .as_ref()
+                .is_some_and(|outcome| outcome.outcome == TaskOutcome::Failure);
+        let Some(parent_ctx) = batch.get_invocation_ctx(
+            &self.indexify_state,
+            &child.namespace,
+            &parent.compute_graph_name,
+            &parent.invocation_id,
+        )?
+        else {
+            warn!(
+                "parent invocation {} of {} of finished child invocation {} not found, it was deleted",
+                parent.invocation_id, parent.compute_graph_name, child.id
+            );
+            return Ok(None);
+        };
+        // The parent's analytics still count this child as pending
+        let parent_pending_tasks = parent_ctx.pending_tasks().saturating_sub(1);
+        if failed {
+            if parent_ctx.completed {
+                return Ok(None);
+            }
+        } else if child_ctx
+            .fn_task_analytics
+            .values()
+            .any(|analytics| analytics.pending_tasks > 0)
+        {
+            // Other branches of the child are still running, so not all of its
+            // outputs exist yet
+            return Ok(None);
+        }
+        let mut result = TaskCreationResult::new(
+            &child.namespace,
+            &parent.compute_graph_name,
+            &parent.invocation_id,
+        );
+        result.subgraph_outcome = Some(SubgraphOutcome {
+            node_name: parent.node_name.clone(),
+            outcome: if failed {
+                TaskOutcome::Failure
+            } else {
+                TaskOutcome::Success
+            },
+        });
+        // Like a failed task, a failed child creates no new work and the
+        // parent finishes once its running tasks and children are done
+        if failed || parent_ctx.outcome() == InvocationOutcome::Failure {
+            info!(
+                "child invocation {} finished in failed invocation, {} tasks still running: {:?}",
+                child.id, parent_pending_tasks, parent.invocation_id
+            );
+            return Ok(Some(result.finished_if_idle(parent_pending_tasks)));
+        }
+
+        let parent_graph = reader
+            .get_compute_graph(&child.namespace, &parent.compute_graph_name)?
+            .ok_or(anyhow!(
+                "compute graph not found: {:?} {:?}",
+                child.namespace,
+                parent.compute_graph_name
+            ))?;
+        let edges = match parent_graph.edges.get(&parent.node_name) {
+            Some(edges) if !edges.is_empty() => edges,
+            _ => return Ok(Some(result.finished_if_idle(parent_pending_tasks))),
+        };
+        let child_graph = reader
+            .get_compute_graph(&child.namespace, &child.compute_graph_name)?
+            .ok_or(anyhow!(
+                "compute graph not found: {:?} {:?}",
+                child.namespace,
+                child.compute_graph_name
+            ))?;
+        let (outputs, _) = reader.list_outputs_by_compute_graph(
+            &child.namespace,
+            &child.compute_graph_name,
+            &child.id,
+            None,
+            None,
+        )?;
+        let leaf_outputs: Vec<_> = outputs
+            .into_iter()
+            .filter(|output| matches!(output.payload, OutputPayload::Fn(_)))
+            .filter(|output| !child_graph.edges.contains_key(&output.compute_fn_name))
+            .collect();
+        for edge in edges {
+            let Some(node) = parent_graph.nodes.get(edge.target()) else {
+                error!("compute fn not found: {:?}", edge);
+                continue;
+            };
+            for output in leaf_outputs
+                .iter()
+                .filter(|output| edge.matches(&output.metadata))
+            {
+                self.create_work(node, &output.key(&child.id), child.priority, &mut result)?;
+            }
+        }
+        // None of the conditional edges matched, so this branch ends here
+        if result.is_empty() && has_conditions(edges) {
+            return Ok(Some(result.finished_if_idle(parent_pending_tasks)));
+        }
+        Ok(Some(result.fail_on_failed_subgraph(parent_pending_tasks)))
     }
 
     async fn handle_task_finished(
         &self,
         task_finished_event: &TaskFinishedEvent,
+        batch: &BatchChanges,
     ) -> Result<TaskCreationResult> {
         let task = self
             .indexify_state
@@ -98,6 +477,30 @@ impl Scheduler {
             )?
             .ok_or(anyhow!("task not found: {:?}", task_finished_event))?;
 
+        let invocation_ctx = batch.invocation_ctx(
+            &self.indexify_state,
+            &task_finished_event.namespace,
+            &task_finished_event.compute_graph,
+            &task_finished_event.invocation_id,
+        )?;
+        let mut result = TaskCreationResult::new(
+            &task_finished_event.namespace,
+            &task_finished_event.compute_graph,
+            &task_finished_event.invocation_id,
+        );
+
+        // A failed task can't feed the downstream functions, and the failure is
+        // already in the invocation's analytics. No new work is created for a
+        // failed invocation, and it's finished once its running tasks are.
+        if invocation_ctx.outcome() == InvocationOutcome::Failure {
+            info!(
+                "invocation failed, {} tasks still running: {:?}",
+                invocation_ctx.pending_tasks(),
+                task_finished_event.invocation_id
+            );
+            return Ok(result.finished_if_idle(invocation_ctx.pending_tasks()));
+        }
+
         let compute_graph = self
             .indexify_state
             .reader()
@@ -115,8 +518,6 @@ impl Scheduler {
         // If this was a router task, we 1. use the edges of the router to create
         // subsequent tasks
         // 2. We use the input to the router as the input of the new tasks
-
-        let mut new_tasks = vec![];
         let outputs = self.indexify_state.reader().get_task_outputs(
             &task_finished_event.namespace,
             &task_finished_event.task_id.to_string(),
@@ -137,71 +538,52 @@ impl Scheduler {
                     continue;
                 }
                 let compute_fn = compute_fn.unwrap();
-                let new_task = compute_fn.create_task(
-                    &task.namespace,
-                    &task.compute_graph_name,
-                    &task.invocation_id,
-                    &task.input_key,
-                )?;
-                new_tasks.push(new_task);
+                self.create_work(compute_fn, &task.input_key, task.priority, &mut result)?;
             }
-            return Ok(TaskCreationResult {
-                namespace: task_finished_event.namespace.clone(),
-                compute_graph: task_finished_event.compute_graph.clone(),
-                invocation_id: task_finished_event.invocation_id.clone(),
-                tasks: new_tasks,
-                invocation_finished: false,
-            });
+            return Ok(result.fail_on_failed_subgraph(invocation_ctx.pending_tasks()));
         }
 
         // Find the edges of the function
         let edges = compute_graph.edges.get(&task_finished_event.compute_fn);
-        if edges.is_none() {
-            let invocation_ctx = self.indexify_state.reader().invocation_ctx(
-                &task_finished_event.namespace,
-                &task_finished_event.compute_graph,
-                &task_finished_event.invocation_id,
-            )?;
-            if invocation_ctx.outstanding_tasks == 0 {
+        let Some(edges) = edges else {
+            if invocation_ctx.pending_tasks() == 0 {
                 info!(
                     "compute graph completed: {:?}",
                     task_finished_event.compute_graph
                 );
-                return Ok(TaskCreationResult {
-                    namespace: task_finished_event.namespace.clone(),
-                    compute_graph: task_finished_event.compute_graph.clone(),
-                    invocation_id: task_finished_event.invocation_id.clone(),
-                    tasks: vec![],
-                    invocation_finished: true,
-                });
             }
-        }
-
-        let edges = edges.unwrap();
+            return Ok(result.finished_if_idle(invocation_ctx.pending_tasks()));
+        };
         for edge in edges {
-            for output in &outputs {
-                let compute_fn = compute_graph.nodes.get(edge);
+            for output in outputs
+                .iter()
+                .filter(|output| edge.matches(&output.metadata))
+            {
+                let compute_fn = compute_graph.nodes.get(edge.target());
                 if compute_fn.is_none() {
                     error!("compute fn not found: {:?}", edge);
                     continue;
                 }
                 let compute_fn = compute_fn.unwrap();
-                let new_task = compute_fn.create_task(
-                    &task.namespace,
-                    &task.compute_graph_name,
-                    &task.invocation_id,
+                self.create_work(
+                    compute_fn,
                     &output.key(&task.invocation_id),
+                    task.priority,
+                    &mut result,
                 )?;
-                new_tasks.push(new_task);
             }
         }
-        Ok(TaskCreationResult {
-            namespace: task_finished_event.namespace.clone(),
-            compute_graph: task_finished_event.compute_graph.clone(),
-            invocation_id: task_finished_event.invocation_id.clone(),
-            tasks: new_tasks,
-            invocation_finished: false,
-        })
+        // None of the conditional edges matched, so this branch ends here
+        if result.is_empty() && has_conditions(edges) {
+            info!(
+                "no edges matched the outputs of {:?}, {} tasks still running: {:?}",
+                task_finished_event.compute_fn,
+                invocation_ctx.pending_tasks(),
+                task_finished_event.invocation_id
+            );
+            return Ok(result.finished_if_idle(invocation_ctx.pending_tasks()));
+        }
+        Ok(result.fail_on_failed_subgraph(invocation_ctx.pending_tasks()))
     }
 
     pub async fn run_scheduler(&self) -> Result<()> {
@@ -211,6 +593,8 @@ impl Scheduler {
             .get_unprocessed_state_changes()?;
         let mut create_task_requests = vec![];
         let mut processed_state_changes = vec![];
+        let mut finished_invocations = HashSet::new();
+        let mut batch = BatchChanges::default();
         for state_change in &state_changes {
             processed_state_changes.push(state_change.id.clone());
             let result = match &state_change.change_type {
@@ -218,33 +602,54 @@ impl Scheduler {
                     self.handle_invoke_compute_graph(invoke_compute_graph_event.clone())
                         .await?,
                 ),
-                ChangeType::TaskFinished(task_finished_event) => {
-                    Some(self.handle_task_finished(task_finished_event).await?)
-                }
+                ChangeType::TaskFinished(task_finished_event) => Some(
+                    self.handle_task_finished(task_finished_event, &batch)
+                        .await?,
+                ),
                 _ => None,
             };
-            if let Some(result) = result {
+            // A finished child invocation continues its parent, which can
+            // itself be a child
+            let mut next = result;
+            while let Some(result) = next {
+                batch.record(&result);
+                next = if result.invocation_finished &&
+                    finished_invocations.insert(result.invocation_id.clone())
+                {
+                    self.handle_child_invocation_finished(&result, &batch)
+                        .await?
+                } else {
+                    None
+                };
                 let request = CreateTasksRequest {
                     namespace: result.namespace.clone(),
                     invocation_id: result.invocation_id.clone(),
                     compute_graph: result.compute_graph.clone(),
                     invocation_finished: result.invocation_finished,
                     tasks: result.tasks,
+                    child_invocations: result.child_invocations,
+                    subgraph_outcome: result.subgraph_outcome,
+                    failed_subgraph_nodes: result.failed_subgraph_nodes,
                 };
                 create_task_requests.push(request);
             }
         }
-        let mut new_allocations = vec![];
-        for state_change in &state_changes {
-            let allocations = match state_change.change_type {
+        // Finished tasks free slots of functions with a concurrency cap. The
+        // unplaced tasks are scheduled once per batch so the allocation counts
+        // the caps are checked against include the whole batch.
+        let reschedule = state_changes.iter().any(|state_change| {
+            matches!(
+                state_change.change_type,
                 ChangeType::TaskCreated |
-                ChangeType::ExecutorAdded |
-                ChangeType::ExecutorRemoved => Some(self.task_allocator.schedule_unplaced_tasks()?),
-                _ => None,
-            };
-            if let Some(allocations) = allocations {
-                new_allocations.extend(allocations);
-            }
+                    ChangeType::TaskFinished(_) |
+                    ChangeType::ExecutorAdded |
+                    ChangeType::ExecutorRemoved |
+                    ChangeType::ExecutorDraining
+            )
+        });
+        let mut new_allocations = vec![];
+        if reschedule {
+            new_allocations = self.task_allocator.schedule_unplaced_tasks()?;
         }
 
         let scheduler_update_request = StateMachineUpdateRequest {
@@ -282,19 +687,38 @@ impl Scheduler {
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::{collections::HashMap, time::Duration};
 
     use data_model::{
+        filter::{Expression, LabelsFilter},
         test_objects::tests::{
             mock_executor,
             mock_executor_id,
+            mock_graph_a,
             mock_invocation_payload,
             mock_invocation_payload_graph_b,
             TEST_NAMESPACE,
         },
+        ComputeFn,
+        ComputeGraph,
         ExecutorId,
+        SubgraphNode,
+        TaskDiagnostics,
+    };
+    use futures::StreamExt;
+    use serde_json::json;
+    use state_store::{
+        requests::{
+            CreateComputeGraphRequest,
+            DeleteComputeGraphRequest,
+            FinalizeTaskRequest,
+            InvokeComputeGraphRequest,
+            RerunInvocationRequest,
+            SetSecretRequest,
+        },
+        test_state_store::tests::TestStateStore,
+        StateMachineError,
     };
-    use state_store::test_state_store::tests::TestStateStore;
 
     use super::*;
     use crate::executors::{self, ExecutorManager};
@@ -373,6 +797,1159 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_tasks_inherit_invocation_priority() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let normal_invocation_id = state_store.with_simple_graph().await;
+        let high_invocation = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name("graph_A".to_string())
+            .payload(DataPayload {
+                path: "urgent".to_string(),
+                size: 23,
+                sha256_hash: "hash1232".to_string(),
+            })
+            .priority(Priority::High)
+            .build()?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: high_invocation.clone(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        scheduler.run_scheduler().await?;
+
+        // No executors are registered, so both tasks wait with the high
+        // priority one first even though it was invoked last
+        let tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].invocation_id, high_invocation.id);
+        assert_eq!(tasks[0].priority, Priority::High);
+        assert_eq!(tasks[1].invocation_id, normal_invocation_id);
+        assert_eq!(tasks[1].priority, Priority::Normal);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_runs_high_priority_tasks_first() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let mut invocation_ids = vec![];
+        for (path, priority) in [("old", Priority::Low), ("urgent", Priority::High)] {
+            let invocation = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: path.to_string(),
+                    size: 23,
+                    sha256_hash: path.to_string(),
+                })
+                .priority(priority)
+                .build()?;
+            invocation_ids.push(invocation.id.clone());
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        invocation_payload: invocation,
+                        idempotency_key: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // Both tasks are allocated to the only executor, which is sent the high
+        // priority task first even though the low priority task is older
+        let tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].invocation_id, invocation_ids[1]);
+        assert_eq!(tasks[0].priority, Priority::High);
+        assert_eq!(tasks[1].invocation_id, invocation_ids[0]);
+        assert!(tasks[0].creation_time > tasks[1].creation_time);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_requeue_dead_letter() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let invocation_id = state_store.with_simple_graph().await;
+        scheduler.run_scheduler().await?;
+        let task = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)?
+            .0
+            .remove(0);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: TaskOutcome::Failure,
+                    executor_id: mock_executor_id(),
+                    diagnostics: TaskDiagnostics {
+                        error_message: Some("boom".to_string()),
+                        ..Default::default()
+                    },
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        scheduler.run_scheduler().await?;
+
+        let (dead_letters, _) =
+            indexify_state
+                .reader()
+                .list_dead_letters(TEST_NAMESPACE, "graph_A", None, None)?;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].task_id, task.id);
+        let failed_task = indexify_state
+            .reader()
+            .dead_letter_task(&dead_letters[0])?
+            .unwrap();
+        assert_eq!(
+            failed_task.diagnostics.error_message,
+            Some("boom".to_string())
+        );
+        let ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(ctx.completed);
+
+        let requeue = |keys: Vec<String>| StateMachineUpdateRequest {
+            payload: RequestPayload::RequeueDeadLetters(keys),
+            state_changes_processed: vec![],
+        };
+        indexify_state
+            .write(requeue(vec![dead_letters[0].key()]))
+            .await?;
+
+        assert!(indexify_state
+            .reader()
+            .list_dead_letters(TEST_NAMESPACE, "graph_A", None, None)?
+            .0
+            .is_empty());
+        let ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(!ctx.completed);
+        assert_eq!(ctx.outcome(), InvocationOutcome::Success);
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+        assert_eq!(unallocated_tasks[0].id, task.id);
+        assert_eq!(unallocated_tasks[0].outcome, TaskOutcome::Unknown);
+        assert_eq!(unallocated_tasks[0].diagnostics.error_message, None);
+
+        // A dead letter can only be requeued once
+        assert!(indexify_state
+            .write(requeue(vec![dead_letters[0].key()]))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rerun_invocation_from_fn() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+        let invocation_id = state_store.with_simple_graph().await;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let list_tasks = || {
+            indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)
+                .map(|(tasks, _)| tasks)
+        };
+        let rerun = |compute_fn: &str| StateMachineUpdateRequest {
+            payload: RequestPayload::RerunInvocation(RerunInvocationRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: "graph_A".to_string(),
+                invocation_id: invocation_id.clone(),
+                compute_fn: compute_fn.to_string(),
+            }),
+            state_changes_processed: vec![],
+        };
+        let task_a = list_tasks()?.remove(0);
+        state_store
+            .finalize_task(&invocation_id, &task_a.id)
+            .await?;
+        scheduler.run_scheduler().await?;
+
+        // Running invocations can't be rerun
+        let err = indexify_state.write(rerun("fn_b")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateMachineError>(),
+            Some(StateMachineError::InvocationInProgress(_))
+        ));
+
+        for task in list_tasks()? {
+            if task.compute_fn_name == "fn_a" {
+                continue;
+            }
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: "graph_A".to_string(),
+                        compute_fn: task.compute_fn_name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        task_id: task.id.clone(),
+                        node_outputs: vec![],
+                        task_outcome: TaskOutcome::Success,
+                        executor_id: mock_executor_id(),
+                        diagnostics: TaskDiagnostics::default(),
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        schedule_all(&indexify_state, &scheduler).await?;
+        let ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(ctx.completed);
+
+        indexify_state.write(rerun("fn_b")).await?;
+
+        // The rerun task waits for the next scheduler pass
+        let tasks = list_tasks()?;
+        assert_eq!(tasks.len(), 3);
+        for task in &tasks {
+            let expected = match task.compute_fn_name.as_str() {
+                "fn_b" => TaskOutcome::Unknown,
+                _ => TaskOutcome::Success,
+            };
+            assert_eq!(task.outcome, expected);
+        }
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+        assert_eq!(unallocated_tasks[0].compute_fn_name, "fn_b");
+        assert_eq!(
+            unallocated_tasks[0].input_key,
+            task_a_output_key(&invocation_id)
+        );
+
+        // Upstream outputs are kept
+        let (outputs, _) = indexify_state.reader().list_outputs_by_compute_graph(
+            TEST_NAMESPACE,
+            "graph_A",
+            &invocation_id,
+            None,
+            None,
+        )?;
+        assert_eq!(outputs.len(), 1);
+        let ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(!ctx.completed);
+        assert_eq!(ctx.fn_task_analytics["fn_b"].pending_tasks, 1);
+        assert_eq!(ctx.fn_task_analytics["fn_a"].successful_tasks, 1);
+        Ok(())
+    }
+
+    fn task_a_output_key(invocation_id: &str) -> String {
+        data_model::test_objects::tests::mock_node_fn_output_fn_a(invocation_id, "graph_A")
+            .key(invocation_id)
+    }
+
+    async fn finalize(
+        indexify_state: &IndexifyState,
+        task: &Task,
+        node_outputs: Vec<data_model::NodeOutput>,
+    ) -> Result<()> {
+        finalize_with_outcome(indexify_state, task, node_outputs, TaskOutcome::Success).await
+    }
+
+    async fn finalize_with_outcome(
+        indexify_state: &IndexifyState,
+        task: &Task,
+        node_outputs: Vec<data_model::NodeOutput>,
+        task_outcome: TaskOutcome,
+    ) -> Result<()> {
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: task.invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs,
+                    task_outcome,
+                    executor_id: mock_executor_id(),
+                    diagnostics: TaskDiagnostics::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_failed_task_waits_for_running_tasks() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let invocation_id = state_store.with_simple_graph().await;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let list_tasks = |compute_fn: &str| -> Result<Vec<Task>> {
+            Ok(indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)?
+                .0
+                .into_iter()
+                .filter(|task| task.compute_fn_name == compute_fn)
+                .collect())
+        };
+        let ctx = || {
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)
+        };
+
+        // fn_a fans out to fn_b and fn_c
+        let task_a = list_tasks("fn_a")?.remove(0);
+        finalize(&indexify_state, &task_a, vec![fn_output(&task_a, "a_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let task_b = list_tasks("fn_b")?.remove(0);
+        let task_c = list_tasks("fn_c")?.remove(0);
+
+        // fn_c is still running, so the invocation isn't finished yet
+        finalize_with_outcome(&indexify_state, &task_b, vec![], TaskOutcome::Failure).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(!ctx()?.completed);
+        assert_eq!(ctx()?.outcome(), InvocationOutcome::Failure);
+
+        finalize(&indexify_state, &task_c, vec![fn_output(&task_c, "c_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(ctx()?.completed);
+        assert_eq!(ctx()?.outcome(), InvocationOutcome::Failure);
+        Ok(())
+    }
+
+    fn fn_output(task: &Task, path: &str) -> Result<data_model::NodeOutput> {
+        data_model::NodeOutputBuilder::default()
+            .namespace(task.namespace.clone())
+            .compute_graph_name(task.compute_graph_name.clone())
+            .compute_fn_name(task.compute_fn_name.clone())
+            .invocation_id(task.invocation_id.clone())
+            .payload(OutputPayload::Fn(DataPayload {
+                path: path.to_string(),
+                size: 12,
+                sha256_hash: path.to_string(),
+            }))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_node_invokes_child_graph() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        // parent: fn_p -> sub (graph_A) -> fn_z
+        // graph_A: fn_a -> fn_b, fn_c
+        let mut parent_graph = mock_graph_a();
+        parent_graph.name = "parent".to_string();
+        let fn_p = Node::Compute(ComputeFn {
+            name: "fn_p".to_string(),
+            description: "".to_string(),
+            placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
+            fn_name: "fn_p".to_string(),
+        });
+        let fn_z = Node::Compute(ComputeFn {
+            name: "fn_z".to_string(),
+            description: "".to_string(),
+            placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
+            fn_name: "fn_z".to_string(),
+        });
+        let sub = Node::Subgraph(SubgraphNode {
+            name: "sub".to_string(),
+            description: "".to_string(),
+            compute_graph_name: "graph_A".to_string(),
+        });
+        parent_graph.start_fn = fn_p.clone();
+        parent_graph.nodes = HashMap::from([
+            ("fn_p".to_string(), fn_p),
+            ("sub".to_string(), sub),
+            ("fn_z".to_string(), fn_z),
+        ]);
+        parent_graph.edges = HashMap::from([
+            ("fn_p".to_string(), vec!["sub".into()]),
+            ("sub".to_string(), vec!["fn_z".into()]),
+        ]);
+        for compute_graph in [mock_graph_a(), parent_graph] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        let invocation = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name("parent".to_string())
+            .payload(DataPayload {
+                path: "parent_input".to_string(),
+                size: 23,
+                sha256_hash: "hash".to_string(),
+            })
+            .build()?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "parent".to_string(),
+                    invocation_payload: invocation.clone(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let executor_tasks = || {
+            indexify_state
+                .reader()
+                .get_tasks_by_executor(&mock_executor_id(), 100)
+        };
+        let task_p = executor_tasks()?.remove(0);
+        assert_eq!(task_p.compute_fn_name, "fn_p");
+        finalize(&indexify_state, &task_p, vec![fn_output(&task_p, "p_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // Reaching the subgraph node starts a child invocation of graph_A
+        let task_a = executor_tasks()?.remove(0);
+        assert_eq!(task_a.compute_graph_name, "graph_A");
+        let child = indexify_state.reader().invocation_payload(
+            TEST_NAMESPACE,
+            "graph_A",
+            &task_a.invocation_id,
+        )?;
+        assert_eq!(child.payload.path, "p_out");
+        assert_eq!(
+            child.parent,
+            Some(ParentInvocation {
+                compute_graph_name: "parent".to_string(),
+                invocation_id: invocation.id.clone(),
+                node_name: "sub".to_string(),
+            })
+        );
+        let parent_ctx = || {
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "parent", &invocation.id)
+        };
+        assert_eq!(parent_ctx()?.fn_task_analytics["sub"].pending_tasks, 1);
+
+        finalize(&indexify_state, &task_a, vec![fn_output(&task_a, "a_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let leaf_tasks = executor_tasks()?;
+        assert_eq!(leaf_tasks.len(), 2);
+
+        // The parent waits for every branch of the child
+        finalize(
+            &indexify_state,
+            &leaf_tasks[0],
+            vec![fn_output(&leaf_tasks[0], "leaf_0")?],
+        )
+        .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert_eq!(executor_tasks()?.len(), 1);
+        assert_eq!(parent_ctx()?.fn_task_analytics["sub"].pending_tasks, 1);
+
+        finalize(
+            &indexify_state,
+            &leaf_tasks[1],
+            vec![fn_output(&leaf_tasks[1], "leaf_1")?],
+        )
+        .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let z_tasks = executor_tasks()?;
+        assert_eq!(z_tasks.len(), 2);
+        assert!(z_tasks
+            .iter()
+            .all(|task| task.compute_fn_name == "fn_z" && task.invocation_id == invocation.id));
+        assert_eq!(parent_ctx()?.fn_task_analytics["sub"].successful_tasks, 1);
+        assert!(!parent_ctx()?.completed);
+
+        for task in &z_tasks {
+            finalize(&indexify_state, task, vec![]).await?;
+        }
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(parent_ctx()?.completed);
+        assert_eq!(parent_ctx()?.outcome(), InvocationOutcome::Success);
+
+        // Rerunning upstream of the subgraph node removes the child invocation
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RerunInvocation(RerunInvocationRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "parent".to_string(),
+                    invocation_id: invocation.id.clone(),
+                    compute_fn: "fn_p".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let reader = indexify_state.reader();
+        assert!(reader
+            .get_invocation_ctx(TEST_NAMESPACE, "graph_A", &task_a.invocation_id)?
+            .is_none());
+        assert!(reader
+            .invocation_payload(TEST_NAMESPACE, "graph_A", &task_a.invocation_id)
+            .is_err());
+        let (child_tasks, _) = reader.list_tasks_by_compute_graph(
+            TEST_NAMESPACE,
+            "graph_A",
+            &task_a.invocation_id,
+            None,
+            None,
+        )?;
+        assert!(child_tasks.is_empty());
+        let gc_urls = reader.get_gc_urls(None)?;
+        for url in ["a_out", "leaf_0", "leaf_1"] {
+            assert!(gc_urls.contains(&url.to_string()));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_with_missing_graph_fails_invocation() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        // parent: fn_p -> sub (graph_missing)
+        let mut parent_graph = mock_graph_a();
+        parent_graph.name = "parent".to_string();
+        let fn_p = Node::Compute(ComputeFn {
+            name: "fn_p".to_string(),
+            description: "".to_string(),
+            placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
+            fn_name: "fn_p".to_string(),
+        });
+        let sub = Node::Subgraph(SubgraphNode {
+            name: "sub".to_string(),
+            description: "".to_string(),
+            compute_graph_name: "graph_missing".to_string(),
+        });
+        parent_graph.start_fn = fn_p.clone();
+        parent_graph.nodes = HashMap::from([("fn_p".to_string(), fn_p), ("sub".to_string(), sub)]);
+        parent_graph.edges = HashMap::from([("fn_p".to_string(), vec!["sub".into()])]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: parent_graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name("parent".to_string())
+            .payload(DataPayload {
+                path: "parent_input".to_string(),
+                size: 23,
+                sha256_hash: "hash".to_string(),
+            })
+            .build()?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "parent".to_string(),
+                    invocation_payload: invocation.clone(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let task_p = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 100)?
+            .remove(0);
+        finalize(&indexify_state, &task_p, vec![fn_output(&task_p, "p_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // The child invocation can't start, so the parent fails
+        let parent_ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "parent", &invocation.id)?;
+        assert!(parent_ctx.completed);
+        assert_eq!(parent_ctx.outcome(), InvocationOutcome::Failure);
+        assert_eq!(parent_ctx.fn_task_analytics["sub"].failed_tasks, 1);
+        assert_eq!(parent_ctx.pending_tasks(), 0);
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deleting_graphs_of_running_subgraph() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        // parent: fn_p -> sub (graph_A)
+        let mut parent_graph = mock_graph_a();
+        parent_graph.name = "parent".to_string();
+        let fn_p = Node::Compute(ComputeFn {
+            name: "fn_p".to_string(),
+            description: "".to_string(),
+            placement_constraints: Default::default(),
+            input: None,
+            output: None,
+            max_concurrency: None,
+            secrets: vec![],
+            fn_name: "fn_p".to_string(),
+        });
+        let sub = Node::Subgraph(SubgraphNode {
+            name: "sub".to_string(),
+            description: "".to_string(),
+            compute_graph_name: "graph_A".to_string(),
+        });
+        parent_graph.start_fn = fn_p.clone();
+        parent_graph.nodes = HashMap::from([("fn_p".to_string(), fn_p), ("sub".to_string(), sub)]);
+        parent_graph.edges = HashMap::from([("fn_p".to_string(), vec!["sub".into()])]);
+        async fn create_graph(
+            indexify_state: &IndexifyState,
+            compute_graph: ComputeGraph,
+        ) -> Result<()> {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await
+        }
+        async fn delete_graph(indexify_state: &IndexifyState, name: &str) -> Result<()> {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        name: name.to_string(),
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await
+        }
+        // Runs fn_p of a new parent invocation, returns it with the first
+        // task of the child invocation it starts
+        async fn start_child(
+            indexify_state: &IndexifyState,
+            scheduler: &Scheduler,
+        ) -> Result<(InvocationPayload, Task)> {
+            let invocation = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("parent".to_string())
+                .payload(DataPayload {
+                    path: "parent_input".to_string(),
+                    size: 23,
+                    sha256_hash: "hash".to_string(),
+                })
+                .build()?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "parent".to_string(),
+                        invocation_payload: invocation.clone(),
+                        idempotency_key: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            schedule_all(indexify_state, scheduler).await?;
+            let task_p = indexify_state
+                .reader()
+                .get_tasks_by_executor(&mock_executor_id(), 100)?
+                .into_iter()
+                .find(|task| task.invocation_id == invocation.id)
+                .ok_or(anyhow!("fn_p task not found"))?;
+            finalize(indexify_state, &task_p, vec![fn_output(&task_p, "p_out")?]).await?;
+            schedule_all(indexify_state, scheduler).await?;
+            let task_a = indexify_state
+                .reader()
+                .get_tasks_by_executor(&mock_executor_id(), 100)?
+                .into_iter()
+                .find(|task| task.compute_fn_name == "fn_a")
+                .ok_or(anyhow!("fn_a task not found"))?;
+            Ok((invocation, task_a))
+        }
+        create_graph(&indexify_state, mock_graph_a()).await?;
+        create_graph(&indexify_state, parent_graph.clone()).await?;
+
+        // A child whose parent was deleted finishes without continuing it
+        let (_, task_a) = start_child(&indexify_state, &scheduler).await?;
+        delete_graph(&indexify_state, "parent").await?;
+        finalize(&indexify_state, &task_a, vec![fn_output(&task_a, "a_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        for task in indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 100)?
+        {
+            finalize(&indexify_state, &task, vec![]).await?;
+        }
+        schedule_all(&indexify_state, &scheduler).await?;
+        let child_ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &task_a.invocation_id)?;
+        assert!(child_ctx.completed);
+
+        // Deleting the child's graph fails the parent waiting on it
+        create_graph(&indexify_state, parent_graph).await?;
+        let (invocation, _) = start_child(&indexify_state, &scheduler).await?;
+        delete_graph(&indexify_state, "graph_A").await?;
+        let parent_ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "parent", &invocation.id)?;
+        assert!(parent_ctx.completed);
+        assert_eq!(parent_ctx.outcome(), InvocationOutcome::Failure);
+        assert_eq!(parent_ctx.fn_task_analytics["sub"].failed_tasks, 1);
+        assert_eq!(parent_ctx.pending_tasks(), 0);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_conditional_edges() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        let condition = |expr: &str| -> Result<LabelsFilter> {
+            Ok(LabelsFilter(vec![Expression::from_str(expr)?]))
+        };
+        let mut graph = mock_graph_a();
+        graph.edges = HashMap::from([(
+            "fn_a".to_string(),
+            vec![
+                Edge::Conditional {
+                    target: "fn_b".to_string(),
+                    condition: condition("score>0.5")?,
+                },
+                Edge::Conditional {
+                    target: "fn_c".to_string(),
+                    condition: condition("score<=0.5")?,
+                },
+            ],
+        )]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        for (path, metadata, expected) in [
+            ("high", json!({"score": 0.9}), Some("fn_b")),
+            ("low", json!({"score": 0.1}), Some("fn_c")),
+            ("none", json!({}), None),
+        ] {
+            let invocation = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: path.to_string(),
+                    size: 23,
+                    sha256_hash: path.to_string(),
+                })
+                .build()?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        invocation_payload: invocation.clone(),
+                        idempotency_key: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            schedule_all(&indexify_state, &scheduler).await?;
+            let list_tasks = || {
+                indexify_state
+                    .reader()
+                    .list_tasks_by_compute_graph(
+                        TEST_NAMESPACE,
+                        "graph_A",
+                        &invocation.id,
+                        None,
+                        None,
+                    )
+                    .map(|(tasks, _)| tasks)
+            };
+            let task_a = list_tasks()?.remove(0);
+            let mut output = fn_output(&task_a, &format!("{}_out", path))?;
+            output.metadata = serde_json::from_value(metadata)?;
+            finalize(&indexify_state, &task_a, vec![output]).await?;
+            schedule_all(&indexify_state, &scheduler).await?;
+
+            let next: Vec<_> = list_tasks()?
+                .into_iter()
+                .filter(|task| task.compute_fn_name != "fn_a")
+                .map(|task| task.compute_fn_name)
+                .collect();
+            let ctx = indexify_state.reader().invocation_ctx(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation.id,
+            )?;
+            match expected {
+                Some(compute_fn) => {
+                    assert_eq!(next, vec![compute_fn.to_string()]);
+                    assert!(!ctx.completed);
+                }
+                // Nothing matched, so the invocation is done
+                None => {
+                    assert!(next.is_empty());
+                    assert!(ctx.completed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_condition_waits_for_other_branches() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        // fn_a -> fn_b, fn_c; fn_b -> fn_c if score>0.5
+        let mut graph = mock_graph_a();
+        graph.edges = HashMap::from([
+            ("fn_a".to_string(), vec!["fn_b".into(), "fn_c".into()]),
+            (
+                "fn_b".to_string(),
+                vec![Edge::Conditional {
+                    target: "fn_c".to_string(),
+                    condition: LabelsFilter(vec![Expression::from_str("score>0.5")?]),
+                }],
+            ),
+        ]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation = mock_invocation_payload();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation.clone(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = || {
+            indexify_state
+                .reader()
+                .get_tasks_by_executor(&mock_executor_id(), 100)
+        };
+        let ctx = || {
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation.id)
+        };
+
+        let task_a = executor_tasks()?.remove(0);
+        finalize(&indexify_state, &task_a, vec![fn_output(&task_a, "a_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let tasks = executor_tasks()?;
+        let task_b = tasks.iter().find(|t| t.compute_fn_name == "fn_b").unwrap();
+        let task_c = tasks.iter().find(|t| t.compute_fn_name == "fn_c").unwrap();
+
+        // fn_b's branch ends without a match while fn_c is still running
+        finalize(&indexify_state, task_b, vec![fn_output(task_b, "b_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert_eq!(executor_tasks()?.len(), 1);
+        assert!(!ctx()?.completed);
+
+        finalize(&indexify_state, task_c, vec![]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(ctx()?.completed);
+        assert_eq!(ctx()?.outcome(), InvocationOutcome::Success);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_leaf_in_same_batch_as_new_tasks_doesnt_finish_invocation() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+
+        // fn_a -> fn_b, fn_c; fn_b -> fn_c
+        let mut graph = mock_graph_a();
+        graph.edges = HashMap::from([
+            ("fn_a".to_string(), vec!["fn_b".into(), "fn_c".into()]),
+            ("fn_b".to_string(), vec!["fn_c".into()]),
+        ]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation = mock_invocation_payload();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation.clone(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let unfinished_tasks = |compute_fn: &str| -> Result<Vec<Task>> {
+            Ok(indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation.id, None, None)?
+                .0
+                .into_iter()
+                .filter(|task| task.compute_fn_name == compute_fn && !task.terminal_state())
+                .collect())
+        };
+        let ctx = || {
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation.id)
+        };
+
+        let task_a = unfinished_tasks("fn_a")?.remove(0);
+        finalize(&indexify_state, &task_a, vec![fn_output(&task_a, "a_out")?]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let task_b = unfinished_tasks("fn_b")?.remove(0);
+        let task_c = unfinished_tasks("fn_c")?.remove(0);
+
+        // Both finish before the scheduler runs, so one batch creates fn_b's
+        // fn_c task and handles the leaf fn_c finishing
+        finalize(&indexify_state, &task_b, vec![fn_output(&task_b, "b_out")?]).await?;
+        finalize(&indexify_state, &task_c, vec![]).await?;
+        scheduler.run_scheduler().await?;
+        assert!(!ctx()?.completed);
+        assert_eq!(ctx()?.pending_tasks(), 1);
+
+        let task_c = unfinished_tasks("fn_c")?.remove(0);
+        finalize(&indexify_state, &task_c, vec![]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(ctx()?.completed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency_caps_allocations() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(fn_a)) = graph.nodes.get_mut("fn_a") {
+            fn_a.max_concurrency = Some(1);
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        for path in ["first", "second"] {
+            let invocation = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: path.to_string(),
+                    size: 23,
+                    sha256_hash: path.to_string(),
+                })
+                .build()?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        invocation_payload: invocation,
+                        idempotency_key: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let allocated = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+
+        // Finishing the running task frees the slot for the waiting one
+        finalize(&indexify_state, &allocated[0], vec![]).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let allocated = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(allocated[0].compute_fn_name, "fn_a");
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_secrets_restrict_placement() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        ex.register_executor(mock_executor()).await?;
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SetSecret(SetSecretRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: "db_password".to_string(),
+                    value: "swordfish".to_string(),
+                    executor_labels: LabelsFilter(vec![Expression::from_str("gpu=true")?]),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(fn_a)) = graph.nodes.get_mut("fn_a") {
+            fn_a.secrets = vec!["db_password".to_string()];
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: mock_invocation_payload(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // The secret doesn't authorize the executor, so the task waits
+        assert!(indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?
+            .is_empty());
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+
+        let mut gpu_executor = mock_executor();
+        gpu_executor.id = ExecutorId::new("gpu".to_string());
+        gpu_executor.labels.insert("gpu".to_string(), json!(true));
+        ex.register_executor(gpu_executor.clone()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let allocated = indexify_state
+            .reader()
+            .get_tasks_by_executor(&gpu_executor.id, 10)?;
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(allocated[0].compute_fn_name, "fn_a");
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_remove() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -453,6 +2030,62 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_drain_executor() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()));
+        state_store.with_simple_graph().await;
+        ex.register_executor(mock_executor()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // Draining keeps the allocated tasks on the executor
+        ex.drain_executor(mock_executor_id(), false).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+
+        // Evicted tasks go back to the queue and aren't placed on the executor
+        // again, even if it reconnects
+        ex.drain_executor(mock_executor_id(), true).await?;
+        ex.register_executor(mock_executor()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 0);
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+
+        ex.resume_executor(mock_executor_id()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 0);
+
+        // Tasks already streamed to the executor aren't evicted
+        let mut stream = state_store::task_stream(indexify_state.clone(), mock_executor_id(), 10);
+        let streamed_tasks = stream.next().await.unwrap()?;
+        assert_eq!(streamed_tasks.len(), 1);
+        ex.drain_executor(mock_executor_id(), true).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_eq!(executor_tasks[0].id, streamed_tasks[0].id);
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 0);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_add_executor() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -1,4 +1,8 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::Result;
 use axum_server::Handle;
@@ -8,7 +12,14 @@ use tokio::{self, signal, sync::watch};
 use tracing::info;
 
 use super::{routes::RouteState, scheduler::Scheduler};
-use crate::{config::ServerConfig, executors::ExecutorManager, gc::Gc, routes::create_routes};
+use crate::{
+    config::ServerConfig,
+    executors::ExecutorManager,
+    gc::Gc,
+    metrics,
+    routes::create_routes,
+    webhooks::WebhookDispatcher,
+};
 
 pub struct Service {
     pub config: ServerConfig,
@@ -21,20 +32,32 @@ impl Service {
 
     pub async fn start(&self) -> Result<()> {
         let (shutdown_tx, shutdown_rx) = watch::channel(());
-        let indexify_state = IndexifyState::new(self.config.state_store_path.parse()?)?;
+        let state_store_path: PathBuf = self.config.state_store_path.parse()?;
+        let registry = Arc::new(metrics::init_provider()?);
+        let indexify_state = match &self.config.secrets_key_path {
+            Some(secrets_key_path) => {
+                IndexifyState::open(state_store_path, Path::new(secrets_key_path))?
+            }
+            None => IndexifyState::new(state_store_path)?,
+        };
         let blob_storage = Arc::new(BlobStorage::new(self.config.blob_storage.clone())?);
+        let _executor_metrics = metrics::executors::Metrics::new(indexify_state.clone());
         let executor_manager = Arc::new(ExecutorManager::new(indexify_state.clone()));
         let route_state = RouteState {
             indexify_state: indexify_state.clone(),
             blob_storage: blob_storage.clone(),
             executor_manager,
+            registry,
+            max_object_body_bytes: self.config.max_object_body_bytes,
         };
-        let app = create_routes(route_state);
+        let app = create_routes(route_state, self.config.max_batch_body_bytes);
         let handle = Handle::new();
         let handle_sh = handle.clone();
         let scheduler = Scheduler::new(indexify_state.clone());
 
         let mut gc = Gc::new(indexify_state.clone(), blob_storage, shutdown_rx.clone());
+        let mut webhook_dispatcher =
+            WebhookDispatcher::new(indexify_state.clone(), shutdown_rx.clone())?;
 
         let state_watcher_rx = indexify_state.get_state_change_watcher();
         tokio::spawn(async move {
@@ -47,6 +70,11 @@ impl Service {
             let _ = gc.start().await;
             info!("garbage collector shutdown");
         });
+        tokio::spawn(async move {
+            info!("starting webhook dispatcher");
+            let _ = webhook_dispatcher.start().await;
+            info!("webhook dispatcher shutdown");
+        });
 
         tokio::spawn(async move {
             shutdown_signal(handle_sh, shutdown_tx).await;
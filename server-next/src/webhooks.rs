@@ -0,0 +1,382 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use data_model::WebhookDelivery;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use indexify_utils::get_epoch_time_in_ms;
+use sha2::Sha256;
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tracing::{error, info, warn};
+
+pub const SIGNATURE_HEADER: &str = "X-Indexify-Signature";
+pub const DELIVERY_ID_HEADER: &str = "X-Indexify-Delivery";
+
+const MAX_DELIVERY_ATTEMPTS: u32 = 10;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+// Deliveries are attempted concurrently so a slow target doesn't hold up the
+// others
+const MAX_CONCURRENT_DELIVERIES: usize = 32;
+
+/// Returns the hex encoded HMAC-SHA256 of the payload, keyed with the secret
+/// of the webhook target.
+pub fn sign_payload(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| anyhow!("invalid webhook secret: {}", e))?;
+    mac.update(body);
+    Ok(format!("{:x}", mac.finalize().into_bytes()))
+}
+
+fn backoff(attempts: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+pub struct WebhookDispatcher {
+    state: Arc<IndexifyState>,
+    client: reqwest::Client,
+    rx: tokio::sync::watch::Receiver<()>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()?;
+        let rx = state.get_webhook_watcher();
+        Ok(Self {
+            state,
+            client,
+            rx,
+            shutdown_rx,
+        })
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        loop {
+            if self.shutdown_rx.has_changed().unwrap_or(false) {
+                info!("webhook dispatcher shutting down");
+                return Ok(());
+            }
+            // The deliveries stay in the state store, so a failed pass is retried
+            let next_attempt_at = match self.deliver_pending().await {
+                Ok(next_attempt_at) => next_attempt_at,
+                Err(err) => {
+                    error!("error delivering webhooks: {:?}", err);
+                    Some(get_epoch_time_in_ms() + INITIAL_BACKOFF.as_millis() as u64)
+                }
+            };
+            let retry = async {
+                match next_attempt_at {
+                    Some(at) => {
+                        let wait = at.saturating_sub(get_epoch_time_in_ms());
+                        tokio::time::sleep(Duration::from_millis(wait)).await
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = self.rx.changed() => { self.rx.borrow_and_update(); }
+                _ = retry => {}
+                _ = self.shutdown_rx.changed() => {
+                    info!("webhook dispatcher shutting down");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Attempts every delivery which is due and returns the time of the
+    /// earliest pending retry.
+    async fn deliver_pending(&self) -> Result<Option<u64>> {
+        let deliveries = self.state.reader().get_webhook_deliveries(None)?;
+        let now = get_epoch_time_in_ms();
+        let (due, waiting): (Vec<_>, Vec<_>) = deliveries
+            .into_iter()
+            .partition(|delivery| delivery.next_attempt_at <= now);
+        let mut next_attempt_at = waiting
+            .iter()
+            .map(|delivery| delivery.next_attempt_at)
+            .min();
+        let mut finished = Vec::new();
+        let mut attempts = futures::stream::iter(due)
+            .map(|delivery| async move {
+                let result = self.deliver(&delivery).await;
+                (delivery, result)
+            })
+            .buffer_unordered(MAX_CONCURRENT_DELIVERIES);
+        while let Some((mut delivery, result)) = attempts.next().await {
+            let Err(err) = result else {
+                finished.push(delivery.key());
+                continue;
+            };
+            delivery.attempts += 1;
+            if delivery.attempts >= MAX_DELIVERY_ATTEMPTS {
+                error!(
+                    "giving up on webhook delivery {} to {} after {} attempts: {:?}",
+                    delivery.id, delivery.target.url, delivery.attempts, err
+                );
+                finished.push(delivery.key());
+                continue;
+            }
+            warn!(
+                "webhook delivery {} to {} failed, attempt {}: {:?}",
+                delivery.id, delivery.target.url, delivery.attempts, err
+            );
+            delivery.next_attempt_at =
+                get_epoch_time_in_ms() + backoff(delivery.attempts).as_millis() as u64;
+            next_attempt_at = next_attempt_at
+                .map(|at| at.min(delivery.next_attempt_at))
+                .or(Some(delivery.next_attempt_at));
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::UpdateWebhookDelivery(delivery),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        if !finished.is_empty() {
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::RemoveWebhookDeliveries(finished),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        Ok(next_attempt_at)
+    }
+
+    async fn deliver(&self, delivery: &WebhookDelivery) -> Result<()> {
+        let body = serde_json::to_vec(&delivery.payload)?;
+        let mut request = self
+            .client
+            .post(&delivery.target.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(DELIVERY_ID_HEADER, &delivery.id);
+        if let Some(secret) = self.state.webhook_secret(delivery)? {
+            let signature = sign_payload(&secret, &body)?;
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", signature));
+        }
+        let response = request.body(body).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "webhook target {} responded with {}",
+                delivery.target.url,
+                response.status()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_invocation_payload, TEST_NAMESPACE},
+        ExecutorId,
+        InvocationFinishedPayload,
+        InvocationOutcome,
+        TaskDiagnostics,
+        TaskOutcome,
+        WebhookTarget,
+    };
+    use state_store::{
+        requests::{CreateComputeGraphRequest, FinalizeTaskRequest, InvokeComputeGraphRequest},
+        test_state_store::tests::TestStateStore,
+    };
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+
+    type ReceivedRequests = Arc<Mutex<Vec<(HeaderMap, Vec<u8>)>>>;
+
+    #[derive(Clone, Default)]
+    struct Listener {
+        requests: ReceivedRequests,
+        failures_left: Arc<AtomicUsize>,
+    }
+
+    async fn receive(
+        State(listener): State<Listener>,
+        headers: HeaderMap,
+        body: axum::body::Bytes,
+    ) -> StatusCode {
+        listener
+            .requests
+            .lock()
+            .unwrap()
+            .push((headers, body.to_vec()));
+        let failed = listener
+            .failures_left
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failed {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    async fn start_listener(failures: usize) -> Result<(String, Listener)> {
+        let listener = Listener::default();
+        listener.failures_left.store(failures, Ordering::SeqCst);
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(listener.clone());
+        let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = tcp.local_addr()?;
+        tokio::spawn(async move { axum::serve(tcp, app).await });
+        Ok((format!("http://{}/hook", addr), listener))
+    }
+
+    async fn fail_invocation(state_store: &TestStateStore, url: &str) -> Result<String> {
+        let indexify_state = state_store.indexify_state.clone();
+        let mut compute_graph = mock_graph_a();
+        compute_graph.webhooks = vec![WebhookTarget {
+            url: url.to_string(),
+            secret: Some("secret".to_string()),
+            sealed_secret: None,
+        }];
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_payload = mock_invocation_payload();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation_payload.clone(),
+                    idempotency_key: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let scheduler = Scheduler::new(indexify_state.clone());
+        scheduler.run_scheduler().await?;
+        let tasks = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_payload.id,
+                None,
+                None,
+            )?
+            .0;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    invocation_id: invocation_payload.id.clone(),
+                    task_id: tasks[0].id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: TaskOutcome::Failure,
+                    executor_id: ExecutorId::new("executor".to_string()),
+                    diagnostics: TaskDiagnostics::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        scheduler.run_scheduler().await?;
+        Ok(invocation_payload.id)
+    }
+
+    #[tokio::test]
+    async fn test_deliver_signed_payload_on_invocation_failure() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let (url, listener) = start_listener(0).await?;
+        let invocation_id = fail_invocation(&state_store, &url).await?;
+
+        let ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(ctx.completed);
+        let deliveries = indexify_state.reader().get_webhook_deliveries(None)?;
+        assert_eq!(deliveries.len(), 1);
+        // Only the sealed secret is stored
+        assert!(deliveries[0].target.secret.is_none());
+        assert!(deliveries[0].target.sealed_secret.is_some());
+
+        let (_, shutdown_rx) = tokio::sync::watch::channel(());
+        let dispatcher = WebhookDispatcher::new(indexify_state.clone(), shutdown_rx)?;
+        assert_eq!(dispatcher.deliver_pending().await?, None);
+        assert!(indexify_state
+            .reader()
+            .get_webhook_deliveries(None)?
+            .is_empty());
+
+        let requests = listener.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        let signature = headers.get(SIGNATURE_HEADER).unwrap().to_str()?;
+        assert_eq!(
+            signature,
+            format!("sha256={}", sign_payload("secret", body)?)
+        );
+        let payload: InvocationFinishedPayload = serde_json::from_slice(body)?;
+        assert_eq!(payload.invocation_id, invocation_id);
+        assert_eq!(payload.outcome, InvocationOutcome::Failure);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_delivery() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let (url, listener) = start_listener(1).await?;
+        fail_invocation(&state_store, &url).await?;
+
+        let (_, shutdown_rx) = tokio::sync::watch::channel(());
+        let dispatcher = WebhookDispatcher::new(indexify_state.clone(), shutdown_rx)?;
+        let next_attempt_at = dispatcher.deliver_pending().await?;
+        assert!(next_attempt_at.is_some());
+        let deliveries = indexify_state.reader().get_webhook_deliveries(None)?;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].attempts, 1);
+
+        // The delivery is not due before the backoff elapses
+        dispatcher.deliver_pending().await?;
+        assert_eq!(listener.requests.lock().unwrap().len(), 1);
+
+        tokio::time::sleep(backoff(1)).await;
+        assert_eq!(dispatcher.deliver_pending().await?, None);
+        assert_eq!(listener.requests.lock().unwrap().len(), 2);
+        assert!(indexify_state
+            .reader()
+            .get_webhook_deliveries(None)?
+            .is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use data_model::{ExecutorId, Task};
+
+/// (namespace, compute graph, compute fn)
+pub type FnKey = (String, String, String);
+
+pub fn fn_key(task: &Task) -> FnKey {
+    (
+        task.namespace.clone(),
+        task.compute_graph_name.clone(),
+        task.compute_fn_name.clone(),
+    )
+}
+
+// <namespace>|<compute_graph>|<invocation_id>|<compute_fn>|<task_id>
+pub fn fn_key_from_task_key(task_key: &[u8]) -> Result<FnKey> {
+    let task_key = std::str::from_utf8(task_key)?;
+    let parts: Vec<&str> = task_key.split('|').collect();
+    if parts.len() != 5 {
+        return Err(anyhow!("invalid task key: {}", task_key));
+    }
+    Ok((
+        parts[0].to_string(),
+        parts[1].to_string(),
+        parts[3].to_string(),
+    ))
+}
+
+/// Number of tasks allocated to every executor and compute fn. Kept in memory
+/// so that scheduling and listing executors don't scan the allocations.
+#[derive(Debug, Default, Clone)]
+pub struct AllocationCounts {
+    executors: HashMap<ExecutorId, usize>,
+    functions: HashMap<FnKey, usize>,
+}
+
+impl AllocationCounts {
+    pub fn executor(&self, executor_id: &ExecutorId) -> usize {
+        self.executors.get(executor_id).copied().unwrap_or_default()
+    }
+
+    pub fn functions(&self) -> &HashMap<FnKey, usize> {
+        &self.functions
+    }
+
+    pub fn add(&mut self, executor_id: ExecutorId, fn_key: FnKey) {
+        *self.executors.entry(executor_id).or_default() += 1;
+        *self.functions.entry(fn_key).or_default() += 1;
+    }
+
+    pub fn apply(&mut self, changes: &AllocationChanges) {
+        for (executor_id, delta) in &changes.executors {
+            apply_delta(&mut self.executors, executor_id, *delta);
+        }
+        for (fn_key, delta) in &changes.functions {
+            apply_delta(&mut self.functions, fn_key, *delta);
+        }
+    }
+}
+
+fn apply_delta<K: Clone + Eq + std::hash::Hash>(
+    counts: &mut HashMap<K, usize>,
+    key: &K,
+    delta: i64,
+) {
+    let count = counts.get(key).copied().unwrap_or_default() as i64 + delta;
+    if count > 0 {
+        counts.insert(key.clone(), count as usize);
+    } else {
+        counts.remove(key);
+    }
+}
+
+/// Allocations added and removed by a write, applied to the counts once its
+/// transaction commits
+#[derive(Debug, Default)]
+pub struct AllocationChanges {
+    executors: HashMap<ExecutorId, i64>,
+    functions: HashMap<FnKey, i64>,
+}
+
+impl AllocationChanges {
+    pub fn allocated(&mut self, executor_id: &ExecutorId, fn_key: FnKey) {
+        self.record(executor_id, fn_key, 1);
+    }
+
+    pub fn unallocated(&mut self, executor_id: &ExecutorId, fn_key: FnKey) {
+        self.record(executor_id, fn_key, -1);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.executors.is_empty()
+    }
+
+    fn record(&mut self, executor_id: &ExecutorId, fn_key: FnKey, delta: i64) {
+        *self.executors.entry(executor_id.clone()).or_default() += delta;
+        *self.functions.entry(fn_key).or_default() += delta;
+    }
+}
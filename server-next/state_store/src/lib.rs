@@ -1,34 +1,40 @@
 use std::{
     cmp::Ordering,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::{
         atomic::{self, AtomicU64},
         Arc,
         RwLock,
     },
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
     vec,
 };
 
+use allocations::{AllocationChanges, AllocationCounts};
 use anyhow::{anyhow, Result};
 use data_model::{
     ChangeType,
+    ExecutorHeartbeat,
     ExecutorId,
+    ExecutorMetadata,
     InvokeComputeGraphEvent,
+    Secret,
     StateChange,
     StateChangeBuilder,
     StateChangeId,
     Task,
     TaskFinishedEvent,
     TaskId,
+    WebhookDelivery,
 };
 use futures::Stream;
 use indexify_utils::get_epoch_time_in_ms;
 use requests::StateMachineUpdateRequest;
 use rocksdb::{ColumnFamilyDescriptor, Options, TransactionDB, TransactionDBOptions};
+use secrets::SecretsCipher;
 use state_machine::IndexifyObjectsColumns;
 use strum::IntoEnumIterator;
 use tokio::sync::{
@@ -36,12 +42,45 @@ use tokio::sync::{
     watch::{Receiver, Sender},
 };
 
+pub mod allocations;
+pub mod metrics;
 pub mod requests;
 pub mod scanner;
+pub mod secrets;
 pub mod serializer;
 pub mod state_machine;
 pub mod test_state_store;
 
+/// Failures caused by the state of the objects being read or written, as
+/// opposed to storage errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateMachineError {
+    /// The invocation has tasks or child invocations in progress
+    InvocationInProgress(String),
+    /// A secret is missing or doesn't authorize the executor
+    SecretUnavailable {
+        name: String,
+        executor_id: ExecutorId,
+    },
+}
+
+impl std::fmt::Display for StateMachineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateMachineError::InvocationInProgress(invocation_id) => {
+                write!(f, "invocation has work in progress: {}", invocation_id)
+            }
+            StateMachineError::SecretUnavailable { name, executor_id } => write!(
+                f,
+                "secret {} is missing or not available to executor {}",
+                name, executor_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateMachineError {}
+
 #[derive(Debug, Clone)]
 pub struct UnfinishedTask {
     pub id: TaskId,
@@ -75,6 +114,14 @@ impl Ord for UnfinishedTask {
 pub struct ExecutorState {
     pub new_task_channel: broadcast::Sender<()>,
     pub num_registered: u64,
+    pub last_heartbeat: Option<ExecutorHeartbeat>,
+    // Mirrors the stored executor's draining flag for the metrics
+    pub draining: bool,
+    // Allocated tasks missing from the executor's heartbeats, and when they
+    // were first noticed missing
+    pub unreported_tasks: HashMap<TaskId, u64>,
+    // Allocated tasks sent to the executor by its task stream
+    pub streamed_tasks: HashSet<TaskId>,
 }
 
 impl ExecutorState {
@@ -83,9 +130,51 @@ impl ExecutorState {
         Self {
             new_task_channel,
             num_registered: 0,
+            last_heartbeat: None,
+            draining: false,
+            unreported_tasks: HashMap::new(),
+            streamed_tasks: HashSet::new(),
         }
     }
 
+    /// Tasks the executor has received, which can't be taken back from it
+    pub fn delivered_tasks(&self) -> HashSet<TaskId> {
+        let mut delivered = self.streamed_tasks.clone();
+        if let Some(heartbeat) = &self.last_heartbeat {
+            delivered.extend(heartbeat.running_task_ids.iter().cloned());
+        }
+        delivered
+    }
+
+    /// Records a heartbeat and returns the allocated tasks the executor hasn't
+    /// reported for longer than `HEARTBEAT_RECONCILE_GRACE`.
+    pub fn record_heartbeat(
+        &mut self,
+        heartbeat: ExecutorHeartbeat,
+        allocated: &[Task],
+    ) -> Vec<Task> {
+        let now = heartbeat.received_at;
+        let running: HashSet<&TaskId> = heartbeat.running_task_ids.iter().collect();
+        let mut unreported = HashMap::new();
+        let mut lost = Vec::new();
+        for task in allocated {
+            if running.contains(&task.id) {
+                continue;
+            }
+            let since = self.unreported_tasks.get(&task.id).copied().unwrap_or(now);
+            if now.saturating_sub(since) >= HEARTBEAT_RECONCILE_GRACE.as_millis() as u64 {
+                lost.push(task.clone());
+            } else {
+                unreported.insert(task.id.clone(), since);
+            }
+        }
+        self.unreported_tasks = unreported;
+        let allocated: HashSet<&TaskId> = allocated.iter().map(|task| &task.id).collect();
+        self.streamed_tasks.retain(|id| allocated.contains(id));
+        self.last_heartbeat = Some(heartbeat);
+        lost
+    }
+
     pub fn added(&mut self) {
         let _ = self.new_task_channel.send(());
     }
@@ -111,18 +200,40 @@ pub type TaskStream = Pin<Box<dyn Stream<Item = Result<Vec<Task>>> + Send + Sync
 
 pub const EXECUTOR_TIMEOUT: Duration = Duration::from_secs(5);
 
+// How long an allocated task can be missing from an executor's heartbeats
+// before it's returned to the queue. Covers tasks allocated after the executor
+// built its heartbeat.
+pub const HEARTBEAT_RECONCILE_GRACE: Duration = Duration::from_secs(30);
+
+// Executors that haven't sent a heartbeat for this long are reported unhealthy
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Default location of the secrets key, next to the state store directory
+pub const SECRETS_KEY_FILE: &str = "secrets.key";
+
 pub struct IndexifyState {
     pub db: Arc<TransactionDB>,
     pub executor_states: RwLock<HashMap<ExecutorId, ExecutorState>>,
+    pub allocation_counts: RwLock<AllocationCounts>,
     pub state_change_tx: Sender<StateChangeId>,
     pub state_change_rx: Receiver<StateChangeId>,
     pub last_state_change_id: Arc<AtomicU64>,
     pub gc_channel_tx: tokio::sync::watch::Sender<()>,
     pub gc_channel_rx: tokio::sync::watch::Receiver<()>,
+    pub webhook_channel_tx: tokio::sync::watch::Sender<()>,
+    pub webhook_channel_rx: tokio::sync::watch::Receiver<()>,
+    pub metrics: metrics::Metrics,
+    secrets: SecretsCipher,
 }
 
 impl IndexifyState {
     pub fn new(path: PathBuf) -> Result<Arc<Self>> {
+        let secrets_key_path = path.with_file_name(SECRETS_KEY_FILE);
+        Self::open(path, &secrets_key_path)
+    }
+
+    pub fn open(path: PathBuf, secrets_key_path: &Path) -> Result<Arc<Self>> {
+        let secrets = SecretsCipher::load_or_create(secrets_key_path)?;
         let (tx, rx) = tokio::sync::watch::channel(StateChangeId::new(std::u64::MAX));
         fs::create_dir_all(path.clone())?;
         let sm_column_families = IndexifyObjectsColumns::iter()
@@ -138,24 +249,33 @@ impl IndexifyState {
         )
         .map_err(|e| anyhow!("failed to open db: {}", e))?;
         let (gc_tx, gc_rx) = tokio::sync::watch::channel(());
+        let (webhook_tx, webhook_rx) = tokio::sync::watch::channel(());
         let s = Arc::new(Self {
             db: Arc::new(db),
             state_change_tx: tx,
             state_change_rx: rx,
             last_state_change_id: Arc::new(AtomicU64::new(0)),
             executor_states: RwLock::new(HashMap::new()),
+            allocation_counts: RwLock::new(AllocationCounts::default()),
             gc_channel_tx: gc_tx,
             gc_channel_rx: gc_rx,
+            webhook_channel_tx: webhook_tx,
+            webhook_channel_rx: webhook_rx,
+            metrics: metrics::Metrics::new(),
+            secrets,
         });
+        s.metrics
+            .set_unallocated_tasks(s.reader().unallocated_tasks()?.len());
+        s.metrics
+            .set_gc_pending_urls(s.reader().get_gc_urls(None)?.len());
+        *s.allocation_counts.write().unwrap() = s.reader().allocation_counts()?;
 
         let executors = s.reader().get_all_executors()?;
         for executor in executors.iter() {
-            s.executor_states
-                .write()
-                .unwrap()
-                .entry(executor.id.clone())
-                .or_default()
-                .num_registered += 1;
+            let mut states = s.executor_states.write().unwrap();
+            let state = states.entry(executor.id.clone()).or_default();
+            state.num_registered += 1;
+            state.draining = executor.draining;
         }
         let cs = s.clone();
         tokio::spawn(async move {
@@ -184,8 +304,19 @@ impl IndexifyState {
         self.gc_channel_rx.clone()
     }
 
+    pub fn get_webhook_watcher(&self) -> Receiver<()> {
+        self.webhook_channel_rx.clone()
+    }
+
     pub async fn write(&self, request: StateMachineUpdateRequest) -> Result<()> {
+        let started_at = Instant::now();
+        let request_kind: &'static str = (&request.payload).into();
         let txn = self.db.transaction();
+        let mut finished_invocations = Vec::new();
+        let mut finished_task = None;
+        let mut gc_urls_added = 0;
+        let mut gc_urls_removed = 0;
+        let mut allocation_changes = AllocationChanges::default();
         let new_state_changes = match request.payload {
             requests::RequestPayload::InvokeComputeGraph(invoke_compute_graph_request) => {
                 let state_changes = self
@@ -198,9 +329,46 @@ impl IndexifyState {
                 )?;
                 state_changes
             }
+            requests::RequestPayload::InvokeComputeGraphBatch(request) => {
+                let mut state_changes = Vec::new();
+                for invocation_payload in request.invocation_payloads {
+                    let invoke_compute_graph_request = requests::InvokeComputeGraphRequest {
+                        namespace: request.batch.namespace.clone(),
+                        compute_graph_name: request.batch.compute_graph_name.clone(),
+                        invocation_payload,
+                        idempotency_key: None,
+                    };
+                    state_changes.extend(
+                        self.invoke_compute_graph(&invoke_compute_graph_request)
+                            .await?,
+                    );
+                    state_machine::create_graph_input(
+                        self.db.clone(),
+                        &txn,
+                        &invoke_compute_graph_request,
+                    )?;
+                }
+                state_machine::create_invocation_batch(self.db.clone(), &txn, &request.batch)?;
+                state_changes
+            }
             requests::RequestPayload::FinalizeTask(finalize_task) => {
+                finished_task = self
+                    .reader()
+                    .get_task(
+                        &finalize_task.namespace,
+                        &finalize_task.compute_graph,
+                        &finalize_task.invocation_id,
+                        &finalize_task.compute_fn,
+                        &finalize_task.task_id.to_string(),
+                    )?
+                    .map(|task| (task, finalize_task.task_outcome.clone()));
                 let state_changes = self.finalize_task(&finalize_task).await?;
-                state_machine::mark_task_completed(self.db.clone(), &txn, &finalize_task)?;
+                state_machine::mark_task_completed(
+                    self.db.clone(),
+                    &txn,
+                    &finalize_task,
+                    &mut allocation_changes,
+                )?;
                 state_changes
             }
             requests::RequestPayload::CreateNameSpace(namespace_request) => {
@@ -208,11 +376,18 @@ impl IndexifyState {
                 vec![]
             }
             requests::RequestPayload::CreateComputeGraph(req) => {
-                state_machine::create_compute_graph(self.db.clone(), req.compute_graph)?;
+                let mut compute_graph = req.compute_graph;
+                for webhook in &mut compute_graph.webhooks {
+                    if let Some(secret) = webhook.secret.take() {
+                        let aad = webhook.secret_key(&compute_graph.namespace, &compute_graph.name);
+                        webhook.sealed_secret = Some(self.secrets.seal_value(&aad, &secret)?);
+                    }
+                }
+                state_machine::create_compute_graph(self.db.clone(), compute_graph)?;
                 vec![]
             }
             requests::RequestPayload::DeleteComputeGraph(request) => {
-                state_machine::delete_compute_graph(
+                gc_urls_added = state_machine::delete_compute_graph(
                     self.db.clone(),
                     &txn,
                     &request.namespace,
@@ -226,9 +401,30 @@ impl IndexifyState {
                 vec![]
             }
             requests::RequestPayload::SchedulerUpdate(request) => {
-                let new_state_changes = self.change_events_for_scheduler_update(&request);
+                let mut new_state_changes = self.change_events_for_scheduler_update(&request);
                 for req in &request.task_requests {
                     state_machine::create_tasks(self.db.clone(), &txn, req)?;
+                    if req.invocation_finished {
+                        finished_invocations
+                            .push((req.namespace.clone(), req.compute_graph.clone()));
+                    }
+                    for child in &req.child_invocations {
+                        let invoke_compute_graph_request = requests::InvokeComputeGraphRequest {
+                            namespace: child.namespace.clone(),
+                            compute_graph_name: child.compute_graph_name.clone(),
+                            invocation_payload: child.clone(),
+                            idempotency_key: None,
+                        };
+                        new_state_changes.extend(
+                            self.invoke_compute_graph(&invoke_compute_graph_request)
+                                .await?,
+                        );
+                        state_machine::create_graph_input(
+                            self.db.clone(),
+                            &txn,
+                            &invoke_compute_graph_request,
+                        )?;
+                    }
                 }
                 for allocation in &request.allocations {
                     state_machine::allocate_tasks(
@@ -236,6 +432,7 @@ impl IndexifyState {
                         &txn,
                         &allocation.task,
                         &allocation.executor,
+                        &mut allocation_changes,
                     )?;
                     self.executor_states
                         .write()
@@ -253,7 +450,15 @@ impl IndexifyState {
                     let entry = states.entry(request.executor.id.clone()).or_default();
                     entry.num_registered += 1;
                 }
-                state_machine::register_executor(self.db.clone(), &txn, &request)?;
+                let draining = state_machine::register_executor(self.db.clone(), &txn, &request)?;
+                if let Some(state) = self
+                    .executor_states
+                    .write()
+                    .unwrap()
+                    .get_mut(&request.executor.id)
+                {
+                    state.draining = draining;
+                }
                 self.register_executor(&request)
             }
             requests::RequestPayload::DeregisterExecutor(request) => {
@@ -275,12 +480,108 @@ impl IndexifyState {
                 if removed {
                     println!("Deregistering executor: {}", request.executor_id);
                     tracing::info!("De-registering executor: {}", request.executor_id);
-                    state_machine::deregister_executor(self.db.clone(), &txn, &request)?;
+                    state_machine::deregister_executor(
+                        self.db.clone(),
+                        &txn,
+                        &request,
+                        &mut allocation_changes,
+                    )?;
                 }
                 state_changes
             }
+            requests::RequestPayload::DrainExecutor(request) => {
+                let delivered = self
+                    .executor_states
+                    .read()
+                    .unwrap()
+                    .get(&request.executor_id)
+                    .map(|state| state.delivered_tasks())
+                    .unwrap_or_default();
+                state_machine::drain_executor(
+                    self.db.clone(),
+                    &txn,
+                    &request,
+                    &delivered,
+                    &mut allocation_changes,
+                )?;
+                if let Some(state) = self
+                    .executor_states
+                    .write()
+                    .unwrap()
+                    .get_mut(&request.executor_id)
+                {
+                    state.draining = request.draining;
+                }
+                self.drain_executor_events(&request)
+            }
+            requests::RequestPayload::ReconcileExecutorTasks(request) => {
+                let tasks = state_machine::reconcile_executor_tasks(
+                    self.db.clone(),
+                    &txn,
+                    &request,
+                    &mut allocation_changes,
+                )?;
+                if !tasks.is_empty() {
+                    tracing::warn!(
+                        "executor {} lost track of {} tasks, returning them to the queue",
+                        request.executor_id,
+                        tasks.len()
+                    );
+                }
+                self.task_created_events(&tasks)
+            }
+            requests::RequestPayload::AddGcUrls(urls) => {
+                gc_urls_added = state_machine::add_gc_urls(self.db.clone(), &txn, urls)?;
+                self.gc_channel_tx.send(()).unwrap();
+                vec![]
+            }
             requests::RequestPayload::RemoveGcUrls(urls) => {
-                state_machine::remove_gc_urls(self.db.clone(), &txn, urls)?;
+                gc_urls_removed = state_machine::remove_gc_urls(self.db.clone(), &txn, urls)?;
+                vec![]
+            }
+            requests::RequestPayload::RemoveExpiredIdempotencyKeys(keys) => {
+                state_machine::remove_expired_idempotency_keys(self.db.clone(), &txn, keys)?;
+                vec![]
+            }
+            requests::RequestPayload::RequeueDeadLetters(keys) => {
+                let (tasks, gc_urls) =
+                    state_machine::requeue_dead_letters(self.db.clone(), &txn, &keys)?;
+                gc_urls_added = gc_urls;
+                self.task_created_events(&tasks)
+            }
+            requests::RequestPayload::RerunInvocation(request) => {
+                let (tasks, gc_urls) =
+                    state_machine::rerun_invocation(self.db.clone(), &txn, &request)?;
+                gc_urls_added = gc_urls;
+                self.task_created_events(&tasks)
+            }
+            requests::RequestPayload::SetSecret(request) => {
+                let (nonce, ciphertext) =
+                    self.secrets
+                        .seal(&request.namespace, &request.name, &request.value)?;
+                let now = get_epoch_time_in_ms();
+                let secret = Secret {
+                    namespace: request.namespace,
+                    name: request.name,
+                    nonce,
+                    ciphertext,
+                    executor_labels: request.executor_labels,
+                    created_at: now,
+                    updated_at: now,
+                };
+                state_machine::set_secret(self.db.clone(), &txn, secret)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteSecret(request) => {
+                state_machine::delete_secret(self.db.clone(), &txn, &request)?;
+                vec![]
+            }
+            requests::RequestPayload::UpdateWebhookDelivery(delivery) => {
+                state_machine::update_webhook_delivery(self.db.clone(), &txn, &delivery)?;
+                vec![]
+            }
+            requests::RequestPayload::RemoveWebhookDeliveries(keys) => {
+                state_machine::remove_webhook_deliveries(self.db.clone(), &txn, keys)?;
                 vec![]
             }
         };
@@ -293,9 +594,34 @@ impl IndexifyState {
             &request.state_changes_processed,
         )?;
         txn.commit()?;
+        if !allocation_changes.is_empty() {
+            self.allocation_counts
+                .write()
+                .unwrap()
+                .apply(&allocation_changes);
+        }
+        self.metrics.record_gc_urls(gc_urls_added, gc_urls_removed);
+        for state_change in &new_state_changes {
+            if let ChangeType::InvokeComputeGraph(event) = &state_change.change_type {
+                self.metrics
+                    .record_invocation_created(&event.namespace, &event.compute_graph);
+            }
+        }
         for state_change in new_state_changes {
             self.state_change_tx.send(state_change.id).unwrap();
         }
+        for (namespace, compute_graph) in &finished_invocations {
+            self.metrics
+                .record_invocation_finished(namespace, compute_graph);
+        }
+        if let Some((task, outcome)) = &finished_task {
+            self.metrics.record_task_finished(task, outcome);
+        }
+        self.metrics
+            .record_write(request_kind, started_at.elapsed());
+        if !finished_invocations.is_empty() {
+            self.webhook_channel_tx.send(()).unwrap();
+        }
         Ok(())
     }
 
@@ -367,6 +693,25 @@ impl IndexifyState {
         state_changes
     }
 
+    fn task_created_events(&self, tasks: &[Task]) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        tasks
+            .iter()
+            .map(|task| {
+                StateChangeBuilder::default()
+                    .change_type(ChangeType::TaskCreated)
+                    .created_at(get_epoch_time_in_ms())
+                    .object_id(task.id.to_string())
+                    .id(StateChangeId::new(last_change_id))
+                    .processed_at(None)
+                    .build()
+                    .unwrap()
+            })
+            .collect()
+    }
+
     fn deregister_executor_events(
         &self,
         request: &requests::DeregisterExecutorRequest,
@@ -385,6 +730,21 @@ impl IndexifyState {
         vec![state_change]
     }
 
+    fn drain_executor_events(&self, request: &requests::DrainExecutorRequest) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::ExecutorDraining)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(request.executor_id.get().to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
     fn register_executor(&self, request: &requests::RegisterExecutorRequest) -> Vec<StateChange> {
         let last_change_id = self
             .last_state_change_id
@@ -400,6 +760,74 @@ impl IndexifyState {
         vec![state_change]
     }
 
+    /// Stores the executor's heartbeat and returns the allocated tasks it has
+    /// stopped reporting.
+    pub fn record_heartbeat(
+        &self,
+        executor_id: &ExecutorId,
+        heartbeat: ExecutorHeartbeat,
+    ) -> Result<Vec<Task>> {
+        let allocated = self
+            .reader()
+            .get_tasks_by_executor(executor_id, usize::MAX)?;
+        let mut states = self.executor_states.write().unwrap();
+        let state = states
+            .get_mut(executor_id)
+            .ok_or(anyhow!("executor not registered: {}", executor_id))?;
+        Ok(state.record_heartbeat(heartbeat, &allocated))
+    }
+
+    pub fn executor_heartbeats(&self) -> HashMap<ExecutorId, ExecutorHeartbeat> {
+        self.executor_states
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(id, state)| {
+                state
+                    .last_heartbeat
+                    .clone()
+                    .map(|heartbeat| (id.clone(), heartbeat))
+            })
+            .collect()
+    }
+
+    /// Opens the named secrets of the namespace. Fails with
+    /// `StateMachineError::SecretUnavailable` if one doesn't exist or doesn't
+    /// authorize the executor.
+    pub fn resolve_secrets(
+        &self,
+        namespace: &str,
+        names: &[String],
+        executor: &ExecutorMetadata,
+    ) -> Result<HashMap<String, String>> {
+        let mut values = HashMap::new();
+        for name in names {
+            let secret = self
+                .reader()
+                .get_secret(namespace, name)?
+                .filter(|secret| secret.authorizes(executor))
+                .ok_or_else(|| StateMachineError::SecretUnavailable {
+                    name: name.clone(),
+                    executor_id: executor.id.clone(),
+                })?;
+            values.insert(name.clone(), self.secrets.open(&secret)?);
+        }
+        Ok(values)
+    }
+
+    /// Returns the secret the payloads of a webhook delivery are signed with
+    pub fn webhook_secret(&self, delivery: &WebhookDelivery) -> Result<Option<String>> {
+        let target = &delivery.target;
+        let Some(sealed) = &target.sealed_secret else {
+            return Ok(None);
+        };
+        let aad = target.secret_key(&delivery.payload.namespace, &delivery.payload.compute_graph);
+        self.secrets
+            .open_value(&aad, sealed)
+            .map(Some)
+            .map_err(|_| anyhow!("failed to open the secret of webhook {}", target.url))
+    }
+
     pub fn reader(&self) -> scanner::StateReader {
         scanner::StateReader::new(self.db.clone())
     }
@@ -419,7 +847,17 @@ pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize
                 .reader()
                 .get_tasks_by_executor(&executor, limit)
                  {
-                    Ok(tasks) => yield Ok(tasks),
+                    Ok(tasks) => {
+                        state
+                            .executor_states
+                            .write()
+                            .unwrap()
+                            .entry(executor.clone())
+                            .or_default()
+                            .streamed_tasks
+                            .extend(tasks.iter().map(|task| task.id.clone()));
+                        yield Ok(tasks)
+                    }
                     Err(e) => {
                         yield Err(e);
                         return;
@@ -437,9 +875,20 @@ mod tests {
     use std::collections::HashMap;
 
     use data_model::{
-        test_objects::tests::{mock_graph_a, TEST_NAMESPACE},
+        filter::{Expression, LabelsFilter},
+        test_objects::tests::{
+            mock_executor,
+            mock_graph_a,
+            mock_invocation_payload,
+            TEST_NAMESPACE,
+        },
         ComputeGraph,
+        DataPayload,
         GraphInvocationCtxBuilder,
+        IdempotencyKey,
+        InvocationBatch,
+        InvocationPayload,
+        InvocationPayloadBuilder,
         Namespace,
         TaskBuilder,
     };
@@ -447,7 +896,11 @@ mod tests {
     use requests::{
         CreateComputeGraphRequest,
         DeleteComputeGraphRequest,
+        InvokeComputeGraphBatchRequest,
+        InvokeComputeGraphRequest,
+        ReconcileExecutorTasksRequest,
         SchedulerUpdateRequest,
+        SetSecretRequest,
         TaskPlacement,
     };
     use tempfile::TempDir;
@@ -555,6 +1008,137 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_delete_compute_graph_removes_invocation_records() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_payload = mock_invocation_payload();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation_payload.clone(),
+                    idempotency_key: Some(IdempotencyKey {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        key: "key-1".to_string(),
+                        invocation_id: invocation_payload.id.clone(),
+                        payload_sha256: "hash1232".to_string(),
+                        expires_at: get_epoch_time_in_ms() + 60_000,
+                    }),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraphBatch(InvokeComputeGraphBatchRequest {
+                    batch: InvocationBatch {
+                        id: "batch_1".to_string(),
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        invocation_ids: vec![],
+                        created_at: 0,
+                    },
+                    invocation_payloads: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let task = TaskBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_fn_name("fn_a".to_string())
+            .compute_graph_name("graph_A".to_string())
+            .input_key(invocation_payload.id.clone())
+            .invocation_id(invocation_payload.id.clone())
+            .build()?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: "graph_A".to_string(),
+                        invocation_id: invocation_payload.id.clone(),
+                        invocation_finished: false,
+                        child_invocations: vec![],
+                        subgraph_outcome: None,
+                        failed_subgraph_nodes: vec![],
+                        tasks: vec![task.clone()],
+                    }],
+                    allocations: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let stdout = DataPayload {
+            path: "stdout_blob".to_string(),
+            size: 3,
+            sha256_hash: "hash".to_string(),
+        };
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(requests::FinalizeTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    invocation_id: invocation_payload.id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: data_model::TaskOutcome::Failure,
+                    executor_id: ExecutorId::new("executor1".to_string()),
+                    diagnostics: data_model::TaskDiagnostics {
+                        stdout: Some(stdout.clone()),
+                        ..Default::default()
+                    },
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let reader = indexify_state.reader();
+        assert!(reader
+            .dead_letter(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_payload.id,
+                &task.id.to_string()
+            )?
+            .is_some());
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: "graph_A".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        for cf in [
+            IndexifyObjectsColumns::DeadLetters,
+            IndexifyObjectsColumns::WebhookDeliveries,
+            IndexifyObjectsColumns::IdempotencyKeys,
+            IndexifyObjectsColumns::InvocationBatches,
+        ] {
+            let cf = cf.cf_db(&indexify_state.db);
+            let mut rows = indexify_state
+                .db
+                .iterator_cf(&cf, rocksdb::IteratorMode::Start);
+            assert!(rows.next().is_none());
+        }
+        assert!(reader.get_gc_urls(None)?.contains(&stdout.path));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_stream() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -586,6 +1170,9 @@ mod tests {
             compute_graph: task.compute_graph_name.clone(),
             invocation_id: task.invocation_id.clone(),
             invocation_finished: false,
+            child_invocations: vec![],
+            subgraph_outcome: None,
+            failed_subgraph_nodes: vec![],
             tasks: vec![task.clone()],
         };
 
@@ -641,6 +1228,9 @@ mod tests {
                 compute_graph: task_1.compute_graph_name.clone(),
                 invocation_id: task_1.invocation_id.clone(),
                 invocation_finished: false,
+                child_invocations: vec![],
+                subgraph_outcome: None,
+                failed_subgraph_nodes: vec![],
             }],
             allocations: vec![TaskPlacement {
                 task: task_1.clone(),
@@ -669,4 +1259,357 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_idempotency_key() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let invocation_payload = mock_invocation_payload();
+        let idempotency_key = IdempotencyKey {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph_name: "graph_A".to_string(),
+            key: "key-1".to_string(),
+            invocation_id: invocation_payload.id.clone(),
+            payload_sha256: "hash1232".to_string(),
+            expires_at: get_epoch_time_in_ms() + 60_000,
+        };
+        let invoke = |idempotency_key: IdempotencyKey| StateMachineUpdateRequest {
+            payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation_payload.clone(),
+                idempotency_key: Some(idempotency_key),
+            }),
+            state_changes_processed: vec![],
+        };
+        indexify_state
+            .write(invoke(idempotency_key.clone()))
+            .await?;
+
+        let stored = indexify_state
+            .reader()
+            .idempotency_key(TEST_NAMESPACE, "graph_A", "key-1")?;
+        assert_eq!(stored, Some(idempotency_key.clone()));
+
+        // A live key can't be claimed by a second invocation
+        assert!(indexify_state
+            .write(invoke(idempotency_key.clone()))
+            .await
+            .is_err());
+
+        // Expired keys are ignored and can be reused
+        let expired = IdempotencyKey {
+            key: "key-2".to_string(),
+            expires_at: get_epoch_time_in_ms() - 1,
+            ..idempotency_key.clone()
+        };
+        indexify_state.write(invoke(expired.clone())).await?;
+        assert!(indexify_state
+            .reader()
+            .idempotency_key(TEST_NAMESPACE, "graph_A", "key-2")?
+            .is_none());
+        indexify_state.write(invoke(expired)).await?;
+
+        // Only the expired key is collected
+        let expired_keys = indexify_state.reader().expired_idempotency_keys(None)?;
+        assert_eq!(
+            expired_keys,
+            vec![IdempotencyKey::key_from(TEST_NAMESPACE, "graph_A", "key-2")]
+        );
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RemoveExpiredIdempotencyKeys(expired_keys),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(indexify_state
+            .reader()
+            .expired_idempotency_keys(None)?
+            .is_empty());
+        assert!(indexify_state
+            .reader()
+            .idempotency_key(TEST_NAMESPACE, "graph_A", "key-1")?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_compute_graph_batch() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))?;
+        let payloads = |cg: &str| -> Vec<InvocationPayload> {
+            (0..3)
+                .map(|i| {
+                    InvocationPayloadBuilder::default()
+                        .namespace(TEST_NAMESPACE.to_string())
+                        .compute_graph_name(cg.to_string())
+                        .payload(DataPayload {
+                            path: format!("input_{}", i),
+                            size: 23,
+                            sha256_hash: format!("hash_{}", i),
+                        })
+                        .build()
+                        .unwrap()
+                })
+                .collect()
+        };
+        let batch = |cg: &str, invocation_payloads: &[InvocationPayload]| InvocationBatch {
+            id: "batch_1".to_string(),
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph_name: cg.to_string(),
+            invocation_ids: invocation_payloads.iter().map(|p| p.id.clone()).collect(),
+            created_at: 0,
+        };
+
+        // The batch is rejected as a whole when the graph doesn't exist
+        let invocation_payloads = payloads("graph_A");
+        let result = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraphBatch(InvokeComputeGraphBatchRequest {
+                    batch: batch("graph_A", &invocation_payloads),
+                    invocation_payloads: invocation_payloads.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(indexify_state
+            .reader()
+            .invocation_batch(TEST_NAMESPACE, "graph_A", "batch_1")?
+            .is_none());
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraphBatch(InvokeComputeGraphBatchRequest {
+                    batch: batch("graph_A", &invocation_payloads),
+                    invocation_payloads: invocation_payloads.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let stored = indexify_state
+            .reader()
+            .invocation_batch(TEST_NAMESPACE, "graph_A", "batch_1")?
+            .unwrap();
+        assert_eq!(stored, batch("graph_A", &invocation_payloads));
+        for invocation_payload in &invocation_payloads {
+            let ctx = indexify_state.reader().invocation_ctx(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_payload.id,
+            )?;
+            assert!(!ctx.completed);
+        }
+        let state_changes = indexify_state.reader().get_unprocessed_state_changes()?;
+        assert_eq!(state_changes.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reconciles_unreported_tasks() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))?;
+
+        let executor_id = ExecutorId::new("executor1".to_string());
+        let task = TaskBuilder::default()
+            .namespace("namespace".to_string())
+            .compute_fn_name("fn".to_string())
+            .compute_graph_name("graph".to_string())
+            .input_key("namespace|graph|ingested_id|fn|id_1".to_string())
+            .invocation_id("ingested_id".to_string())
+            .build()?;
+        let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
+            .namespace(task.namespace.clone())
+            .compute_graph_name(task.compute_graph_name.clone())
+            .invocation_id(task.invocation_id.clone())
+            .fn_task_analytics(HashMap::new())
+            .build()?;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            graph_invocation_ctx.key(),
+            &JsonEncoder::encode(&graph_invocation_ctx)?,
+        )?;
+        indexify_state
+            .executor_states
+            .write()
+            .unwrap()
+            .entry(executor_id.clone())
+            .or_default();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: task.namespace.clone(),
+                        compute_graph: task.compute_graph_name.clone(),
+                        invocation_id: task.invocation_id.clone(),
+                        invocation_finished: false,
+                        child_invocations: vec![],
+                        subgraph_outcome: None,
+                        failed_subgraph_nodes: vec![],
+                        tasks: vec![task.clone()],
+                    }],
+                    allocations: vec![TaskPlacement {
+                        task: task.clone(),
+                        executor: executor_id.clone(),
+                    }],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let fn_key = allocations::fn_key(&task);
+        {
+            let counts = indexify_state.allocation_counts.read().unwrap();
+            assert_eq!(counts.executor(&executor_id), 1);
+            assert_eq!(counts.functions().get(&fn_key), Some(&1));
+        }
+
+        // A reported task is never reconciled
+        let heartbeat = ExecutorHeartbeat {
+            running_task_ids: vec![task.id.clone()],
+            received_at: 1_000,
+            ..Default::default()
+        };
+        let lost = indexify_state.record_heartbeat(&executor_id, heartbeat)?;
+        assert!(lost.is_empty());
+
+        // A missing task gets a grace period
+        let heartbeat = ExecutorHeartbeat {
+            received_at: 2_000,
+            ..Default::default()
+        };
+        let lost = indexify_state.record_heartbeat(&executor_id, heartbeat)?;
+        assert!(lost.is_empty());
+
+        let heartbeat = ExecutorHeartbeat {
+            received_at: 2_000 + HEARTBEAT_RECONCILE_GRACE.as_millis() as u64,
+            ..Default::default()
+        };
+        let lost = indexify_state.record_heartbeat(&executor_id, heartbeat)?;
+        assert_eq!(lost.len(), 1);
+        assert_eq!(lost[0].id, task.id);
+        assert_eq!(
+            indexify_state.executor_heartbeats()[&executor_id].received_at,
+            2_000 + HEARTBEAT_RECONCILE_GRACE.as_millis() as u64
+        );
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::ReconcileExecutorTasks(ReconcileExecutorTasksRequest {
+                    executor_id: executor_id.clone(),
+                    tasks: lost,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let allocated = indexify_state
+            .reader()
+            .get_tasks_by_executor(&executor_id, 10)?;
+        assert!(allocated.is_empty());
+        let unallocated = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated.len(), 1);
+        assert_eq!(unallocated[0].id, task.id);
+        {
+            let counts = indexify_state.allocation_counts.read().unwrap();
+            assert_eq!(counts.executor(&executor_id), 0);
+            assert_eq!(counts.functions().get(&fn_key), None);
+        }
+
+        let unknown = ExecutorId::new("unknown".to_string());
+        assert!(indexify_state
+            .record_heartbeat(&unknown, ExecutorHeartbeat::default())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_secrets() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))?;
+        let set_secret =
+            |name: &str, value: &str, executor_labels: LabelsFilter| StateMachineUpdateRequest {
+                payload: RequestPayload::SetSecret(SetSecretRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: name.to_string(),
+                    value: value.to_string(),
+                    executor_labels,
+                }),
+                state_changes_processed: vec![],
+            };
+        indexify_state
+            .write(set_secret("api_key", "hunter2", LabelsFilter::default()))
+            .await?;
+        let gpu_only = LabelsFilter(vec![Expression::from_str("gpu=true")?]);
+        indexify_state
+            .write(set_secret("db_password", "swordfish", gpu_only))
+            .await?;
+
+        // Values are stored sealed
+        let stored = indexify_state.reader().list_secrets(TEST_NAMESPACE)?;
+        assert_eq!(stored.len(), 2);
+        for secret in &stored {
+            assert!(!String::from_utf8_lossy(&secret.ciphertext).contains("hunter2"));
+        }
+
+        let unavailable = |result: Result<HashMap<String, String>>| match result {
+            Err(e) => match e.downcast_ref::<StateMachineError>() {
+                Some(StateMachineError::SecretUnavailable { name, .. }) => Some(name.clone()),
+                _ => None,
+            },
+            Ok(_) => None,
+        };
+        let names = vec!["api_key".to_string(), "db_password".to_string()];
+        let mut executor = mock_executor();
+        let api_key = indexify_state.resolve_secrets(TEST_NAMESPACE, &names[..1], &executor)?;
+        assert_eq!(
+            api_key,
+            HashMap::from([("api_key".to_string(), "hunter2".to_string())])
+        );
+        // Unauthorized executors don't get a partial set of secrets
+        assert_eq!(
+            unavailable(indexify_state.resolve_secrets(TEST_NAMESPACE, &names, &executor)),
+            Some("db_password".to_string())
+        );
+
+        executor
+            .labels
+            .insert("gpu".to_string(), serde_json::json!(true));
+        let values = indexify_state.resolve_secrets(TEST_NAMESPACE, &names, &executor)?;
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["db_password"], "swordfish");
+
+        let missing = vec!["missing".to_string()];
+        assert_eq!(
+            unavailable(indexify_state.resolve_secrets(TEST_NAMESPACE, &missing, &executor)),
+            Some("missing".to_string())
+        );
+        // Secrets are scoped to their namespace
+        assert_eq!(
+            unavailable(indexify_state.resolve_secrets("other", &names, &executor)),
+            Some("api_key".to_string())
+        );
+        Ok(())
+    }
 }
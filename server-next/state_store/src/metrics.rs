@@ -0,0 +1,157 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use data_model::{Task, TaskOutcome};
+use opentelemetry::{
+    metrics::{Counter, Histogram, ObservableGauge, Unit},
+    KeyValue,
+};
+
+pub struct Metrics {
+    pub invocations_created: Counter<u64>,
+    pub invocations_finished: Counter<u64>,
+    pub task_latency: Histogram<f64>,
+    pub write_latency: Histogram<f64>,
+    pub unallocated_tasks: ObservableGauge<u64>,
+    pub gc_pending_urls: ObservableGauge<u64>,
+    unallocated_task_count: Arc<AtomicU64>,
+    gc_pending_url_count: Arc<AtomicU64>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let meter = opentelemetry::global::meter("indexify-state-store");
+
+        let invocations_created = meter
+            .u64_counter("indexify.invocations_created")
+            .with_description("Invocations created, including subgraph invocations")
+            .init();
+        let invocations_finished = meter
+            .u64_counter("indexify.invocations_finished")
+            .with_description("Invocations that finished")
+            .init();
+        let task_latency = meter
+            .f64_histogram("indexify.task_latency")
+            .with_unit(Unit::new("s"))
+            .with_description("Time from a task's creation until its outcome is reported")
+            .init();
+        let write_latency = meter
+            .f64_histogram("indexify.state_store.write_latency")
+            .with_unit(Unit::new("s"))
+            .with_description("Latency of state store writes by request")
+            .init();
+
+        let unallocated_task_count = Arc::new(AtomicU64::new(0));
+        let unallocated_tasks = meter
+            .u64_observable_gauge("indexify.unallocated_tasks")
+            .with_callback({
+                let unallocated_task_count = unallocated_task_count.clone();
+                move |observer| {
+                    observer.observe(unallocated_task_count.load(Ordering::Relaxed), &[]);
+                }
+            })
+            .with_description("Tasks left waiting for an executor by the last scheduling pass")
+            .init();
+
+        let gc_pending_url_count = Arc::new(AtomicU64::new(0));
+        let gc_pending_urls = meter
+            .u64_observable_gauge("indexify.gc.pending_urls")
+            .with_callback({
+                let gc_pending_url_count = gc_pending_url_count.clone();
+                move |observer| {
+                    observer.observe(gc_pending_url_count.load(Ordering::Relaxed), &[]);
+                }
+            })
+            .with_description("Blobs waiting to be garbage collected")
+            .init();
+
+        Metrics {
+            invocations_created,
+            invocations_finished,
+            task_latency,
+            write_latency,
+            unallocated_tasks,
+            gc_pending_urls,
+            unallocated_task_count,
+            gc_pending_url_count,
+        }
+    }
+
+    /// Recorded by the task scheduler after every pass so scrapes don't have
+    /// to scan the unallocated tasks.
+    pub fn set_unallocated_tasks(&self, count: usize) {
+        self.unallocated_task_count
+            .store(count as u64, Ordering::Relaxed);
+    }
+
+    /// Counted once when the state store is opened, then kept up to date by
+    /// the writes adding and removing GC urls.
+    pub fn set_gc_pending_urls(&self, count: usize) {
+        self.gc_pending_url_count
+            .store(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_gc_urls(&self, added: u64, removed: u64) {
+        if added == 0 && removed == 0 {
+            return;
+        }
+        let _ =
+            self.gc_pending_url_count
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    Some((count + added).saturating_sub(removed))
+                });
+    }
+
+    pub fn record_invocation_created(&self, namespace: &str, compute_graph: &str) {
+        self.invocations_created
+            .add(1, &graph_labels(namespace, compute_graph));
+    }
+
+    pub fn record_invocation_finished(&self, namespace: &str, compute_graph: &str) {
+        self.invocations_finished
+            .add(1, &graph_labels(namespace, compute_graph));
+    }
+
+    pub fn record_task_finished(&self, task: &Task, outcome: &TaskOutcome) {
+        let outcome = match outcome {
+            TaskOutcome::Success => "success",
+            TaskOutcome::Failure => "failure",
+            TaskOutcome::Unknown => "unknown",
+        };
+        let latency = SystemTime::now()
+            .duration_since(task.creation_time)
+            .unwrap_or(Duration::ZERO);
+        self.task_latency.record(
+            latency.as_secs_f64(),
+            &[
+                KeyValue::new("namespace", task.namespace.clone()),
+                KeyValue::new("compute_graph", task.compute_graph_name.clone()),
+                KeyValue::new("compute_fn", task.compute_fn_name.clone()),
+                KeyValue::new("outcome", outcome),
+            ],
+        );
+    }
+
+    pub fn record_write(&self, request: &'static str, latency: Duration) {
+        self.write_latency
+            .record(latency.as_secs_f64(), &[KeyValue::new("request", request)]);
+    }
+}
+
+fn graph_labels(namespace: &str, compute_graph: &str) -> [KeyValue; 2] {
+    [
+        KeyValue::new("namespace", namespace.to_string()),
+        KeyValue::new("compute_graph", compute_graph.to_string()),
+    ]
+}
@@ -1,12 +1,18 @@
 use data_model::{
+    filter::LabelsFilter,
     ComputeGraph,
     ExecutorId,
     ExecutorMetadata,
+    IdempotencyKey,
+    InvocationBatch,
     InvocationPayload,
     NodeOutput,
     StateChangeId,
     Task,
+    TaskDiagnostics,
     TaskId,
+    TaskOutcome,
+    WebhookDelivery,
 };
 
 pub struct StateMachineUpdateRequest {
@@ -14,8 +20,10 @@ pub struct StateMachineUpdateRequest {
     pub state_changes_processed: Vec<StateChangeId>,
 }
 
+#[derive(strum::IntoStaticStr)]
 pub enum RequestPayload {
     InvokeComputeGraph(InvokeComputeGraphRequest),
+    InvokeComputeGraphBatch(InvokeComputeGraphBatchRequest),
     FinalizeTask(FinalizeTaskRequest),
     CreateNameSpace(NamespaceRequest),
     CreateComputeGraph(CreateComputeGraphRequest),
@@ -24,7 +32,18 @@ pub enum RequestPayload {
     SchedulerUpdate(SchedulerUpdateRequest),
     RegisterExecutor(RegisterExecutorRequest),
     DeregisterExecutor(DeregisterExecutorRequest),
+    DrainExecutor(DrainExecutorRequest),
+    ReconcileExecutorTasks(ReconcileExecutorTasksRequest),
+    // Blobs uploaded by requests which failed to write their objects
+    AddGcUrls(Vec<String>),
     RemoveGcUrls(Vec<String>),
+    RemoveExpiredIdempotencyKeys(Vec<String>),
+    UpdateWebhookDelivery(WebhookDelivery),
+    RemoveWebhookDeliveries(Vec<String>),
+    RequeueDeadLetters(Vec<String>),
+    RerunInvocation(RerunInvocationRequest),
+    SetSecret(SetSecretRequest),
+    DeleteSecret(DeleteSecretRequest),
 }
 
 pub struct FinalizeTaskRequest {
@@ -36,12 +55,27 @@ pub struct FinalizeTaskRequest {
     pub node_outputs: Vec<NodeOutput>,
     pub task_outcome: data_model::TaskOutcome,
     pub executor_id: ExecutorId,
+    pub diagnostics: TaskDiagnostics,
+}
+
+pub struct RerunInvocationRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    // The function to rerun; everything downstream of it is recomputed
+    pub compute_fn: String,
 }
 
 pub struct InvokeComputeGraphRequest {
     pub namespace: String,
     pub compute_graph_name: String,
     pub invocation_payload: InvocationPayload,
+    pub idempotency_key: Option<IdempotencyKey>,
+}
+
+pub struct InvokeComputeGraphBatchRequest {
+    pub batch: InvocationBatch,
+    pub invocation_payloads: Vec<InvocationPayload>,
 }
 
 pub struct NamespaceRequest {
@@ -71,6 +105,18 @@ pub struct CreateTasksRequest {
     pub tasks: Vec<Task>,
     // Invocation ID -> Finished
     pub invocation_finished: bool,
+    // Child invocations started by the invocation's subgraph nodes
+    pub child_invocations: Vec<InvocationPayload>,
+    // Set when a child invocation of one of the subgraph nodes finished
+    pub subgraph_outcome: Option<SubgraphOutcome>,
+    // Subgraph nodes whose child invocation couldn't be started
+    pub failed_subgraph_nodes: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SubgraphOutcome {
+    pub node_name: String,
+    pub outcome: TaskOutcome,
 }
 
 #[derive(Debug)]
@@ -96,3 +142,29 @@ pub struct RegisterExecutorRequest {
 pub struct DeregisterExecutorRequest {
     pub executor_id: ExecutorId,
 }
+
+// The value is sealed by `IndexifyState` before it's written
+pub struct SetSecretRequest {
+    pub namespace: String,
+    pub name: String,
+    pub value: String,
+    pub executor_labels: LabelsFilter,
+}
+
+pub struct DeleteSecretRequest {
+    pub namespace: String,
+    pub name: String,
+}
+
+pub struct DrainExecutorRequest {
+    pub executor_id: ExecutorId,
+    pub draining: bool,
+    // Return the tasks allocated to the executor to the unallocated queue
+    pub evict: bool,
+}
+
+pub struct ReconcileExecutorTasksRequest {
+    pub executor_id: ExecutorId,
+    // Allocated tasks the executor no longer reports
+    pub tasks: Vec<Task>,
+}
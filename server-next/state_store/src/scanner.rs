@@ -3,20 +3,28 @@ use std::{mem, sync::Arc};
 use anyhow::{anyhow, Result};
 use data_model::{
     ComputeGraph,
+    DeadLetter,
     ExecutorId,
     ExecutorMetadata,
     GraphInvocationCtx,
+    IdempotencyKey,
+    InvocationBatch,
     InvocationPayload,
     Namespace,
     NodeOutput,
+    Secret,
     StateChange,
     Task,
+    WebhookDelivery,
 };
 use rocksdb::{Direction, IteratorMode, ReadOptions, TransactionDB};
 use serde::de::DeserializeOwned;
 
 use super::state_machine::IndexifyObjectsColumns;
-use crate::serializer::{JsonEncode, JsonEncoder};
+use crate::{
+    allocations::{self, AllocationCounts},
+    serializer::{JsonEncode, JsonEncoder},
+};
 #[derive(Debug)]
 pub struct FilterResponse<T> {
     pub items: Vec<T>,
@@ -307,6 +315,106 @@ impl StateReader {
         Ok(urls)
     }
 
+    pub fn get_webhook_deliveries(&self, limit: Option<usize>) -> Result<Vec<WebhookDelivery>> {
+        let (deliveries, _) = self.get_rows_from_cf_with_limits::<WebhookDelivery>(
+            &[],
+            None,
+            IndexifyObjectsColumns::WebhookDeliveries,
+            limit,
+        )?;
+        Ok(deliveries)
+    }
+
+    /// Returns the keys of expired idempotency keys, for the garbage collector
+    pub fn expired_idempotency_keys(&self, limit: Option<usize>) -> Result<Vec<String>> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let cf = IndexifyObjectsColumns::IdempotencyKeys.cf_db(&self.db);
+        let mut keys = Vec::new();
+        for kv in self.db.iterator_cf(&cf, IteratorMode::Start) {
+            let (key, value) = kv?;
+            let idempotency_key: IdempotencyKey = JsonEncoder::decode(&value)?;
+            if idempotency_key.is_expired() {
+                keys.push(String::from_utf8(key.into_vec())?);
+                if keys.len() >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Returns the idempotency key if it was used and hasn't expired yet.
+    pub fn idempotency_key(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>> {
+        let key = IdempotencyKey::key_from(namespace, compute_graph, key);
+        let idempotency_key =
+            self.get_from_cf::<IdempotencyKey, _>(&IndexifyObjectsColumns::IdempotencyKeys, key)?;
+        Ok(idempotency_key.filter(|k| !k.is_expired()))
+    }
+
+    pub fn invocation_batch(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        batch_id: &str,
+    ) -> Result<Option<InvocationBatch>> {
+        let key = InvocationBatch::key_from(namespace, compute_graph, batch_id);
+        self.get_from_cf(&IndexifyObjectsColumns::InvocationBatches, key)
+    }
+
+    pub fn list_dead_letters(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<DeadLetter>, Option<Vec<u8>>)> {
+        let key = format!("{}|{}|", namespace, compute_graph);
+        self.get_rows_from_cf_with_limits::<DeadLetter>(
+            key.as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::DeadLetters,
+            limit,
+        )
+    }
+
+    pub fn dead_letter(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        task_id: &str,
+    ) -> Result<Option<DeadLetter>> {
+        let key = DeadLetter::key_from(namespace, compute_graph, invocation_id, task_id);
+        self.get_from_cf(&IndexifyObjectsColumns::DeadLetters, key)
+    }
+
+    /// The failed task a dead letter refers to, with the diagnostics of the
+    /// failure
+    pub fn dead_letter_task(&self, dead_letter: &DeadLetter) -> Result<Option<Task>> {
+        self.get_from_cf(&IndexifyObjectsColumns::Tasks, dead_letter.task_key())
+    }
+
+    pub fn dead_letters_by_invocation(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Vec<DeadLetter>> {
+        let key = format!("{}|{}|{}|", namespace, compute_graph, invocation_id);
+        let (dead_letters, _) = self.get_rows_from_cf_with_limits::<DeadLetter>(
+            key.as_bytes(),
+            None,
+            IndexifyObjectsColumns::DeadLetters,
+            None,
+        )?;
+        Ok(dead_letters)
+    }
+
     pub fn get_unprocessed_state_changes(&self) -> Result<Vec<StateChange>> {
         let cf = IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&self.db);
         let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
@@ -398,6 +506,24 @@ impl StateReader {
         Ok(compute_graph)
     }
 
+    pub fn list_secrets(&self, namespace: &str) -> Result<Vec<Secret>> {
+        let prefix = format!("{}|", namespace);
+        let (secrets, _) = self.get_rows_from_cf_with_limits::<Secret>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::Secrets,
+            None,
+        )?;
+        Ok(secrets)
+    }
+
+    pub fn get_secret(&self, namespace: &str, name: &str) -> Result<Option<Secret>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::Secrets,
+            Secret::key_from(namespace, name),
+        )
+    }
+
     pub fn list_outputs_by_compute_graph(
         &self,
         namespace: &str,
@@ -476,6 +602,27 @@ impl StateReader {
         Ok(res.items)
     }
 
+    /// Counts the allocated tasks of every executor and compute fn
+    pub fn allocation_counts(&self) -> Result<AllocationCounts> {
+        let (allocations, _) = self.get_raw_rows_from_cf_with_limits(
+            &[],
+            None,
+            IndexifyObjectsColumns::TaskAllocations,
+            None,
+        )?;
+        let mut counts = AllocationCounts::default();
+        for (allocation_key, _) in allocations {
+            let executor_id = allocation_key
+                .split(|&b| b == b'|')
+                .next()
+                .ok_or(anyhow!("invalid allocation key"))?;
+            let executor_id = ExecutorId::new(String::from_utf8(executor_id.to_vec())?);
+            let task_key = Task::key_from_allocation_key(&allocation_key)?;
+            counts.add(executor_id, allocations::fn_key_from_task_key(&task_key)?);
+        }
+        Ok(counts)
+    }
+
     pub fn get_all_executors(&self) -> Result<Vec<ExecutorMetadata>> {
         let (executors, _) = self.get_rows_from_cf_with_limits::<ExecutorMetadata>(
             &[],
@@ -486,21 +633,28 @@ impl StateReader {
         Ok(executors)
     }
 
+    pub fn get_executor(&self, executor_id: &ExecutorId) -> Result<Option<ExecutorMetadata>> {
+        self.get_from_cf(&IndexifyObjectsColumns::Executors, executor_id.to_string())
+    }
+
     pub fn invocation_ctx(
         &self,
         namespace: &str,
         compute_graph: &str,
         invocation_id: &str,
     ) -> Result<GraphInvocationCtx> {
+        self.get_invocation_ctx(namespace, compute_graph, invocation_id)?
+            .ok_or(anyhow!("invocation ctx not found"))
+    }
+
+    pub fn get_invocation_ctx(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<GraphInvocationCtx>> {
         let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
-            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
-            &key,
-        )?;
-        match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
-            None => Err(anyhow!("invocation ctx not found")),
-        }
+        self.get_from_cf(&IndexifyObjectsColumns::GraphInvocationCtx, key)
     }
 
     pub fn invocation_payload(
@@ -509,15 +663,18 @@ impl StateReader {
         compute_graph: &str,
         invocation_id: &str,
     ) -> Result<InvocationPayload> {
+        self.get_invocation_payload(namespace, compute_graph, invocation_id)?
+            .ok_or(anyhow!("invocation payload not found"))
+    }
+
+    pub fn get_invocation_payload(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<InvocationPayload>> {
         let key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
-            &IndexifyObjectsColumns::GraphInvocations.cf_db(&self.db),
-            &key,
-        )?;
-        match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
-            None => Err(anyhow!("invocation payload not found")),
-        }
+        self.get_from_cf(&IndexifyObjectsColumns::GraphInvocations, key)
     }
 
     pub fn unallocated_tasks(&self) -> Result<Vec<Task>> {
@@ -530,8 +687,10 @@ impl StateReader {
             )
             .map_err(|e| anyhow!("unable to read unallocated tasks {}", e))?;
         let mut keys = vec![];
-        for (key, _) in &unallocated_task_rows {
-            let k: &[u8] = &key;
+        for (key, value) in &unallocated_task_rows {
+            // Rows queued before priorities were introduced have the task key
+            // as the row key and no value
+            let k: &[u8] = if value.is_empty() { key } else { value };
             keys.push(k);
         }
         let tasks = self.get_rows_from_cf_multi_key(keys, IndexifyObjectsColumns::Tasks)?;
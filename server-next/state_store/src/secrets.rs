@@ -0,0 +1,157 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use data_model::{SealedValue, Secret};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+const KEY_LEN: usize = 32;
+
+/// Seals secret values with AES-256-GCM before they are written to the state
+/// store. The secret's key is used as associated data, so a sealed value
+/// can't be moved to another secret or namespace.
+pub struct SecretsCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl SecretsCipher {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if key.len() != KEY_LEN {
+            return Err(anyhow!("secrets key must be {} bytes", KEY_LEN));
+        }
+        let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow!("invalid secrets key"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Reads the key from `path`, generating it on first start. The key is
+    /// kept outside the state store so a copy of the database alone doesn't
+    /// reveal the secrets.
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let key = fs::read(path)
+                .map_err(|e| anyhow!("failed to read secrets key {}: {}", path.display(), e))?;
+            return Self::new(&key);
+        }
+        let rng = SystemRandom::new();
+        let mut key = [0u8; KEY_LEN];
+        rng.fill(&mut key)
+            .map_err(|_| anyhow!("failed to generate secrets key"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(path)
+            .and_then(|mut file| file.write_all(&key))
+            .map_err(|e| anyhow!("failed to write secrets key {}: {}", path.display(), e))?;
+        tracing::info!("generated secrets key at {}", path.display());
+        Self::new(&key)
+    }
+
+    /// Returns the nonce and the ciphertext of the value.
+    pub fn seal(&self, namespace: &str, name: &str, value: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+        let sealed = self
+            .seal_value(&Secret::key_from(namespace, name), value)
+            .map_err(|_| anyhow!("failed to seal secret {}", name))?;
+        Ok((sealed.nonce, sealed.ciphertext))
+    }
+
+    pub fn open(&self, secret: &Secret) -> Result<String> {
+        let sealed = SealedValue {
+            nonce: secret.nonce.clone(),
+            ciphertext: secret.ciphertext.clone(),
+        };
+        self.open_value(&secret.key(), &sealed)
+            .map_err(|_| anyhow!("failed to open secret {}", secret.name))
+    }
+
+    /// Seals a value bound to `aad`, which has to be passed again to open it.
+    pub fn seal_value(&self, aad: &str, value: &str) -> Result<SealedValue> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("failed to generate nonce"))?;
+        let mut ciphertext = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad.as_bytes()),
+                &mut ciphertext,
+            )
+            .map_err(|_| anyhow!("failed to seal value"))?;
+        Ok(SealedValue {
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    pub fn open_value(&self, aad: &str, sealed: &SealedValue) -> Result<String> {
+        let nonce = Nonce::try_assume_unique_for_key(&sealed.nonce)
+            .map_err(|_| anyhow!("invalid nonce"))?;
+        let mut ciphertext = sealed.ciphertext.clone();
+        let value = self
+            .key
+            .open_in_place(nonce, Aad::from(aad.as_bytes()), &mut ciphertext)
+            .map_err(|_| anyhow!("failed to open value"))?;
+        Ok(String::from_utf8(value.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(cipher: &SecretsCipher, namespace: &str, name: &str, value: &str) -> Secret {
+        let (nonce, ciphertext) = cipher.seal(namespace, name, value).unwrap();
+        Secret {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            nonce,
+            ciphertext,
+            executor_labels: Default::default(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let key_path = temp_dir.path().join("secrets.key");
+        let cipher = SecretsCipher::load_or_create(&key_path)?;
+        let sealed = secret(&cipher, "ns", "api_key", "hunter2");
+        assert!(!sealed
+            .ciphertext
+            .windows(b"hunter2".len())
+            .any(|w| w == b"hunter2"));
+
+        // The key survives restarts
+        let cipher = SecretsCipher::load_or_create(&key_path)?;
+        assert_eq!(cipher.open(&sealed)?, "hunter2");
+
+        // A sealed value is bound to its secret
+        let mut moved = sealed.clone();
+        moved.name = "other".to_string();
+        assert!(cipher.open(&moved).is_err());
+
+        let other = SecretsCipher::new(&[7u8; KEY_LEN])?;
+        assert!(other.open(&sealed).is_err());
+        Ok(())
+    }
+}
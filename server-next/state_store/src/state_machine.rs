@@ -1,18 +1,31 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use data_model::{
     ComputeGraph,
+    DeadLetter,
     ExecutorId,
+    ExecutorMetadata,
     GraphInvocationCtx,
     GraphInvocationCtxBuilder,
+    IdempotencyKey,
+    InvocationBatch,
+    InvocationFinishedPayload,
+    InvocationPayload,
     Namespace,
     NodeOutput,
     OutputPayload,
+    Secret,
     StateChange,
     StateChangeId,
     Task,
     TaskAnalytics,
+    TaskId,
+    WebhookDelivery,
+    WebhookOutputRef,
 };
 use indexify_utils::{get_epoch_time_in_ms, OptionInspectNone};
 use rocksdb::{
@@ -29,14 +42,22 @@ use strum::AsRefStr;
 use tracing::error;
 
 use super::serializer::{JsonEncode, JsonEncoder};
-use crate::requests::{
-    CreateTasksRequest,
-    DeleteInvocationRequest,
-    DeregisterExecutorRequest,
-    FinalizeTaskRequest,
-    InvokeComputeGraphRequest,
-    NamespaceRequest,
-    RegisterExecutorRequest,
+use crate::{
+    allocations::{self, AllocationChanges},
+    requests::{
+        CreateTasksRequest,
+        DeleteInvocationRequest,
+        DeleteSecretRequest,
+        DeregisterExecutorRequest,
+        DrainExecutorRequest,
+        FinalizeTaskRequest,
+        InvokeComputeGraphRequest,
+        NamespaceRequest,
+        ReconcileExecutorTasksRequest,
+        RegisterExecutorRequest,
+        RerunInvocationRequest,
+    },
+    StateMachineError,
 };
 
 pub type ContentId = String;
@@ -58,17 +79,25 @@ pub enum IndexifyObjectsColumns {
     Tasks,              //  Ns_CG_<Invocation_Id>_Fn_TaskId -> Task
     GraphInvocationCtx, //  Ns_CG_IngestedId -> GraphInvocationCtx
 
-    GraphInvocations, //  Ns_Graph_Id -> InvocationPayload
-    FnOutputs,        //  Ns_Graph_<Ingested_Id>_Fn_Id -> NodeOutput
-    TaskOutputs,      //  NS_TaskID -> NodeOutputID
+    GraphInvocations,  //  Ns_Graph_Id -> InvocationPayload
+    ChildInvocations,  //  Ns_ParentGraph_ParentId_Node_ChildId -> Child invocation key
+    IdempotencyKeys,   //  Ns_Graph_Key -> IdempotencyKey
+    InvocationBatches, //  Ns_Graph_BatchId -> InvocationBatch
+    FnOutputs,         //  Ns_Graph_<Ingested_Id>_Fn_Id -> NodeOutput
+    TaskOutputs,       //  NS_TaskID -> NodeOutputID
 
     StateChanges, //  StateChangeId -> StateChange
 
     UnprocessedStateChanges, //  StateChangeId -> Empty
     TaskAllocations,         //  ExecutorId -> Task_Key
-    UnallocatedTasks,        //  Task_Key -> Empty
+    UnallocatedTasks,        //  Priority_CreationTime_TaskKey -> Task_Key
 
     GcUrls, // List of URLs pending deletion
+
+    WebhookDeliveries, //  Ns_CG_<Invocation_Id>_DeliveryId -> WebhookDelivery
+    DeadLetters,       //  Ns_CG_<Invocation_Id>_TaskId -> DeadLetter
+
+    Secrets, //  Ns_Name -> Secret (sealed value)
 }
 
 impl IndexifyObjectsColumns {
@@ -121,6 +150,40 @@ pub fn create_graph_input(
         req.invocation_payload.key(),
         &serialized_data_object,
     )?;
+    // Reruns of the parent remove the child invocations of its subgraph nodes
+    if let Some(parent) = &req.invocation_payload.parent {
+        txn.put_cf(
+            &IndexifyObjectsColumns::ChildInvocations.cf_db(&db),
+            format!(
+                "{}|{}|{}|{}|{}",
+                req.namespace,
+                parent.compute_graph_name,
+                parent.invocation_id,
+                parent.node_name,
+                req.invocation_payload.id
+            ),
+            req.invocation_payload.key(),
+        )?;
+    }
+    if let Some(idempotency_key) = &req.idempotency_key {
+        let cf = IndexifyObjectsColumns::IdempotencyKeys.cf_db(&db);
+        let existing = txn.get_for_update_cf(&cf, idempotency_key.key(), true)?;
+        if let Some(existing) = existing {
+            let existing: IdempotencyKey = JsonEncoder::decode(&existing)?;
+            if !existing.is_expired() {
+                return Err(anyhow!(
+                    "idempotency key {} is already used by invocation {}",
+                    existing.key,
+                    existing.invocation_id
+                ));
+            }
+        }
+        txn.put_cf(
+            &cf,
+            idempotency_key.key(),
+            &JsonEncoder::encode(idempotency_key)?,
+        )?;
+    }
 
     let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
         .namespace(req.namespace.to_string())
@@ -136,6 +199,19 @@ pub fn create_graph_input(
     Ok(())
 }
 
+pub(crate) fn create_invocation_batch(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    batch: &InvocationBatch,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::InvocationBatches.cf_db(&db),
+        batch.key(),
+        &JsonEncoder::encode(batch)?,
+    )?;
+    Ok(())
+}
+
 pub(crate) fn delete_input_data_object(
     db: Arc<TransactionDB>,
     req: &DeleteInvocationRequest,
@@ -194,17 +270,49 @@ fn delete_cf_prefix(
     Ok(())
 }
 
+/// Returns the number of blobs newly queued for garbage collection
 pub fn delete_compute_graph(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     namespace: &str,
     name: &str,
-) -> Result<()> {
+) -> Result<u64> {
     txn.delete_cf(
         &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
         format!("{}|{}", namespace, name),
     )?;
     let prefix = format!("{}|{}|", namespace, name);
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = iter?;
+        let invocation = JsonEncoder::decode::<InvocationPayload>(&value)?;
+        let Some(parent) = &invocation.parent else {
+            continue;
+        };
+        // The parent already continued if the child finished
+        let ctx = txn.get_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db), &key)?;
+        if let Some(ctx) = ctx {
+            if JsonEncoder::decode::<GraphInvocationCtx>(&ctx)?.completed {
+                continue;
+            }
+        }
+        txn.delete_cf(
+            &IndexifyObjectsColumns::ChildInvocations.cf_db(&db),
+            format!(
+                "{}|{}|{}|{}|{}",
+                invocation.namespace,
+                parent.compute_graph_name,
+                parent.invocation_id,
+                parent.node_name,
+                invocation.id
+            ),
+        )?;
+        fail_parent_invocation(db.clone(), txn, &invocation)?;
+    }
     delete_cf_prefix(
         txn,
         &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
@@ -217,6 +325,8 @@ pub fn delete_compute_graph(
         prefix.as_bytes(),
     )?;
 
+    let mut gc_urls = 0;
+
     for iter in make_prefix_iterator(
         txn,
         &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
@@ -229,30 +339,152 @@ pub fn delete_compute_graph(
             OutputPayload::Router(_) => {}
             OutputPayload::Fn(payload) => {
                 println!("delete_compute_graph: {:?}", value.clone());
-                txn.put_cf(
-                    &IndexifyObjectsColumns::GcUrls.cf_db(&db),
-                    payload.path.as_bytes(),
-                    &[],
-                )?;
+                gc_urls += add_gc_url(&db, txn, &payload.path)? as u64;
             }
         }
         txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
     }
 
+    // The captured output of the tasks is only referenced by their diagnostics
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = iter?;
+        let task = JsonEncoder::decode::<Task>(&value)?;
+        for payload in task.diagnostics.payloads() {
+            gc_urls += add_gc_url(&db, txn, &payload.path)? as u64;
+        }
+    }
+    for cf in [
+        IndexifyObjectsColumns::ChildInvocations,
+        IndexifyObjectsColumns::DeadLetters,
+        IndexifyObjectsColumns::WebhookDeliveries,
+        IndexifyObjectsColumns::IdempotencyKeys,
+        IndexifyObjectsColumns::InvocationBatches,
+    ] {
+        delete_cf_prefix(txn, &cf.cf_db(&db), prefix.as_bytes())?;
+    }
+
+    Ok(gc_urls)
+}
+
+/// A deleted child invocation that didn't finish never will, so the subgraph
+/// node of its parent fails. The parent finishes once its other tasks and
+/// children are done, like when a child fails, and a finished parent which is
+/// itself a child fails its own parent in turn.
+fn fail_parent_invocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    child: &InvocationPayload,
+) -> Result<()> {
+    let Some(parent) = &child.parent else {
+        return Ok(());
+    };
+    let ctx_cf = IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db);
+    let key = GraphInvocationCtx::key_from(
+        &child.namespace,
+        &parent.compute_graph_name,
+        &parent.invocation_id,
+    );
+    // The parent was deleted with its graph
+    let Some(parent_ctx) = txn.get_for_update_cf(&ctx_cf, &key, true)? else {
+        return Ok(());
+    };
+    let mut parent_ctx: GraphInvocationCtx = JsonEncoder::decode(&parent_ctx)?;
+    if parent_ctx.completed {
+        return Ok(());
+    }
+    parent_ctx
+        .fn_task_analytics
+        .entry(parent.node_name.clone())
+        .or_default()
+        .fail();
+    txn.put_cf(&ctx_cf, &key, JsonEncoder::encode(&parent_ctx)?)?;
+    if parent_ctx.pending_tasks() > 0 {
+        return Ok(());
+    }
+    mark_invocation_finished(
+        db.clone(),
+        txn,
+        &child.namespace,
+        &parent.compute_graph_name,
+        &parent.invocation_id,
+    )?;
+    let parent_invocation = txn.get_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        &key,
+    )?;
+    if let Some(parent_invocation) = parent_invocation {
+        let parent_invocation = JsonEncoder::decode::<InvocationPayload>(&parent_invocation)?;
+        fail_parent_invocation(db, txn, &parent_invocation)?;
+    }
     Ok(())
 }
 
-pub fn remove_gc_urls(
+// Queues a blob for deletion by the garbage collector. Returns whether it
+// wasn't queued already, so the GC backlog can be counted without scanning.
+fn add_gc_url(db: &TransactionDB, txn: &Transaction<TransactionDB>, url: &str) -> Result<bool> {
+    let cf = IndexifyObjectsColumns::GcUrls.cf_db(db);
+    if txn.get_for_update_cf(&cf, url.as_bytes(), true)?.is_some() {
+        return Ok(false);
+    }
+    txn.put_cf(&cf, url.as_bytes(), [])?;
+    Ok(true)
+}
+
+/// Returns the number of urls which weren't queued already
+pub fn add_gc_urls(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     urls: Vec<String>,
-) -> Result<()> {
+) -> Result<u64> {
+    let mut added = 0;
     for url in urls {
-        txn.delete_cf(&IndexifyObjectsColumns::GcUrls.cf_db(&db), &url)?;
+        added += add_gc_url(&db, txn, &url)? as u64;
+    }
+    Ok(added)
+}
+
+/// Removes idempotency keys which are still expired, a key can be reused
+/// after it was listed for removal
+pub fn remove_expired_idempotency_keys(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    keys: Vec<String>,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::IdempotencyKeys.cf_db(&db);
+    for key in keys {
+        let Some(existing) = txn.get_for_update_cf(&cf, &key, true)? else {
+            continue;
+        };
+        let existing: IdempotencyKey = JsonEncoder::decode(&existing)?;
+        if existing.is_expired() {
+            txn.delete_cf(&cf, &key)?;
+        }
     }
     Ok(())
 }
 
+/// Returns the number of urls which were still queued
+pub fn remove_gc_urls(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    urls: Vec<String>,
+) -> Result<u64> {
+    let cf = IndexifyObjectsColumns::GcUrls.cf_db(&db);
+    let mut removed = 0;
+    for url in urls {
+        if txn.get_for_update_cf(&cf, &url, true)?.is_some() {
+            txn.delete_cf(&cf, &url)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
 pub fn make_prefix_iterator<'a>(
     txn: &'a Transaction<TransactionDB>,
     cf_handle: &impl AsColumnFamilyRef,
@@ -290,8 +522,8 @@ pub(crate) fn create_tasks(
         )?;
         txn.put_cf(
             &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+            task.make_unallocated_key(),
             task.key(),
-            &[],
         )?;
 
         let key = format!(
@@ -316,6 +548,12 @@ pub(crate) fn create_tasks(
             serialized_analytics,
         )?;
     }
+    if !req.child_invocations.is_empty() ||
+        req.subgraph_outcome.is_some() ||
+        !req.failed_subgraph_nodes.is_empty()
+    {
+        update_subgraph_analytics(db.clone(), txn, req)?;
+    }
     if req.invocation_finished {
         mark_invocation_finished(
             db,
@@ -328,17 +566,80 @@ pub(crate) fn create_tasks(
     Ok(())
 }
 
+// Subgraph nodes are tracked in the parent's analytics like functions, with
+// one pending entry per child invocation
+fn update_subgraph_analytics(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateTasksRequest,
+) -> Result<()> {
+    let key = GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let graph_ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(anyhow!(
+            "Graph context not found for invocation: {}",
+            &req.invocation_id
+        ))?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    for child in &req.child_invocations {
+        if let Some(parent) = &child.parent {
+            graph_ctx
+                .fn_task_analytics
+                .entry(parent.node_name.clone())
+                .or_default()
+                .pending();
+        }
+    }
+    if let Some(subgraph_outcome) = &req.subgraph_outcome {
+        let analytics = graph_ctx
+            .fn_task_analytics
+            .entry(subgraph_outcome.node_name.clone())
+            .or_default();
+        match subgraph_outcome.outcome {
+            data_model::TaskOutcome::Success => analytics.success(),
+            data_model::TaskOutcome::Failure => analytics.fail(),
+            _ => {}
+        }
+    }
+    // Counted as a child invocation that failed as soon as it started
+    for node_name in &req.failed_subgraph_nodes {
+        let analytics = graph_ctx
+            .fn_task_analytics
+            .entry(node_name.clone())
+            .or_default();
+        analytics.pending();
+        analytics.fail();
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        key,
+        JsonEncoder::encode(&graph_ctx)?,
+    )?;
+    Ok(())
+}
+
 pub fn allocate_tasks(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     task: &Task,
     executor_id: &ExecutorId,
+    allocation_changes: &mut AllocationChanges,
 ) -> Result<()> {
     txn.put_cf(
         &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
         task.make_allocation_key(executor_id),
         &[],
     )?;
+    allocation_changes.allocated(executor_id, allocations::fn_key(task));
+    txn.delete_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        task.make_unallocated_key(),
+    )?;
+    // Tasks queued before priorities were introduced are keyed by the task key
     txn.delete_cf(
         &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
         task.key(),
@@ -350,6 +651,7 @@ pub fn mark_task_completed(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     req: &FinalizeTaskRequest,
+    allocation_changes: &mut AllocationChanges,
 ) -> Result<()> {
     let task_key = format!(
         "{}|{}|{}|{}|{}",
@@ -409,18 +711,43 @@ pub fn mark_task_completed(
         serialized_analytics,
     )?;
 
-    txn.delete_cf(
-        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
-        &task.make_allocation_key(&req.executor_id),
-    )?;
+    for allocation_key in [
+        task.make_allocation_key(&req.executor_id),
+        task.make_legacy_allocation_key(&req.executor_id),
+    ] {
+        if txn
+            .get_for_update_cf(
+                &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                &allocation_key,
+                true,
+            )?
+            .is_some()
+        {
+            txn.delete_cf(
+                &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                &allocation_key,
+            )?;
+            allocation_changes.unallocated(&req.executor_id, allocations::fn_key(&task));
+        }
+    }
 
     task.outcome = req.task_outcome.clone();
+    task.diagnostics = req.diagnostics.clone();
     let task_bytes = JsonEncoder::encode(&task)?;
     txn.put_cf(
         &IndexifyObjectsColumns::Tasks.cf_db(&db),
         task.key(),
         task_bytes,
     )?;
+    if task.outcome == data_model::TaskOutcome::Failure {
+        let dead_letter =
+            DeadLetter::new(&task, req.executor_id.clone(), get_epoch_time_in_ms());
+        txn.put_cf(
+            &IndexifyObjectsColumns::DeadLetters.cf_db(&db),
+            dead_letter.key(),
+            JsonEncoder::encode(&dead_letter)?,
+        )?;
+    }
     Ok(())
 }
 
@@ -477,6 +804,300 @@ pub(crate) fn mark_state_changes_processed(
     Ok(())
 }
 
+/// Puts dead lettered tasks back in the unallocated queue and reopens their
+/// invocations. The diagnostics of the failed attempt are dropped and their
+/// captured output garbage collected. Returns the requeued tasks and the
+/// number of urls queued for garbage collection.
+pub fn requeue_dead_letters(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    keys: &[String],
+) -> Result<(Vec<Task>, u64)> {
+    let mut tasks = Vec::new();
+    let mut gc_urls = 0;
+    for key in keys {
+        let dead_letter = txn
+            .get_for_update_cf(&IndexifyObjectsColumns::DeadLetters.cf_db(&db), key, true)?
+            .ok_or(anyhow!("dead letter not found: {}", key))?;
+        let dead_letter: DeadLetter = JsonEncoder::decode(&dead_letter)?;
+        let task = txn
+            .get_for_update_cf(
+                &IndexifyObjectsColumns::Tasks.cf_db(&db),
+                dead_letter.task_key(),
+                true,
+            )?
+            .ok_or(anyhow!("task of dead letter not found: {}", key))?;
+        let mut task: Task = JsonEncoder::decode(&task)?;
+        task.outcome = data_model::TaskOutcome::Unknown;
+        let diagnostics = std::mem::take(&mut task.diagnostics);
+        for payload in diagnostics.payloads() {
+            gc_urls += add_gc_url(&db, txn, &payload.path)? as u64;
+        }
+        txn.put_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(&db),
+            task.key(),
+            JsonEncoder::encode(&task)?,
+        )?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+            task.make_unallocated_key(),
+            task.key(),
+        )?;
+        txn.delete_cf(&IndexifyObjectsColumns::DeadLetters.cf_db(&db), key)?;
+
+        let ctx_key = GraphInvocationCtx::key_from(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.invocation_id,
+        );
+        let graph_ctx = txn
+            .get_for_update_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+                &ctx_key,
+                true,
+            )?
+            .ok_or(anyhow!(
+                "Graph context not found for invocation: {}",
+                &task.invocation_id
+            ))?;
+        let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+        let analytics = graph_ctx
+            .fn_task_analytics
+            .entry(task.compute_fn_name.clone())
+            .or_default();
+        analytics.failed_tasks = analytics.failed_tasks.saturating_sub(1);
+        analytics.pending();
+        graph_ctx.completed = false;
+        txn.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            ctx_key,
+            JsonEncoder::encode(&graph_ctx)?,
+        )?;
+        tasks.push(task);
+    }
+    Ok((tasks, gc_urls))
+}
+
+/// Deletes the tasks, outputs and child invocations of a function and
+/// everything downstream of it, then re-creates the function's tasks from their stored inputs. Returns
+/// the new tasks and the number of blobs newly queued for garbage collection.
+pub(crate) fn rerun_invocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RerunInvocationRequest,
+) -> Result<(Vec<Task>, u64)> {
+    let ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let graph_ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &ctx_key,
+            true,
+        )?
+        .ok_or(anyhow!(
+            "Graph context not found for invocation: {}",
+            &req.invocation_id
+        ))?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    // A failure finishes the invocation while tasks of other branches and
+    // child invocations of subgraph nodes can still be running
+    if !graph_ctx.completed || graph_ctx.pending_tasks() > 0 {
+        return Err(StateMachineError::InvocationInProgress(req.invocation_id.clone()).into());
+    }
+    let compute_graph = txn
+        .get_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            format!("{}|{}", req.namespace, req.compute_graph),
+        )?
+        .ok_or(anyhow!("compute graph not found: {}", req.compute_graph))?;
+    let compute_graph: ComputeGraph = JsonEncoder::decode(&compute_graph)?;
+    let compute_fns = compute_graph.downstream_fns(&req.compute_fn)?;
+
+    let prefix = format!("{}|", ctx_key);
+    let mut rerun_tasks = Vec::new();
+    let mut gc_urls = 0;
+    for item in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = item?;
+        let task = JsonEncoder::decode::<Task>(&value)?;
+        if !task.terminal_state() {
+            return Err(StateMachineError::InvocationInProgress(req.invocation_id.clone()).into());
+        }
+        if !compute_fns.contains(&task.compute_fn_name) {
+            continue;
+        }
+        delete_cf_prefix(
+            txn,
+            &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
+            format!("{}|{}|", task.namespace, task.id).as_bytes(),
+        )?;
+        txn.delete_cf(
+            &IndexifyObjectsColumns::DeadLetters.cf_db(&db),
+            DeadLetter::key_from(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.invocation_id,
+                &task.id.to_string(),
+            ),
+        )?;
+        txn.delete_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &key)?;
+        for payload in task.diagnostics.payloads() {
+            gc_urls += add_gc_url(&db, txn, &payload.path)? as u64;
+        }
+        if task.compute_fn_name == req.compute_fn {
+            rerun_tasks.push(task);
+        }
+    }
+    if rerun_tasks.is_empty() {
+        return Err(anyhow!(
+            "compute fn {} has no tasks in invocation {}",
+            req.compute_fn,
+            req.invocation_id
+        ));
+    }
+
+    for compute_fn in &compute_fns {
+        let prefix = format!("{}|{}|", ctx_key, compute_fn);
+        for item in make_prefix_iterator(
+            txn,
+            &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+            prefix.as_bytes(),
+            &None,
+        ) {
+            let (key, value) = item?;
+            let output = JsonEncoder::decode::<NodeOutput>(&value)?;
+            if let OutputPayload::Fn(payload) = &output.payload {
+                gc_urls += add_gc_url(&db, txn, &payload.path)? as u64;
+            }
+            txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
+        }
+        gc_urls += delete_child_invocations(&db, txn, &prefix, &req.invocation_id)?;
+        graph_ctx.fn_task_analytics.remove(compute_fn);
+    }
+
+    let node = compute_graph
+        .nodes
+        .get(&req.compute_fn)
+        .ok_or(anyhow!("compute fn not found: {}", req.compute_fn))?;
+    let mut tasks = Vec::new();
+    for old_task in rerun_tasks {
+        let mut task = node.create_task(
+            &req.namespace,
+            &req.compute_graph,
+            &req.invocation_id,
+            &old_task.input_key,
+        )?;
+        task.priority = old_task.priority;
+        txn.put_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(&db),
+            task.key(),
+            JsonEncoder::encode(&task)?,
+        )?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+            task.make_unallocated_key(),
+            task.key(),
+        )?;
+        graph_ctx
+            .fn_task_analytics
+            .entry(task.compute_fn_name.clone())
+            .or_default()
+            .pending();
+        tasks.push(task);
+    }
+    graph_ctx.completed = false;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        ctx_key,
+        JsonEncoder::encode(&graph_ctx)?,
+    )?;
+    Ok((tasks, gc_urls))
+}
+
+/// Deletes the child invocations indexed under the prefix, with the child
+/// invocations they started in turn. Their inputs belong to the parent and are
+/// kept. Fails if any of their tasks is still running. Returns the number of
+/// blobs newly queued for garbage collection.
+fn delete_child_invocations(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    prefix: &str,
+    rerun_invocation_id: &str,
+) -> Result<u64> {
+    let mut gc_urls = 0;
+    for item in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::ChildInvocations.cf_db(db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (index_key, child_key) = item?;
+        let child_prefix = format!("{}|", String::from_utf8(child_key.to_vec())?);
+        for item in make_prefix_iterator(
+            txn,
+            &IndexifyObjectsColumns::Tasks.cf_db(db),
+            child_prefix.as_bytes(),
+            &None,
+        ) {
+            let (key, value) = item?;
+            let task = JsonEncoder::decode::<Task>(&value)?;
+            if !task.terminal_state() {
+                return Err(StateMachineError::InvocationInProgress(
+                    rerun_invocation_id.to_string(),
+                )
+                .into());
+            }
+            delete_cf_prefix(
+                txn,
+                &IndexifyObjectsColumns::TaskOutputs.cf_db(db),
+                format!("{}|{}|", task.namespace, task.id).as_bytes(),
+            )?;
+            for payload in task.diagnostics.payloads() {
+                gc_urls += add_gc_url(db, txn, &payload.path)? as u64;
+            }
+            txn.delete_cf(&IndexifyObjectsColumns::Tasks.cf_db(db), &key)?;
+        }
+        for item in make_prefix_iterator(
+            txn,
+            &IndexifyObjectsColumns::FnOutputs.cf_db(db),
+            child_prefix.as_bytes(),
+            &None,
+        ) {
+            let (key, value) = item?;
+            let output = JsonEncoder::decode::<NodeOutput>(&value)?;
+            if let OutputPayload::Fn(payload) = &output.payload {
+                gc_urls += add_gc_url(db, txn, &payload.path)? as u64;
+            }
+            txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(db), &key)?;
+        }
+        gc_urls += delete_child_invocations(db, txn, &child_prefix, rerun_invocation_id)?;
+        for cf in [
+            IndexifyObjectsColumns::DeadLetters,
+            IndexifyObjectsColumns::WebhookDeliveries,
+        ] {
+            delete_cf_prefix(txn, &cf.cf_db(db), child_prefix.as_bytes())?;
+        }
+        let child_key = &child_prefix[..child_prefix.len() - 1];
+        txn.delete_cf(
+            &IndexifyObjectsColumns::GraphInvocations.cf_db(db),
+            child_key,
+        )?;
+        txn.delete_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(db),
+            child_key,
+        )?;
+        txn.delete_cf(
+            &IndexifyObjectsColumns::ChildInvocations.cf_db(db),
+            &index_key,
+        )?;
+    }
+    Ok(gc_urls)
+}
+
 fn mark_invocation_finished(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
@@ -492,6 +1113,9 @@ fn mark_invocation_finished(
             &invocation_id
         ))?;
     let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    if graph_ctx.completed {
+        return Ok(());
+    }
     graph_ctx.completed = true;
     let serialized_graph_ctx = JsonEncoder::encode(&graph_ctx)?;
     txn.put_cf(
@@ -499,31 +1123,153 @@ fn mark_invocation_finished(
         key,
         serialized_graph_ctx,
     )?;
+    enqueue_webhook_deliveries(db, txn, &graph_ctx)?;
     Ok(())
 }
 
+fn enqueue_webhook_deliveries(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    graph_ctx: &GraphInvocationCtx,
+) -> Result<()> {
+    let compute_graph_key = format!("{}|{}", graph_ctx.namespace, graph_ctx.compute_graph_name);
+    let compute_graph = match txn.get_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        &compute_graph_key,
+    )? {
+        Some(compute_graph) => JsonEncoder::decode::<ComputeGraph>(&compute_graph)?,
+        None => return Ok(()),
+    };
+    if compute_graph.webhooks.is_empty() {
+        return Ok(());
+    }
+
+    // Only the outputs of the terminal functions of the graph are reported
+    let mut outputs = Vec::new();
+    let prefix = format!("{}|", graph_ctx.key());
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = iter?;
+        let output = JsonEncoder::decode::<NodeOutput>(&value)?;
+        if let OutputPayload::Fn(_) = output.payload {
+            if !compute_graph.edges.contains_key(&output.compute_fn_name) {
+                outputs.push(WebhookOutputRef {
+                    compute_fn: output.compute_fn_name,
+                    id: output.id,
+                });
+            }
+        }
+    }
+    let payload = InvocationFinishedPayload {
+        namespace: graph_ctx.namespace.clone(),
+        compute_graph: graph_ctx.compute_graph_name.clone(),
+        invocation_id: graph_ctx.invocation_id.clone(),
+        outcome: graph_ctx.outcome(),
+        outputs,
+        finished_at: get_epoch_time_in_ms(),
+    };
+    for target in compute_graph.webhooks {
+        let delivery = WebhookDelivery::new(target, payload.clone());
+        txn.put_cf(
+            &IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db),
+            delivery.key(),
+            JsonEncoder::encode(&delivery)?,
+        )?;
+    }
+    Ok(())
+}
+
+pub fn update_webhook_delivery(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    delivery: &WebhookDelivery,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db),
+        delivery.key(),
+        JsonEncoder::encode(delivery)?,
+    )?;
+    Ok(())
+}
+
+pub fn remove_webhook_deliveries(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    keys: Vec<String>,
+) -> Result<()> {
+    for key in keys {
+        txn.delete_cf(&IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db), &key)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn set_secret(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    mut secret: Secret,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::Secrets.cf_db(&db);
+    if let Some(existing) = txn.get_for_update_cf(&cf, secret.key(), true)? {
+        let existing: Secret = JsonEncoder::decode(&existing)?;
+        secret.created_at = existing.created_at;
+    }
+    txn.put_cf(&cf, secret.key(), JsonEncoder::encode(&secret)?)?;
+    Ok(())
+}
+
+pub(crate) fn delete_secret(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteSecretRequest,
+) -> Result<()> {
+    txn.delete_cf(
+        &IndexifyObjectsColumns::Secrets.cf_db(&db),
+        Secret::key_from(&req.namespace, &req.name),
+    )?;
+    Ok(())
+}
+
+/// Returns whether the registered executor is draining
 pub(crate) fn register_executor(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     req: &RegisterExecutorRequest,
-) -> Result<()> {
-    let serialized_executor_metadata = JsonEncoder::encode(&req.executor)?;
+) -> Result<bool> {
+    let mut executor = req.executor.clone();
+    // An executor reconnecting while drained stays drained
+    if let Some(existing) = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        executor.key(),
+        true,
+    )? {
+        let existing: ExecutorMetadata = JsonEncoder::decode(&existing)?;
+        executor.draining |= existing.draining;
+    }
+    let serialized_executor_metadata = JsonEncoder::encode(&executor)?;
     txn.put_cf(
         &IndexifyObjectsColumns::Executors.cf_db(&db),
         req.executor.key(),
         serialized_executor_metadata,
     )?;
-    Ok(())
+    Ok(executor.draining)
 }
 
-pub(crate) fn deregister_executor(
+// Moves the tasks allocated to the executor back to the unallocated queue,
+// except for the `kept` tasks
+fn unallocate_executor_tasks(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    req: &DeregisterExecutorRequest,
+    executor_id: &ExecutorId,
+    kept: &HashSet<TaskId>,
+    allocation_changes: &mut AllocationChanges,
 ) -> Result<()> {
     let mut read_options = ReadOptions::default();
     read_options.set_readahead_size(4_194_304);
-    let prefix = format!("{}|", req.executor_id);
+    let prefix = format!("{}|", executor_id);
     let iterator_mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
     let iter = txn.iterator_cf_opt(
         &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
@@ -532,17 +1278,121 @@ pub(crate) fn deregister_executor(
     );
     for key in iter {
         let (key, _) = key?;
-        txn.delete_cf(&IndexifyObjectsColumns::TaskAllocations.cf_db(&db), &key)?;
+        if !key.starts_with(prefix.as_bytes()) {
+            break;
+        }
         let task_key = Task::key_from_allocation_key(&key)?;
+        let Some(task) = txn.get_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key)? else {
+            error!(
+                "allocated task not found: {}",
+                String::from_utf8_lossy(&task_key)
+            );
+            txn.delete_cf(&IndexifyObjectsColumns::TaskAllocations.cf_db(&db), &key)?;
+            allocation_changes
+                .unallocated(executor_id, allocations::fn_key_from_task_key(&task_key)?);
+            continue;
+        };
+        let task = JsonEncoder::decode::<Task>(&task)?;
+        if kept.contains(&task.id) {
+            continue;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::TaskAllocations.cf_db(&db), &key)?;
+        allocation_changes.unallocated(executor_id, allocations::fn_key(&task));
         txn.put_cf(
             &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
-            &task_key,
-            &[],
+            task.make_unallocated_key(),
+            task.key(),
         )?;
     }
+    Ok(())
+}
+
+pub(crate) fn deregister_executor(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeregisterExecutorRequest,
+    allocation_changes: &mut AllocationChanges,
+) -> Result<()> {
+    unallocate_executor_tasks(
+        db.clone(),
+        txn,
+        &req.executor_id,
+        &HashSet::new(),
+        allocation_changes,
+    )?;
     txn.delete_cf(
         &IndexifyObjectsColumns::Executors.cf_db(&db),
         req.executor_id.to_string(),
     )?;
     Ok(())
 }
+
+/// Returns tasks the executor has lost track of to the unallocated queue.
+/// Tasks that finished or were moved since the heartbeat are skipped.
+pub(crate) fn reconcile_executor_tasks(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &ReconcileExecutorTasksRequest,
+    allocation_changes: &mut AllocationChanges,
+) -> Result<Vec<Task>> {
+    let mut requeued = Vec::new();
+    for task in &req.tasks {
+        let mut allocation_key = None;
+        for key in [
+            task.make_allocation_key(&req.executor_id),
+            task.make_legacy_allocation_key(&req.executor_id),
+        ] {
+            if txn
+                .get_for_update_cf(
+                    &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                    &key,
+                    true,
+                )?
+                .is_some()
+            {
+                allocation_key = Some(key);
+                break;
+            }
+        }
+        let Some(allocation_key) = allocation_key else {
+            continue;
+        };
+        txn.delete_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+            &allocation_key,
+        )?;
+        allocation_changes.unallocated(&req.executor_id, allocations::fn_key(task));
+        txn.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+            task.make_unallocated_key(),
+            task.key(),
+        )?;
+        requeued.push(task.clone());
+    }
+    Ok(requeued)
+}
+
+pub(crate) fn drain_executor(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DrainExecutorRequest,
+    delivered: &HashSet<TaskId>,
+    allocation_changes: &mut AllocationChanges,
+) -> Result<()> {
+    let key = req.executor_id.to_string();
+    let executor = txn
+        .get_for_update_cf(&IndexifyObjectsColumns::Executors.cf_db(&db), &key, true)?
+        .ok_or(anyhow!("executor not found: {}", req.executor_id))?;
+    let mut executor: ExecutorMetadata = JsonEncoder::decode(&executor)?;
+    executor.draining = req.draining;
+    txn.put_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        &key,
+        JsonEncoder::encode(&executor)?,
+    )?;
+    // Tasks the executor already received keep running there
+    if req.evict {
+        unallocate_executor_tasks(db, txn, &req.executor_id, delivered, allocation_changes)?;
+    }
+    Ok(())
+}
@@ -13,6 +13,7 @@ pub mod tests {
             TEST_NAMESPACE,
         },
         ExecutorId,
+        TaskDiagnostics,
         TaskId,
         TaskOutcome,
     };
@@ -57,6 +58,7 @@ pub mod tests {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph_name: "graph_A".to_string(),
                 invocation_payload: invocation_payload.clone(),
+                idempotency_key: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -86,6 +88,7 @@ pub mod tests {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph_name: "graph_B".to_string(),
                 invocation_payload: invocation_payload.clone(),
+                idempotency_key: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -107,6 +110,7 @@ pub mod tests {
                 node_outputs: vec![mock_node_fn_output_fn_a(&invocation_id, "graph_A")],
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
+                diagnostics: TaskDiagnostics::default(),
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -130,6 +134,7 @@ pub mod tests {
                 node_outputs: vec![mock_node_fn_output_fn_a(&invocation_id, "graph_B")],
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
+                diagnostics: TaskDiagnostics::default(),
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -149,6 +154,7 @@ pub mod tests {
                 node_outputs: vec![mock_node_router_output_x(&invocation_id, "graph_B")],
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
+                diagnostics: TaskDiagnostics::default(),
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
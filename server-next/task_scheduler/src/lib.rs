@@ -1,11 +1,39 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::{anyhow, Result};
-use data_model::{ExecutorId, Node, Task};
+use data_model::{ExecutorId, Node, Secret, Task};
 use rand::seq::SliceRandom;
 use state_store::{requests::TaskPlacement, IndexifyState};
 use tracing::info;
 
+// Unallocated tasks are promoted by one priority class every interval they
+// wait, so a steady stream of high priority work can't starve low priority
+// tasks
+const STARVATION_PROMOTION_INTERVAL: Duration = Duration::from_secs(60);
+
+fn effective_rank(task: &Task, now: SystemTime) -> u8 {
+    let waited = now.duration_since(task.creation_time).unwrap_or_default();
+    let promotions = waited.as_secs() / STARVATION_PROMOTION_INTERVAL.as_secs();
+    task.priority
+        .rank()
+        .saturating_sub(promotions.min(u8::MAX as u64) as u8)
+}
+
+/// Orders tasks by their effective priority and then by age.
+fn prioritize(tasks: &mut [Task], now: SystemTime) {
+    tasks.sort_by_key(|task| (effective_rank(task, now), task.creation_time));
+}
+
+fn has_capacity(node: &Node, running: usize) -> bool {
+    match node.max_concurrency() {
+        Some(max_concurrency) => running < max_concurrency as usize,
+        None => true,
+    }
+}
+
 pub struct TaskScheduler {
     indexify_state: Arc<IndexifyState>,
 }
@@ -16,13 +44,26 @@ impl TaskScheduler {
     }
 
     pub fn schedule_unplaced_tasks(&self) -> Result<Vec<TaskPlacement>> {
-        let tasks = self.indexify_state.reader().unallocated_tasks()?;
+        let mut tasks = self.indexify_state.reader().unallocated_tasks()?;
+        prioritize(&mut tasks, SystemTime::now());
         info!("allocating {:?} tasks", tasks);
-        self.schedule_tasks(tasks)
+        let num_tasks = tasks.len();
+        let placements = self.schedule_tasks(tasks)?;
+        self.indexify_state
+            .metrics
+            .set_unallocated_tasks(num_tasks - placements.len());
+        Ok(placements)
     }
 
     pub fn schedule_tasks(&self, tasks: Vec<Task>) -> Result<Vec<TaskPlacement>> {
         let mut task_allocations = Vec::new();
+        let mut allocated = self
+            .indexify_state
+            .allocation_counts
+            .read()
+            .unwrap()
+            .functions()
+            .clone();
         for task in tasks {
             let cg = self
                 .indexify_state
@@ -33,9 +74,21 @@ impl TaskScheduler {
                 .nodes
                 .get(&task.compute_fn_name)
                 .ok_or(anyhow!("Compute fn not found"))?;
-            let executor_ids = self.filter_executors(&compute_fn)?;
+            // Tasks over the function's cap stay unallocated until a running
+            // task finishes
+            let fn_key = (
+                task.namespace.clone(),
+                task.compute_graph_name.clone(),
+                task.compute_fn_name.clone(),
+            );
+            let running = allocated.get(&fn_key).copied().unwrap_or_default();
+            if !has_capacity(compute_fn, running) {
+                continue;
+            }
+            let executor_ids = self.filter_executors(&task.namespace, compute_fn)?;
             let executor_id = executor_ids.choose(&mut rand::thread_rng());
             if let Some(executor_id) = executor_id {
+                *allocated.entry(fn_key).or_default() += 1;
                 info!("Assigning task {:?} to executor {:?}", task.id, executor_id);
                 task_allocations.push(TaskPlacement {
                     task,
@@ -46,15 +99,74 @@ impl TaskScheduler {
         Ok(task_allocations)
     }
 
-    fn filter_executors(&self, node: &Node) -> Result<Vec<ExecutorId>> {
-        let executors = self.indexify_state.reader().get_all_executors()?;
+    // Executors must also be authorized for every secret of the function.
+    // Missing secrets fail the task when it's handed to the executor.
+    fn filter_executors(&self, namespace: &str, node: &Node) -> Result<Vec<ExecutorId>> {
+        let reader = self.indexify_state.reader();
+        let executors = reader.get_all_executors()?;
+        let mut secrets: Vec<Secret> = Vec::new();
+        for name in node.secrets() {
+            if let Some(secret) = reader.get_secret(namespace, name)? {
+                secrets.push(secret);
+            }
+        }
         let mut filtered_executors = Vec::new();
 
         for executor in &executors {
-            if node.matches_executor(executor) {
+            if !executor.draining &&
+                node.matches_executor(executor) &&
+                secrets.iter().all(|secret| secret.authorizes(executor))
+            {
                 filtered_executors.push(executor.id.clone());
             }
         }
         Ok(filtered_executors)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use data_model::{Priority, TaskBuilder};
+
+    use super::*;
+
+    fn task(name: &str, priority: Priority, age: Duration, now: SystemTime) -> Task {
+        let mut task = TaskBuilder::default()
+            .namespace("test".to_string())
+            .compute_graph_name("graph".to_string())
+            .compute_fn_name(name.to_string())
+            .input_key("input".to_string())
+            .invocation_id("invocation".to_string())
+            .priority(priority)
+            .build()
+            .unwrap();
+        task.creation_time = now - age;
+        task
+    }
+
+    #[test]
+    fn test_prioritize_by_priority_and_age() {
+        let now = SystemTime::now();
+        let mut tasks = vec![
+            task("low", Priority::Low, Duration::from_secs(5), now),
+            task("normal_new", Priority::Normal, Duration::from_secs(1), now),
+            task("high", Priority::High, Duration::from_secs(0), now),
+            task("normal_old", Priority::Normal, Duration::from_secs(2), now),
+        ];
+        prioritize(&mut tasks, now);
+        let order: Vec<_> = tasks.iter().map(|t| t.compute_fn_name.as_str()).collect();
+        assert_eq!(order, vec!["high", "normal_old", "normal_new", "low"]);
+    }
+
+    #[test]
+    fn test_starved_tasks_are_promoted() {
+        let now = SystemTime::now();
+        let mut tasks = vec![
+            task("high", Priority::High, Duration::from_secs(0), now),
+            task("low", Priority::Low, STARVATION_PROMOTION_INTERVAL * 2, now),
+        ];
+        prioritize(&mut tasks, now);
+        // Both are rank 0 now and the older task goes first
+        assert_eq!(tasks[0].compute_fn_name, "low");
+    }
+}
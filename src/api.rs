@@ -5,15 +5,21 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use filter::{Expression, LabelsFilter};
-use indexify_internal_api::{self as internal_api, ContentOffset};
+use filter::{Filter, LabelsFilter};
+use indexify_internal_api::{self as internal_api, ContentOffset, Vector};
 use indexify_proto::indexify_coordinator::{self};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, BytesOrString};
 use strum::{Display, EnumString};
 use utoipa::{openapi, IntoParams, ToSchema};
 
-use crate::{api_utils, metadata_storage, state::forwardable_raft::RaftState, vectordbs};
+use crate::{
+    api_utils,
+    metadata_storage,
+    state::forwardable_raft::RaftState,
+    vector_index::SearchQuery,
+    vectordbs,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ExtractionGraphLink {
@@ -83,7 +89,7 @@ impl TryFrom<indexify_coordinator::ExtractionPolicy> for ExtractionPolicy {
         let expressions: Result<Vec<_>> = value
             .filter
             .iter()
-            .map(|e| filter::Expression::from_str(e))
+            .map(|e| e.parse::<filter::Filter>())
             .collect();
         Ok(Self {
             id: value.id,
@@ -159,16 +165,24 @@ impl From<vectordbs::IndexDistance> for IndexDistance {
 
 fn filter_schema() -> openapi::Array {
     openapi::ArrayBuilder::new()
-        .description(Some("Filter for content labels, list of expressions."))
+        .description(Some(
+            "Filter for content labels, list of expressions which all have to match.",
+        ))
         .items(
             openapi::ObjectBuilder::new()
                 .schema_type(openapi::SchemaType::String)
                 .description(Some(
-                    "filter expression in format key/operator/value, e.g. key>=value",
+                    "filter expression in format key/operator/value, e.g. key>=value, combined \
+                     with AND, OR, NOT and parentheses. Also supports key IN (a, b), key NOT IN \
+                     (a, b), key EXISTS, key STARTS_WITH \"a\", key CONTAINS \"a\" and dotted \
+                     nested keys",
                 ))
                 .build(),
         )
-        .example(Some(serde_json::json!(vec!["key1=value1", "key2>=value2"])))
+        .example(Some(serde_json::json!(vec![
+            "key1=value1",
+            "key2>=value2 OR key3 IN (a, b)"
+        ])))
         .build()
 }
 
@@ -223,6 +237,26 @@ pub struct RunExtractorsResponse {}
 pub struct EmbeddingSchema {
     pub dim: usize,
     pub distance: IndexDistance,
+    /// Named vectors stored with each embedding, e.g. token embeddings for
+    /// late interaction
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vectors: HashMap<String, VectorSchema>,
+    /// Embeddings are sparse and `dim` is the size of the vocabulary
+    #[serde(default)]
+    pub sparse: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VectorSchema {
+    pub dim: usize,
+    pub distance: IndexDistance,
+    /// Each value is a list of vectors, searched with multi-vector queries
+    /// and scored with MaxSim. On Qdrant the search is approximate: the
+    /// `multivector_candidates_factor * k` documents closest to the mean of
+    /// the query are scored (8 times `k` by default), so documents with a
+    /// higher MaxSim score can be missed.
+    #[serde(default)]
+    pub multivector: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Display, ToSchema)]
@@ -318,12 +352,81 @@ pub struct ListIndexesResponse {
 
 #[derive(Debug, Serialize, Deserialize, IntoParams, ToSchema)]
 pub struct SearchRequest {
+    #[serde(default)]
     pub query: String,
+    /// Precomputed query embedding, searched without calling the extractor
+    pub embedding: Option<Vec<f32>>,
+    /// Precomputed multi-vector query, for multi-vector named vectors. See
+    /// `VectorSchema::multivector` for the recall on Qdrant.
+    pub multi_embedding: Option<Vec<Vec<f32>>>,
+    /// Precomputed sparse query embedding, for sparse indexes
+    pub sparse_embedding: Option<internal_api::SparseVector>,
+    /// Id of indexed content whose embedding is used as the query
+    pub content_id: Option<String>,
+    /// Named vector of the index to search, defaults to the index embedding
+    pub vector: Option<String>,
     pub k: Option<u64>,
     #[serde(default)]
     #[schema(schema_with = filter_schema)]
     pub filters: LabelsFilter,
     pub include_content: Option<bool>,
+    #[serde(default)]
+    pub mode: SearchMode,
+    pub rerank: Option<Rerank>,
+}
+
+/// Reorders the search results with a reranker extractor. The extractor
+/// receives `{"query": ..., "documents": [...]}` as JSON content and returns a
+/// feature whose data is `{"scores": [...]}`, one score per document.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Rerank {
+    pub extractor: String,
+    /// Number of candidates fetched from the index and reranked, defaults to
+    /// a multiple of k
+    pub candidates: Option<u64>,
+}
+
+impl SearchRequest {
+    /// Exactly one of the text query, the embeddings and the content id has
+    /// to be set.
+    pub fn search_query(&self) -> Result<SearchQuery> {
+        match (
+            self.query.is_empty(),
+            self.embedding.clone(),
+            self.multi_embedding.clone(),
+            self.sparse_embedding.clone(),
+            self.content_id.clone(),
+        ) {
+            (false, None, None, None, None) => Ok(SearchQuery::Text(self.query.clone())),
+            (true, Some(embedding), None, None, None) => {
+                Ok(SearchQuery::Embedding(Vector::Dense(embedding)))
+            }
+            (true, None, Some(embedding), None, None) => {
+                Ok(SearchQuery::Embedding(Vector::Multi(embedding)))
+            }
+            (true, None, None, Some(embedding), None) => {
+                Ok(SearchQuery::SparseEmbedding(embedding))
+            }
+            (true, None, None, None, Some(content_id)) => Ok(SearchQuery::ContentId(content_id)),
+            _ => Err(anyhow!(
+                "exactly one of query, embedding, multi_embedding, sparse_embedding or content_id must be set"
+            )),
+        }
+    }
+}
+
+/// How an index is searched. Keyword search ranks the text of text content,
+/// which the index store keeps with its embeddings, hybrid search fuses the
+/// vector and keyword rankings.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, ToSchema)]
+pub enum SearchMode {
+    #[serde(rename = "vector")]
+    #[default]
+    Vector,
+    #[serde(rename = "keyword")]
+    Keyword,
+    #[serde(rename = "hybrid")]
+    Hybrid,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -368,7 +471,7 @@ pub struct ListContent {
     )]
     pub ingested_content_id: String,
     #[serde(default)]
-    pub labels_filter: Vec<Expression>,
+    pub labels_filter: Vec<Filter>,
 
     pub limit: Option<u64>,
 
@@ -392,6 +495,8 @@ pub struct DocumentFragment {
     pub text: String,
     pub mime_type: String,
     pub confidence_score: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
     pub labels: HashMap<String, serde_json::Value>,
     pub root_content_metadata: Option<ContentMetadata>,
     pub content_metadata: ContentMetadata,
@@ -600,6 +705,8 @@ pub enum FeatureType {
     Embedding,
     #[serde(rename = "metadata")]
     Metadata,
+    #[serde(rename = "sparse_embedding")]
+    SparseEmbedding,
     #[serde(rename = "unknown")]
     Unknown,
 }
@@ -609,6 +716,7 @@ impl From<internal_api::FeatureType> for FeatureType {
         match feature_type {
             internal_api::FeatureType::Embedding => FeatureType::Embedding,
             internal_api::FeatureType::Metadata => FeatureType::Metadata,
+            internal_api::FeatureType::SparseEmbedding => FeatureType::SparseEmbedding,
             internal_api::FeatureType::Unknown => FeatureType::Unknown,
         }
     }
@@ -931,6 +1039,50 @@ pub struct AddGraphToContent {
     pub content_ids: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReembedRequest {
+    /// Extractor producing the new embeddings. Defaults to the extractor of
+    /// the extraction policy.
+    pub extractor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReembedResponse {
+    /// Indexes being rebuilt. They keep serving search from their current
+    /// version until re-embedding is complete.
+    pub indexes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Parquet,
+    Arrow,
+}
+
+impl From<ExportFormat> for vectordbs::export::ExportFormat {
+    fn from(value: ExportFormat) -> Self {
+        match value {
+            ExportFormat::Parquet => vectordbs::export::ExportFormat::Parquet,
+            ExportFormat::Arrow => vectordbs::export::ExportFormat::Arrow,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct ExportIndexParams {
+    /// File format of the export, parquet or arrow (Arrow IPC file)
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportIndexResponse {
+    /// Number of embeddings added to the index
+    pub num_vectors: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ExtractionGraphRequest {
     pub name: String,
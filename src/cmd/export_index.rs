@@ -0,0 +1,70 @@
+use std::{fs::File, io::BufWriter};
+
+use clap::Args as ClapArgs;
+
+use super::GlobalArgs;
+use crate::{
+    prelude::*,
+    server_config::ServerConfig,
+    vectordbs::{
+        self,
+        export::{export_index, ExportFormat},
+        IndexDistance,
+    },
+};
+
+#[derive(Debug, ClapArgs)]
+pub struct Args {
+    /// path to the server config file, the index is read from its vector
+    /// store
+    #[arg(long, short = 'c')]
+    config_path: Option<String>,
+
+    /// name of the index table in the vector store
+    #[arg(long)]
+    index: String,
+
+    #[arg(long, value_enum, default_value_t = ExportFormat::Parquet)]
+    format: ExportFormat,
+
+    /// path of the export file
+    #[arg(long, short = 'o')]
+    output: String,
+
+    /// distance of the index, recorded in the export so the index can be
+    /// re-created on import
+    #[arg(long)]
+    distance: Option<IndexDistance>,
+}
+
+impl Args {
+    pub async fn run(self, _: GlobalArgs) {
+        let Self {
+            config_path,
+            index,
+            format,
+            output,
+            distance,
+        } = self;
+
+        let config = if let Some(config_path) = config_path {
+            ServerConfig::from_path(&config_path)
+                .unwrap_or_else(|e| panic!("failed to load config file `{}`: {}", config_path, e))
+        } else {
+            info!("No config file provided. Using defaults");
+            ServerConfig::default()
+        };
+        let vector_db = vectordbs::create_vectordb(config.index_config)
+            .await
+            .expect("failed to connect to the vector store");
+        let file = File::create(&output)
+            .unwrap_or_else(|e| panic!("failed to create `{}`: {}", output, e));
+        let exported = export_index(&vector_db, &index, distance, format, BufWriter::new(file))
+            .await
+            .unwrap_or_else(|e| panic!("failed to export index `{}`: {}", index, e));
+        info!(
+            "exported {} embeddings from `{}` to `{}`",
+            exported, index, output
+        );
+    }
+}
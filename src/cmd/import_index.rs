@@ -0,0 +1,184 @@
+use std::fs::File;
+
+use clap::Args as ClapArgs;
+
+use super::GlobalArgs;
+use crate::{
+    prelude::*,
+    server_config::ServerConfig,
+    vectordbs::{self, export::IndexImport, IndexDistance},
+};
+
+#[derive(Debug, ClapArgs)]
+pub struct Args {
+    /// path to the server config file, the export is loaded into its vector
+    /// store
+    #[arg(long, short = 'c')]
+    config_path: Option<String>,
+
+    /// name of the index table in the vector store
+    #[arg(long)]
+    index: String,
+
+    /// path of a parquet or arrow ipc export file
+    #[arg(long, short = 'i')]
+    input: String,
+
+    /// create the index before loading the export, using the dimension and
+    /// distance recorded in the export
+    #[arg(long)]
+    create: bool,
+
+    /// distance of the created index, overrides the distance recorded in the
+    /// export
+    #[arg(long)]
+    distance: Option<IndexDistance>,
+}
+
+impl Args {
+    pub async fn run(self, _: GlobalArgs) {
+        let Self {
+            config_path,
+            index,
+            input,
+            create,
+            distance,
+        } = self;
+
+        let config = if let Some(config_path) = config_path {
+            ServerConfig::from_path(&config_path)
+                .unwrap_or_else(|e| panic!("failed to load config file `{}`: {}", config_path, e))
+        } else {
+            info!("No config file provided. Using defaults");
+            ServerConfig::default()
+        };
+        let vector_db = vectordbs::create_vectordb(config.index_config)
+            .await
+            .expect("failed to connect to the vector store");
+        let file =
+            File::open(&input).unwrap_or_else(|e| panic!("failed to open `{}`: {}", input, e));
+        let import = IndexImport::new(file)
+            .unwrap_or_else(|e| panic!("failed to read export `{}`: {}", input, e));
+        if create {
+            let params = import
+                .create_index_params(&index, distance)
+                .unwrap_or_else(|e| panic!("unable to create index `{}`: {}", index, e));
+            vector_db
+                .create_index(params)
+                .await
+                .unwrap_or_else(|e| panic!("failed to create index `{}`: {}", index, e));
+        }
+        let imported = import
+            .load(&vector_db, &index)
+            .await
+            .unwrap_or_else(|e| panic!("failed to import into index `{}`: {}", index, e));
+        info!(
+            "imported {} embeddings from `{}` into `{}`",
+            imported, input, index
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::BufWriter, sync::Arc};
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::{
+        cmd::Cli,
+        server_config::{HnswConfig, IndexStoreKind},
+        test_util::db_utils::{create_metadata, test_mock_content_metadata},
+        vectordbs::{
+            export::{export_index, ExportFormat},
+            hnsw::Hnsw,
+            CreateIndexParams,
+            VectorChunk,
+            VectorDBTS,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_import_index_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let source: VectorDBTS = Arc::new(
+            Hnsw::new(&HnswConfig {
+                path: dir.path().join("source").to_str().unwrap().to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        source
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "source-index".to_string(),
+                vector_dim: 2,
+                distance: IndexDistance::Dot,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        let chunks = (0..3)
+            .map(|i| {
+                let content_id = format!("content-{}", i);
+                VectorChunk {
+                    content_id: content_id.clone(),
+                    embedding: vec![i as f32, 1.],
+                    vectors: HashMap::new(),
+                    sparse_embedding: None,
+                    metadata: create_metadata(vec![("key", &i.to_string())]),
+                    root_content_metadata: None,
+                    content_metadata: test_mock_content_metadata(&content_id, "", "graph1"),
+                    text: None,
+                }
+            })
+            .collect();
+        source
+            .add_embedding("source-index", chunks)
+            .await
+            .unwrap();
+        let export_path = dir.path().join("export.parquet");
+        let file = File::create(&export_path).unwrap();
+        export_index(
+            &source,
+            "source-index",
+            Some(IndexDistance::Dot),
+            ExportFormat::Parquet,
+            BufWriter::new(file),
+        )
+        .await
+        .unwrap();
+
+        let mut config = ServerConfig::default();
+        config.index_config.index_store = IndexStoreKind::Hnsw;
+        config.index_config.hnsw_config = Some(HnswConfig {
+            path: dir.path().join("target").to_str().unwrap().to_string(),
+            ..Default::default()
+        });
+        let config_path = dir.path().join("config.yaml");
+        std::fs::write(&config_path, serde_yaml::to_string(&config).unwrap()).unwrap();
+
+        Cli::try_parse_from([
+            "indexify",
+            "import-index",
+            "--config-path",
+            config_path.to_str().unwrap(),
+            "--index",
+            "target-index",
+            "--input",
+            export_path.to_str().unwrap(),
+            "--create",
+        ])
+        .unwrap()
+        .run()
+        .await;
+
+        let target = vectordbs::create_vectordb(config.index_config)
+            .await
+            .unwrap();
+        assert_eq!(target.num_vectors("target-index").await.unwrap(), 3);
+    }
+}
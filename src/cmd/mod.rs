@@ -2,6 +2,8 @@ use clap::{Args, Parser, Subcommand};
 
 mod coordinator;
 mod discovery;
+mod export_index;
+mod import_index;
 mod init_compose;
 mod init_config;
 mod server;
@@ -30,6 +32,10 @@ pub enum Commands {
     InitConfig(init_config::Args),
     InitCompose(init_compose::Args),
     Discovery(discovery::Args),
+    /// Export a vector index to a Parquet or Arrow IPC file
+    ExportIndex(export_index::Args),
+    /// Bulk load an index export into a vector index
+    ImportIndex(import_index::Args),
 }
 
 /// The main CLI struct. This is the root of the CLI tree.
@@ -52,6 +58,8 @@ impl Cli {
             Commands::InitConfig(args) => args.run(self.global_args).await,
             Commands::InitCompose(args) => args.run(self.global_args).await,
             Commands::Discovery(args) => args.run(self.global_args).await,
+            Commands::ExportIndex(args) => args.run(self.global_args).await,
+            Commands::ImportIndex(args) => args.run(self.global_args).await,
         }
     }
 }
@@ -402,12 +402,16 @@ impl Coordinator {
         self.shared_state.list_indexes(namespace).await
     }
 
-    pub async fn get_index(&self, namespace: &str, name: &str) -> Result<internal_api::Index> {
+    pub async fn get_index(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<internal_api::Index>> {
         let mut s = DefaultHasher::new();
         namespace.hash(&mut s);
         name.hash(&mut s);
         let id = format!("{:x}", s.finish());
-        self.shared_state.get_index(&id).await
+        self.shared_state.find_index(&id).await
     }
 
     pub async fn update_indexes_state(&self, indexes: Vec<internal_api::Index>) -> Result<()> {
@@ -546,6 +550,8 @@ impl Coordinator {
                             extractor_name: extractor.name.clone(),
                             graph_name: extraction_graph.name.clone(),
                             visibility: false,
+                            version: 0,
+                            migration: None,
                         };
                         index_to_create.name = index_to_create.build_name(&output_name);
                         index_to_create.table_name = index_to_create.build_table_name(&output_name);
@@ -568,6 +574,229 @@ impl Coordinator {
         Ok(indexes_to_create)
     }
 
+    /// Records a new version of every index of the extraction policy, built
+    /// with `extractor` or the policy's current extractor. The returned
+    /// indexes carry the migration whose tables have to be created before
+    /// the migration is started.
+    pub async fn create_index_migration(
+        &self,
+        namespace: &str,
+        extraction_graph: &str,
+        extraction_policy: &str,
+        extractor: Option<&str>,
+    ) -> Result<Vec<internal_api::Index>> {
+        let extraction_policy = self
+            .get_extraction_policy(namespace, extraction_graph, extraction_policy)
+            .await?;
+        let extractor = self.get_extractor(extractor.unwrap_or(&extraction_policy.extractor))?;
+        let mut indexes = self
+            .shared_state
+            .indexes_for_extraction_policy(&extraction_policy)
+            .await?;
+        if indexes.is_empty() {
+            return Err(anyhow!(
+                "extraction policy {} has no indexes",
+                extraction_policy.name
+            ));
+        }
+        let mut failed_tables = HashSet::new();
+        for index in indexes.iter_mut() {
+            // A migration that was created but not started is replaced and
+            // a failed one is retried with the next version
+            let version = match &index.migration {
+                Some(migration) => {
+                    if !migration.started {
+                        migration.version
+                    } else if migration.failed() {
+                        failed_tables.insert(migration.table_name.clone());
+                        migration.version + 1
+                    } else {
+                        return Err(anyhow!(
+                            "index {} is already migrating to version {}",
+                            index.name,
+                            migration.version
+                        ));
+                    }
+                }
+                None => index.version + 1,
+            };
+            let output_name = index
+                .output_name()
+                .ok_or_else(|| anyhow!("unable to find the output of index {}", index.name))?
+                .to_string();
+            let schema = match extractor.outputs.get(&output_name) {
+                Some(OutputSchema::Embedding(schema)) => serde_json::to_value(schema)?.to_string(),
+                _ => {
+                    return Err(anyhow!(
+                        "extractor {} has no embedding output named {}",
+                        extractor.name,
+                        output_name
+                    ))
+                }
+            };
+            index.migration = Some(internal_api::IndexMigration {
+                version,
+                table_name: index.build_versioned_table_name(&output_name, version),
+                schema,
+                extractor_name: extractor.name.clone(),
+                ..Default::default()
+            });
+        }
+        self.shared_state.set_indexes(indexes.clone()).await?;
+        if !failed_tables.is_empty() {
+            let gc_task = self
+                .garbage_collector
+                .create_drop_tables_task(namespace, failed_tables)
+                .await;
+            self.shared_state.create_gc_tasks(vec![gc_task]).await?;
+        }
+        Ok(indexes)
+    }
+
+    /// Starts embedding new content into the migration tables and schedules
+    /// the re-embedding of the existing content. The extraction policy keeps
+    /// writing into the current tables, and the indexes keep serving search
+    /// from them, until the migration is promoted.
+    pub async fn start_index_migration(
+        &self,
+        namespace: &str,
+        extraction_graph: &str,
+        extraction_policy: &str,
+    ) -> Result<()> {
+        let extraction_policy = self
+            .get_extraction_policy(namespace, extraction_graph, extraction_policy)
+            .await?;
+        let mut indexes = self
+            .shared_state
+            .indexes_for_extraction_policy(&extraction_policy)
+            .await?;
+        indexes.retain(|index| index.migration.is_some());
+        if indexes.is_empty() {
+            return Err(anyhow!(
+                "extraction policy {} has no pending index migration",
+                extraction_policy.name
+            ));
+        }
+        for index in indexes.iter_mut() {
+            let Some(migration) = index.migration.as_mut() else {
+                continue;
+            };
+            if migration.started {
+                return Err(anyhow!(
+                    "index {} is already migrating to version {}",
+                    index.name,
+                    migration.version
+                ));
+            }
+            migration.started = true;
+        }
+        self.shared_state
+            .start_index_migration(&extraction_policy, indexes)
+            .await
+    }
+
+    /// Counts a finished re-embedding task against the migrations of the
+    /// indexes it wrote into.
+    async fn handle_index_migration_task_finished(&self, state_change: StateChange) -> Result<()> {
+        let task = self
+            .shared_state
+            .task_with_id(&state_change.object_id)
+            .await?;
+        let extraction_policy = self
+            .get_extraction_policy(
+                &task.namespace,
+                &task.extraction_graph_name,
+                &task.extraction_policy_name,
+            )
+            .await?;
+        let mut indexes = self
+            .shared_state
+            .indexes_for_extraction_policy(&extraction_policy)
+            .await?;
+        indexes.retain_mut(|index| match index.migration.as_mut() {
+            Some(migration)
+                if task
+                    .output_index_table_mapping
+                    .values()
+                    .any(|table| table == &migration.table_name) =>
+            {
+                migration.pending_tasks = migration.pending_tasks.saturating_sub(1);
+                if task.outcome == internal_api::TaskOutcome::Failed {
+                    migration.failed_tasks += 1;
+                }
+                true
+            }
+            _ => false,
+        });
+        self.update_index_migrations(indexes, state_change).await
+    }
+
+    /// Promotes the migrations whose re-embedding tasks all succeeded and
+    /// drops the tables of the index versions they replace. A migration with
+    /// failed tasks keeps the current version live until it is retried.
+    async fn update_index_migrations(
+        &self,
+        mut indexes: Vec<internal_api::Index>,
+        state_change: StateChange,
+    ) -> Result<()> {
+        let mut replaced_tables = HashSet::new();
+        let mut promoted = Vec::new();
+        for index in indexes.iter_mut() {
+            let Some(migration) = &index.migration else {
+                continue;
+            };
+            if migration.failed() {
+                warn!(
+                    "migration of index {} to version {} failed, {} re-embedding tasks failed",
+                    index.name, migration.version, migration.failed_tasks
+                );
+                continue;
+            }
+            if !migration.finished() {
+                continue;
+            }
+            let table_name = index.table_name.clone();
+            if index.promote_migration() {
+                info!(
+                    "promoting index {} to version {}, table: {}",
+                    index.name, index.version, index.table_name
+                );
+                replaced_tables.insert(table_name);
+                promoted.push(index.clone());
+            }
+        }
+        // The indexes belong to a single extraction policy, which switches
+        // over to the promoted tables and extractor
+        let (extraction_policy, gc_tasks) = match promoted.first() {
+            Some(index) => {
+                let mut extraction_policy = self
+                    .get_extraction_policy(
+                        &index.namespace,
+                        &index.graph_name,
+                        &index.extraction_policy_name,
+                    )
+                    .await?;
+                for index in &promoted {
+                    if let Some(output_name) = index.output_name() {
+                        extraction_policy
+                            .output_table_mapping
+                            .insert(output_name.to_string(), index.table_name.clone());
+                    }
+                    extraction_policy.extractor.clone_from(&index.extractor_name);
+                }
+                let gc_task = self
+                    .garbage_collector
+                    .create_drop_tables_task(&index.namespace, replaced_tables)
+                    .await;
+                (Some(extraction_policy), vec![gc_task])
+            }
+            None => (None, Vec::new()),
+        };
+        self.shared_state
+            .update_index_migrations(indexes, extraction_policy, gc_tasks, state_change)
+            .await
+    }
+
     pub async fn get_graph_analytics(
         &self,
         namespace: &str,
@@ -797,6 +1026,13 @@ impl Coordinator {
                     self.scheduler.handle_executor_removed(change).await?
                 }
                 ChangeType::ContentUpdated => self.handle_content_updated(change).await?,
+                ChangeType::IndexMigrationStarted => {
+                    let indexes = self.scheduler.create_index_migration_tasks(&change).await?;
+                    self.update_index_migrations(indexes, change).await?
+                }
+                ChangeType::IndexMigrationTaskFinished => {
+                    self.handle_index_migration_task_finished(change).await?
+                }
                 ChangeType::ExtractionGraphDeleted {
                     ref start_content_id,
                 } => {
@@ -868,6 +1104,7 @@ impl Coordinator {
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::HashSet,
         fs,
         sync::Arc,
         time::{Duration, Instant},
@@ -883,7 +1120,13 @@ mod tests {
         ExtractionGraphNode,
     };
     use indexify_proto::indexify_coordinator::CreateContentStatus;
-    use internal_api::{ContentMetadataId, ContentOffset, ContentSource, TaskOutcome};
+    use internal_api::{
+        ContentMetadataId,
+        ContentOffset,
+        ContentSource,
+        ServerTaskType,
+        TaskOutcome,
+    };
     use tokio::time::timeout;
 
     use super::Coordinator;
@@ -1084,6 +1327,252 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_index_migration() -> Result<(), anyhow::Error> {
+        let (coordinator, shared_state) = setup_coordinator().await;
+        coordinator.create_namespace(DEFAULT_TEST_NAMESPACE).await?;
+
+        let executor_id = "test_executor_id";
+        coordinator
+            .register_executor(mock_executor(
+                executor_id.to_string(),
+                vec![mock_extractor()],
+            ))
+            .await?;
+        coordinator.run_scheduler().await?;
+
+        let eg = create_test_extraction_graph("extraction_graph_1", vec!["extraction_policy_1"]);
+        let index = coordinator
+            .create_extraction_graph(eg.clone())
+            .await?
+            .first()
+            .unwrap()
+            .clone();
+        coordinator.run_scheduler().await?;
+
+        //  Embed a piece of content into the current index
+        let content_metadata = test_mock_content_metadata("test_id", "", &eg.name);
+        coordinator
+            .create_content_metadata(vec![content_metadata.clone()])
+            .await?;
+        coordinator.run_scheduler().await?;
+        let tasks = shared_state.tasks_for_executor(executor_id, None).await?;
+        assert_eq!(tasks.len(), 1);
+        coordinator
+            .update_task(&tasks[0].id, executor_id, TaskOutcome::Success)
+            .await?;
+        coordinator.run_scheduler().await?;
+
+        //  Create the next version of the index and start re-embedding into it
+        let migration = coordinator
+            .create_index_migration(
+                DEFAULT_TEST_NAMESPACE,
+                &eg.name,
+                "extraction_policy_1",
+                None,
+            )
+            .await?
+            .first()
+            .unwrap()
+            .migration
+            .clone()
+            .unwrap();
+        assert_eq!(migration.version, 1);
+        assert_eq!(migration.table_name, format!("{}.v1", index.table_name));
+        coordinator
+            .start_index_migration(DEFAULT_TEST_NAMESPACE, &eg.name, "extraction_policy_1")
+            .await?;
+        assert!(coordinator
+            .create_index_migration(
+                DEFAULT_TEST_NAMESPACE,
+                &eg.name,
+                "extraction_policy_1",
+                None
+            )
+            .await
+            .is_err());
+        coordinator.run_scheduler().await?;
+
+        let tasks = shared_state.tasks_for_executor(executor_id, None).await?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].content_metadata.id, content_metadata.id);
+        assert_eq!(
+            tasks[0].output_index_table_mapping.get("test_output"),
+            Some(&migration.table_name)
+        );
+
+        //  The index is served from the current table until re-embedding is done
+        let current = shared_state.get_index(&index.id).await?;
+        assert_eq!(current.table_name, index.table_name);
+        let current_migration = current.migration.unwrap();
+        assert!(current_migration.tasks_created);
+        assert_eq!(current_migration.pending_tasks, 1);
+
+        //  New content is written to the current and the migration table
+        let policy = coordinator
+            .get_extraction_policy(DEFAULT_TEST_NAMESPACE, &eg.name, "extraction_policy_1")
+            .await?;
+        assert_eq!(
+            policy.output_table_mapping.get("test_output"),
+            Some(&index.table_name)
+        );
+        let new_content = test_mock_content_metadata("test_id_2", "", &eg.name);
+        coordinator
+            .create_content_metadata(vec![new_content.clone()])
+            .await?;
+        coordinator.run_scheduler().await?;
+        let new_tasks: Vec<_> = shared_state
+            .tasks_for_executor(executor_id, None)
+            .await?
+            .into_iter()
+            .filter(|task| task.content_metadata.id == new_content.id)
+            .collect();
+        assert_eq!(new_tasks.len(), 2);
+        let tables: HashSet<_> = new_tasks
+            .iter()
+            .filter_map(|task| task.output_index_table_mapping.get("test_output").cloned())
+            .collect();
+        assert_eq!(
+            tables,
+            HashSet::from([index.table_name.clone(), migration.table_name.clone()])
+        );
+        let current_migration = shared_state.get_index(&index.id).await?.migration.unwrap();
+        assert_eq!(current_migration.pending_tasks, 2);
+
+        for task in tasks.iter().chain(new_tasks.iter()) {
+            coordinator
+                .update_task(&task.id, executor_id, TaskOutcome::Success)
+                .await?;
+        }
+        coordinator.run_scheduler().await?;
+        let promoted = shared_state.get_index(&index.id).await?;
+        assert_eq!(promoted.table_name, migration.table_name);
+        assert_eq!(promoted.version, 1);
+        assert!(promoted.migration.is_none());
+        let policy = coordinator
+            .get_extraction_policy(DEFAULT_TEST_NAMESPACE, &eg.name, "extraction_policy_1")
+            .await?;
+        assert_eq!(
+            policy.output_table_mapping.get("test_output"),
+            Some(&migration.table_name)
+        );
+
+        //  The table of the replaced version is dropped
+        let gc_tasks = shared_state.list_all_gc_tasks().await?;
+        assert!(gc_tasks.iter().any(|gc_task| {
+            gc_task.task_type == ServerTaskType::DropIndexes &&
+                gc_task.output_tables == HashSet::from([index.table_name.clone()])
+        }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_failed_index_migration() -> Result<(), anyhow::Error> {
+        let (coordinator, shared_state) = setup_coordinator().await;
+        coordinator.create_namespace(DEFAULT_TEST_NAMESPACE).await?;
+
+        let executor_id = "test_executor_id";
+        coordinator
+            .register_executor(mock_executor(
+                executor_id.to_string(),
+                vec![mock_extractor()],
+            ))
+            .await?;
+        coordinator.run_scheduler().await?;
+
+        let eg = create_test_extraction_graph("extraction_graph_1", vec!["extraction_policy_1"]);
+        let index = coordinator
+            .create_extraction_graph(eg.clone())
+            .await?
+            .first()
+            .unwrap()
+            .clone();
+        coordinator.run_scheduler().await?;
+
+        let content_metadata = test_mock_content_metadata("test_id", "", &eg.name);
+        coordinator
+            .create_content_metadata(vec![content_metadata.clone()])
+            .await?;
+        coordinator.run_scheduler().await?;
+        let tasks = shared_state.tasks_for_executor(executor_id, None).await?;
+        coordinator
+            .update_task(&tasks[0].id, executor_id, TaskOutcome::Success)
+            .await?;
+        coordinator.run_scheduler().await?;
+
+        coordinator
+            .create_index_migration(
+                DEFAULT_TEST_NAMESPACE,
+                &eg.name,
+                "extraction_policy_1",
+                None,
+            )
+            .await?;
+        coordinator
+            .start_index_migration(DEFAULT_TEST_NAMESPACE, &eg.name, "extraction_policy_1")
+            .await?;
+        coordinator.run_scheduler().await?;
+
+        //  A failed re-embedding task keeps the current version live
+        let tasks = shared_state.tasks_for_executor(executor_id, None).await?;
+        assert_eq!(tasks.len(), 1);
+        coordinator
+            .update_task(&tasks[0].id, executor_id, TaskOutcome::Failed)
+            .await?;
+        coordinator.run_scheduler().await?;
+        let current = shared_state.get_index(&index.id).await?;
+        assert_eq!(current.table_name, index.table_name);
+        assert_eq!(current.version, 0);
+        let failed = current.migration.unwrap();
+        assert!(failed.failed());
+        assert_eq!(failed.failed_tasks, 1);
+        let policy = coordinator
+            .get_extraction_policy(DEFAULT_TEST_NAMESPACE, &eg.name, "extraction_policy_1")
+            .await?;
+        assert_eq!(
+            policy.output_table_mapping.get("test_output"),
+            Some(&index.table_name)
+        );
+
+        //  Retrying builds the next version and drops the failed one
+        let retry = coordinator
+            .create_index_migration(
+                DEFAULT_TEST_NAMESPACE,
+                &eg.name,
+                "extraction_policy_1",
+                None,
+            )
+            .await?
+            .first()
+            .unwrap()
+            .migration
+            .clone()
+            .unwrap();
+        assert_eq!(retry.version, 2);
+        coordinator
+            .start_index_migration(DEFAULT_TEST_NAMESPACE, &eg.name, "extraction_policy_1")
+            .await?;
+        let gc_tasks = shared_state.list_all_gc_tasks().await?;
+        assert!(gc_tasks.iter().any(|gc_task| {
+            gc_task.task_type == ServerTaskType::DropIndexes &&
+                gc_task.output_tables == HashSet::from([failed.table_name.clone()])
+        }));
+        coordinator.run_scheduler().await?;
+
+        let tasks = shared_state.tasks_for_executor(executor_id, None).await?;
+        assert_eq!(tasks.len(), 1);
+        coordinator
+            .update_task(&tasks[0].id, executor_id, TaskOutcome::Success)
+            .await?;
+        coordinator.run_scheduler().await?;
+        let promoted = shared_state.get_index(&index.id).await?;
+        assert_eq!(promoted.table_name, retry.table_name);
+        assert_eq!(promoted.version, 2);
+        Ok(())
+    }
+
     #[tokio::test]
     #[tracing_test::traced_test]
     async fn test_run_content_through_multiple_extraction_graphs() -> Result<(), anyhow::Error> {
@@ -2521,7 +3010,8 @@ mod tests {
             key: "label1".to_string(),
             value: serde_json::json!("value1"),
             operator: Operator::Eq,
-        }]);
+        }
+        .into()]);
         coordinator.create_extraction_graph(eg.clone()).await?;
 
         //  Create some content
@@ -195,7 +195,7 @@ impl CoordinatorServiceServer {
             let expressions: Result<Vec<_>> = policy_request
                 .filter
                 .iter()
-                .map(|e| filter::Expression::from_str(e))
+                .map(|e| e.parse::<filter::Filter>())
                 .collect();
 
             let policy = ExtractionPolicyBuilder::default()
@@ -236,6 +236,60 @@ impl CoordinatorService for CoordinatorServiceServer {
         Ok(tonic::Response::new(indexify_coordinator::Empty {}))
     }
 
+    async fn create_index_migration(
+        &self,
+        request: tonic::Request<indexify_coordinator::CreateIndexMigrationRequest>,
+    ) -> Result<tonic::Response<indexify_coordinator::CreateIndexMigrationResponse>, tonic::Status>
+    {
+        let request = request.into_inner();
+        let extractor = if request.extractor.is_empty() {
+            None
+        } else {
+            Some(request.extractor.as_str())
+        };
+        let indexes = self
+            .coordinator
+            .create_index_migration(
+                &request.namespace,
+                &request.extraction_graph,
+                &request.extraction_policy,
+                extractor,
+            )
+            .await
+            .map_err(|e| tonic::Status::aborted(e.to_string()))?;
+        // Describe the new versions so the caller can create their tables
+        let indexes = indexes
+            .into_iter()
+            .filter_map(|index| {
+                let migration = index.migration.clone()?;
+                let mut index: indexify_coordinator::Index = index.into();
+                index.table_name = migration.table_name;
+                index.schema = migration.schema;
+                index.extractor = migration.extractor_name;
+                Some(index)
+            })
+            .collect();
+        Ok(tonic::Response::new(
+            indexify_coordinator::CreateIndexMigrationResponse { indexes },
+        ))
+    }
+
+    async fn start_index_migration(
+        &self,
+        request: tonic::Request<indexify_coordinator::StartIndexMigrationRequest>,
+    ) -> Result<tonic::Response<indexify_coordinator::Empty>, tonic::Status> {
+        let request = request.into_inner();
+        self.coordinator
+            .start_index_migration(
+                &request.namespace,
+                &request.extraction_graph,
+                &request.extraction_policy,
+            )
+            .await
+            .map_err(|e| tonic::Status::aborted(e.to_string()))?;
+        Ok(tonic::Response::new(indexify_coordinator::Empty {}))
+    }
+
     async fn content_stream(
         &self,
         request: tonic::Request<indexify_coordinator::ContentStreamRequest>,
@@ -458,7 +512,7 @@ impl CoordinatorService for CoordinatorServiceServer {
         let expressions: Result<Vec<_>> = req
             .labels_filter
             .iter()
-            .map(|e| filter::Expression::from_str(e))
+            .map(|e| e.parse::<filter::Filter>())
             .collect();
         let labels_filter =
             filter::LabelsFilter(expressions.map_err(|e| tonic::Status::aborted(e.to_string()))?);
@@ -903,8 +957,10 @@ impl CoordinatorService for CoordinatorServiceServer {
             .get_index(&request.namespace, &request.name)
             .await
             .map_err(|e| tonic::Status::aborted(e.to_string()))?;
+        // A missing index is returned as none, so clients can tell it apart
+        // from failures
         Ok(tonic::Response::new(GetIndexResponse {
-            index: Some(index.into()),
+            index: index.map(|index| index.into()),
         }))
     }
 
@@ -17,7 +17,7 @@ use mime::Mime;
 use nanoid::nanoid;
 use serde_json::json;
 use sha2::{Digest, Sha256};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
     api::{self, BeginExtractedContentIngest, ExtractionGraphLink, ExtractionGraphRequest},
@@ -30,7 +30,8 @@ use crate::{
         MetadataReaderTS,
         MetadataStorageTS,
     },
-    vector_index::{ScoredText, VectorIndexManager},
+    vector_index::{ScoredText, SearchError, SearchOptions, VectorIndexManager},
+    vectordbs::export::ExportFormat,
 };
 
 pub struct WriteStreamResult {
@@ -46,7 +47,7 @@ fn index_in_features(
     index_name: &str,
 ) -> bool {
     features.iter().any(|f| match f.feature_type {
-        api::FeatureType::Embedding => output_index_map
+        api::FeatureType::Embedding | api::FeatureType::SparseEmbedding => output_index_map
             .get(&f.name)
             .map_or(false, |index| index == index_name),
         _ => false,
@@ -104,6 +105,51 @@ impl DataManager {
         Ok(())
     }
 
+    /// Rebuilds the indexes of an extraction policy into new tables. The
+    /// tables of the new index versions are created before the coordinator
+    /// starts re-embedding content into them.
+    pub async fn reembed_extraction_policy(
+        &self,
+        namespace: &str,
+        extraction_graph: &str,
+        extraction_policy: &str,
+        extractor: Option<String>,
+    ) -> Result<Vec<String>> {
+        let req = indexify_coordinator::CreateIndexMigrationRequest {
+            namespace: namespace.to_string(),
+            extraction_graph: extraction_graph.to_string(),
+            extraction_policy: extraction_policy.to_string(),
+            extractor: extractor.unwrap_or_default(),
+        };
+        let response = self
+            .get_coordinator_client()
+            .await?
+            .create_index_migration(req)
+            .await?
+            .into_inner();
+        for index in &response.indexes {
+            let embedding_schema: internal_api::EmbeddingSchema =
+                serde_json::from_str(&index.schema)?;
+            self.vector_index_manager
+                .create_index(&index.table_name, embedding_schema)
+                .await?;
+        }
+        let req = indexify_coordinator::StartIndexMigrationRequest {
+            namespace: namespace.to_string(),
+            extraction_graph: extraction_graph.to_string(),
+            extraction_policy: extraction_policy.to_string(),
+        };
+        self.get_coordinator_client()
+            .await?
+            .start_index_migration(req)
+            .await?;
+        Ok(response
+            .indexes
+            .into_iter()
+            .map(|index| index.name)
+            .collect())
+    }
+
     pub async fn add_graph_to_content(
         &self,
         namespace: String,
@@ -742,20 +788,9 @@ impl DataManager {
     pub async fn write_extracted_embedding(
         &self,
         name: &str,
-        embedding: &[f32],
-        content_id: &str,
+        embeddings: internal_api::ExtractedEmbeddings,
         output_index_map: &HashMap<String, String>,
-        metadata: HashMap<String, serde_json::Value>,
-        root_content_metadata: Option<internal_api::ContentMetadata>,
-        content_metadata: internal_api::ContentMetadata,
     ) -> Result<()> {
-        let embeddings = internal_api::ExtractedEmbeddings {
-            content_id: content_id.to_string(),
-            embedding: embedding.to_vec(),
-            metadata,
-            root_content_metadata,
-            content_metadata,
-        };
         let index_table = output_index_map
             .get(name)
             .ok_or(anyhow!("index table not {} found", name))?;
@@ -854,6 +889,27 @@ impl DataManager {
         output_index_map: &HashMap<String, String>,
     ) -> Result<()> {
         let content_metadata: internal_api::ContentMetadata = content_metadata.try_into()?;
+        // Every embedding of the content stores the same text, it's read once
+        let has_embeddings = features.iter().any(|feature| {
+            matches!(
+                feature.feature_type,
+                api::FeatureType::Embedding | api::FeatureType::SparseEmbedding
+            )
+        });
+        let text = if has_embeddings {
+            self.vector_index_manager
+                .content_text(&content_metadata)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!(
+                        "unable to read content {} for keyword searches: {}",
+                        content_metadata.id.id, e
+                    );
+                    None
+                })
+        } else {
+            None
+        };
         for feature in &features {
             match feature.feature_type {
                 api::FeatureType::Embedding => {
@@ -861,16 +917,36 @@ impl DataManager {
                         serde_json::from_value(feature.data.clone()).map_err(|e| {
                             anyhow!("unable to get embedding from extracted data {}", e)
                         })?;
-                    self.write_extracted_embedding(
-                        &feature.name,
-                        &embedding_payload.values,
-                        &content_metadata.id.id,
-                        output_index_map,
-                        metadata.clone(),
-                        root_content_metadata.clone(),
-                        content_metadata.clone(),
-                    )
-                    .await?;
+                    let embeddings = internal_api::ExtractedEmbeddings {
+                        content_id: content_metadata.id.id.clone(),
+                        embedding: embedding_payload.values,
+                        vectors: embedding_payload.vectors,
+                        sparse_embedding: None,
+                        metadata: metadata.clone(),
+                        root_content_metadata: root_content_metadata.clone(),
+                        content_metadata: content_metadata.clone(),
+                        text: text.clone(),
+                    };
+                    self.write_extracted_embedding(&feature.name, embeddings, output_index_map)
+                        .await?;
+                }
+                api::FeatureType::SparseEmbedding => {
+                    let sparse_embedding: internal_api::SparseVector =
+                        serde_json::from_value(feature.data.clone()).map_err(|e| {
+                            anyhow!("unable to get sparse embedding from extracted data {}", e)
+                        })?;
+                    let embeddings = internal_api::ExtractedEmbeddings {
+                        content_id: content_metadata.id.id.clone(),
+                        embedding: Vec::new(),
+                        vectors: HashMap::new(),
+                        sparse_embedding: Some(sparse_embedding),
+                        metadata: metadata.clone(),
+                        root_content_metadata: root_content_metadata.clone(),
+                        content_metadata: content_metadata.clone(),
+                        text: text.clone(),
+                    };
+                    self.write_extracted_embedding(&feature.name, embeddings, output_index_map)
+                        .await?;
                 }
                 api::FeatureType::Metadata => {
                     let extracted_attributes = ExtractedMetadata::new(
@@ -989,11 +1065,31 @@ impl DataManager {
         &self,
         namespace: &str,
         index_name: &str,
-        query: &str,
-        k: u64,
-        filter: LabelsFilter,
-        include_content: bool,
+        options: SearchOptions,
     ) -> Result<Vec<ScoredText>> {
+        let req = indexify_coordinator::GetIndexRequest {
+            namespace: namespace.to_string(),
+            name: index_name.to_string(),
+        };
+        let index = self
+            .get_coordinator_client()
+            .await?
+            .get_index(req)
+            .await?
+            .into_inner()
+            .index
+            .ok_or(SearchError::NotFound(format!(
+                "index {} not found",
+                index_name
+            )))?;
+        self.vector_index_manager.search(index, options).await
+    }
+
+    async fn get_index(
+        &self,
+        namespace: &str,
+        index_name: &str,
+    ) -> Result<(String, internal_api::EmbeddingSchema)> {
         let req = indexify_coordinator::GetIndexRequest {
             namespace: namespace.to_string(),
             name: index_name.to_string(),
@@ -1006,8 +1102,33 @@ impl DataManager {
             .into_inner()
             .index
             .ok_or(anyhow!("Index not found"))?;
+        let schema = serde_json::from_str(&index.schema)?;
+        Ok((index.table_name, schema))
+    }
+
+    #[tracing::instrument]
+    pub async fn export_index(
+        &self,
+        namespace: &str,
+        index_name: &str,
+        format: ExportFormat,
+    ) -> Result<std::fs::File> {
+        let (table_name, schema) = self.get_index(namespace, index_name).await?;
+        self.vector_index_manager
+            .export_index(&table_name, &schema, format)
+            .await
+    }
+
+    #[tracing::instrument(skip(file))]
+    pub async fn import_index(
+        &self,
+        namespace: &str,
+        index_name: &str,
+        file: std::fs::File,
+    ) -> Result<u64> {
+        let (table_name, schema) = self.get_index(namespace, index_name).await?;
         self.vector_index_manager
-            .search(index, query, k as usize, filter, include_content)
+            .import_index(&table_name, &schema, file)
             .await
     }
 
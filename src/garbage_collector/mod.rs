@@ -79,10 +79,20 @@ impl GarbageCollector {
             .flatten()
             .cloned()
             .collect();
+        self.create_drop_tables_task(&graph.namespace, output_tables)
+            .await
+    }
+
+    /// Drops index tables that are no longer served.
+    pub async fn create_drop_tables_task(
+        &self,
+        namespace: &str,
+        tables: HashSet<String>,
+    ) -> GarbageCollectionTask {
         let mut gc_task = GarbageCollectionTask::new(
-            &graph.namespace,
+            namespace,
             ContentMetadata::default(),
-            output_tables,
+            tables,
             ServerTaskType::DropIndexes,
         );
         gc_task.assigned_to = self.choose_server().await;
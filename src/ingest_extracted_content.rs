@@ -477,6 +477,7 @@ mod tests {
             outcome: TaskOutcome::Unknown,
             index_tables: Vec::new(),
             creation_time: SystemTime::now(),
+            index_migration: None,
         };
         task.output_index_table_mapping = vec![
             ("name1".to_string(), "test_index1".to_string()),
@@ -760,6 +761,8 @@ mod tests {
         let schema = indexify_internal_api::EmbeddingSchema {
             dim: 3,
             distance: "cosine".to_string(),
+            vectors: HashMap::new(),
+            sparse: false,
         };
 
         let _ = ingest_state
@@ -902,6 +905,8 @@ mod tests {
         let schema = indexify_internal_api::EmbeddingSchema {
             dim: 3,
             distance: "cosine".to_string(),
+            vectors: HashMap::new(),
+            sparse: false,
         };
 
         let _ = ingest_state
@@ -1037,6 +1042,8 @@ mod tests {
         let schema = indexify_internal_api::EmbeddingSchema {
             dim: 3,
             distance: "cosine".to_string(),
+            vectors: HashMap::new(),
+            sparse: false,
         };
         state
             .data_manager
@@ -1091,9 +1098,12 @@ mod tests {
             let embedding = ExtractedEmbeddings {
                 content_id: content.id.id.clone(),
                 embedding: vec![1.0, 2.0, 3.0],
+                vectors: HashMap::new(),
+                sparse_embedding: None,
                 metadata: HashMap::new(),
                 content_metadata: content.clone(),
                 root_content_metadata: None,
+                text: None,
             };
             state
                 .data_manager
@@ -1238,6 +1248,8 @@ mod tests {
         let schema = indexify_internal_api::EmbeddingSchema {
             dim: 3,
             distance: "cosine".to_string(),
+            vectors: HashMap::new(),
+            sparse: false,
         };
         state
             .data_manager
@@ -1292,9 +1304,12 @@ mod tests {
             let embedding = ExtractedEmbeddings {
                 content_id: content.id.id.clone(),
                 embedding: vec![1.0, 2.0, 3.0],
+                vectors: HashMap::new(),
+                sparse_embedding: None,
                 metadata: HashMap::new(),
                 content_metadata: content.clone(),
                 root_content_metadata: None,
+                text: None,
             };
             state
                 .data_manager
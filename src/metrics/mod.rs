@@ -700,6 +700,7 @@ pub mod vector_storage {
         pub vector_search_db: Histogram<f64>,
         pub vector_search_retrieve_metadata: Histogram<f64>,
         pub vector_search_retrieve_blob: Histogram<f64>,
+        pub vector_search_rerank: Histogram<f64>,
     }
 
     impl Default for Metrics {
@@ -752,6 +753,11 @@ pub mod vector_storage {
                 .with_description("Vector search retrieve content blob latencies in seconds")
                 .init();
 
+            let vector_search_rerank = meter
+                .f64_histogram("indexify.vector_search_rerank")
+                .with_description("Vector search rerank latencies in seconds")
+                .init();
+
             Metrics {
                 vector_metadata_update,
                 vector_upsert,
@@ -761,6 +767,7 @@ pub mod vector_storage {
                 vector_search_db,
                 vector_search_retrieve_metadata,
                 vector_search_retrieve_blob,
+                vector_search_rerank,
             }
         }
     }
@@ -9,11 +9,15 @@ use indexify_internal_api::{self as internal_api, ExtractionPolicy, StateChange}
 use tracing::{error, info};
 
 use crate::{
-    state::SharedState,
+    coordinator_filters::matches_mime_type,
+    state::{store::requests::StateChangeProcessed, SharedState},
     task_allocator::{planner::plan::TaskAllocationPlan, TaskAllocator},
     utils::timestamp_secs,
 };
 
+/// Content re-embedded per raft write when an index migration starts
+const MIGRATION_TASKS_PAGE_SIZE: u64 = 1000;
+
 pub struct Scheduler {
     shared_state: SharedState,
     task_allocator: TaskAllocator,
@@ -105,11 +109,45 @@ impl Scheduler {
             .match_extraction_policies_for_content(&content, &graph_names)
             .await?;
         let tables = self.tables_for_policies(&extraction_policies).await?;
+        let mut migrating_indexes = Vec::new();
         for extraction_policy in extraction_policies {
             let task = self
                 .create_task(&extraction_policy, &content, &tables)
                 .await?;
             tasks.push(task);
+
+            // Content is embedded into the tables of started index migrations
+            // too, the live tables keep receiving it until the migrations are
+            // promoted
+            let mut indexes: Vec<internal_api::Index> = self
+                .shared_state
+                .indexes_for_extraction_policy(&extraction_policy)
+                .await?
+                .into_iter()
+                .filter(|index| {
+                    index
+                        .migration
+                        .as_ref()
+                        .map_or(false, |migration| migration.accepts_new_content())
+                })
+                .collect();
+            let Some((migration_policy, version)) =
+                migration_policy(&extraction_policy, &indexes)
+            else {
+                continue;
+            };
+            let migration_tables = self
+                .tables_for_policies(std::slice::from_ref(&migration_policy))
+                .await?;
+            let task = self
+                .create_migration_task(&migration_policy, &content, &migration_tables, version)
+                .await?;
+            if self.shared_state.task_with_id(&task.id).await.is_ok() {
+                continue;
+            }
+            count_migration_tasks(&mut indexes, std::slice::from_ref(&task));
+            tasks.push(task);
+            migrating_indexes.extend(indexes);
         }
         if tasks.is_empty() {
             return self
@@ -121,9 +159,22 @@ impl Scheduler {
                 .await;
         }
 
-        self.shared_state
-            .create_tasks(tasks.clone(), state_change.id)
-            .await?;
+        if migrating_indexes.is_empty() {
+            self.shared_state
+                .create_tasks(tasks.clone(), state_change.id)
+                .await?;
+        } else {
+            self.shared_state
+                .create_index_migration_tasks(
+                    tasks.clone(),
+                    migrating_indexes,
+                    vec![StateChangeProcessed {
+                        state_change_id: state_change.id,
+                        processed_at: timestamp_secs(),
+                    }],
+                )
+                .await?;
+        }
         let allocation_plan = self.allocate_tasks(tasks).await?;
         if !allocation_plan.0.is_empty() {
             self.shared_state
@@ -134,6 +185,124 @@ impl Scheduler {
         }
     }
 
+    /// Re-runs the extraction policy over all the content it was already
+    /// applied to, writing into the tables of its started index migrations.
+    /// The tasks are created a page of content at a time, the migrations
+    /// record how far they got so a restarted coordinator resumes from there.
+    /// Returns the migrating indexes, the state change is left for the
+    /// caller to complete the migrations.
+    pub async fn create_index_migration_tasks(
+        &self,
+        state_change: &StateChange,
+    ) -> Result<Vec<internal_api::Index>> {
+        let extraction_policy = self
+            .shared_state
+            .get_extraction_policy(&state_change.object_id)
+            .await?;
+        let mut indexes: Vec<internal_api::Index> = self
+            .shared_state
+            .indexes_for_extraction_policy(&extraction_policy)
+            .await?
+            .into_iter()
+            .filter(|index| {
+                index
+                    .migration
+                    .as_ref()
+                    .map_or(false, |migration| migration.started)
+            })
+            .collect();
+        let Some((migration_policy, version)) = migration_policy(&extraction_policy, &indexes)
+        else {
+            return Ok(indexes);
+        };
+        let extractor = self
+            .shared_state
+            .extractor_with_name(&migration_policy.extractor)?;
+        let key_prefix = internal_api::ContentMetadata::make_prefix_graph_key(
+            &extraction_policy.namespace,
+            &extraction_policy.graph_name,
+            &Some(extraction_policy.content_source.clone()),
+        );
+        let tables = self
+            .tables_for_policies(std::slice::from_ref(&migration_policy))
+            .await?;
+        let mut cursor = indexes
+            .iter()
+            .find_map(|index| index.migration.as_ref()?.tasks_cursor.clone());
+        while indexes.iter().any(|index| {
+            index
+                .migration
+                .as_ref()
+                .map_or(false, |migration| !migration.tasks_created)
+        }) {
+            let content = self
+                .shared_state
+                .list_content(
+                    |c| {
+                        !c.tombstoned &&
+                            c.extraction_policy_ids.contains_key(&extraction_policy.id) &&
+                            matches_mime_type(&extractor.input_mime_types, &c.content_type)
+                    },
+                    &key_prefix,
+                    internal_api::ContentMetadata::id_from_graph_key,
+                    cursor.as_deref(),
+                    Some(MIGRATION_TASKS_PAGE_SIZE),
+                )
+                .await?;
+            let mut tasks = Vec::new();
+            for content in content.items {
+                let task = self
+                    .create_migration_task(&migration_policy, &content, &tables, version)
+                    .await?;
+                // Content ingested since the migration started already has
+                // its task
+                if self.shared_state.task_with_id(&task.id).await.is_ok() {
+                    continue;
+                }
+                tasks.push(task);
+            }
+            cursor = (!content.restart_key.is_empty()).then_some(content.restart_key);
+            count_migration_tasks(&mut indexes, &tasks);
+            for migration in indexes.iter_mut().filter_map(|index| index.migration.as_mut()) {
+                migration.tasks_cursor.clone_from(&cursor);
+                migration.tasks_created = cursor.is_none();
+            }
+
+            // The migrations count their tasks before the tasks can finish
+            self.shared_state
+                .create_index_migration_tasks(tasks.clone(), indexes.clone(), vec![])
+                .await?;
+            if tasks.is_empty() {
+                continue;
+            }
+            let allocation_plan = self.allocate_tasks(tasks).await?;
+            if !allocation_plan.0.is_empty() {
+                self.shared_state
+                    .assign_tasks(allocation_plan.0)
+                    .await?;
+            }
+        }
+        Ok(indexes)
+    }
+
+    /// A re-embedding task of the migration with `version`, created from the
+    /// policy returned by `migration_policy`
+    async fn create_migration_task(
+        &self,
+        migration_policy: &ExtractionPolicy,
+        content: &internal_api::ContentMetadata,
+        index_tables: &[String],
+        version: u64,
+    ) -> Result<internal_api::Task> {
+        let mut task = self
+            .create_task(migration_policy, content, index_tables)
+            .await?;
+        // Keep the ids of the original tasks intact
+        task.id = format!("{}-v{}", task.id, version);
+        task.index_migration = Some(version);
+        Ok(task)
+    }
+
     pub async fn allocate_tasks(
         &self,
         tasks: Vec<internal_api::Task>,
@@ -226,8 +395,48 @@ impl Scheduler {
             outcome: internal_api::TaskOutcome::Unknown,
             index_tables: index_tables.to_vec(),
             creation_time: SystemTime::now(),
+            index_migration: None,
         };
         info!("created task: {:?}", task);
         Ok(task)
     }
 }
+
+/// The extraction policy as the migrations of `indexes` run it: with their
+/// extractor, writing into their tables. `None` if none of the indexes is
+/// migrating.
+fn migration_policy(
+    extraction_policy: &ExtractionPolicy,
+    indexes: &[internal_api::Index],
+) -> Option<(ExtractionPolicy, u64)> {
+    let mut migration_policy = extraction_policy.clone();
+    let mut version = None;
+    for index in indexes {
+        let (Some(migration), Some(output_name)) = (&index.migration, index.output_name()) else {
+            continue;
+        };
+        migration_policy
+            .extractor
+            .clone_from(&migration.extractor_name);
+        migration_policy
+            .output_table_mapping
+            .insert(output_name.to_string(), migration.table_name.clone());
+        version = version.max(Some(migration.version));
+    }
+    version.map(|version| (migration_policy, version))
+}
+
+/// Counts re-embedding tasks as pending in the migrations whose tables they
+/// write into
+fn count_migration_tasks(indexes: &mut [internal_api::Index], tasks: &[internal_api::Task]) {
+    for migration in indexes.iter_mut().filter_map(|index| index.migration.as_mut()) {
+        migration.pending_tasks += tasks
+            .iter()
+            .filter(|task| {
+                task.output_index_table_mapping
+                    .values()
+                    .any(|table| table == &migration.table_name)
+            })
+            .count() as u64;
+    }
+}
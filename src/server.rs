@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    io::Seek,
     net::SocketAddr,
     str::FromStr,
     sync::Arc,
@@ -37,10 +38,12 @@ use mime::Mime;
 use prometheus::Encoder;
 use serde_json::json;
 use tokio::{
+    io::AsyncWriteExt,
     signal,
     sync::{mpsc, watch},
 };
 use tokio_stream::StreamExt;
+use tokio_util::io::ReaderStream;
 use tonic::Streaming;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
@@ -64,7 +67,7 @@ use crate::{
     metrics,
     server_config::ServerConfig,
     tls::build_mtls_config,
-    vector_index::VectorIndexManager,
+    vector_index::{ImportError, SearchError, SearchOptions, VectorIndexManager},
     vectordbs,
 };
 
@@ -101,8 +104,11 @@ pub struct NamespaceEndpointState {
             upload_file,
             ingest_remote_file,
             add_graph_to_content,
+            reembed_extraction_policy,
             list_tasks,
             index_search,
+            export_index,
+            import_index,
             get_content_tree_metadata,
             download_content,
             extraction_graph_analytics,
@@ -110,14 +116,15 @@ pub struct NamespaceEndpointState {
         components(
             schemas(IndexDistance,
                 TextAddRequest, TextAdditionResponse, Text, IndexSearchResponse,
-                DocumentFragment, ListIndexesResponse, ExtractorOutputSchema, Index, SearchRequest, ListNamespacesResponse, ListExtractorsResponse
+                DocumentFragment, ListIndexesResponse, ExtractorOutputSchema, Index, SearchRequest, SearchMode, Rerank, ListNamespacesResponse, ListExtractorsResponse
             , ExtractorDescription, DataNamespace, ExtractionPolicy, ExtractionPolicyRequest, ExtractionPolicyResponse, Executor,
             MetadataResponse, ExtractedMetadata, ListExecutorsResponse, EmbeddingSchema, ExtractResponse, ExtractRequest,
             Feature, FeatureType, GetContentMetadataResponse, ListTasksResponse,  Task, ExtractionGraph,
             Content, ContentMetadata, ListContentResponse, GetNamespaceResponse, ExtractionPolicyResponse, ListTasks,
             ListExtractionGraphResponse, ExtractionGraphLink, ExtractionGraphRequest, ExtractionGraphResponse,
             AddGraphToContent, NewContentStreamResponse, ExtractionGraphAnalytics, TaskAnalytics,
-            IngestRemoteFileResponse, IngestRemoteFile
+            IngestRemoteFileResponse, IngestRemoteFile, ReembedRequest, ReembedResponse,
+            VectorSchema, ExportFormat, ExportIndexParams, ImportIndexResponse, internal_api::SparseVector
         )
         ),
         tags(
@@ -236,6 +243,10 @@ impl Server {
                 "/namespaces/:namespace/extraction_graphs/:extraction_graph/extraction_policies/:extraction_policy/tasks",
                 get(list_tasks).with_state(namespace_endpoint_state.clone()),
             )
+            .route(
+                "/namespaces/:namespace/extraction_graphs/:extraction_graph/extraction_policies/:extraction_policy/reembed",
+                post(reembed_extraction_policy).with_state(namespace_endpoint_state.clone()),
+            )
             .route(
                 "/namespaces/:namespace/extraction_graphs/:extraction_graph/analytics",
                 get(extraction_graph_analytics).with_state(namespace_endpoint_state.clone()),
@@ -267,6 +278,14 @@ impl Server {
                 "/namespaces/:namespace/indexes/:index/search",
                 post(index_search).with_state(namespace_endpoint_state.clone()),
             )
+            .route(
+                "/namespaces/:namespace/indexes/:index/export",
+                get(export_index).with_state(namespace_endpoint_state.clone()),
+            )
+            .route(
+                "/namespaces/:namespace/indexes/:index/import",
+                post(import_index).with_state(namespace_endpoint_state.clone()),
+            )
             .route(
                 "/namespaces/:namespace/active_content",
                 get(active_content).with_state(namespace_endpoint_state.clone()),
@@ -814,7 +833,10 @@ async fn update_labels(
         ("ingested_content_id" = Option<String>, Query, description = "Filter by ingested content ID"),
         ("labels_filter" = Option<Vec<String>>, Query, description = "Filter by labels.
         Filter expression is the name of the label, comparison operator, and desired value, e.g. &labels_filter=key>=value.
-        Multiple expressions can be specified as separate query parameters."),
+        Expressions can be combined with AND, OR, NOT and parentheses, and also support
+        key IN (a, b), key NOT IN (a, b), key EXISTS, key STARTS_WITH \"a\" and key CONTAINS \"a\".
+        Dotted keys such as a.b address nested labels.
+        Multiple expressions can be specified as separate query parameters and must all match."),
         ("start_id" = Option<String>, Query, description = "Pagination start ID.
         Omit to start from beginning. To continue iteration,
         specify id of the last content in the previous response"),
@@ -1522,6 +1544,49 @@ async fn add_graph_to_content(
     Ok(())
 }
 
+/// Re-embed the content of an extraction policy into new versions of its
+/// indexes. Search is served from the current versions until the new ones
+/// are complete, and content ingested meanwhile is embedded into both.
+/// Re-embedding that failed is retried by calling this again.
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/extraction_graphs/{extraction_graph}/extraction_policies/{extraction_policy}/reembed",
+    params(
+        ("namespace" = String, Path, description = "Namespace of the extraction graph"),
+        ("extraction_graph" = String, Path, description = "Extraction graph name"),
+        ("extraction_policy" = String, Path, description = "Extraction policy name"),
+    ),
+    request_body = ReembedRequest,
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "Re-embedding started successfully", body = ReembedResponse),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to start re-embedding")
+    ),
+)]
+#[axum::debug_handler]
+async fn reembed_extraction_policy(
+    Path((namespace, extraction_graph, extraction_policy)): Path<(String, String, String)>,
+    State(state): State<NamespaceEndpointState>,
+    Json(payload): Json<ReembedRequest>,
+) -> Result<Json<ReembedResponse>, IndexifyAPIError> {
+    let indexes = state
+        .data_manager
+        .reembed_extraction_policy(
+            &namespace,
+            &extraction_graph,
+            &extraction_policy,
+            payload.extractor,
+        )
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("failed to re-embed extraction policy: {}", e),
+            )
+        })?;
+    Ok(Json(ReembedResponse { indexes }))
+}
+
 /// List all executors running extractors in the cluster
 #[tracing::instrument]
 #[utoipa::path(
@@ -1715,6 +1780,88 @@ async fn list_indexes(
     Ok(Json(ListIndexesResponse { indexes }))
 }
 
+/// Export all the embeddings of a vector index in a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/indexes/{index}/export",
+    params(ExportIndexParams),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Parquet or Arrow IPC file with the embeddings and their metadata", body = Vec<u8>),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to export index")
+    ),
+)]
+#[axum::debug_handler]
+async fn export_index(
+    Path((namespace, index)): Path<(String, String)>,
+    State(state): State<NamespaceEndpointState>,
+    Query(params): Query<ExportIndexParams>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let format = vectordbs::export::ExportFormat::from(params.format);
+    let file = state
+        .data_manager
+        .export_index(&namespace, &index, format)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let len = file
+        .metadata()
+        .map_err(|e| IndexifyAPIError::internal_error(e.into()))?
+        .len();
+    let stream = ReaderStream::new(tokio::fs::File::from_std(file));
+    Response::builder()
+        .header("Content-Type", format.content_type())
+        .header("Content-Length", len)
+        .body(Body::from_stream(stream))
+        .map_err(|e| IndexifyAPIError::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
+
+/// Bulk load a Parquet or Arrow IPC index export into a vector index in a
+/// namespace
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/indexes/{index}/import",
+    request_body(content_type = "application/octet-stream", content = Vec<u8>),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Embeddings imported into the index", body = ImportIndexResponse),
+        (status = BAD_REQUEST, description = "Invalid index export"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to import index")
+    ),
+)]
+#[axum::debug_handler]
+async fn import_index(
+    Path((namespace, index)): Path<(String, String)>,
+    State(state): State<NamespaceEndpointState>,
+    body: Body,
+) -> Result<Json<ImportIndexResponse>, IndexifyAPIError> {
+    let file = spool_to_file(body)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let num_vectors = state
+        .data_manager
+        .import_index(&namespace, &index, file)
+        .await
+        .map_err(|e| match e.downcast_ref::<ImportError>() {
+            Some(_) => IndexifyAPIError::new(StatusCode::BAD_REQUEST, &e.to_string()),
+            None => IndexifyAPIError::internal_error(e),
+        })?;
+    Ok(Json(ImportIndexResponse { num_vectors }))
+}
+
+/// Writes a request body to an anonymous temporary file, so uploads larger
+/// than memory can be read back a part at a time
+async fn spool_to_file(body: Body) -> Result<std::fs::File> {
+    let mut file = tokio::fs::File::from_std(tempfile::tempfile()?);
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        file.write_all(&chunk?).await?;
+    }
+    file.flush().await?;
+    let mut file = file.into_std().await;
+    file.rewind()?;
+    Ok(file)
+}
+
 /// Search a vector index in a namespace
 #[utoipa::path(
     post,
@@ -1722,6 +1869,8 @@ async fn list_indexes(
     tag = "retrieval",
     responses(
         (status = 200, description = "Index search results", body = IndexSearchResponse),
+        (status = BAD_REQUEST, description = "Invalid search query"),
+        (status = NOT_FOUND, description = "Index or query content not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to search index")
     ),
 )]
@@ -1731,18 +1880,28 @@ async fn index_search(
     State(state): State<NamespaceEndpointState>,
     Json(query): Json<SearchRequest>,
 ) -> Result<Json<IndexSearchResponse>, IndexifyAPIError> {
+    let options = SearchOptions {
+        query: query
+            .search_query()
+            .map_err(|e| IndexifyAPIError::new(StatusCode::BAD_REQUEST, &e.to_string()))?,
+        vector: query.vector,
+        k: query.k.unwrap_or(DEFAULT_SEARCH_LIMIT) as usize,
+        filter: query.filters,
+        include_content: query.include_content.unwrap_or(true),
+        mode: query.mode,
+        rerank: query.rerank,
+    };
     let results = state
         .data_manager
-        .search(
-            &namespace,
-            &index,
-            &query.query,
-            query.k.unwrap_or(DEFAULT_SEARCH_LIMIT),
-            query.filters,
-            query.include_content.unwrap_or(true),
-        )
+        .search(&namespace, &index, options)
         .await
-        .map_err(IndexifyAPIError::internal_error)?;
+        .map_err(|e| match e.downcast_ref::<SearchError>() {
+            Some(SearchError::InvalidQuery(_)) => {
+                IndexifyAPIError::new(StatusCode::BAD_REQUEST, &e.to_string())
+            }
+            Some(SearchError::NotFound(_)) => IndexifyAPIError::not_found(&e.to_string()),
+            None => IndexifyAPIError::internal_error(e),
+        })?;
     let document_fragments: Vec<DocumentFragment> = results
         .iter()
         .map(|text| DocumentFragment {
@@ -1751,6 +1910,7 @@ async fn index_search(
             text: text.text.clone(),
             labels: text.labels.clone(),
             confidence_score: text.confidence_score,
+            rerank_score: text.rerank_score,
             root_content_metadata: text.root_content_metadata.clone().map(|r| r.into()),
             content_metadata: text.content_metadata.clone().into(),
         })
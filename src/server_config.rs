@@ -63,8 +63,9 @@ impl Default for MetadataStoreConfig {
 pub enum IndexStoreKind {
     Qdrant,
     PgVector,
-    //OpenSearchKnn,
+    OpenSearchKnn,
     Lancedb,
+    Hnsw,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,16 +81,49 @@ impl Default for LancedbConfig {
         }
     }
 }
+/// Configuration of the in-process HNSW index, `m` is the number of
+/// neighbours linked per node and the `ef` parameters the size of the
+/// candidate lists when building and searching the graph.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct HnswConfig {
+    pub path: String,
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            path: "/tmp/indexify/hnsw".into(),
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct QdrantConfig {
     pub addr: String,
+    /// Multi-vectors are searched by their mean vector, this many times `k`
+    /// candidates are then scored with MaxSim. Higher values miss fewer
+    /// documents with a high MaxSim score at the cost of slower searches.
+    #[serde(default = "default_multivector_candidates_factor")]
+    pub multivector_candidates_factor: u64,
+}
+
+fn default_multivector_candidates_factor() -> u64 {
+    8
 }
 
 impl Default for QdrantConfig {
     fn default() -> Self {
         Self {
             addr: "http://127.0.0.1:6334".into(),
+            multivector_candidates_factor: default_multivector_candidates_factor(),
         }
     }
 }
@@ -100,6 +134,14 @@ pub struct OpenSearchBasicConfig {
     pub addr: String,
     pub username: String,
     pub password: String,
+    /// PEM certificate of the CA that signed the server certificate, when
+    /// it isn't signed by a CA trusted by the system
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// Accept any server certificate. Only meant for local development
+    /// clusters with self-signed certificates.
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
 }
 
 impl Default for OpenSearchBasicConfig {
@@ -108,6 +150,8 @@ impl Default for OpenSearchBasicConfig {
             addr: "https://localhost:9200".into(),
             username: "admin".into(),
             password: "admin".into(),
+            ca_cert_path: None,
+            insecure_skip_verify: false,
         }
     }
 }
@@ -141,6 +185,7 @@ pub struct VectorIndexConfig {
     pub pg_vector_config: Option<PgVectorConfig>,
     pub open_search_basic: Option<OpenSearchBasicConfig>,
     pub lancedb_config: Option<LancedbConfig>,
+    pub hnsw_config: Option<HnswConfig>,
 }
 
 impl Default for VectorIndexConfig {
@@ -151,6 +196,7 @@ impl Default for VectorIndexConfig {
             pg_vector_config: Some(PgVectorConfig::default()),
             open_search_basic: Some(OpenSearchBasicConfig::default()),
             lancedb_config: Some(LancedbConfig::default()),
+            hnsw_config: Some(HnswConfig::default()),
         }
     }
 }
@@ -818,7 +818,7 @@ impl App {
         };
         // Trigger garbage collection for previous content if the root content has been
         // updated.
-        let new_state_changes = match root_content_id {
+        let mut new_state_changes = match root_content_id {
             Some(id) if id.version > 1 => vec![StateChange::new(
                 id.to_string(),
                 ChangeType::TaskCompleted {
@@ -828,6 +828,13 @@ impl App {
             )],
             _ => Vec::new(),
         };
+        if task.terminal_state() && task.index_migration.is_some() {
+            new_state_changes.push(StateChange::new(
+                task.id.clone(),
+                ChangeType::IndexMigrationTaskFinished,
+                timestamp_secs(),
+            ));
+        }
         let req = StateMachineUpdateRequest {
             payload: RequestPayload::UpdateTask {
                 task,
@@ -997,6 +1004,18 @@ impl App {
         Ok(())
     }
 
+    /// Like `commit_task_assignments` for tasks created while processing a
+    /// state change that isn't done yet
+    pub async fn assign_tasks(&self, assignments: HashMap<TaskId, ExecutorId>) -> Result<()> {
+        let req = StateMachineUpdateRequest {
+            payload: RequestPayload::AssignTask { assignments },
+            new_state_changes: vec![],
+            state_changes_processed: vec![],
+        };
+        self.forwardable_raft.client_write(req).await?;
+        Ok(())
+    }
+
     pub async fn create_content_batch(
         &self,
         content_metadata: Vec<internal_api::ContentMetadata>,
@@ -1397,11 +1416,14 @@ impl App {
     }
 
     pub async fn get_index(&self, id: &str) -> Result<internal_api::Index> {
-        let index = self
-            .state_machine
-            .get_from_cf::<internal_api::Index, _>(StateMachineColumns::IndexTable, id)?
-            .ok_or_else(|| anyhow!("Index with id {} not found", id))?;
-        Ok(index)
+        self.find_index(id)
+            .await?
+            .ok_or_else(|| anyhow!("Index with id {} not found", id))
+    }
+
+    pub async fn find_index(&self, id: &str) -> Result<Option<internal_api::Index>> {
+        self.state_machine
+            .get_from_cf::<internal_api::Index, _>(StateMachineColumns::IndexTable, id)
     }
 
     pub async fn set_indexes(&self, indexes: Vec<internal_api::Index>) -> Result<()> {
@@ -1414,6 +1436,93 @@ impl App {
         Ok(())
     }
 
+    pub async fn indexes_for_extraction_policy(
+        &self,
+        extraction_policy: &ExtractionPolicy,
+    ) -> Result<Vec<internal_api::Index>> {
+        let indexes = self
+            .list_indexes(&extraction_policy.namespace)
+            .await?
+            .into_iter()
+            .filter(|index| {
+                index.graph_name == extraction_policy.graph_name &&
+                    index.extraction_policy_name == extraction_policy.name
+            })
+            .collect();
+        Ok(indexes)
+    }
+
+    pub async fn start_index_migration(
+        &self,
+        extraction_policy: &ExtractionPolicy,
+        indexes: Vec<internal_api::Index>,
+    ) -> Result<()> {
+        let req = StateMachineUpdateRequest {
+            new_state_changes: vec![StateChange::new(
+                extraction_policy.id.clone(),
+                ChangeType::IndexMigrationStarted,
+                timestamp_secs(),
+            )],
+            payload: RequestPayload::StartIndexMigration { indexes },
+            state_changes_processed: vec![],
+        };
+        self.forwardable_raft
+            .client_write(req)
+            .await
+            .map_err(|e| anyhow!("unable to start index migration: {}", e.to_string()))?;
+        Ok(())
+    }
+
+    /// Creates re-embedding tasks and records them in the migrations of
+    /// `indexes` in a single write, so a migration never counts tasks that
+    /// don't exist.
+    pub async fn create_index_migration_tasks(
+        &self,
+        tasks: Vec<internal_api::Task>,
+        indexes: Vec<internal_api::Index>,
+        state_changes_processed: Vec<StateChangeProcessed>,
+    ) -> Result<()> {
+        let req = StateMachineUpdateRequest {
+            payload: RequestPayload::CreateIndexMigrationTasks { tasks, indexes },
+            new_state_changes: vec![],
+            state_changes_processed,
+        };
+        self.forwardable_raft
+            .client_write(req)
+            .await
+            .map_err(|e| anyhow!("unable to create index migration tasks: {}", e.to_string()))?;
+        Ok(())
+    }
+
+    /// Writes the progress of index migrations. `extraction_policy` is
+    /// pointed at the tables of promoted migrations, the tables they replace
+    /// are dropped by `gc_tasks`.
+    pub async fn update_index_migrations(
+        &self,
+        indexes: Vec<internal_api::Index>,
+        extraction_policy: Option<ExtractionPolicy>,
+        gc_tasks: Vec<GarbageCollectionTask>,
+        state_change: StateChange,
+    ) -> Result<()> {
+        let req = StateMachineUpdateRequest {
+            payload: RequestPayload::UpdateIndexMigrations {
+                indexes,
+                extraction_policy,
+                gc_tasks,
+            },
+            new_state_changes: vec![],
+            state_changes_processed: vec![StateChangeProcessed {
+                state_change_id: state_change.id,
+                processed_at: timestamp_secs(),
+            }],
+        };
+        self.forwardable_raft
+            .client_write(req)
+            .await
+            .map_err(|e| anyhow!("unable to update index migrations: {}", e.to_string()))?;
+        Ok(())
+    }
+
     pub async fn list_state_changes(&self) -> Result<Vec<StateChange>> {
         let state_changes = self
             .state_machine
@@ -1620,6 +1729,7 @@ mod tests {
             input_params: Default::default(),
             index_tables: Default::default(),
             creation_time: SystemTime::now(),
+            index_migration: None,
         }
     }
 
@@ -1855,17 +1965,20 @@ mod tests {
                 key: "label1".to_string(),
                 operator: Operator::Eq,
                 value: serde_json::json!("value1"),
-            },
+            }
+            .into(),
             Expression {
                 key: "label2".to_string(),
                 operator: Operator::Eq,
                 value: serde_json::json!("value2"),
-            },
+            }
+            .into(),
             Expression {
                 key: "label3".to_string(),
                 operator: Operator::Eq,
                 value: serde_json::json!("value3"),
-            },
+            }
+            .into(),
         ]);
 
         node.create_extraction_graph(eg.clone(), StructuredDataSchema::default(), vec![])
@@ -1945,17 +2058,20 @@ mod tests {
                 key: "label1".to_string(),
                 operator: Operator::Eq,
                 value: serde_json::json!("value1"),
-            },
+            }
+            .into(),
             Expression {
                 key: "label2".to_string(),
                 operator: Operator::Eq,
                 value: serde_json::json!("value2"),
-            },
+            }
+            .into(),
             Expression {
                 key: "label3".to_string(),
                 operator: Operator::Eq,
                 value: serde_json::json!("value3"),
-            },
+            }
+            .into(),
         ]);
 
         node.create_extraction_graph(eg.clone(), StructuredDataSchema::default(), vec![])
@@ -2071,7 +2187,8 @@ mod tests {
             key: "label1".to_string(),
             operator: Operator::Eq,
             value: serde_json::json!("value1"),
-        }]);
+        }
+        .into()]);
         let _structured_data_schema = StructuredDataSchema::default();
         node.create_extraction_graph(
             eg.clone(),
@@ -31,5 +31,6 @@ pub fn convert_v1_task(
         outcome: task.outcome,
         index_tables: task.index_tables,
         creation_time: std::time::UNIX_EPOCH,
+        index_migration: None,
     })
 }
@@ -100,6 +100,7 @@ pub fn convert_v2_task(
         outcome: task.outcome,
         index_tables: task.index_tables,
         creation_time: task.creation_time,
+        index_migration: None,
     })
 }
 
@@ -787,7 +787,8 @@ impl StateMachineStore {
 
                 //  if the payload is a GC task, send it via channel
                 match req.payload {
-                    RequestPayload::CreateOrAssignGarbageCollectionTask { gc_tasks } => {
+                    RequestPayload::CreateOrAssignGarbageCollectionTask { gc_tasks } |
+                    RequestPayload::UpdateIndexMigrations { gc_tasks, .. } => {
                         self.send_gc_tasks(gc_tasks);
                     }
                     RequestPayload::DeleteExtractionGraph {
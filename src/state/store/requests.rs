@@ -90,6 +90,25 @@ pub enum RequestPayload {
     SetIndex {
         indexes: Vec<internal_api::Index>,
     },
+    // Marks the pending index migrations as started, new content is embedded
+    // into their tables next to the live ones from then on.
+    StartIndexMigration {
+        indexes: Vec<internal_api::Index>,
+    },
+    // Creates re-embedding tasks together with the progress of the index
+    // migrations they write into.
+    CreateIndexMigrationTasks {
+        tasks: Vec<internal_api::Task>,
+        indexes: Vec<internal_api::Index>,
+    },
+    // Records finished re-embedding tasks and promotes the completed index
+    // migrations together with pointing the extraction policy at the promoted
+    // tables and dropping the tables they replace.
+    UpdateIndexMigrations {
+        indexes: Vec<internal_api::Index>,
+        extraction_policy: Option<internal_api::ExtractionPolicy>,
+        gc_tasks: Vec<GarbageCollectionTask>,
+    },
     UpdateTask {
         task: internal_api::Task,
         executor_id: Option<String>,
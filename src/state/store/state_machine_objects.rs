@@ -1229,6 +1229,36 @@ impl IndexifyState {
         Ok(())
     }
 
+    /// Replaces an extraction policy and its copy in the extraction graph
+    fn update_extraction_policy(
+        &self,
+        db: &OptimisticTransactionDB,
+        txn: &Transaction<OptimisticTransactionDB>,
+        extraction_policy: &ExtractionPolicy,
+    ) -> Result<(), StateMachineError> {
+        self.set_extraction_policy(db, txn, extraction_policy)?;
+        let graphs = self.get_extraction_graphs_by_name(
+            &extraction_policy.namespace,
+            &[&extraction_policy.graph_name],
+            db,
+        )?;
+        if let Some(Some(mut graph)) = graphs.into_iter().next() {
+            for policy in graph.extraction_policies.iter_mut() {
+                if policy.id == extraction_policy.id {
+                    *policy = extraction_policy.clone();
+                }
+            }
+            let serialized_eg = JsonEncoder::encode(&graph)?;
+            txn.put_cf(
+                &StateMachineColumns::ExtractionGraphs.cf(db),
+                graph.key(),
+                serialized_eg,
+            )
+            .map_err(|e| StateMachineError::DatabaseError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
     fn set_namespace(
         &self,
         db: &OptimisticTransactionDB,
@@ -1453,6 +1483,33 @@ impl IndexifyState {
                     self.set_index(db, &txn, index, &index.id)?;
                 }
             }
+            RequestPayload::StartIndexMigration { indexes } => {
+                for index in indexes {
+                    self.set_index(db, &txn, index, &index.id)?;
+                }
+            }
+            RequestPayload::CreateIndexMigrationTasks { tasks, indexes } => {
+                self.update_tasks(db, &txn, tasks.clone(), SystemTime::UNIX_EPOCH)?;
+                for task in tasks {
+                    self.inc_root_ref_count(task.content_metadata.get_root_id());
+                }
+                for index in indexes {
+                    self.set_index(db, &txn, index, &index.id)?;
+                }
+            }
+            RequestPayload::UpdateIndexMigrations {
+                indexes,
+                extraction_policy,
+                gc_tasks,
+            } => {
+                for index in indexes {
+                    self.set_index(db, &txn, index, &index.id)?;
+                }
+                if let Some(extraction_policy) = extraction_policy {
+                    self.update_extraction_policy(db, &txn, extraction_policy)?;
+                }
+                self.set_garbage_collection_tasks(db, &txn, gc_tasks)?;
+            }
             RequestPayload::CreateTasks { tasks } => {
                 self.update_tasks(db, &txn, tasks.clone(), SystemTime::UNIX_EPOCH)?;
                 for task in tasks {
@@ -1685,7 +1742,8 @@ impl IndexifyState {
 
                 Ok(())
             }
-            RequestPayload::CreateTasks { tasks } => {
+            RequestPayload::CreateTasks { tasks } |
+            RequestPayload::CreateIndexMigrationTasks { tasks, .. } => {
                 for task in tasks {
                     self.unassigned_tasks.insert(&task.id, task.creation_time);
                     self.unfinished_tasks_by_extractor
@@ -1789,6 +1847,8 @@ impl IndexifyState {
             RequestPayload::JoinCluster { .. } |
             RequestPayload::RemoveExecutor { .. } |
             RequestPayload::SetIndex { .. } |
+            RequestPayload::StartIndexMigration { .. } |
+            RequestPayload::UpdateIndexMigrations { .. } |
             RequestPayload::TombstoneContent { .. } |
             RequestPayload::TombstoneContentTree { .. } => Ok(()),
         }
@@ -325,6 +325,7 @@ mod tests {
             outcome: internal_api::TaskOutcome::Unknown,
             index_tables: vec![],
             creation_time: SystemTime::now(),
+            index_migration: None,
         }
     }
 
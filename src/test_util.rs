@@ -159,6 +159,8 @@ pub mod db_utils {
             internal_api::OutputSchema::Embedding(internal_api::EmbeddingSchema {
                 dim: 384,
                 distance: "cosine".to_string(),
+                vectors: HashMap::new(),
+                sparse: false,
             }),
         );
         internal_api::ExtractorDescription {
@@ -1,4 +1,11 @@
-use std::{collections::HashMap, fmt, str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    str::FromStr,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
@@ -6,7 +13,8 @@ use filter::LabelsFilter;
 use futures::future::join_all;
 use indexify_internal_api as internal_api;
 use indexify_proto::indexify_coordinator::Index;
-use internal_api::ExtractedEmbeddings;
+use internal_api::{ExtractedEmbeddings, SparseVector, Vector};
+use serde::de::DeserializeOwned;
 use tracing::info;
 
 use crate::{
@@ -15,9 +23,97 @@ use crate::{
     coordinator_client::CoordinatorClient,
     extractor_router::ExtractorRouter,
     metrics::{vector_storage::Metrics, Timer},
-    vectordbs::{CreateIndexParams, IndexDistance, SearchResult, VectorChunk, VectorDBTS},
+    vectordbs::{
+        export::{export_index, ExportFormat, IndexImport},
+        CreateIndexParams,
+        IndexDistance,
+        SearchResult,
+        VectorChunk,
+        VectorDBTS,
+        VectorParams,
+    },
 };
 
+/// Candidates fetched per requested result when reranking
+const RERANK_CANDIDATES_FACTOR: usize = 4;
+
+/// Constant of reciprocal rank fusion, dampens the weight of the top ranks.
+const RRF_K: f32 = 60.0;
+
+/// Searches that fail because of the request rather than the server.
+#[derive(Debug)]
+pub enum SearchError {
+    /// The query can't be used with the index or the search options
+    InvalidQuery(String),
+    /// The index or the content searched with doesn't exist
+    NotFound(String),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::InvalidQuery(message) => write!(f, "invalid search: {}", message),
+            SearchError::NotFound(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+/// Imports that fail because of the export or the index imported into rather
+/// than the server.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The export isn't a readable Parquet or Arrow IPC index export
+    InvalidExport(String),
+    /// The exported embeddings don't have the dimension of the index
+    DimensionMismatch {
+        index: String,
+        index_dim: usize,
+        export_dim: usize,
+    },
+    /// Exports only hold dense embeddings
+    SparseIndex(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::InvalidExport(message) => write!(f, "invalid index export: {}", message),
+            ImportError::DimensionMismatch {
+                index,
+                index_dim,
+                export_dim,
+            } => write!(
+                f,
+                "index {} has dimension {}, the export has dimension {}",
+                index, index_dim, export_dim
+            ),
+            ImportError::SparseIndex(index) => {
+                write!(f, "exports can't be imported into sparse index {}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+fn invalid_export(e: anyhow::Error) -> anyhow::Error {
+    ImportError::InvalidExport(e.to_string()).into()
+}
+
+fn invalid_query(message: String) -> anyhow::Error {
+    SearchError::InvalidQuery(message).into()
+}
+
+fn content_not_found(content_id: &str, index: &str) -> anyhow::Error {
+    SearchError::NotFound(format!(
+        "content {} not found in index {}",
+        content_id, index
+    ))
+    .into()
+}
+
 pub struct VectorIndexManager {
     vector_db: VectorDBTS,
     extractor_router: ExtractorRouter,
@@ -31,16 +127,123 @@ impl fmt::Debug for VectorIndexManager {
     }
 }
 
+/// What an index is searched with.
+#[derive(Debug, Clone)]
+pub enum SearchQuery {
+    /// Text, embedded by the index's extractor
+    Text(String),
+    /// A precomputed embedding, or multi-vector for multi-vector named
+    /// vectors
+    Embedding(Vector),
+    /// A precomputed sparse embedding, for sparse indexes
+    SparseEmbedding(SparseVector),
+    /// The stored embedding of content already in the index, the content
+    /// itself is left out of the results
+    ContentId(String),
+}
+
+/// Options of an index search, built from a search request.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub query: SearchQuery,
+    /// Named vector searched, the index embedding when `None`
+    pub vector: Option<String>,
+    pub k: usize,
+    pub filter: LabelsFilter,
+    pub include_content: bool,
+    pub mode: api::SearchMode,
+    pub rerank: Option<api::Rerank>,
+}
+
 pub struct ScoredText {
     pub text: String,
     pub content_id: String,
     pub mime_type: String,
     pub labels: HashMap<String, serde_json::Value>,
     pub confidence_score: f32,
+    pub rerank_score: Option<f32>,
     pub root_content_metadata: Option<internal_api::ContentMetadata>,
     pub content_metadata: internal_api::ContentMetadata,
 }
 
+/// Text content for embedding a text query with an extractor.
+fn text_content(text: &str) -> api::Content {
+    api::Content {
+        content_type: mime::TEXT_PLAIN.to_string(),
+        bytes: text.as_bytes().into(),
+        features: vec![],
+        labels: HashMap::new(),
+    }
+}
+
+/// Reranking over-fetches so results below the top k can move up.
+fn rerank_candidates(rerank: &api::Rerank, k: usize) -> usize {
+    rerank
+        .candidates
+        .map(|candidates| candidates as usize)
+        .unwrap_or(k * RERANK_CANDIDATES_FACTOR)
+        .max(k)
+}
+
+/// Orders the candidates by their rerank scores, highest first, and keeps
+/// the top k. The text fetched for the reranker is dropped unless the
+/// content was requested.
+fn apply_rerank_scores(
+    mut results: Vec<ScoredText>,
+    scores: Vec<f32>,
+    k: usize,
+    include_content: bool,
+) -> Vec<ScoredText> {
+    for (result, score) in results.iter_mut().zip(scores) {
+        result.rerank_score = Some(score);
+    }
+    results.sort_by(|a, b| {
+        b.rerank_score
+            .unwrap_or_default()
+            .total_cmp(&a.rerank_score.unwrap_or_default())
+    });
+    results.truncate(k);
+    if !include_content {
+        for result in &mut results {
+            result.text.clear();
+        }
+    }
+    results
+}
+
+/// Merges ranked result lists with reciprocal rank fusion. Every list
+/// contributes `1 / (RRF_K + rank)` for each of its results, which becomes
+/// the fused confidence score.
+fn reciprocal_rank_fusion(lists: Vec<Vec<SearchResult>>, k: usize) -> Vec<SearchResult> {
+    let mut fused: Vec<SearchResult> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut scores: Vec<f32> = Vec::new();
+    for list in lists {
+        for (rank, result) in list.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+            match positions.get(&result.content_id) {
+                Some(position) => scores[*position] += score,
+                None => {
+                    positions.insert(result.content_id.clone(), fused.len());
+                    fused.push(result);
+                    scores.push(score);
+                }
+            }
+        }
+    }
+    let mut fused: Vec<(SearchResult, f32)> = fused.into_iter().zip(scores).collect();
+    // Stable, so ties keep the order of the first list
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    fused
+        .into_iter()
+        .take(k)
+        .map(|(result, score)| SearchResult {
+            confidence_score: score,
+            ..result
+        })
+        .collect()
+}
+
 impl VectorIndexManager {
     pub fn new(coordinator_client: Arc<CoordinatorClient>, vector_db: VectorDBTS) -> Result<Self> {
         let extractor_router = ExtractorRouter::new(coordinator_client.clone())?;
@@ -58,11 +261,30 @@ impl VectorIndexManager {
         index_name: &str,
         schema: internal_api::EmbeddingSchema,
     ) -> Result<String> {
+        if schema.sparse && !schema.vectors.is_empty() {
+            return Err(anyhow!("sparse indexes can't have named vectors"));
+        }
+        let vectors = schema
+            .vectors
+            .iter()
+            .map(|(name, vector)| {
+                Ok((
+                    name.clone(),
+                    VectorParams {
+                        dim: vector.dim as u64,
+                        distance: IndexDistance::from_str(vector.distance.as_str())?,
+                        multivector: vector.multivector,
+                    },
+                ))
+            })
+            .collect::<Result<_>>()?;
         let create_index_params = CreateIndexParams {
             vectordb_index_name: index_name.to_string(),
             vector_dim: schema.dim as u64,
             distance: IndexDistance::from_str(schema.distance.as_str())?,
             unique_params: None,
+            vectors,
+            sparse: schema.sparse,
         };
         info!("Creating index: {:?}", create_index_params);
         self.vector_db.create_index(create_index_params).await?;
@@ -81,13 +303,18 @@ impl VectorIndexManager {
         let _timer = Timer::start(&self.metrics.vector_upsert);
         let mut vector_chunks = Vec::new();
         embeddings.iter().for_each(|embedding| {
-            let vector_chunk = VectorChunk::new(
-                embedding.content_id.clone(),
-                embedding.embedding.clone(),
-                embedding.metadata.clone(),
-                embedding.root_content_metadata.clone(),
-                &embedding.content_metadata,
-            );
+            let vector_chunk = VectorChunk {
+                vectors: embedding.vectors.clone(),
+                sparse_embedding: embedding.sparse_embedding.clone(),
+                text: embedding.text.clone(),
+                ..VectorChunk::new(
+                    embedding.content_id.clone(),
+                    embedding.embedding.clone(),
+                    embedding.metadata.clone(),
+                    embedding.root_content_metadata.clone(),
+                    &embedding.content_metadata,
+                )
+            };
             vector_chunks.push(vector_chunk);
         });
         self.vector_db
@@ -96,6 +323,22 @@ impl VectorIndexManager {
         Ok(())
     }
 
+    /// Reads the text of content to store with its embeddings for keyword
+    /// searches, `None` if the content isn't text.
+    pub async fn content_text(
+        &self,
+        content_metadata: &internal_api::ContentMetadata,
+    ) -> Result<Option<String>> {
+        if !content_metadata.content_type.starts_with("text/") {
+            return Ok(None);
+        }
+        let bytes = self
+            .content_reader
+            .bytes(&content_metadata.storage_url)
+            .await?;
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
     pub async fn remove_embedding(&self, vector_index_name: &str, content_id: &str) -> Result<()> {
         let _timer = Timer::start(&self.metrics.vector_delete);
         self.vector_db
@@ -124,31 +367,125 @@ impl VectorIndexManager {
             .await
     }
 
-    pub async fn search(
+    /// Exports every embedding in the index to a Parquet or Arrow IPC file.
+    /// The export is written to an anonymous temporary file, which is removed
+    /// once the returned handle is dropped, so it can be streamed without
+    /// holding it in memory.
+    pub async fn export_index(
         &self,
-        index: Index,
-        query: &str,
-        k: usize,
-        filter: filter::LabelsFilter,
-        include_content: bool,
-    ) -> Result<Vec<ScoredText>> {
+        index: &str,
+        schema: &internal_api::EmbeddingSchema,
+        format: ExportFormat,
+    ) -> Result<File> {
+        if schema.sparse {
+            return Err(anyhow!("sparse index {} can't be exported", index));
+        }
+        let distance = IndexDistance::from_str(schema.distance.as_str())?;
+        let mut file = tempfile::tempfile()?;
+        let mut writer = BufWriter::new(&mut file);
+        let exported =
+            export_index(&self.vector_db, index, Some(distance), format, &mut writer).await?;
+        writer.flush()?;
+        drop(writer);
+        file.rewind()?;
+        info!("exported {} embeddings from index {}", exported, index);
+        Ok(file)
+    }
+
+    /// Loads an index export from a file into an existing index. The
+    /// dimension of the exported embeddings has to match the index.
+    pub async fn import_index(
+        &self,
+        index: &str,
+        schema: &internal_api::EmbeddingSchema,
+        file: File,
+    ) -> Result<u64> {
+        if schema.sparse {
+            return Err(ImportError::SparseIndex(index.to_string()).into());
+        }
+        let import = IndexImport::new(file).map_err(invalid_export)?;
+        if let Some(dim) = import.dim {
+            if dim != schema.dim {
+                return Err(ImportError::DimensionMismatch {
+                    index: index.to_string(),
+                    index_dim: schema.dim,
+                    export_dim: dim,
+                }
+                .into());
+            }
+        }
+        let _timer = Timer::start(&self.metrics.vector_upsert);
+        let mut imported = 0;
+        for chunks in import.chunks() {
+            let chunks = chunks.map_err(invalid_export)?;
+            imported += chunks.len() as u64;
+            self.vector_db.add_embedding(index, chunks).await?;
+        }
+        info!("imported {} embeddings into index {}", imported, index);
+        Ok(imported)
+    }
+
+    pub async fn search(&self, index: Index, options: SearchOptions) -> Result<Vec<ScoredText>> {
         let _timer = Timer::start(&self.metrics.vector_search);
+        let SearchOptions {
+            query,
+            vector,
+            k,
+            filter,
+            include_content,
+            mode,
+            rerank,
+        } = options;
 
-        let content = api::Content {
-            content_type: mime::TEXT_PLAIN.to_string(),
-            bytes: query.as_bytes().into(),
-            features: vec![],
-            labels: HashMap::new(),
+        let rerank = match (rerank, &query) {
+            (Some(rerank), SearchQuery::Text(text)) => Some((rerank, text.clone())),
+            (Some(_), _) => {
+                return Err(invalid_query("reranking requires a text query".to_string()))
+            }
+            (None, _) => None,
+        };
+        let candidates = match &rerank {
+            Some((rerank, _)) => rerank_candidates(rerank, k),
+            None => k,
         };
 
-        let embedding = self.generate_embedding(&index.extractor, content).await?;
-
-        let search_result = self
-            .search_vector_db(index.table_name, embedding.values, k as u64, filter)
-            .await?;
+        let search_result = match (mode, &query) {
+            (api::SearchMode::Vector, _) => {
+                self.vector_search(&index, &query, vector.as_deref(), candidates, filter)
+                    .await?
+            }
+            (api::SearchMode::Keyword, SearchQuery::Text(text)) => {
+                self.vector_db
+                    .keyword_search(index.table_name.clone(), text, candidates as u64, filter)
+                    .await?
+            }
+            (api::SearchMode::Hybrid, SearchQuery::Text(text)) => {
+                let keyword_results = self
+                    .vector_db
+                    .keyword_search(
+                        index.table_name.clone(),
+                        text,
+                        candidates as u64,
+                        filter.clone(),
+                    )
+                    .await?;
+                let vector_results = self
+                    .vector_search(&index, &query, vector.as_deref(), candidates, filter)
+                    .await?;
+                reciprocal_rank_fusion(vec![vector_results, keyword_results], candidates)
+            }
+            (mode, _) => {
+                return Err(invalid_query(format!(
+                    "{:?} search requires a text query",
+                    mode
+                )))
+            }
+        };
 
+        // The reranker needs the text of the candidates
+        let fetch_content = include_content || rerank.is_some();
         let mut content_byte_map = HashMap::new();
-        if include_content {
+        if fetch_content {
             content_byte_map = self.retrieve_content_blob(&search_result).await?;
         }
 
@@ -156,7 +493,7 @@ impl VectorIndexManager {
         for result in search_result {
             let content = content_byte_map.get(result.content_id.as_str());
             // Only skip specified to include content but content is not found.
-            if content.is_none() && include_content {
+            if content.is_none() && fetch_content {
                 continue;
             }
             let text =
@@ -177,19 +514,238 @@ impl VectorIndexManager {
                 mime_type: result.content_metadata.content_type.clone(),
                 labels,
                 confidence_score: result.confidence_score,
+                rerank_score: None,
                 root_content_metadata: result.root_content_metadata,
                 content_metadata: result.content_metadata.clone(),
             };
             index_search_results.push(search_result);
         }
+
+        if let Some((rerank, query)) = rerank {
+            let scores = self
+                .rerank_scores(&rerank.extractor, &query, &index_search_results)
+                .await?;
+            index_search_results =
+                apply_rerank_scores(index_search_results, scores, k, include_content);
+        }
         Ok(index_search_results)
     }
 
-    async fn generate_embedding(
+    /// Scores the results with the reranker extractor, one score per result.
+    async fn rerank_scores(
+        &self,
+        extractor: &str,
+        query: &str,
+        results: &[ScoredText],
+    ) -> Result<Vec<f32>> {
+        let _timer = Timer::start(&self.metrics.vector_search_rerank);
+        if results.is_empty() {
+            return Ok(vec![]);
+        }
+        let documents: Vec<&str> = results.iter().map(|result| result.text.as_str()).collect();
+        let payload = serde_json::json!({ "query": query, "documents": documents });
+        let content = api::Content {
+            content_type: mime::APPLICATION_JSON.to_string(),
+            bytes: serde_json::to_vec(&payload)?,
+            features: vec![],
+            labels: HashMap::new(),
+        };
+        let feature = self
+            .extractor_router
+            .extract_content(extractor, content, None)
+            .await
+            .map_err(|e| anyhow!("unable to rerank results: {}", e.to_string()))?
+            .features
+            .pop()
+            .ok_or(anyhow!("reranker {} returned no scores", extractor))?;
+        let scores: Vec<f32> = serde_json::from_value(
+            feature
+                .data
+                .get("scores")
+                .cloned()
+                .ok_or(anyhow!("reranker {} returned no scores", extractor))?,
+        )?;
+        if scores.len() != results.len() {
+            return Err(anyhow!(
+                "reranker {} returned {} scores for {} documents",
+                extractor,
+                scores.len(),
+                results.len()
+            ));
+        }
+        Ok(scores)
+    }
+
+    /// Searches the embedding of the index or, when `vector` is set, one of
+    /// its named vectors.
+    async fn vector_search(
+        &self,
+        index: &Index,
+        query: &SearchQuery,
+        vector: Option<&str>,
+        k: usize,
+        filter: LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let schema: internal_api::EmbeddingSchema = serde_json::from_str(&index.schema)?;
+        if schema.sparse {
+            if vector.is_some() {
+                return Err(invalid_query(format!(
+                    "sparse index {} has no named vectors",
+                    index.name
+                )));
+            }
+            return self
+                .sparse_search(index, schema.dim, query, k, filter)
+                .await;
+        }
+        let (query_vector, exclude) = match query {
+            SearchQuery::Text(text) => {
+                let mut embedding: internal_api::Embedding = self
+                    .generate_embedding(&index.extractor, text_content(text))
+                    .await?;
+                let query_vector = match vector {
+                    Some(name) => embedding.vectors.remove(name).ok_or(anyhow!(
+                        "extractor {} returned no vector {}",
+                        index.extractor,
+                        name
+                    ))?,
+                    None => Vector::Dense(embedding.values),
+                };
+                (query_vector, None)
+            }
+            SearchQuery::Embedding(embedding) => {
+                let (dim, multivector) = match vector {
+                    Some(name) => {
+                        let vector = schema.vectors.get(name).ok_or_else(|| {
+                            invalid_query(format!("index {} has no vector {}", index.name, name))
+                        })?;
+                        (vector.dim, vector.multivector)
+                    }
+                    None => (schema.dim, false),
+                };
+                if embedding.is_multi() != multivector {
+                    return Err(invalid_query(
+                        "multi-vectors are searched with multi-vector queries, other vectors with embeddings"
+                            .to_string(),
+                    ));
+                }
+                if let Some(query_dim) = embedding.dim().filter(|query_dim| *query_dim != dim) {
+                    return Err(invalid_query(format!(
+                        "query embedding has {} dimensions, index {} has {}",
+                        query_dim, index.name, dim
+                    )));
+                }
+                (embedding.clone(), None)
+            }
+            SearchQuery::ContentId(content_id) => {
+                let mut chunk = self
+                    .vector_db
+                    .get_points(&index.table_name, vec![content_id.clone()])
+                    .await?
+                    .pop()
+                    .ok_or_else(|| content_not_found(content_id, &index.name))?;
+                let query_vector = match vector {
+                    Some(name) => chunk.vectors.remove(name).ok_or_else(|| {
+                        invalid_query(format!("content {} has no vector {}", content_id, name))
+                    })?,
+                    None => Vector::Dense(chunk.embedding),
+                };
+                (query_vector, Some(content_id))
+            }
+            SearchQuery::SparseEmbedding(_) => {
+                return Err(invalid_query(format!(
+                    "index {} is dense, sparse embeddings can only search sparse indexes",
+                    index.name
+                )))
+            }
+        };
+
+        let Some(exclude) = exclude else {
+            return self
+                .search_vector_db(
+                    index.table_name.clone(),
+                    vector,
+                    query_vector,
+                    k as u64,
+                    filter,
+                )
+                .await;
+        };
+        // The content is its own nearest neighbour, search one more
+        let mut results = self
+            .search_vector_db(
+                index.table_name.clone(),
+                vector,
+                query_vector,
+                k as u64 + 1,
+                filter,
+            )
+            .await?;
+        results.retain(|result| &result.content_id != exclude);
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Searches a sparse index by dot product. Text queries are embedded by
+    /// the index's extractor, which returns a sparse embedding feature.
+    async fn sparse_search(
+        &self,
+        index: &Index,
+        dim: usize,
+        query: &SearchQuery,
+        k: usize,
+        filter: LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let (query_vector, exclude) = match query {
+            SearchQuery::Text(text) => {
+                let embedding: SparseVector = self
+                    .generate_embedding(&index.extractor, text_content(text))
+                    .await?;
+                (embedding, None)
+            }
+            SearchQuery::SparseEmbedding(embedding) => (embedding.clone(), None),
+            SearchQuery::Embedding(_) => {
+                return Err(invalid_query(format!(
+                    "index {} is sparse, it can only be searched with sparse embeddings",
+                    index.name
+                )))
+            }
+            SearchQuery::ContentId(content_id) => {
+                let chunk = self
+                    .vector_db
+                    .get_points(&index.table_name, vec![content_id.clone()])
+                    .await?
+                    .pop()
+                    .ok_or_else(|| content_not_found(content_id, &index.name))?;
+                let embedding = chunk
+                    .sparse_embedding
+                    .ok_or(anyhow!("content {} has no sparse embedding", content_id))?;
+                (embedding, Some(content_id))
+            }
+        };
+        query_vector
+            .validate(dim)
+            .map_err(|e| invalid_query(e.to_string()))?;
+
+        let _timer = Timer::start(&self.metrics.vector_search_db);
+        // The content is its own nearest neighbour, search one more
+        let limit = if exclude.is_some() { k + 1 } else { k };
+        let mut results = self
+            .vector_db
+            .search_sparse(index.table_name.clone(), query_vector, limit as u64, filter)
+            .await?;
+        if let Some(exclude) = exclude {
+            results.retain(|result| &result.content_id != exclude);
+            results.truncate(k);
+        }
+        Ok(results)
+    }
+
+    async fn generate_embedding<T: DeserializeOwned>(
         &self,
         extractor: &str,
         content: api::Content,
-    ) -> Result<internal_api::Embedding> {
+    ) -> Result<T> {
         let _timer = Timer::start(&self.metrics.vector_search_extract_embeddings);
         let feature = self
             .extractor_router
@@ -209,12 +765,27 @@ impl VectorIndexManager {
     async fn search_vector_db(
         &self,
         index: String,
-        embedding: Vec<f32>,
+        vector: Option<&str>,
+        query: Vector,
         k: u64,
         filter: LabelsFilter,
     ) -> Result<Vec<SearchResult>> {
         let _timer = Timer::start(&self.metrics.vector_search_db);
-        let search_result = self.vector_db.search(index, embedding, k, filter).await?;
+        let search_result = match (vector, query) {
+            (Some(vector), query) => {
+                self.vector_db
+                    .search_vector(index, vector, query, k, filter)
+                    .await?
+            }
+            (None, Vector::Dense(embedding)) => {
+                self.vector_db.search(index, embedding, k, filter).await?
+            }
+            (None, Vector::Multi(_)) => {
+                return Err(invalid_query(
+                    "multi-vector queries require a named vector".to_string(),
+                ))
+            }
+        };
         Ok(search_result)
     }
 
@@ -248,3 +819,263 @@ impl VectorIndexManager {
         Ok(content_byte_map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use internal_api::{ContentMetadata, ContentMetadataId, EmbeddingSchema};
+
+    use super::*;
+    use crate::server_config::{HnswConfig, IndexStoreKind, ServerConfig};
+
+    async fn create_manager(dir: &Path) -> Result<VectorIndexManager> {
+        let mut config = ServerConfig::default();
+        config.index_config.index_store = IndexStoreKind::Hnsw;
+        config.index_config.hnsw_config = Some(HnswConfig {
+            path: dir.join("hnsw").to_str().unwrap().to_string(),
+            ..Default::default()
+        });
+        let vector_db = crate::vectordbs::create_vectordb(config.index_config.clone()).await?;
+        let coordinator_client = Arc::new(CoordinatorClient::new(Arc::new(config)));
+        VectorIndexManager::new(coordinator_client, vector_db)
+    }
+
+    fn embedding(content_id: &str, embedding: Vec<f32>) -> ExtractedEmbeddings {
+        ExtractedEmbeddings {
+            content_id: content_id.to_string(),
+            embedding,
+            vectors: HashMap::new(),
+            sparse_embedding: None,
+            metadata: HashMap::new(),
+            root_content_metadata: None,
+            content_metadata: ContentMetadata {
+                id: ContentMetadataId::new(content_id),
+                ..Default::default()
+            },
+            text: None,
+        }
+    }
+
+    async fn create_index(manager: &VectorIndexManager) -> Result<Index> {
+        let schema = EmbeddingSchema {
+            dim: 3,
+            distance: "cosine".to_string(),
+            vectors: HashMap::new(),
+            sparse: false,
+        };
+        manager.create_index("idx", schema.clone()).await?;
+        manager
+            .add_embedding(
+                "idx",
+                vec![
+                    embedding("a", vec![1.0, 0.0, 0.0]),
+                    embedding("b", vec![0.9, 0.1, 0.0]),
+                    embedding("c", vec![0.0, 0.0, 1.0]),
+                ],
+            )
+            .await?;
+        Ok(Index {
+            name: "idx".to_string(),
+            table_name: "idx".to_string(),
+            schema: serde_json::to_string(&schema)?,
+            ..Default::default()
+        })
+    }
+
+    async fn search(
+        manager: &VectorIndexManager,
+        index: &Index,
+        query: SearchQuery,
+        vector: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let options = SearchOptions {
+            query,
+            vector: vector.map(|vector| vector.to_string()),
+            k: 2,
+            filter: LabelsFilter::default(),
+            include_content: false,
+            mode: api::SearchMode::Vector,
+            rerank: None,
+        };
+        let results = manager.search(index.clone(), options).await?;
+        Ok(results
+            .into_iter()
+            .map(|result| result.content_id)
+            .collect())
+    }
+
+    fn search_error(result: Result<Vec<String>>) -> SearchError {
+        match result.unwrap_err().downcast::<SearchError>() {
+            Ok(e) => e,
+            Err(e) => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_with_embedding() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let manager = create_manager(dir.path()).await?;
+        let index = create_index(&manager).await?;
+
+        let query = SearchQuery::Embedding(Vector::Dense(vec![1.0, 0.0, 0.0]));
+        assert_eq!(search(&manager, &index, query, None).await?, vec!["a", "b"]);
+
+        let query = SearchQuery::Embedding(Vector::Dense(vec![1.0, 0.0]));
+        assert!(matches!(
+            search_error(search(&manager, &index, query, None).await),
+            SearchError::InvalidQuery(_)
+        ));
+
+        let query = SearchQuery::Embedding(Vector::Dense(vec![1.0, 0.0, 0.0]));
+        assert!(matches!(
+            search_error(search(&manager, &index, query, Some("tokens")).await),
+            SearchError::InvalidQuery(_)
+        ));
+
+        let query = SearchQuery::Embedding(Vector::Multi(vec![vec![1.0, 0.0, 0.0]]));
+        assert!(matches!(
+            search_error(search(&manager, &index, query, None).await),
+            SearchError::InvalidQuery(_)
+        ));
+
+        let query = SearchQuery::SparseEmbedding(SparseVector {
+            indices: vec![0],
+            values: vec![1.0],
+        });
+        assert!(matches!(
+            search_error(search(&manager, &index, query, None).await),
+            SearchError::InvalidQuery(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_with_content_id() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let manager = create_manager(dir.path()).await?;
+        let index = create_index(&manager).await?;
+
+        // The content itself is left out of its neighbours
+        let query = SearchQuery::ContentId("a".to_string());
+        assert_eq!(search(&manager, &index, query, None).await?, vec!["b", "c"]);
+
+        let query = SearchQuery::ContentId("unknown".to_string());
+        assert!(matches!(
+            search_error(search(&manager, &index, query, None).await),
+            SearchError::NotFound(_)
+        ));
+
+        let query = SearchQuery::ContentId("a".to_string());
+        assert!(matches!(
+            search_error(search(&manager, &index, query, Some("tokens")).await),
+            SearchError::InvalidQuery(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keyword_search() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let manager = create_manager(dir.path()).await?;
+        let index = create_index(&manager).await?;
+        manager
+            .add_embedding(
+                "idx",
+                vec![
+                    ExtractedEmbeddings {
+                        text: Some("the pump uses gasket AB-1234".to_string()),
+                        ..embedding("b", vec![0.9, 0.1, 0.0])
+                    },
+                    ExtractedEmbeddings {
+                        text: Some("the pump is red".to_string()),
+                        ..embedding("c", vec![0.0, 0.0, 1.0])
+                    },
+                ],
+            )
+            .await?;
+
+        let search = |query: SearchQuery| {
+            let options = SearchOptions {
+                query,
+                vector: None,
+                k: 2,
+                filter: LabelsFilter::default(),
+                include_content: false,
+                mode: api::SearchMode::Keyword,
+                rerank: None,
+            };
+            manager.search(index.clone(), options)
+        };
+        let results = search(SearchQuery::Text("pump".to_string())).await?;
+        let ids: Vec<&str> = results.iter().map(|r| r.content_id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b"]);
+
+        let query = SearchQuery::Embedding(Vector::Dense(vec![1.0, 0.0, 0.0]));
+        let error = search(query).await.unwrap_err().downcast::<SearchError>()?;
+        assert!(matches!(error, SearchError::InvalidQuery(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let result = |content_id: &str| SearchResult {
+            content_id: content_id.to_string(),
+            ..Default::default()
+        };
+        let vector = vec![result("a"), result("b"), result("c")];
+        let keyword = vec![result("c"), result("d")];
+        let fused = reciprocal_rank_fusion(vec![vector, keyword], 3);
+        let ids: Vec<&str> = fused.iter().map(|r| r.content_id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "a", "b"]);
+        assert!(fused[0].confidence_score > fused[1].confidence_score);
+    }
+
+    fn scored_text(content_id: &str, confidence_score: f32) -> ScoredText {
+        ScoredText {
+            text: format!("text of {}", content_id),
+            content_id: content_id.to_string(),
+            mime_type: mime::TEXT_PLAIN.to_string(),
+            labels: HashMap::new(),
+            confidence_score,
+            rerank_score: None,
+            root_content_metadata: None,
+            content_metadata: ContentMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn test_rerank() {
+        let rerank = api::Rerank {
+            extractor: "reranker".to_string(),
+            candidates: None,
+        };
+        assert_eq!(rerank_candidates(&rerank, 3), 3 * RERANK_CANDIDATES_FACTOR);
+        // Fewer candidates than results are never fetched
+        let rerank = api::Rerank {
+            candidates: Some(2),
+            ..rerank
+        };
+        assert_eq!(rerank_candidates(&rerank, 3), 3);
+
+        let candidates = || {
+            vec![
+                scored_text("a", 0.9),
+                scored_text("b", 0.8),
+                scored_text("c", 0.7),
+                scored_text("d", 0.6),
+            ]
+        };
+        let results = apply_rerank_scores(candidates(), vec![0.1, 0.5, 0.9, 0.3], 2, true);
+        let ids: Vec<&str> = results.iter().map(|r| r.content_id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b"]);
+        assert_eq!(results[0].rerank_score, Some(0.9));
+        assert_eq!(results[0].confidence_score, 0.7);
+        assert_eq!(results[0].text, "text of c");
+
+        // The text was only fetched for the reranker
+        let results = apply_rerank_scores(candidates(), vec![0.1, 0.5, 0.9, 0.3], 2, false);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.text.is_empty()));
+    }
+}
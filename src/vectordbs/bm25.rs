@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+// BM25 parameters, the usual defaults
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Splits text into lowercase alphanumeric terms. Part numbers such as
+/// `AB-1234` are kept whole in addition to their parts so exact lookups match.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        if word.is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = word
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.len() > 1 {
+            terms.extend(parts.iter().map(|part| part.to_string()));
+        }
+        terms.push(word);
+    }
+    terms
+}
+
+/// Ranks the documents of a search by BM25, for the databases that can't
+/// rank text themselves. Documents are added as they're read, only the
+/// counts of the query terms are kept for those containing any of them.
+pub struct Bm25<T> {
+    terms: Vec<String>,
+    num_docs: usize,
+    added_docs: usize,
+    total_len: usize,
+    matches: Vec<(T, usize, Vec<u32>)>,
+}
+
+impl<T> Bm25<T> {
+    pub fn new(query: &str) -> Self {
+        let mut seen = HashSet::new();
+        let terms = tokenize(query)
+            .into_iter()
+            .filter(|term| seen.insert(term.clone()))
+            .collect();
+        Self {
+            terms,
+            num_docs: 0,
+            added_docs: 0,
+            total_len: 0,
+            matches: Vec::new(),
+        }
+    }
+
+    /// Query terms made of letters and digits only, compound terms such as
+    /// part numbers are left out as their parts are query terms too.
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.terms
+            .iter()
+            .map(String::as_str)
+            .filter(|term| term.chars().all(char::is_alphanumeric))
+    }
+
+    /// Adds a document searched. Documents without any query term only count
+    /// towards the number of documents and their average length.
+    pub fn add(&mut self, document: T, text: &str) {
+        let document_terms = tokenize(text);
+        self.added_docs += 1;
+        self.total_len += document_terms.len();
+        let mut counts = vec![0; self.terms.len()];
+        for term in &document_terms {
+            if let Some(position) = self.terms.iter().position(|t| t == term) {
+                counts[position] += 1;
+            }
+        }
+        if counts.iter().any(|count| *count > 0) {
+            self.matches.push((document, document_terms.len(), counts));
+        }
+    }
+
+    /// Sets the number of documents searched when only those containing a
+    /// query term are added. Their average length then stands in for the
+    /// average length of all documents.
+    pub fn set_num_docs(&mut self, num_docs: usize) {
+        self.num_docs = num_docs;
+    }
+
+    /// The k best scored documents, highest score first. Ties keep the
+    /// order the documents were added in.
+    pub fn rank(self, k: usize) -> Vec<(T, f32)> {
+        let num_docs = self.num_docs.max(self.added_docs) as f32;
+        let avg_len = (self.total_len as f32 / self.added_docs.max(1) as f32).max(1.0);
+        let idfs: Vec<f32> = (0..self.terms.len())
+            .map(|term| {
+                let df = self
+                    .matches
+                    .iter()
+                    .filter(|(_, _, counts)| counts[term] > 0)
+                    .count() as f32;
+                ((num_docs - df + 0.5) / (df + 0.5) + 1.0).ln()
+            })
+            .collect();
+        let mut ranked: Vec<(T, f32)> = self
+            .matches
+            .into_iter()
+            .map(|(document, len, counts)| {
+                let norm = K1 * (1.0 - B + B * len as f32 / avg_len);
+                let score = counts
+                    .iter()
+                    .zip(&idfs)
+                    .map(|(&tf, idf)| {
+                        let tf = tf as f32;
+                        idf * tf * (K1 + 1.0) / (tf + norm)
+                    })
+                    .sum();
+                (document, score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(k);
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Replace part AB-1234, now!"),
+            vec!["replace", "part", "ab", "1234", "ab-1234", "now"]
+        );
+    }
+
+    #[test]
+    fn test_rank() {
+        let mut bm25 = Bm25::new("pump AB-1234");
+        assert_eq!(bm25.words().collect::<Vec<_>>(), vec!["pump", "ab", "1234"]);
+        bm25.add("1", "the pump uses gasket AB-1234");
+        bm25.add("2", "the pump is red");
+        bm25.add("3", "a valve and a hose");
+        let ranked = bm25.rank(10);
+        let ids: Vec<&str> = ranked.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        assert!(ranked[0].1 > ranked[1].1);
+
+        // Shorter documents rank higher for the same term counts
+        let mut bm25 = Bm25::new("pump");
+        bm25.add("1", "the pump uses gasket AB-1234");
+        bm25.add("2", "the pump is red");
+        let ids: Vec<&str> = bm25.rank(10).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec!["2", "1"]);
+
+        // Terms found in fewer of the documents searched weigh more
+        let mut bm25 = Bm25::new("pump valve");
+        bm25.add("1", "pump");
+        bm25.add("2", "valve");
+        bm25.set_num_docs(1000);
+        let ranked = bm25.rank(1);
+        assert_eq!(ranked.len(), 1);
+        assert!(ranked[0].1 > 6.0);
+    }
+}
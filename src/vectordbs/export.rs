@@ -0,0 +1,444 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, Write},
+    str::FromStr,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use arrow_array::{
+    types::Float32Type,
+    Array,
+    ArrayRef,
+    Float32Array,
+    ListArray,
+    RecordBatch,
+    StringArray,
+};
+use arrow_ipc::{reader::FileReader, writer::FileWriter};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use futures::TryStreamExt;
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+
+use super::{CreateIndexParams, IndexDistance, VectorChunk, VectorDBTS};
+
+/// Number of embeddings read from an export and written to the vector
+/// database at a time.
+const PAGE_SIZE: u64 = 1000;
+
+/// Number of embeddings in a Parquet row group, the writer buffers a row group
+/// in memory before writing it out.
+const ROW_GROUP_SIZE: usize = 10 * PAGE_SIZE as usize;
+
+const DIM_KEY: &str = "indexify.dim";
+const DISTANCE_KEY: &str = "indexify.distance";
+
+const PARQUET_MAGIC: &[u8] = b"PAR1";
+const ARROW_MAGIC: &[u8] = b"ARROW1";
+
+/// File format of an index export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Parquet,
+    /// Arrow IPC file
+    Arrow,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+            ExportFormat::Arrow => "application/vnd.apache.arrow.file",
+        }
+    }
+}
+
+fn export_schema(dim: Option<usize>, distance: Option<&IndexDistance>) -> Schema {
+    let mut metadata = HashMap::new();
+    if let Some(dim) = dim {
+        metadata.insert(DIM_KEY.to_string(), dim.to_string());
+    }
+    if let Some(distance) = distance {
+        metadata.insert(DISTANCE_KEY.to_string(), distance.to_string());
+    }
+    Schema::new_with_metadata(
+        vec![
+            Field::new("content_id", DataType::Utf8, false),
+            Field::new(
+                "embedding",
+                DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                false,
+            ),
+            Field::new("metadata", DataType::Utf8, false),
+            Field::new("content_metadata", DataType::Utf8, false),
+            Field::new("root_content_metadata", DataType::Utf8, true),
+            Field::new("vectors", DataType::Utf8, true),
+            Field::new("text", DataType::Utf8, true),
+        ],
+        metadata,
+    )
+}
+
+fn chunks_to_batch(schema: SchemaRef, chunks: &[VectorChunk]) -> Result<RecordBatch> {
+    let content_ids = StringArray::from_iter_values(chunks.iter().map(|c| c.content_id.as_str()));
+    let embeddings = ListArray::from_iter_primitive::<Float32Type, _, _>(
+        chunks
+            .iter()
+            .map(|c| Some(c.embedding.iter().map(|v| Some(*v)))),
+    );
+    let metadata = chunks
+        .iter()
+        .map(|c| serde_json::to_string(&c.metadata))
+        .collect::<Result<Vec<_>, _>>()?;
+    let content_metadata = chunks
+        .iter()
+        .map(|c| serde_json::to_string(&c.content_metadata))
+        .collect::<Result<Vec<_>, _>>()?;
+    let root_content_metadata = chunks
+        .iter()
+        .map(|c| {
+            c.root_content_metadata
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Named vectors are kept as JSON, they differ in shape from index to index
+    let vectors = chunks
+        .iter()
+        .map(|c| {
+            (!c.vectors.is_empty())
+                .then(|| serde_json::to_string(&c.vectors))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let texts = StringArray::from_iter(chunks.iter().map(|c| c.text.as_deref()));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(content_ids),
+        Arc::new(embeddings),
+        Arc::new(StringArray::from(metadata)),
+        Arc::new(StringArray::from(content_metadata)),
+        Arc::new(StringArray::from(root_content_metadata)),
+        Arc::new(StringArray::from(vectors)),
+        Arc::new(texts),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn column<'a, T: Array + 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| anyhow!("missing column {}", name))?
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| anyhow!("unexpected type for column {}", name))
+}
+
+fn batch_to_chunks(batch: &RecordBatch) -> Result<Vec<VectorChunk>> {
+    let content_ids = column::<StringArray>(batch, "content_id")?;
+    let embeddings = column::<ListArray>(batch, "embedding")?;
+    let metadata = column::<StringArray>(batch, "metadata")?;
+    let content_metadata = column::<StringArray>(batch, "content_metadata")?;
+    let root_content_metadata = column::<StringArray>(batch, "root_content_metadata")?;
+    let vectors = column::<StringArray>(batch, "vectors").ok();
+    let texts = column::<StringArray>(batch, "text").ok();
+    let mut chunks = Vec::with_capacity(batch.num_rows());
+    for i in 0..batch.num_rows() {
+        let embedding = embeddings.value(i);
+        let embedding = embedding
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .ok_or_else(|| anyhow!("embeddings must be lists of float32"))?;
+        chunks.push(VectorChunk {
+            content_id: content_ids.value(i).to_string(),
+            embedding: embedding.values().to_vec(),
+            vectors: match vectors {
+                Some(vectors) if !vectors.is_null(i) => serde_json::from_str(vectors.value(i))?,
+                _ => HashMap::new(),
+            },
+            sparse_embedding: None,
+            metadata: serde_json::from_str(metadata.value(i))?,
+            content_metadata: serde_json::from_str(content_metadata.value(i))?,
+            root_content_metadata: if root_content_metadata.is_null(i) {
+                None
+            } else {
+                Some(serde_json::from_str(root_content_metadata.value(i))?)
+            },
+            text: texts
+                .filter(|texts| !texts.is_null(i))
+                .map(|texts| texts.value(i).to_string()),
+        });
+    }
+    Ok(chunks)
+}
+
+enum BatchWriter<W: Write + Send> {
+    Parquet(ArrowWriter<W>),
+    Arrow(FileWriter<W>),
+}
+
+impl<W: Write + Send> BatchWriter<W> {
+    fn new(format: ExportFormat, writer: W, schema: SchemaRef) -> Result<Self> {
+        Ok(match format {
+            ExportFormat::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .set_max_row_group_size(ROW_GROUP_SIZE)
+                    .build();
+                BatchWriter::Parquet(ArrowWriter::try_new(writer, schema, Some(props))?)
+            }
+            ExportFormat::Arrow => BatchWriter::Arrow(FileWriter::try_new(writer, &schema)?),
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            BatchWriter::Parquet(writer) => writer.write(batch)?,
+            BatchWriter::Arrow(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            BatchWriter::Parquet(writer) => {
+                writer.close()?;
+            }
+            BatchWriter::Arrow(mut writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// Writes every embedding of a vector index, with its metadata and content
+/// metadata, to `writer`. Returns the number of embeddings exported.
+pub async fn export_index<W: Write + Send>(
+    vector_db: &VectorDBTS,
+    index: &str,
+    distance: Option<IndexDistance>,
+    format: ExportFormat,
+    writer: W,
+) -> Result<u64> {
+    let mut pages = vector_db.scan_points(index);
+    let first = pages.try_next().await?.unwrap_or_default();
+    let dim = first.first().map(|chunk| chunk.embedding.len());
+    let schema = Arc::new(export_schema(dim, distance.as_ref()));
+    let mut writer = BatchWriter::new(format, writer, schema.clone())?;
+    let mut exported = 0;
+    let mut page = Some(first);
+    while let Some(chunks) = page {
+        if !chunks.is_empty() {
+            writer.write(&chunks_to_batch(schema.clone(), &chunks)?)?;
+            exported += chunks.len() as u64;
+        }
+        page = pages.try_next().await?;
+    }
+    writer.finish()?;
+    Ok(exported)
+}
+
+/// An index export read back from Parquet or Arrow IPC.
+pub struct IndexImport {
+    pub dim: Option<usize>,
+    pub distance: Option<IndexDistance>,
+    batches: Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>> + Send>,
+}
+
+impl IndexImport {
+    /// Reads an index export, the format is detected from the file. Batches
+    /// are read from the file as they're consumed.
+    pub fn new(mut file: File) -> Result<Self> {
+        let mut magic = Vec::new();
+        (&mut file)
+            .take(PARQUET_MAGIC.len().max(ARROW_MAGIC.len()) as u64)
+            .read_to_end(&mut magic)?;
+        file.rewind()?;
+        let (schema, batches): (SchemaRef, Box<dyn Iterator<Item = _> + Send>) =
+            if magic.starts_with(PARQUET_MAGIC) {
+                let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+                let schema = builder.schema().clone();
+                let reader = builder.with_batch_size(PAGE_SIZE as usize).build()?;
+                (schema, Box::new(reader))
+            } else if magic.starts_with(ARROW_MAGIC) {
+                let reader = FileReader::try_new(BufReader::new(file), None)?;
+                (reader.schema(), Box::new(reader))
+            } else {
+                return Err(anyhow!(
+                    "unrecognized index export, expected a parquet or arrow ipc file"
+                ));
+            };
+        let dim = schema
+            .metadata()
+            .get(DIM_KEY)
+            .map(|dim| dim.parse())
+            .transpose()?;
+        let distance = schema
+            .metadata()
+            .get(DISTANCE_KEY)
+            .map(|distance| IndexDistance::from_str(distance))
+            .transpose()?;
+        Ok(Self {
+            dim,
+            distance,
+            batches,
+        })
+    }
+
+    /// Parameters to create an index the export can be loaded into.
+    /// `distance` overrides the distance recorded in the export.
+    pub fn create_index_params(
+        &self,
+        index: &str,
+        distance: Option<IndexDistance>,
+    ) -> Result<CreateIndexParams> {
+        let dim = self
+            .dim
+            .ok_or_else(|| anyhow!("the export does not record the embedding dimension"))?;
+        let distance = distance
+            .or_else(|| self.distance.clone())
+            .ok_or_else(|| anyhow!("the export does not record the index distance"))?;
+        Ok(CreateIndexParams {
+            vectordb_index_name: index.to_string(),
+            vector_dim: dim as u64,
+            distance,
+            unique_params: None,
+            vectors: HashMap::new(),
+            sparse: false,
+        })
+    }
+
+    /// Reads the export a batch of embeddings at a time.
+    pub fn chunks(self) -> impl Iterator<Item = Result<Vec<VectorChunk>>> + Send {
+        self.batches
+            .map(|batch| batch_to_chunks(&batch?))
+            .filter(|chunks| !matches!(chunks, Ok(batch) if batch.is_empty()))
+    }
+
+    /// Bulk loads the export into an existing index. Returns the number of
+    /// embeddings imported.
+    pub async fn load(self, vector_db: &VectorDBTS, index: &str) -> Result<u64> {
+        let mut imported = 0;
+        for chunks in self.chunks() {
+            let chunks = chunks?;
+            imported += chunks.len() as u64;
+            vector_db.add_embedding(index, chunks).await?;
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::db_utils::{create_metadata, test_mock_content_metadata},
+        vectordbs::hnsw::Hnsw,
+    };
+
+    async fn create_index(path: &std::path::Path, index: &str) -> VectorDBTS {
+        let vector_db: VectorDBTS = Arc::new(
+            Hnsw::new(&crate::server_config::HnswConfig {
+                path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index.to_string(),
+                vector_dim: 2,
+                distance: IndexDistance::Dot,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        vector_db
+    }
+
+    async fn roundtrip(format: ExportFormat) {
+        let dir = tempfile::tempdir().unwrap();
+        let source = create_index(&dir.path().join("source"), "source-index").await;
+        let chunks: Vec<VectorChunk> = (0..2500)
+            .map(|i| {
+                let content_id = format!("{:x}", i + 1);
+                VectorChunk {
+                    content_id: content_id.clone(),
+                    embedding: vec![i as f32, 1.],
+                    vectors: HashMap::new(),
+                    sparse_embedding: None,
+                    metadata: create_metadata(vec![("key", &i.to_string())]),
+                    root_content_metadata: (i % 2 == 0)
+                        .then(|| test_mock_content_metadata("root", "", "graph1")),
+                    content_metadata: test_mock_content_metadata(&content_id, "root", "graph1"),
+                    text: (i % 3 == 0).then(|| format!("text {}", i)),
+                }
+            })
+            .collect();
+        source
+            .add_embedding("source-index", chunks.clone())
+            .await
+            .unwrap();
+
+        let mut file = tempfile::tempfile().unwrap();
+        let exported = export_index(
+            &source,
+            "source-index",
+            Some(IndexDistance::Dot),
+            format,
+            &mut file,
+        )
+        .await
+        .unwrap();
+        assert_eq!(exported, 2500);
+        file.rewind().unwrap();
+
+        let import = IndexImport::new(file).unwrap();
+        let params = import.create_index_params("target-index", None).unwrap();
+        assert_eq!(params.vector_dim, 2);
+        assert_eq!(params.distance.to_string(), "dot");
+        let target = create_index(&dir.path().join("target"), "target-index").await;
+        assert_eq!(import.load(&target, "target-index").await.unwrap(), 2500);
+        assert_eq!(target.num_vectors("target-index").await.unwrap(), 2500);
+
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let mut imported = target.get_points("target-index", ids).await.unwrap();
+        imported.sort_by(|a, b| a.content_id.cmp(&b.content_id));
+        assert_eq!(imported[0].embedding, vec![9., 1.]);
+        assert_eq!(imported[0].metadata, chunks[9].metadata);
+        assert_eq!(imported[0].content_metadata.id.id, "a");
+        assert!(imported[0].root_content_metadata.is_none());
+        assert_eq!(imported[0].text.as_deref(), Some("text 9"));
+        assert!(imported[1].text.is_none());
+        assert_eq!(
+            imported[1].root_content_metadata.as_ref().unwrap().id.id,
+            "root"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parquet_roundtrip() {
+        roundtrip(ExportFormat::Parquet).await;
+    }
+
+    #[tokio::test]
+    async fn test_arrow_roundtrip() {
+        roundtrip(ExportFormat::Arrow).await;
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"content_id,embedding").unwrap();
+        file.rewind().unwrap();
+        assert!(IndexImport::new(file).is_err());
+    }
+}
@@ -0,0 +1,911 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use indexify_internal_api::{SparseVector, Vector};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::RwLock,
+};
+use tracing::warn;
+
+use super::{bm25::Bm25, CreateIndexParams, IndexDistance, SearchResult, VectorChunk, VectorDb};
+use crate::server_config::HnswConfig;
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    chunk: VectorChunk,
+    // Removed and replaced points stay in the graph to keep it navigable
+    // until the graph is compacted
+    deleted: bool,
+    // Neighbours on every layer the node is part of, from layer 0 up
+    neighbours: Vec<Vec<usize>>,
+}
+
+/// A Hierarchical Navigable Small World graph over the points of one index.
+#[derive(Serialize, Deserialize)]
+struct Graph {
+    dim: usize,
+    distance: IndexDistance,
+    m: usize,
+    ef_construction: usize,
+    nodes: Vec<Node>,
+    ids: HashMap<String, usize>,
+    entry_point: Option<usize>,
+}
+
+impl Graph {
+    fn new(dim: usize, distance: IndexDistance, m: usize, ef_construction: usize) -> Self {
+        Self {
+            dim,
+            distance,
+            m,
+            ef_construction,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry_point: None,
+        }
+    }
+
+    /// Distance between two vectors, lower is closer.
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.distance {
+            IndexDistance::Cosine => {
+                let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+                for (x, y) in a.iter().zip(b) {
+                    dot += x * y;
+                    norm_a += x * x;
+                    norm_b += y * y;
+                }
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    return 1.0;
+                }
+                1.0 - dot / (norm_a.sqrt() * norm_b.sqrt())
+            }
+            IndexDistance::Dot => -a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+            IndexDistance::Euclidean => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+
+    fn distance_to(&self, query: &[f32], node: usize) -> f32 {
+        self.distance(query, &self.nodes[node].chunk.embedding)
+    }
+
+    fn level(&self, node: usize) -> usize {
+        self.nodes[node].neighbours.len() - 1
+    }
+
+    fn random_level(&self) -> usize {
+        let ml = 1.0 / (self.m as f64).ln();
+        let uniform: f64 = rand::thread_rng().gen_range(f64::EPSILON..1.0);
+        (-uniform.ln() * ml).floor() as usize
+    }
+
+    /// Returns up to `ef` nodes closest to the query on the layer, closest
+    /// first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut results = BinaryHeap::new();
+        for &node in entry_points {
+            let candidate = Candidate {
+                distance: self.distance_to(query, node),
+                node,
+            };
+            candidates.push(Reverse(candidate));
+            results.push(candidate);
+        }
+        while let Some(Reverse(closest)) = candidates.pop() {
+            let furthest = results.peek().map(|c: &Candidate| c.distance);
+            if results.len() >= ef && furthest.is_some_and(|furthest| closest.distance > furthest) {
+                break;
+            }
+            for &neighbour in &self.nodes[closest.node].neighbours[layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let candidate = Candidate {
+                    distance: self.distance_to(query, neighbour),
+                    node: neighbour,
+                };
+                let furthest = results.peek().map(|c: &Candidate| c.distance);
+                if results.len() < ef || furthest.is_some_and(|f| candidate.distance < f) {
+                    candidates.push(Reverse(candidate));
+                    results.push(candidate);
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+        results.into_sorted_vec()
+    }
+
+    fn check_dim(&self, embedding: &[f32]) -> Result<()> {
+        if embedding.len() != self.dim {
+            return Err(anyhow!(
+                "embedding has {} dimensions, expected {}",
+                embedding.len(),
+                self.dim
+            ));
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, chunk: VectorChunk) -> Result<()> {
+        self.check_dim(&chunk.embedding)?;
+        self.remove(&chunk.content_id);
+
+        let level = self.random_level();
+        let node = self.nodes.len();
+        let query = chunk.embedding.clone();
+        self.ids.insert(chunk.content_id.clone(), node);
+        self.nodes.push(Node {
+            chunk,
+            deleted: false,
+            neighbours: vec![Vec::new(); level + 1],
+        });
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(node);
+            return Ok(());
+        };
+
+        let top_level = self.level(entry_point);
+        let mut entry_points = vec![entry_point];
+        for layer in (level + 1..=top_level).rev() {
+            entry_points = vec![self.search_layer(&query, &entry_points, 1, layer)[0].node];
+        }
+        for layer in (0..=level.min(top_level)).rev() {
+            let found = self.search_layer(&query, &entry_points, self.ef_construction, layer);
+            let max_neighbours = if layer == 0 { 2 * self.m } else { self.m };
+            let neighbours: Vec<usize> = found.iter().take(self.m).map(|c| c.node).collect();
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].neighbours[layer].push(node);
+                if self.nodes[neighbour].neighbours[layer].len() > max_neighbours {
+                    self.prune(neighbour, layer, max_neighbours);
+                }
+            }
+            self.nodes[node].neighbours[layer] = neighbours;
+            entry_points = found.iter().map(|c| c.node).collect();
+        }
+        if level > top_level {
+            self.entry_point = Some(node);
+        }
+        Ok(())
+    }
+
+    // Keeps the closest neighbours of the node on the layer
+    fn prune(&mut self, node: usize, layer: usize, max_neighbours: usize) {
+        let embedding = &self.nodes[node].chunk.embedding;
+        let mut neighbours: Vec<Candidate> = self.nodes[node].neighbours[layer]
+            .iter()
+            .map(|&neighbour| Candidate {
+                distance: self.distance(embedding, &self.nodes[neighbour].chunk.embedding),
+                node: neighbour,
+            })
+            .collect();
+        neighbours.sort();
+        self.nodes[node].neighbours[layer] = neighbours
+            .into_iter()
+            .take(max_neighbours)
+            .map(|c| c.node)
+            .collect();
+    }
+
+    fn remove(&mut self, content_id: &str) {
+        if let Some(node) = self.ids.remove(content_id) {
+            self.nodes[node].deleted = true;
+        }
+    }
+
+    fn update_metadata(&mut self, content_id: &str, metadata: HashMap<String, serde_json::Value>) {
+        if let Some(&node) = self.ids.get(content_id) {
+            self.nodes[node].chunk.metadata = metadata;
+        }
+    }
+
+    fn apply(&mut self, entry: LogEntry) -> Result<()> {
+        match entry {
+            LogEntry::Insert(chunk) => self.insert(chunk)?,
+            LogEntry::Remove(content_id) => self.remove(&content_id),
+            LogEntry::UpdateMetadata {
+                content_id,
+                metadata,
+            } => self.update_metadata(&content_id, metadata),
+        }
+        self.compact()
+    }
+
+    /// Rebuilds the graph without the deleted nodes once they make up most
+    /// of it.
+    fn compact(&mut self) -> Result<()> {
+        if self.nodes.len() < 2 * self.ids.len() + self.m {
+            return Ok(());
+        }
+        let nodes = std::mem::take(&mut self.nodes);
+        self.ids.clear();
+        self.entry_point = None;
+        for node in nodes.into_iter().filter(|node| !node.deleted) {
+            self.insert(node.chunk)?;
+        }
+        Ok(())
+    }
+
+    fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef_search: usize,
+        filter: &filter::LabelsFilter,
+    ) -> Vec<Candidate> {
+        let Some(entry_point) = self.entry_point else {
+            return vec![];
+        };
+        let matches = |node: usize| {
+            let node = &self.nodes[node];
+            !node.deleted && (filter.is_empty() || filter.matches(&node.chunk.metadata))
+        };
+        let mut entry_points = vec![entry_point];
+        for layer in (1..=self.level(entry_point)).rev() {
+            entry_points = vec![self.search_layer(query, &entry_points, 1, layer)[0].node];
+        }
+        let mut results: Vec<Candidate> = self
+            .search_layer(query, &entry_points, ef_search.max(k), 0)
+            .into_iter()
+            .filter(|c| matches(c.node))
+            .take(k)
+            .collect();
+        // Selective filters can leave too few matches among the visited
+        // nodes, fall back to an exact scan
+        if results.len() < k {
+            results = self
+                .ids
+                .values()
+                .filter(|&&node| matches(node))
+                .map(|&node| Candidate {
+                    distance: self.distance_to(query, node),
+                    node,
+                })
+                .collect();
+            results.sort();
+            results.truncate(k);
+        }
+        results
+    }
+}
+
+/// Changes to a graph since its last snapshot.
+#[derive(Serialize, Deserialize)]
+enum LogEntry {
+    Insert(VectorChunk),
+    Remove(String),
+    UpdateMetadata {
+        content_id: String,
+        metadata: HashMap<String, serde_json::Value>,
+    },
+}
+
+/// Logs are replaced by a snapshot once they have more entries than half the
+/// points of the graph, and at least this many. Snapshots are written less
+/// often as the graph grows, so their cost per change stays constant.
+const MIN_SNAPSHOT_LOG_ENTRIES: usize = 1000;
+
+struct Index {
+    graph: Graph,
+    log_entries: usize,
+}
+
+/// In-process vector index for single node deployments. Every index is an
+/// HNSW graph held in memory. Changes are appended to a log under the
+/// configured path, which is replayed on top of the last snapshot of the
+/// graph when the index is loaded. Search results are scored with the
+/// distance to the query, lower is closer.
+pub struct Hnsw {
+    path: PathBuf,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    indexes: RwLock<HashMap<String, Index>>,
+}
+
+impl fmt::Debug for Hnsw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hnsw").field("path", &self.path).finish()
+    }
+}
+
+impl Hnsw {
+    pub async fn new(config: &HnswConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        tokio::fs::create_dir_all(&path)
+            .await
+            .map_err(|e| anyhow!("unable to create hnsw directory {}: {}", config.path, e))?;
+        let mut indexes = HashMap::new();
+        let mut entries = tokio::fs::read_dir(&path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file = entry.path();
+            if file.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(name) = file.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let bytes = tokio::fs::read(&file).await?;
+            let graph: Graph = serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow!("unable to load hnsw index {}: {}", file.display(), e))?;
+            indexes.insert(
+                name.to_string(),
+                Index {
+                    graph,
+                    log_entries: 0,
+                },
+            );
+        }
+        let hnsw = Self {
+            path,
+            m: config.m,
+            ef_construction: config.ef_construction,
+            ef_search: config.ef_search,
+            indexes: RwLock::new(HashMap::new()),
+        };
+        for (name, index) in indexes.iter_mut() {
+            if hnsw.replay(name, &mut index.graph).await? {
+                hnsw.snapshot(name, index).await?;
+            }
+        }
+        *hnsw.indexes.write().await = indexes;
+        Ok(hnsw)
+    }
+
+    fn index_file(&self, index: &str) -> PathBuf {
+        self.path.join(format!("{}.json", index))
+    }
+
+    fn log_file(&self, index: &str) -> PathBuf {
+        self.path.join(format!("{}.log", index))
+    }
+
+    /// Applies the changes logged since the snapshot, returns whether there
+    /// were any. A partially written last entry left by a crash is skipped.
+    async fn replay(&self, index: &str, graph: &mut Graph) -> Result<bool> {
+        let file = match tokio::fs::File::open(self.log_file(index)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(anyhow!("unable to read hnsw log {}: {}", index, e)),
+        };
+        let mut lines = BufReader::new(file).lines();
+        while let Some(line) = lines.next_line().await? {
+            match serde_json::from_str::<LogEntry>(&line) {
+                Ok(entry) => graph.apply(entry)?,
+                Err(e) => {
+                    warn!("skipping unreadable entry of hnsw log {}: {}", index, e);
+                    break;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Writes the graph and truncates its log. Replaying a log over a
+    /// snapshot that already has its changes is harmless, so a crash in
+    /// between loses nothing.
+    async fn snapshot(&self, name: &str, index: &mut Index) -> Result<()> {
+        let file = self.index_file(name);
+        let tmp_file = file.with_extension("json.tmp");
+        let bytes = serde_json::to_vec(&index.graph)?;
+        tokio::fs::write(&tmp_file, bytes).await?;
+        tokio::fs::rename(&tmp_file, &file)
+            .await
+            .map_err(|e| anyhow!("unable to save hnsw index {}: {}", name, e))?;
+        match tokio::fs::remove_file(self.log_file(name)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(anyhow!("unable to truncate hnsw log {}: {}", name, e));
+            }
+            _ => {}
+        }
+        index.log_entries = 0;
+        Ok(())
+    }
+
+    /// Logs the changes and then applies them to the graph.
+    async fn write(&self, name: &str, index: &mut Index, entries: Vec<LogEntry>) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut lines = Vec::new();
+        for entry in &entries {
+            serde_json::to_writer(&mut lines, entry)?;
+            lines.push(b'\n');
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_file(name))
+            .await?;
+        file.write_all(&lines)
+            .await
+            .map_err(|e| anyhow!("unable to write hnsw log {}: {}", name, e))?;
+        index.log_entries += entries.len();
+        for entry in entries {
+            index.graph.apply(entry)?;
+        }
+        if index.log_entries > (index.graph.ids.len() / 2).max(MIN_SNAPSHOT_LOG_ENTRIES) {
+            self.snapshot(name, index).await?;
+        }
+        Ok(())
+    }
+}
+
+fn index_mut<'a>(indexes: &'a mut HashMap<String, Index>, index: &str) -> Result<&'a mut Index> {
+    indexes
+        .get_mut(index)
+        .ok_or(anyhow!("index {} not found", index))
+}
+
+fn graph<'a>(indexes: &'a HashMap<String, Index>, index: &str) -> Result<&'a Graph> {
+    indexes
+        .get(index)
+        .map(|index| &index.graph)
+        .ok_or(anyhow!("index {} not found", index))
+}
+
+fn check_index_name(index: &str) -> Result<()> {
+    if index.is_empty() || Path::new(index).file_name() != Some(OsStr::new(index)) {
+        return Err(anyhow!("invalid index name {}", index));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl VectorDb for Hnsw {
+    fn name(&self) -> String {
+        "hnsw".into()
+    }
+
+    #[tracing::instrument]
+    async fn create_index(&self, index: CreateIndexParams) -> Result<()> {
+        check_index_name(&index.vectordb_index_name)?;
+        if !index.vectors.is_empty() {
+            return Err(anyhow!(
+                "named vectors are not supported by {}",
+                self.name()
+            ));
+        }
+        if index.sparse {
+            return Err(anyhow!(
+                "sparse indexes are not supported by {}",
+                self.name()
+            ));
+        }
+        let mut indexes = self.indexes.write().await;
+        if indexes.contains_key(&index.vectordb_index_name) {
+            return Ok(());
+        }
+        let mut new_index = Index {
+            graph: Graph::new(
+                index.vector_dim as usize,
+                index.distance,
+                self.m,
+                self.ef_construction,
+            ),
+            log_entries: 0,
+        };
+        self.snapshot(&index.vectordb_index_name, &mut new_index)
+            .await?;
+        indexes.insert(index.vectordb_index_name, new_index);
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn add_embedding(&self, index: &str, chunks: Vec<VectorChunk>) -> Result<()> {
+        let mut indexes = self.indexes.write().await;
+        let hnsw_index = index_mut(&mut indexes, index)?;
+        for chunk in &chunks {
+            hnsw_index.graph.check_dim(&chunk.embedding)?;
+        }
+        let entries = chunks.into_iter().map(LogEntry::Insert).collect();
+        self.write(index, hnsw_index, entries).await
+    }
+
+    #[tracing::instrument]
+    async fn remove_embedding(&self, index: &str, content_id: &str) -> Result<()> {
+        let mut indexes = self.indexes.write().await;
+        let hnsw_index = index_mut(&mut indexes, index)?;
+        if !hnsw_index.graph.ids.contains_key(content_id) {
+            return Ok(());
+        }
+        let entries = vec![LogEntry::Remove(content_id.to_string())];
+        self.write(index, hnsw_index, entries).await
+    }
+
+    #[tracing::instrument]
+    async fn get_points(&self, index: &str, content_ids: Vec<String>) -> Result<Vec<VectorChunk>> {
+        let indexes = self.indexes.read().await;
+        let graph = graph(&indexes, index)?;
+        Ok(content_ids
+            .iter()
+            .filter_map(|content_id| graph.ids.get(content_id))
+            .map(|&node| graph.nodes[node].chunk.clone())
+            .collect())
+    }
+
+    async fn list_points(
+        &self,
+        index: &str,
+        after: Option<String>,
+        limit: u64,
+    ) -> Result<Vec<VectorChunk>> {
+        let indexes = self.indexes.read().await;
+        let graph = graph(&indexes, index)?;
+        let mut content_ids: Vec<&String> = graph
+            .ids
+            .keys()
+            .filter(|content_id| after.as_ref().map_or(true, |after| *content_id > after))
+            .collect();
+        content_ids.sort();
+        Ok(content_ids
+            .into_iter()
+            .take(limit as usize)
+            .map(|content_id| graph.nodes[graph.ids[content_id]].chunk.clone())
+            .collect())
+    }
+
+    #[tracing::instrument]
+    async fn update_metadata(
+        &self,
+        index: &str,
+        content_id: String,
+        metadata: HashMap<String, serde_json::Value>,
+    ) -> Result<()> {
+        let mut indexes = self.indexes.write().await;
+        let hnsw_index = index_mut(&mut indexes, index)?;
+        if !hnsw_index.graph.ids.contains_key(&content_id) {
+            return Ok(());
+        }
+        let entries = vec![LogEntry::UpdateMetadata {
+            content_id,
+            metadata,
+        }];
+        self.write(index, hnsw_index, entries).await
+    }
+
+    #[tracing::instrument]
+    async fn search(
+        &self,
+        index: String,
+        query_embedding: Vec<f32>,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let indexes = self.indexes.read().await;
+        let graph = graph(&indexes, &index)?;
+        if query_embedding.len() != graph.dim {
+            return Err(anyhow!(
+                "query embedding has {} dimensions, expected {}",
+                query_embedding.len(),
+                graph.dim
+            ));
+        }
+        Ok(graph
+            .search(&query_embedding, k as usize, self.ef_search, &filter)
+            .into_iter()
+            .map(|candidate| {
+                let chunk = graph.nodes[candidate.node].chunk.clone();
+                SearchResult {
+                    content_id: chunk.content_id,
+                    confidence_score: candidate.distance,
+                    metadata: chunk.metadata,
+                    root_content_metadata: chunk.root_content_metadata,
+                    content_metadata: chunk.content_metadata,
+                }
+            })
+            .collect())
+    }
+
+    async fn search_vector(
+        &self,
+        _index: String,
+        _vector: &str,
+        _query: Vector,
+        _k: u64,
+        _filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        Err(anyhow!(
+            "named vectors are not supported by {}",
+            self.name()
+        ))
+    }
+
+    async fn search_sparse(
+        &self,
+        _index: String,
+        _query: SparseVector,
+        _k: u64,
+        _filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        Err(anyhow!(
+            "sparse indexes are not supported by {}",
+            self.name()
+        ))
+    }
+
+    // The text of every point matching the filter is scanned
+    #[tracing::instrument]
+    async fn keyword_search(
+        &self,
+        index: String,
+        query: &str,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let indexes = self.indexes.read().await;
+        let graph = graph(&indexes, &index)?;
+        let mut bm25 = Bm25::new(query);
+        for &node in graph.ids.values() {
+            let chunk = &graph.nodes[node].chunk;
+            let Some(text) = &chunk.text else {
+                continue;
+            };
+            if filter.is_empty() || filter.matches(&chunk.metadata) {
+                bm25.add(chunk, text);
+            }
+        }
+        Ok(bm25
+            .rank(k as usize)
+            .into_iter()
+            .map(|(chunk, score)| SearchResult {
+                content_id: chunk.content_id.clone(),
+                confidence_score: score,
+                metadata: chunk.metadata.clone(),
+                root_content_metadata: chunk.root_content_metadata.clone(),
+                content_metadata: chunk.content_metadata.clone(),
+            })
+            .collect())
+    }
+
+    #[tracing::instrument]
+    async fn drop_index(&self, index: &str) -> Result<()> {
+        let mut indexes = self.indexes.write().await;
+        indexes.remove(index);
+        for file in [self.index_file(index), self.log_file(index)] {
+            match tokio::fs::remove_file(file).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(anyhow!("unable to drop index: {}", e));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn num_vectors(&self, index: &str) -> Result<u64> {
+        let indexes = self.indexes.read().await;
+        let graph = graph(&indexes, index)?;
+        Ok(graph.ids.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        test_util::db_utils::test_mock_content_metadata,
+        vectordbs::{
+            tests::{
+                basic_search,
+                crud_operations,
+                insertion_idempotent,
+                keyword_search,
+                list_points,
+                search_filter_expressions,
+                search_filters,
+                search_nested_filters,
+                store_metadata,
+            },
+            VectorDBTS,
+        },
+    };
+
+    async fn create_hnsw(path: &Path, index_name: &str) -> VectorDBTS {
+        let hnsw: VectorDBTS = Arc::new(
+            Hnsw::new(&HnswConfig {
+                path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        hnsw.create_index(CreateIndexParams {
+            vectordb_index_name: index_name.into(),
+            vector_dim: 2,
+            distance: IndexDistance::Cosine,
+            unique_params: None,
+            vectors: HashMap::new(),
+            sparse: false,
+        })
+        .await
+        .unwrap();
+        hnsw
+    }
+
+    #[tokio::test]
+    async fn test_search_basic() {
+        let dir = tempfile::tempdir().unwrap();
+        basic_search(create_hnsw(dir.path(), "hello-index").await, "hello-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_store_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        store_metadata(
+            create_hnsw(dir.path(), "metadata-index").await,
+            "metadata-index",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_insertion_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        insertion_idempotent(
+            create_hnsw(dir.path(), "idempotent-index").await,
+            "idempotent-index",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_deletion() {
+        let dir = tempfile::tempdir().unwrap();
+        crud_operations(create_hnsw(dir.path(), "crud-index").await, "crud-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_search_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        search_filters(
+            create_hnsw(dir.path(), "filters-index").await,
+            "filters-index",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_persistence_and_recall() {
+        let dir = tempfile::tempdir().unwrap();
+        let index_name = "recall-index";
+        let hnsw = create_hnsw(dir.path(), index_name).await;
+        let chunks: Vec<VectorChunk> = (0..500)
+            .map(|i| {
+                let angle = i as f32 * 0.01;
+                let content_id = i.to_string();
+                let content_metadata = test_mock_content_metadata(&content_id, "1", "graph1");
+                VectorChunk::new(
+                    content_id,
+                    vec![angle.cos(), angle.sin()],
+                    HashMap::new(),
+                    None,
+                    &content_metadata,
+                )
+            })
+            .collect();
+        hnsw.add_embedding(index_name, chunks).await.unwrap();
+        for i in (0..500).step_by(50) {
+            hnsw.remove_embedding(index_name, &i.to_string())
+                .await
+                .unwrap();
+        }
+        let metadata = HashMap::from([("color".to_string(), serde_json::json!("red"))]);
+        hnsw.update_metadata(index_name, "123".to_string(), metadata.clone())
+            .await
+            .unwrap();
+
+        // Reopened from the log written by the first instance, and then from
+        // the snapshot the log was replaced with
+        for _ in 0..2 {
+            let hnsw = create_hnsw(dir.path(), index_name).await;
+            assert_eq!(hnsw.num_vectors(index_name).await.unwrap(), 490);
+            let points = hnsw
+                .get_points(index_name, vec!["123".to_string()])
+                .await
+                .unwrap();
+            assert_eq!(points[0].metadata, metadata);
+        }
+        assert!(!dir.path().join(format!("{}.log", index_name)).exists());
+        let hnsw = create_hnsw(dir.path(), index_name).await;
+        let angle = 1.23f32;
+        let results = hnsw
+            .search(
+                index_name.to_string(),
+                vec![angle.cos(), angle.sin()],
+                3,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.content_id.as_str()).collect();
+        assert_eq!(ids[0], "123");
+        assert!(ids.contains(&"122") && ids.contains(&"124"));
+    }
+
+    #[tokio::test]
+    async fn test_search_filter_expressions() {
+        let dir = tempfile::tempdir().unwrap();
+        search_filter_expressions(
+            create_hnsw(dir.path(), "expressions-index").await,
+            "expressions-index",
+        )
+        .await;
+        search_nested_filters(
+            create_hnsw(dir.path(), "nested-index").await,
+            "nested-index",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_list_points() {
+        let dir = tempfile::tempdir().unwrap();
+        list_points(create_hnsw(dir.path(), "list-index").await, "list-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_keyword_search() {
+        let dir = tempfile::tempdir().unwrap();
+        keyword_search(
+            create_hnsw(dir.path(), "keyword-index").await,
+            "keyword-index",
+        )
+        .await;
+    }
+}
@@ -1,16 +1,19 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Debug},
     sync::Arc,
 };
 
 use anyhow::{anyhow, Result};
 use arrow_array::{
+    builder::{FixedSizeListBuilder, Float32Builder, ListBuilder},
     cast::{as_boolean_array, as_string_array},
     types::{self, Float32Type},
     Array,
+    ArrayRef,
     BooleanArray,
     FixedSizeListArray,
+    ListArray,
     PrimitiveArray,
     RecordBatch,
     RecordBatchIterator,
@@ -19,42 +22,124 @@ use arrow_array::{
 use arrow_schema::{DataType, Field, Schema, SchemaRef};
 use async_trait::async_trait;
 use filter::Operator;
-use futures::{StreamExt, TryStreamExt};
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use indexify_internal_api::{SparseVector, Vector};
 use itertools::izip;
 use lance::dataset::{BatchUDF, WriteParams};
 use lancedb::{
-    query::{ExecutableQuery, QueryBase},
+    query::{ExecutableQuery, QueryBase, Select},
     table::{NewColumnTransform, WriteOptions},
     Connection,
     Table,
 };
 use tracing;
 
-use super::{CreateIndexParams, SearchResult, VectorChunk, VectorDb};
+use super::{
+    bm25::Bm25,
+    max_sim,
+    CreateIndexParams,
+    IndexDistance,
+    SearchResult,
+    VectorChunk,
+    VectorDb,
+    VectorParams,
+};
 use crate::server_config::LancedbConfig;
 
-fn from_filter_to_str(filter: &filter::LabelsFilter) -> String {
-    filter
-        .expressions()
+/// Named vectors are stored in columns with this prefix
+const VECTOR_COLUMN_PREFIX: &str = "vector_";
+
+/// Field metadata key holding the distance of a named vector column
+const DISTANCE_KEY: &str = "distance";
+
+/// Column of the text stored for keyword searches
+const TEXT_COLUMN: &str = "content_text";
+
+fn from_filter_to_str(filter: &filter::LabelsFilter) -> Result<String> {
+    Ok(filter
+        .filters()
         .iter()
-        .map(|f| {
-            let value = from_filter_value_to_str(&f.value);
-            match f.operator {
-                Operator::Eq => format!("{} = {}", f.key, value),
-                Operator::Neq => format!("{} != {}", f.key, value),
-                Operator::Gt => format!("{} > {}", f.key, value),
-                Operator::Lt => format!("{} < {}", f.key, value),
-                Operator::GtEq => format!("{} >= {}", f.key, value),
-                Operator::LtEq => format!("{} <= {}", f.key, value),
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" AND ")
+        .map(from_filter_tree_to_str)
+        .collect::<Result<Vec<_>>>()?
+        .join(" AND "))
+}
+
+fn from_filter_tree_to_str(filter: &filter::Filter) -> Result<String> {
+    let join = |filters: &[filter::Filter], separator: &str| -> Result<String> {
+        let filters = filters
+            .iter()
+            .map(from_filter_tree_to_str)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("({})", filters.join(separator)))
+    };
+    match filter {
+        filter::Filter::Expression(expression) => from_expression_to_str(expression),
+        filter::Filter::And(filters) => join(filters, " AND "),
+        filter::Filter::Or(filters) => join(filters, " OR "),
+        // Comparisons with missing labels are NULL rather than false, so a
+        // plain NOT would drop those rows too
+        filter::Filter::Not(filter) => Ok(format!(
+            "({}) IS NOT TRUE",
+            from_filter_tree_to_str(filter)?
+        )),
+    }
+}
+
+/// Keys are written into the predicate as column names, anything but
+/// letters, digits, underscores and dots for nested fields is rejected
+fn column_name(key: &str) -> Result<&str> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    if key.is_empty() || !key.chars().all(valid) {
+        return Err(anyhow!("invalid filter key: {}", key));
+    }
+    Ok(key)
+}
+
+fn from_expression_to_str(f: &filter::Expression) -> Result<String> {
+    let key = column_name(&f.key)?;
+    let value = from_filter_value_to_str(&f.value);
+    let values = || {
+        f.value
+            .as_array()
+            .map(|values| {
+                values
+                    .iter()
+                    .map(from_filter_value_to_str)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .join(", ")
+    };
+    let pattern = |prefix: &str| {
+        let text = match &f.value {
+            serde_json::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        };
+        let text = text
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+            .replace('\'', "''");
+        format!("'{prefix}{text}%'")
+    };
+    Ok(match f.operator {
+        Operator::Eq => format!("{} = {}", key, value),
+        Operator::Neq => format!("{} != {}", key, value),
+        Operator::Gt => format!("{} > {}", key, value),
+        Operator::Lt => format!("{} < {}", key, value),
+        Operator::GtEq => format!("{} >= {}", key, value),
+        Operator::LtEq => format!("{} <= {}", key, value),
+        Operator::In => format!("{} IN ({})", key, values()),
+        Operator::NotIn => format!("{} NOT IN ({})", key, values()),
+        Operator::Exists => format!("{} IS NOT NULL", key),
+        Operator::StartsWith => format!("{} LIKE {}", key, pattern("")),
+        Operator::Contains => format!("{} LIKE {}", key, pattern("%")),
+    })
 }
 
 fn from_filter_value_to_str(value: &serde_json::Value) -> String {
     match value {
-        serde_json::Value::String(s) => format!("'{s}'"),
+        serde_json::Value::String(s) => format!("'{}'", s.replace('\'', "''")),
         serde_json::Value::Number(n) => n.to_string(),
         serde_json::Value::Bool(b) => b.to_string(),
         _ => "".to_string(),
@@ -71,6 +156,146 @@ impl Debug for LanceDb {
     }
 }
 
+fn vectors_from_column(column: &Arc<dyn Array>) -> Result<Vec<Vec<f32>>> {
+    let column = column
+        .as_any()
+        .downcast_ref::<FixedSizeListArray>()
+        .ok_or_else(|| anyhow!("vector column is not a fixed size list"))?;
+    let mut vectors = Vec::with_capacity(column.len());
+    for i in 0..column.len() {
+        if column.is_null(i) {
+            vectors.push(Vec::new());
+            continue;
+        }
+        let values = column.value(i);
+        let values = values
+            .as_any()
+            .downcast_ref::<PrimitiveArray<Float32Type>>()
+            .ok_or_else(|| anyhow!("vector column is not a list of float32"))?;
+        vectors.push(values.values().to_vec());
+    }
+    Ok(vectors)
+}
+
+/// Name of the vector stored in the column, `None` for the embedding and
+/// metadata columns.
+fn vector_name(field: &Field) -> Option<&str> {
+    match field.data_type() {
+        DataType::FixedSizeList(..) | DataType::List(_) => {
+            field.name().strip_prefix(VECTOR_COLUMN_PREFIX)
+        }
+        _ => None,
+    }
+}
+
+fn vector_field(name: &str, vector: &VectorParams) -> Field {
+    let data_type = DataType::FixedSizeList(
+        Arc::new(Field::new("item", DataType::Float32, true)),
+        vector.dim as i32,
+    );
+    // Multi-vectors are lists of fixed size lists
+    let data_type = if vector.multivector {
+        DataType::List(Arc::new(Field::new("item", data_type, true)))
+    } else {
+        data_type
+    };
+    Field::new(format!("{}{}", VECTOR_COLUMN_PREFIX, name), data_type, true).with_metadata(
+        HashMap::from([(DISTANCE_KEY.to_string(), vector.distance.to_string())]),
+    )
+}
+
+fn named_vectors_from_column(column: &Arc<dyn Array>) -> Result<Vec<Option<Vector>>> {
+    if let Some(list) = column.as_any().downcast_ref::<ListArray>() {
+        let mut vectors = Vec::with_capacity(list.len());
+        for i in 0..list.len() {
+            if list.is_null(i) {
+                vectors.push(None);
+                continue;
+            }
+            vectors.push(Some(Vector::Multi(vectors_from_column(&list.value(i))?)));
+        }
+        return Ok(vectors);
+    }
+    Ok(vectors_from_column(column)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, vector)| (!column.is_null(i)).then_some(Vector::Dense(vector)))
+        .collect())
+}
+
+fn named_vectors_to_array<'a>(
+    field: &Field,
+    vectors: impl Iterator<Item = Option<&'a Vector>>,
+) -> Result<ArrayRef> {
+    let check_dim = |values: &[f32], dim: i32| {
+        if values.len() != dim as usize {
+            return Err(anyhow!(
+                "vector {} has dimension {}, expected {}",
+                field.name(),
+                values.len(),
+                dim
+            ));
+        }
+        Ok(())
+    };
+    match field.data_type() {
+        DataType::FixedSizeList(_, dim) => {
+            let mut rows = Vec::new();
+            for vector in vectors {
+                match vector {
+                    Some(Vector::Dense(values)) => {
+                        check_dim(values, *dim)?;
+                        rows.push(Some(values.iter().map(|v| Some(*v))));
+                    }
+                    Some(Vector::Multi(_)) => {
+                        return Err(anyhow!("vector {} is not a multi-vector", field.name()))
+                    }
+                    None => rows.push(None),
+                }
+            }
+            Ok(Arc::new(FixedSizeListArray::from_iter_primitive::<
+                Float32Type,
+                _,
+                _,
+            >(rows, *dim)))
+        }
+        DataType::List(item) => {
+            let DataType::FixedSizeList(_, dim) = item.data_type() else {
+                return Err(anyhow!("unsupported vector column {}", field.name()));
+            };
+            let mut builder =
+                ListBuilder::new(FixedSizeListBuilder::new(Float32Builder::new(), *dim))
+                    .with_field(item.clone());
+            for vector in vectors {
+                match vector {
+                    Some(Vector::Multi(rows)) => {
+                        for values in rows {
+                            check_dim(values, *dim)?;
+                            builder.values().values().append_slice(values);
+                            builder.values().append(true);
+                        }
+                        builder.append(true);
+                    }
+                    Some(Vector::Dense(_)) => {
+                        return Err(anyhow!("vector {} is a multi-vector", field.name()))
+                    }
+                    None => builder.append_null(),
+                }
+            }
+            Ok(Arc::new(builder.finish()))
+        }
+        _ => Err(anyhow!("unsupported vector column {}", field.name())),
+    }
+}
+
+fn to_lance_distance(distance: &IndexDistance) -> lancedb::DistanceType {
+    match distance {
+        IndexDistance::Cosine => lancedb::DistanceType::Cosine,
+        IndexDistance::Dot => lancedb::DistanceType::Dot,
+        IndexDistance::Euclidean => lancedb::DistanceType::L2,
+    }
+}
+
 async fn vector_chunk_from_batch(
     batch: RecordBatch,
     schema: SchemaRef,
@@ -81,16 +306,20 @@ async fn vector_chunk_from_batch(
     let mut content_metadatas = Vec::new();
     let mut root_content_metadatas: Vec<Option<String>> = Vec::new();
     let mut metadatas: Vec<HashMap<String, serde_json::Value>> = Vec::new();
+    let mut texts: Vec<Option<String>> = Vec::new();
+    let mut named_vectors = Vec::new();
     for field in schema.fields() {
         let field_name = field.name();
-        if field_name == "id" {
+        if let Some(name) = vector_name(field) {
+            let column = batch.column_by_name(field_name).unwrap();
+            named_vectors.push((name.to_string(), named_vectors_from_column(column)?));
+        } else if field_name == "id" {
             ids = as_string_array(batch.column_by_name(field_name).unwrap())
                 .iter()
                 .map(|x| x.unwrap().to_string())
                 .collect();
         } else if field_name == "vector" {
-            // Looks like we are not using the embedding
-            // so we are just doing empty Vec to make the structs happy
+            embeddings = vectors_from_column(batch.column_by_name(field_name).unwrap())?;
         } else if field_name == "content_metadata" {
             for row in as_string_array(batch.column_by_name(field_name).unwrap()) {
                 let row = row.map(|s| s.to_string()).unwrap_or_default();
@@ -100,6 +329,11 @@ async fn vector_chunk_from_batch(
             for row in as_string_array(batch.column_by_name(field_name).unwrap()) {
                 root_content_metadatas.push(row.map(|x| x.to_string()));
             }
+        } else if field_name == TEXT_COLUMN {
+            texts = as_string_array(batch.column_by_name(field_name).unwrap())
+                .iter()
+                .map(|row| row.map(|x| x.to_string()))
+                .collect();
         } else {
             let column = batch.column_by_name(field_name).unwrap();
             let rows = from_arrow_column_to_json_values(column)?;
@@ -116,20 +350,23 @@ async fn vector_chunk_from_batch(
             }
         }
     }
-    // Looks like we are not using the embedding
-    // so we are just doing empty Vec to make the structs happy
+    // Keep the chunks aligned with the ids if no vectors were read
     if embeddings.is_empty() {
         embeddings = vec![Vec::new(); ids.len()];
     }
     if metadatas.is_empty() {
         metadatas = vec![HashMap::new(); ids.len()];
     }
-    for (id, embedding, metadata, root_content_metadata, content_metadata) in izip!(
+    if texts.is_empty() {
+        texts = vec![None; ids.len()];
+    }
+    for (id, embedding, metadata, root_content_metadata, content_metadata, text) in izip!(
         ids,
         embeddings,
         metadatas,
         root_content_metadatas,
-        content_metadatas
+        content_metadatas,
+        texts
     ) {
         let root_content_metadata: Option<indexify_internal_api::ContentMetadata> =
             match root_content_metadata {
@@ -140,11 +377,21 @@ async fn vector_chunk_from_batch(
         results.push(VectorChunk {
             content_id: id,
             embedding,
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata,
             content_metadata,
             root_content_metadata,
+            text,
         });
     }
+    for (name, vectors) in named_vectors {
+        for (chunk, vector) in results.iter_mut().zip(vectors) {
+            if let Some(vector) = vector {
+                chunk.vectors.insert(name.clone(), vector);
+            }
+        }
+    }
     Ok(results)
 }
 
@@ -158,6 +405,38 @@ impl LanceDb {
             conn: Arc::new(conn),
         })
     }
+
+    /// Reads the rows of the documents scored by a scan, in the order of the
+    /// scores.
+    async fn scored_results(
+        &self,
+        index: &str,
+        scores: Vec<(String, f32)>,
+    ) -> Result<Vec<SearchResult>> {
+        if scores.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = scores.iter().map(|(id, _)| id.clone()).collect();
+        let mut chunks: HashMap<String, VectorChunk> = self
+            .get_points(index, ids)
+            .await?
+            .into_iter()
+            .map(|chunk| (chunk.content_id.clone(), chunk))
+            .collect();
+        Ok(scores
+            .into_iter()
+            .filter_map(|(id, confidence_score)| {
+                let chunk = chunks.remove(&id)?;
+                Some(SearchResult {
+                    content_id: chunk.content_id,
+                    confidence_score,
+                    metadata: chunk.metadata,
+                    content_metadata: chunk.content_metadata,
+                    root_content_metadata: chunk.root_content_metadata,
+                })
+            })
+            .collect())
+    }
 }
 
 // Update the schema of the table with the missing fields from the metadata keys
@@ -233,7 +512,13 @@ impl VectorDb for LanceDb {
 
     #[tracing::instrument]
     async fn create_index(&self, index: CreateIndexParams) -> Result<()> {
-        let schema = Arc::new(Schema::new(vec![
+        if index.sparse {
+            return Err(anyhow!(
+                "sparse indexes are not supported by {}",
+                self.name()
+            ));
+        }
+        let mut fields = vec![
             Field::new("id", DataType::Utf8, false),
             Field::new(
                 "vector",
@@ -245,7 +530,14 @@ impl VectorDb for LanceDb {
             ),
             Field::new("root_content_metadata", DataType::Utf8, true),
             Field::new("content_metadata", DataType::Utf8, false),
-        ]));
+            Field::new(TEXT_COLUMN, DataType::Utf8, true),
+        ];
+        let mut vectors: Vec<_> = index.vectors.iter().collect();
+        vectors.sort_by_key(|(name, _)| *name);
+        for (name, vector) in vectors {
+            fields.push(vector_field(name, vector));
+        }
+        let schema = Arc::new(Schema::new(fields));
         let batches = RecordBatchIterator::new(vec![], schema.clone());
         let table_names = self.conn.table_names().execute().await?;
         if table_names.contains(&index.vectordb_index_name) {
@@ -282,10 +574,13 @@ impl VectorDb for LanceDb {
                 .map(|c| Some(c.embedding.iter().map(|e| Some(*e)))),
             vector_dim,
         );
-        let metadata = chunks
+        let mut metadata = chunks
             .first()
             .map(|c| c.metadata.clone())
             .unwrap_or_default();
+        // Tables created before text was stored get the text column on their
+        // next write
+        metadata.insert(TEXT_COLUMN.to_string(), serde_json::json!(""));
 
         let mut content_metadatas = Vec::<String>::new();
         let mut root_content_metadatas = Vec::<Option<String>>::new();
@@ -303,6 +598,12 @@ impl VectorDb for LanceDb {
         let root_content_metadata_array = StringArray::from(root_content_metadatas.to_vec());
 
         let schema = update_schema_with_missing_fields(&tbl, metadata).await?;
+        for name in chunks.iter().flat_map(|c| c.vectors.keys()) {
+            let column = format!("{}{}", VECTOR_COLUMN_PREFIX, name);
+            if schema.field_with_name(&column).is_err() {
+                return Err(anyhow!("index {} has no vector named {}", index, name));
+            }
+        }
 
         let mut arrays: Vec<Arc<dyn Array>> = vec![
             Arc::new(ids),
@@ -316,6 +617,19 @@ impl VectorDb for LanceDb {
             {
                 continue;
             }
+            if let Some(name) = vector_name(field) {
+                arrays.push(named_vectors_to_array(
+                    field,
+                    chunks.iter().map(|c| c.vectors.get(name)),
+                )?);
+                continue;
+            }
+            if field.name() == TEXT_COLUMN {
+                arrays.push(Arc::new(StringArray::from_iter(
+                    chunks.iter().map(|c| c.text.as_deref()),
+                )));
+                continue;
+            }
 
             let all_values: Vec<Option<&serde_json::Value>> = chunks
                 .iter()
@@ -382,6 +696,71 @@ impl VectorDb for LanceDb {
         Ok(results)
     }
 
+    async fn list_points(
+        &self,
+        index: &str,
+        after: Option<String>,
+        limit: u64,
+    ) -> Result<Vec<VectorChunk>> {
+        let tbl = self
+            .conn
+            .open_table(index)
+            .execute()
+            .await
+            .map_err(|e| anyhow!("unable to open table: {}", e))?;
+        let schema = tbl.schema().await?;
+        let mut query = tbl.query();
+        if let Some(after) = &after {
+            query = query.only_if(format!("id > '{}'", after.replace('\'', "''")));
+        }
+        let mut stream = query
+            .execute()
+            .await
+            .map_err(|e| anyhow!("unable to select records: {}", e))?;
+
+        // Tables are not scanned in order, keep the first `limit` content ids
+        let mut page = BTreeMap::new();
+        while let Some(batch) = stream.next().await {
+            let batch = batch.map_err(|e| anyhow!("unable to read records: {}", e))?;
+            for chunk in vector_chunk_from_batch(batch, schema.clone()).await? {
+                page.insert(chunk.content_id.clone(), chunk);
+                if page.len() > limit as usize {
+                    page.pop_last();
+                }
+            }
+        }
+        Ok(page.into_values().collect())
+    }
+
+    // Paging with `list_points` would scan the table once per page, it's read
+    // in a single scan instead
+    fn scan_points<'a>(&'a self, index: &'a str) -> BoxStream<'a, Result<Vec<VectorChunk>>>
+    where
+        Self: Sync,
+    {
+        Box::pin(async_stream::try_stream! {
+            let tbl = self
+                .conn
+                .open_table(index)
+                .execute()
+                .await
+                .map_err(|e| anyhow!("unable to open table: {}", e))?;
+            let schema = tbl.schema().await?;
+            let mut stream = tbl
+                .query()
+                .execute()
+                .await
+                .map_err(|e| anyhow!("unable to select records: {}", e))?;
+            while let Some(batch) = stream.next().await {
+                let batch = batch.map_err(|e| anyhow!("unable to read records: {}", e))?;
+                let chunks = vector_chunk_from_batch(batch, schema.clone()).await?;
+                if !chunks.is_empty() {
+                    yield chunks;
+                }
+            }
+        })
+    }
+
     async fn update_metadata(
         &self,
         index: &str,
@@ -442,51 +821,152 @@ impl VectorDb for LanceDb {
         // We need to pass the distance metric from
         // data manager to the vector db
         let tbl = self.conn.open_table(&index).execute().await?;
-        let mut query = tbl
-            .vector_search(query_embedding)
-            .map_err(|e| anyhow!("unable to create vector search query: {}", e))?
-            .distance_type(lancedb::DistanceType::Cosine);
+        nearest_neighbours(
+            &tbl,
+            "vector",
+            query_embedding,
+            &IndexDistance::Cosine,
+            k,
+            &filter,
+        )
+        .await
+    }
+
+    #[tracing::instrument]
+    async fn search_vector(
+        &self,
+        index: String,
+        vector: &str,
+        query: Vector,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let tbl = self.conn.open_table(&index).execute().await?;
+        let table_schema = tbl
+            .schema()
+            .await
+            .map_err(|e| anyhow!("unable to get schema of table: {}", e))?;
+        let column = format!("{}{}", VECTOR_COLUMN_PREFIX, vector);
+        let field = table_schema
+            .field_with_name(&column)
+            .map_err(|_| anyhow!("index {} has no vector named {}", index, vector))?;
+        let distance = field
+            .metadata()
+            .get(DISTANCE_KEY)
+            .and_then(|distance| distance.parse().ok())
+            .unwrap_or(IndexDistance::Cosine);
+        let query = match query {
+            Vector::Dense(query) => {
+                return nearest_neighbours(&tbl, &column, query, &distance, k, &filter).await
+            }
+            Vector::Multi(query) => query,
+        };
+
+        // Multi-vectors aren't indexed, every row is scored with MaxSim. Only
+        // the ids and multi-vectors are scanned, the rows of the best scored
+        // documents are read afterwards
+        let mut scan = tbl
+            .query()
+            .select(Select::Columns(vec!["id".to_string(), column.clone()]));
         if !filter.is_empty() {
-            query = query.only_if(from_filter_to_str(&filter));
+            scan = scan.only_if(from_filter_to_str(&filter)?);
         }
-        let res = query
-            .column("vector")
-            .limit(k as usize)
+        let mut stream = scan
             .execute()
             .await
-            .unwrap()
-            .try_collect::<Vec<_>>()
-            .await
-            .unwrap();
-        let mut results = vec![];
-        for rb in &res {
-            let distances = rb.column_by_name("_distance").unwrap();
-            let distance_values = distances
-                .as_any()
-                .downcast_ref::<PrimitiveArray<Float32Type>>()
-                .unwrap()
-                .values()
-                .iter();
+            .map_err(|e| anyhow!("unable to select records: {}", e))?;
+        let mut scores: Vec<(String, f32)> = Vec::new();
+        while let Some(batch) = stream.next().await {
+            let batch = batch.map_err(|e| anyhow!("unable to read records: {}", e))?;
+            let ids = as_string_array(
+                batch
+                    .column_by_name("id")
+                    .ok_or(anyhow!("missing column id"))?,
+            );
+            let documents = named_vectors_from_column(
+                batch
+                    .column_by_name(&column)
+                    .ok_or(anyhow!("missing column {}", column))?,
+            )?;
+            for (id, document) in izip!(ids, documents) {
+                let (Some(id), Some(Vector::Multi(document))) = (id, document) else {
+                    continue;
+                };
+                scores.push((id.to_string(), max_sim(&query, &document, &distance)));
+            }
+            scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+            scores.truncate(k as usize);
+        }
+        self.scored_results(&index, scores).await
+    }
 
-            let table_schema = tbl
-                .schema()
-                .await
-                .map_err(|e| anyhow!("unable to get schema of table: {}", e))?;
-            let vector_chunks = vector_chunk_from_batch(rb.clone(), table_schema)
-                .await
-                .map_err(|e| anyhow!("unable to get vector chunks from batch: {}", e))?;
+    async fn search_sparse(
+        &self,
+        _index: String,
+        _query: SparseVector,
+        _k: u64,
+        _filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        Err(anyhow!(
+            "sparse indexes are not supported by {}",
+            self.name()
+        ))
+    }
 
-            for (chunk, distance) in izip!(vector_chunks, distance_values) {
-                results.push(SearchResult {
-                    content_id: chunk.content_id,
-                    confidence_score: *distance,
-                    metadata: chunk.metadata,
-                    content_metadata: chunk.content_metadata,
-                    root_content_metadata: chunk.root_content_metadata,
-                });
+    // Text isn't indexed, the text of every row matching the filter is
+    // scanned and ranked, the rows of the best ranked documents are read
+    // afterwards
+    #[tracing::instrument]
+    async fn keyword_search(
+        &self,
+        index: String,
+        query: &str,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let tbl = self.conn.open_table(&index).execute().await?;
+        let table_schema = tbl
+            .schema()
+            .await
+            .map_err(|e| anyhow!("unable to get schema of table: {}", e))?;
+        // Tables written before text was stored have no text column yet
+        if table_schema.field_with_name(TEXT_COLUMN).is_err() {
+            return Ok(Vec::new());
+        }
+        let mut condition = format!("{} IS NOT NULL", TEXT_COLUMN);
+        if !filter.is_empty() {
+            condition = format!("{} AND ({})", condition, from_filter_to_str(&filter)?);
+        }
+        let mut stream = tbl
+            .query()
+            .select(Select::Columns(vec![
+                "id".to_string(),
+                TEXT_COLUMN.to_string(),
+            ]))
+            .only_if(condition)
+            .execute()
+            .await
+            .map_err(|e| anyhow!("unable to select records: {}", e))?;
+        let mut bm25 = Bm25::new(query);
+        while let Some(batch) = stream.next().await {
+            let batch = batch.map_err(|e| anyhow!("unable to read records: {}", e))?;
+            let ids = as_string_array(
+                batch
+                    .column_by_name("id")
+                    .ok_or(anyhow!("missing column id"))?,
+            );
+            let texts = as_string_array(
+                batch
+                    .column_by_name(TEXT_COLUMN)
+                    .ok_or(anyhow!("missing column {}", TEXT_COLUMN))?,
+            );
+            for (id, text) in izip!(ids, texts) {
+                if let (Some(id), Some(text)) = (id, text) {
+                    bm25.add(id.to_string(), text);
+                }
             }
         }
-        Ok(results)
+        self.scored_results(&index, bm25.rank(k as usize)).await
     }
 
     #[tracing::instrument]
@@ -510,6 +990,63 @@ impl VectorDb for LanceDb {
     }
 }
 
+/// Runs a vector search on one of the vector columns of the table. The
+/// confidence score of the results is their distance to the query.
+async fn nearest_neighbours(
+    tbl: &Table,
+    column: &str,
+    query_embedding: Vec<f32>,
+    distance: &IndexDistance,
+    k: u64,
+    filter: &filter::LabelsFilter,
+) -> Result<Vec<SearchResult>> {
+    let mut query = tbl
+        .vector_search(query_embedding)
+        .map_err(|e| anyhow!("unable to create vector search query: {}", e))?
+        .distance_type(to_lance_distance(distance));
+    if !filter.is_empty() {
+        query = query.only_if(from_filter_to_str(filter)?);
+    }
+    let res = query
+        .column(column)
+        .limit(k as usize)
+        .execute()
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    let mut results = vec![];
+    for rb in &res {
+        let distances = rb.column_by_name("_distance").unwrap();
+        let distance_values = distances
+            .as_any()
+            .downcast_ref::<PrimitiveArray<Float32Type>>()
+            .unwrap()
+            .values()
+            .iter();
+
+        let table_schema = tbl
+            .schema()
+            .await
+            .map_err(|e| anyhow!("unable to get schema of table: {}", e))?;
+        let vector_chunks = vector_chunk_from_batch(rb.clone(), table_schema)
+            .await
+            .map_err(|e| anyhow!("unable to get vector chunks from batch: {}", e))?;
+
+        for (chunk, distance) in izip!(vector_chunks, distance_values) {
+            results.push(SearchResult {
+                content_id: chunk.content_id,
+                confidence_score: *distance,
+                metadata: chunk.metadata,
+                content_metadata: chunk.content_metadata,
+                root_content_metadata: chunk.root_content_metadata,
+            });
+        }
+    }
+    Ok(results)
+}
+
 fn from_serde_json_to_arrow_array(
     datatype: &DataType,
     values: Vec<Option<&serde_json::Value>>,
@@ -641,6 +1178,10 @@ mod tests {
             basic_search,
             crud_operations,
             insertion_idempotent,
+            keyword_search,
+            list_points,
+            named_vectors,
+            search_filter_expressions,
             search_filters,
             store_metadata,
         },
@@ -665,6 +1206,8 @@ mod tests {
                 vector_dim: 2,
                 distance: crate::vectordbs::IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -688,6 +1231,8 @@ mod tests {
                 vector_dim: 2,
                 distance: crate::vectordbs::IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -712,6 +1257,8 @@ mod tests {
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -736,6 +1283,8 @@ mod tests {
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -759,9 +1308,121 @@ mod tests {
                 vector_dim: 2,
                 distance: crate::vectordbs::IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
         search_filters(lance, "hello-index").await;
     }
+
+    // Metadata objects are not stored as columns, so nested keys can't be
+    // filtered on
+    #[tokio::test]
+    async fn test_search_filter_expressions() {
+        let _ = std::fs::remove_dir_all("/tmp/lance.db/");
+        let lance: VectorDBTS = Arc::new(
+            LanceDb::new(&LancedbConfig {
+                path: "/tmp/lance.db".to_string(),
+            })
+            .await
+            .unwrap(),
+        );
+        lance
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "expressions-index".into(),
+                vector_dim: 2,
+                distance: crate::vectordbs::IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        search_filter_expressions(lance, "expressions-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_list_points() {
+        let _ = std::fs::remove_dir_all("/tmp/lance.db/");
+        let lance: VectorDBTS = Arc::new(
+            LanceDb::new(&LancedbConfig {
+                path: "/tmp/lance.db".to_string(),
+            })
+            .await
+            .unwrap(),
+        );
+        lance
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "list-index".into(),
+                vector_dim: 2,
+                distance: crate::vectordbs::IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        list_points(lance, "list-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_keyword_search() {
+        let _ = std::fs::remove_dir_all("/tmp/lance.db/");
+        let lance: VectorDBTS = Arc::new(
+            LanceDb::new(&LancedbConfig {
+                path: "/tmp/lance.db".to_string(),
+            })
+            .await
+            .unwrap(),
+        );
+        lance
+            .create_index(CreateIndexParams {
+                vectordb_index_name: "keyword-index".into(),
+                vector_dim: 2,
+                distance: crate::vectordbs::IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        keyword_search(lance, "keyword-index").await;
+    }
+
+    #[test]
+    fn test_filter_to_str() {
+        let filter = filter::LabelsFilter(vec![
+            "tag IN (a, \"it's\") OR NOT n>=2"
+                .parse::<filter::Filter>()
+                .unwrap(),
+            "name CONTAINS \"50%\" AND name EXISTS"
+                .parse::<filter::Filter>()
+                .unwrap(),
+        ]);
+        assert_eq!(
+            from_filter_to_str(&filter).unwrap(),
+            "(tag IN ('a', 'it''s') OR (n >= 2) IS NOT TRUE) AND \
+             (name LIKE '%50\\%%' AND name IS NOT NULL)"
+        );
+        let filter = filter::LabelsFilter(vec![filter::Filter::Expression(filter::Expression {
+            key: "1 = 1 OR id".to_string(),
+            value: serde_json::json!("a"),
+            operator: filter::Operator::Eq,
+        })]);
+        assert!(from_filter_to_str(&filter).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_named_vectors() {
+        let _ = std::fs::remove_dir_all("/tmp/lance.db/");
+        let lance: VectorDBTS = Arc::new(
+            LanceDb::new(&LancedbConfig {
+                path: "/tmp/lance.db".to_string(),
+            })
+            .await
+            .unwrap(),
+        );
+        named_vectors(lance, "named-vectors-index").await;
+    }
 }
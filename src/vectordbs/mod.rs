@@ -2,15 +2,18 @@ use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 use async_trait::async_trait;
-use filter::Expression;
-use indexify_internal_api::ContentMetadata;
+use futures::stream::BoxStream;
+use indexify_internal_api::{ContentMetadata, SparseVector, Vector};
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 
 use crate::server_config::{IndexStoreKind, VectorIndexConfig};
 
+pub mod bm25;
+pub mod export;
+pub mod hnsw;
 pub mod lancedb;
-//pub mod open_search;
+pub mod open_search;
 pub mod pg_vector;
 pub mod qdrant;
 
@@ -39,9 +42,23 @@ pub struct CreateIndexParams {
     pub distance: IndexDistance,
     // TODO: Probably better if this is a HashMap<String, String> (?), or a generic (?)
     pub unique_params: Option<Vec<String>>,
+    /// Named vectors stored alongside the embedding
+    pub vectors: HashMap<String, VectorParams>,
+    /// The index stores sparse embeddings of dimension `vector_dim`, scored by
+    /// their dot product
+    pub sparse: bool,
 }
 
-#[derive(Debug, Default, Clone)]
+/// Configuration of a named vector of an index.
+#[derive(Clone, Debug)]
+pub struct VectorParams {
+    pub dim: u64,
+    pub distance: IndexDistance,
+    /// Values are multi-vectors, scored with MaxSim
+    pub multivector: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub content_id: String,
     pub confidence_score: f32,
@@ -52,13 +69,21 @@ pub struct SearchResult {
 
 pub type VectorDBTS = Arc<dyn VectorDb + Sync + Send>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VectorChunk {
     pub content_id: String,
     pub embedding: Vec<f32>,
+    #[serde(default)]
+    pub vectors: HashMap<String, Vector>,
+    /// Embedding of sparse indexes, which leave `embedding` empty
+    #[serde(default)]
+    pub sparse_embedding: Option<SparseVector>,
     pub metadata: HashMap<String, serde_json::Value>,
     pub root_content_metadata: Option<ContentMetadata>,
     pub content_metadata: ContentMetadata,
+    /// Text of the content, stored for keyword searches
+    #[serde(default)]
+    pub text: Option<String>,
 }
 impl VectorChunk {
     pub fn new(
@@ -71,14 +96,21 @@ impl VectorChunk {
         Self {
             content_id,
             embedding,
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata,
             root_content_metadata: root_content,
             content_metadata: content_metadata.clone(),
+            text: None,
         }
     }
 }
 
 /// A trait that defines the interface for interacting with a vector database.
+/// Number of embeddings read at a time when an index is scanned a page at a
+/// time
+const SCAN_PAGE_SIZE: u64 = 1000;
+
 /// The vector database is responsible for storing and querying vector
 /// embeddings.
 #[async_trait]
@@ -97,6 +129,36 @@ pub trait VectorDb {
     /// Retrieves the vector embeddings for the specified content IDs
     async fn get_points(&self, index: &str, content_ids: Vec<String>) -> Result<Vec<VectorChunk>>;
 
+    /// Retrieves up to `limit` vector embeddings ordered by content ID,
+    /// starting after the content ID `after`. Used to page through a whole
+    /// index.
+    async fn list_points(
+        &self,
+        index: &str,
+        after: Option<String>,
+        limit: u64,
+    ) -> Result<Vec<VectorChunk>>;
+
+    /// Streams every vector embedding of the index a batch at a time, in no
+    /// particular order. Pages through the index with `list_points` unless
+    /// the database can read it in a single scan.
+    fn scan_points<'a>(&'a self, index: &'a str) -> BoxStream<'a, Result<Vec<VectorChunk>>>
+    where
+        Self: Sync,
+    {
+        Box::pin(async_stream::try_stream! {
+            let mut after = None;
+            loop {
+                let page = self.list_points(index, after, SCAN_PAGE_SIZE).await?;
+                let Some(last) = page.last() else {
+                    break;
+                };
+                after = Some(last.content_id.clone());
+                yield page;
+            }
+        })
+    }
+
     /// Update metadata for the specified content ID
     async fn update_metadata(
         &self,
@@ -115,6 +177,38 @@ pub trait VectorDb {
         filter: filter::LabelsFilter,
     ) -> Result<Vec<SearchResult>>;
 
+    /// Searches the named vector `vector` of the specified index. Multi-vector
+    /// queries are scored against multi-vectors with MaxSim.
+    async fn search_vector(
+        &self,
+        index: String,
+        vector: &str,
+        query: Vector,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>>;
+
+    /// Searches a sparse index for the embeddings with the highest dot product
+    /// with a sparse query.
+    async fn search_sparse(
+        &self,
+        index: String,
+        query: SparseVector,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>>;
+
+    /// Searches the text stored with the embeddings of the specified index
+    /// for the terms of a text query, best matches first. Embeddings stored
+    /// without text are never found.
+    async fn keyword_search(
+        &self,
+        index: String,
+        query: &str,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>>;
+
     /// Deletes the specified vector index from the vector database.
     async fn drop_index(&self, index: &str) -> Result<()>;
 
@@ -126,6 +220,58 @@ pub trait VectorDb {
     //  TODO: Add delete content using namespace and content id
 }
 
+fn similarity(a: &[f32], b: &[f32], distance: &IndexDistance) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    match distance {
+        IndexDistance::Dot => dot,
+        IndexDistance::Cosine => {
+            let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm_a == 0.0 || norm_b == 0.0 {
+                return 0.0;
+            }
+            dot / (norm_a * norm_b)
+        }
+        IndexDistance::Euclidean => -a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum::<f32>()
+            .sqrt(),
+    }
+}
+
+/// Late interaction score of a multi-vector query: the sum, over the query
+/// vectors, of their best similarity with any of the document vectors.
+pub fn max_sim(query: &[Vec<f32>], document: &[Vec<f32>], distance: &IndexDistance) -> f32 {
+    query
+        .iter()
+        .map(|q| {
+            document
+                .iter()
+                .map(|d| similarity(q, d, distance))
+                .fold(f32::NEG_INFINITY, f32::max)
+        })
+        .filter(|score| score.is_finite())
+        .sum()
+}
+
+/// Mean of a multi-vector, used where a backend can only index one vector per
+/// point.
+pub fn mean_vector(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let dim = vectors.first().map(|v| v.len()).unwrap_or_default();
+    let mut mean = vec![0.0; dim];
+    for vector in vectors {
+        for (m, x) in mean.iter_mut().zip(vector) {
+            *m += x;
+        }
+    }
+    for m in &mut mean {
+        *m /= vectors.len() as f32;
+    }
+    mean
+}
+
 /// Creates a new vector database based on the specified configuration.
 pub async fn create_vectordb(config: VectorIndexConfig) -> Result<VectorDBTS> {
     match config.index_store {
@@ -133,13 +279,15 @@ pub async fn create_vectordb(config: VectorIndexConfig) -> Result<VectorDBTS> {
         IndexStoreKind::PgVector => Ok(Arc::new(
             pg_vector::PgVector::new(config.pg_vector_config.unwrap()).await?,
         )),
-        // TODO Bring it back
-        //IndexStoreKind::OpenSearchKnn => Ok(Arc::new(OpenSearchKnn::new(
-        //    config.open_search_basic.unwrap(),
-        //))),
+        IndexStoreKind::OpenSearchKnn => Ok(Arc::new(open_search::OpenSearchKnn::new(
+            config.open_search_basic.unwrap(),
+        ))),
         IndexStoreKind::Lancedb => Ok(Arc::new(
             lancedb::LanceDb::new(&config.lancedb_config.unwrap()).await?,
         )),
+        IndexStoreKind::Hnsw => Ok(Arc::new(
+            hnsw::Hnsw::new(&config.hnsw_config.unwrap_or_default()).await?,
+        )),
     }
 }
 
@@ -148,13 +296,15 @@ mod tests {
     use std::collections::HashMap;
 
     use filter::{Expression, Operator};
+    use futures::TryStreamExt;
+    use indexify_internal_api::{SparseVector, Vector};
     use serde_json::json;
 
-    use super::VectorDBTS;
+    use super::{max_sim, mean_vector, VectorDBTS};
     use crate::{
         data_manager::DataManager,
         test_util::db_utils::{create_metadata, test_mock_content_metadata},
-        vectordbs::VectorChunk,
+        vectordbs::{CreateIndexParams, IndexDistance, VectorChunk, VectorParams},
     };
 
     pub async fn crud_operations(vector_db: VectorDBTS, index_name: &str) {
@@ -162,9 +312,12 @@ mod tests {
         let chunk = VectorChunk {
             content_id: content_id.into(),
             embedding: vec![0., 2.],
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata: create_metadata(vec![("key1", "value1"), ("key2", "value2")]),
             root_content_metadata: Some(test_mock_content_metadata(content_id, "1", "graph1")),
             content_metadata: test_mock_content_metadata(content_id, "1", "graph1"),
+            text: None,
         };
         vector_db
             .add_embedding(index_name, vec![chunk.clone()])
@@ -190,9 +343,12 @@ mod tests {
         let chunk = VectorChunk {
             content_id: "0".into(),
             embedding: vec![0., 2.],
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata: metadata1.clone(),
             root_content_metadata: Some(test_mock_content_metadata("0", "1", "graph1")),
             content_metadata: test_mock_content_metadata("0", "1", "graph1"),
+            text: None,
         };
         vector_db
             .add_embedding(index_name, vec![chunk])
@@ -216,9 +372,12 @@ mod tests {
         let chunk1 = VectorChunk {
             content_id: content_ids[0].clone(),
             embedding: vec![0.1, 0.2],
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata: metadata1.clone(),
             root_content_metadata: Some(test_mock_content_metadata("0", "1", "graph1")),
             content_metadata: test_mock_content_metadata("0", "1", "graph1"),
+            text: None,
         };
         vector_db
             .add_embedding(index_name, vec![chunk1])
@@ -228,9 +387,12 @@ mod tests {
         let chunk2 = VectorChunk {
             content_id: content_ids[1].clone(),
             embedding: vec![0.3, 0.4],
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata: metadata2.clone(),
             root_content_metadata: Some(test_mock_content_metadata("0", "1", "graph1")),
             content_metadata: test_mock_content_metadata("0", "1", "graph1"),
+            text: None,
         };
         vector_db
             .add_embedding(index_name, vec![chunk2])
@@ -273,9 +435,12 @@ mod tests {
         let chunk = VectorChunk {
             content_id: "0".into(),
             embedding: vec![0., 2.],
+            vectors: HashMap::new(),
+            sparse_embedding: None,
             metadata: metadata1.clone(),
             root_content_metadata: Some(test_mock_content_metadata("0", "1", "graph1")),
             content_metadata: test_mock_content_metadata("0", "1", "graph1"),
+            text: None,
         };
         vector_db
             .add_embedding(index_name, vec![chunk.clone()])
@@ -325,9 +490,12 @@ mod tests {
             let chunk = VectorChunk {
                 content_id: content_id.clone(),
                 embedding,
+                vectors: HashMap::new(),
+                sparse_embedding: None,
                 metadata: metadata.clone(),
                 root_content_metadata: Some(content_metadata.clone()),
                 content_metadata,
+                text: None,
             };
             chunks.push(chunk);
         }
@@ -349,7 +517,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(vec![filter]),
+                filter::LabelsFilter(vec![filter.into()]),
             )
             .await
             .unwrap();
@@ -369,7 +537,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(vec![filter]),
+                filter::LabelsFilter(vec![filter.into()]),
             )
             .await
             .unwrap();
@@ -389,7 +557,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(vec![filter]),
+                filter::LabelsFilter(vec![filter.into()]),
             )
             .await
             .unwrap();
@@ -416,7 +584,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(expressions),
+                filter::LabelsFilter(expressions.into_iter().map(Into::into).collect()),
             )
             .await
             .unwrap();
@@ -436,7 +604,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(vec![expression]),
+                filter::LabelsFilter(vec![expression.into()]),
             )
             .await
             .unwrap();
@@ -456,7 +624,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(vec![expression]),
+                filter::LabelsFilter(vec![expression.into()]),
             )
             .await
             .unwrap();
@@ -482,7 +650,7 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(expressions),
+                filter::LabelsFilter(expressions.into_iter().map(Into::into).collect()),
             )
             .await
             .unwrap();
@@ -501,11 +669,408 @@ mod tests {
                 index_name.to_string(),
                 query.clone(),
                 k,
-                filter::LabelsFilter(vec![expression]),
+                filter::LabelsFilter(vec![expression.into()]),
             )
             .await
             .unwrap();
 
         assert_eq!(res.len(), 0);
     }
+
+    /// Stores text with the embeddings and searches it by keyword.
+    pub async fn keyword_search(vector_db: VectorDBTS, index_name: &str) {
+        let red = create_metadata(vec![("color", "red")]);
+        let mut chunks: Vec<VectorChunk> = [
+            ("1", "the pump uses gasket AB-1234", red),
+            ("2", "the pump is red", HashMap::new()),
+            ("3", "a valve and a hose", HashMap::new()),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (content_id, text, metadata))| VectorChunk {
+            text: Some(text.to_string()),
+            ..VectorChunk::new(
+                content_id.into(),
+                vec![i as f32 + 0.1, 0.2],
+                metadata,
+                None,
+                &test_mock_content_metadata(content_id, "1", "graph1"),
+            )
+        })
+        .collect();
+        // Embeddings stored without text are never found by keyword
+        chunks.push(VectorChunk::new(
+            "4".into(),
+            vec![0.5, 0.5],
+            HashMap::new(),
+            None,
+            &test_mock_content_metadata("4", "1", "graph1"),
+        ));
+        vector_db.add_embedding(index_name, chunks).await.unwrap();
+
+        let points = vector_db
+            .get_points(index_name, vec!["3".into()])
+            .await
+            .unwrap();
+        assert_eq!(points[0].text.as_deref(), Some("a valve and a hose"));
+
+        let search = |query: &'static str, filter: &'static str| {
+            let vector_db = vector_db.clone();
+            async move {
+                let filter = match filter {
+                    "" => filter::LabelsFilter::default(),
+                    filter => filter::LabelsFilter(vec![filter.parse().unwrap()]),
+                };
+                vector_db
+                    .keyword_search(index_name.into(), query, 10, filter)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|result| {
+                        assert!(result.confidence_score > 0.);
+                        result.content_id
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(search("AB-1234", "").await, vec!["1".to_string()]);
+        assert_eq!(
+            search("pump", "").await,
+            vec!["2".to_string(), "1".to_string()]
+        );
+        assert_eq!(search("pump", "color=red").await, vec!["1".to_string()]);
+        assert!(search("gearbox", "").await.is_empty());
+
+        vector_db.remove_embedding(index_name, "3").await.unwrap();
+        assert!(search("valve", "").await.is_empty());
+    }
+
+    /// Searches with the given filter and returns the positions of the
+    /// matching content ids, sorted.
+    async fn filtered(
+        vector_db: &VectorDBTS,
+        index_name: &str,
+        content_ids: &[String],
+        filter: &str,
+    ) -> Vec<usize> {
+        let filter = filter::LabelsFilter(vec![filter.parse::<filter::Filter>().unwrap()]);
+        let res = vector_db
+            .search(index_name.to_string(), vec![0.1, 0.2], 10, filter)
+            .await
+            .unwrap();
+        let mut positions: Vec<usize> = res
+            .iter()
+            .map(|r| {
+                content_ids
+                    .iter()
+                    .position(|id| *id == r.content_id)
+                    .unwrap()
+            })
+            .collect();
+        positions.sort();
+        positions
+    }
+
+    async fn add_with_metadata(
+        vector_db: &VectorDBTS,
+        index_name: &str,
+        metadatas: Vec<serde_json::Value>,
+    ) -> Vec<String> {
+        let content_ids: Vec<String> = metadatas.iter().map(|_| make_id()).collect();
+        let chunks = content_ids
+            .iter()
+            .zip(metadatas)
+            .enumerate()
+            .map(|(i, (content_id, metadata))| {
+                let content_metadata = test_mock_content_metadata(content_id, "1", "graph1");
+                VectorChunk {
+                    content_id: content_id.clone(),
+                    embedding: vec![i as f32 + 0.1, i as f32 + 0.2],
+                    vectors: HashMap::new(),
+                    sparse_embedding: None,
+                    metadata: serde_json::from_value(metadata).unwrap(),
+                    root_content_metadata: Some(content_metadata.clone()),
+                    content_metadata,
+                    text: None,
+                }
+            })
+            .collect();
+        vector_db.add_embedding(index_name, chunks).await.unwrap();
+        content_ids
+    }
+
+    pub async fn search_filter_expressions(vector_db: VectorDBTS, index_name: &str) {
+        let content_ids = add_with_metadata(
+            &vector_db,
+            index_name,
+            vec![
+                json!({"color": "red", "name": "pump AB-1", "size": 10}),
+                json!({"color": "blue", "name": "valve", "size": 20}),
+                json!({"name": "pump housing", "size": 30}),
+            ],
+        )
+        .await;
+        let filtered =
+            |filter: &'static str| filtered(&vector_db, index_name, &content_ids, filter);
+
+        assert_eq!(filtered("color=red OR size>=30").await, vec![0, 2]);
+        assert_eq!(filtered("NOT color=red").await, vec![1, 2]);
+        assert_eq!(filtered("color IN (red, blue)").await, vec![0, 1]);
+        assert_eq!(filtered("color NOT IN (red)").await, vec![1]);
+        assert_eq!(filtered("color EXISTS").await, vec![0, 1]);
+        assert_eq!(filtered("NOT color EXISTS").await, vec![2]);
+        assert_eq!(filtered("name STARTS_WITH pump").await, vec![0, 2]);
+        assert_eq!(filtered("name CONTAINS ous").await, vec![2]);
+        assert_eq!(
+            filtered("(color=red OR color=blue) AND NOT size<15").await,
+            vec![1]
+        );
+    }
+
+    pub async fn search_nested_filters(vector_db: VectorDBTS, index_name: &str) {
+        let content_ids = add_with_metadata(
+            &vector_db,
+            index_name,
+            vec![
+                json!({"dims": {"width": 3, "unit": "cm"}}),
+                json!({"dims": {"width": 5, "unit": "mm"}}),
+            ],
+        )
+        .await;
+        let filtered =
+            |filter: &'static str| filtered(&vector_db, index_name, &content_ids, filter);
+
+        assert_eq!(filtered("dims.width>4").await, vec![1]);
+        assert_eq!(filtered("dims.unit IN (cm)").await, vec![0]);
+        assert_eq!(filtered("dims.unit=mm OR dims.width<=3").await, vec![0, 1]);
+        assert!(filtered("dims.depth EXISTS").await.is_empty());
+    }
+
+    pub async fn list_points(vector_db: VectorDBTS, index_name: &str) {
+        let mut content_ids = add_with_metadata(
+            &vector_db,
+            index_name,
+            (0..5).map(|i| json!({ "position": i })).collect(),
+        )
+        .await;
+
+        let mut listed = Vec::new();
+        let mut after = None;
+        loop {
+            let page = vector_db
+                .list_points(index_name, after.clone(), 2)
+                .await
+                .unwrap();
+            assert!(page.len() <= 2);
+            match page.last() {
+                Some(last) => after = Some(last.content_id.clone()),
+                None => break,
+            }
+            listed.extend(page);
+        }
+        for chunk in &listed {
+            let position = content_ids
+                .iter()
+                .position(|id| *id == chunk.content_id)
+                .unwrap();
+            assert_eq!(chunk.metadata.get("position"), Some(&json!(position)));
+            assert_eq!(chunk.embedding.len(), 2);
+            assert_eq!(chunk.content_metadata.id.id, chunk.content_id);
+        }
+        let mut listed_ids: Vec<String> = listed.into_iter().map(|c| c.content_id).collect();
+        listed_ids.sort();
+        content_ids.sort();
+        assert_eq!(listed_ids, content_ids);
+
+        let mut scanned_ids: Vec<String> = vector_db
+            .scan_points(index_name)
+            .try_concat()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| c.content_id)
+            .collect();
+        scanned_ids.sort();
+        assert_eq!(scanned_ids, content_ids);
+    }
+
+    /// Creates the index with a dense and a multi-vector named vector and
+    /// searches both.
+    pub async fn named_vectors(vector_db: VectorDBTS, index_name: &str) {
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index_name.into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::from([
+                    (
+                        "title".to_string(),
+                        VectorParams {
+                            dim: 2,
+                            distance: IndexDistance::Cosine,
+                            multivector: false,
+                        },
+                    ),
+                    (
+                        "tokens".to_string(),
+                        VectorParams {
+                            dim: 2,
+                            distance: IndexDistance::Dot,
+                            multivector: true,
+                        },
+                    ),
+                ]),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        let chunks: Vec<VectorChunk> = [
+            ("1", vec![1., 0.], vec![vec![1., 0.], vec![0., 1.]]),
+            ("2", vec![0., 1.], vec![vec![0., 3.]]),
+        ]
+        .into_iter()
+        .map(|(content_id, title, tokens)| VectorChunk {
+            content_id: content_id.into(),
+            embedding: vec![1., 1.],
+            vectors: HashMap::from([
+                ("title".to_string(), Vector::Dense(title)),
+                ("tokens".to_string(), Vector::Multi(tokens)),
+            ]),
+            sparse_embedding: None,
+            metadata: HashMap::new(),
+            root_content_metadata: None,
+            content_metadata: test_mock_content_metadata(content_id, "1", "graph1"),
+            text: None,
+        })
+        .collect();
+        vector_db
+            .add_embedding(index_name, chunks.clone())
+            .await
+            .unwrap();
+
+        let mut points = vector_db
+            .get_points(index_name, vec!["1".into(), "2".into()])
+            .await
+            .unwrap();
+        points.sort_by(|a, b| a.content_id.cmp(&b.content_id));
+        for (point, chunk) in points.iter().zip(&chunks) {
+            assert_eq!(point.vectors, chunk.vectors);
+        }
+
+        let search = |vector: &'static str, query: Vector| {
+            let vector_db = vector_db.clone();
+            async move {
+                vector_db
+                    .search_vector(index_name.into(), vector, query, 2, Default::default())
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|result| result.content_id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            search("title", Vector::Dense(vec![0.9, 0.1])).await[0],
+            "1".to_string()
+        );
+        assert_eq!(
+            search("tokens", Vector::Multi(vec![vec![1., 0.]])).await,
+            vec!["1".to_string(), "2".to_string()]
+        );
+        assert_eq!(
+            search("tokens", Vector::Multi(vec![vec![0., 1.]])).await,
+            vec!["2".to_string(), "1".to_string()]
+        );
+        assert!(vector_db
+            .search_vector(
+                index_name.into(),
+                "missing",
+                Vector::Dense(vec![1., 0.]),
+                2,
+                Default::default()
+            )
+            .await
+            .is_err());
+
+        let results = vector_db
+            .search(index_name.into(), vec![1., 1.], 2, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    /// Creates a sparse index and searches it by dot product.
+    pub async fn sparse_embeddings(vector_db: VectorDBTS, index_name: &str) {
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index_name.into(),
+                vector_dim: 100,
+                distance: IndexDistance::Dot,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: true,
+            })
+            .await
+            .unwrap();
+        let chunks: Vec<VectorChunk> = [
+            ("1", vec![1, 5], vec![1., 2.]),
+            ("2", vec![5, 42], vec![3., 1.]),
+            ("3", vec![7], vec![4.]),
+        ]
+        .into_iter()
+        .map(|(content_id, indices, values)| VectorChunk {
+            sparse_embedding: Some(SparseVector { indices, values }),
+            ..VectorChunk::new(
+                content_id.into(),
+                vec![],
+                HashMap::new(),
+                None,
+                &test_mock_content_metadata(content_id, "1", "graph1"),
+            )
+        })
+        .collect();
+        vector_db.add_embedding(index_name, chunks).await.unwrap();
+
+        let points = vector_db
+            .get_points(index_name, vec!["2".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            points[0].sparse_embedding,
+            Some(SparseVector {
+                indices: vec![5, 42],
+                values: vec![3., 1.],
+            })
+        );
+
+        // "2" scores 2 * 3 and "1" scores 1 * 1 + 2 * 2
+        let results = vector_db
+            .search_sparse(
+                index_name.into(),
+                SparseVector {
+                    indices: vec![1, 5],
+                    values: vec![1., 2.],
+                },
+                2,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        let content_ids: Vec<&str> = results.iter().map(|r| r.content_id.as_str()).collect();
+        assert_eq!(content_ids, vec!["2", "1"]);
+        assert!((results[0].confidence_score - 6.).abs() < 1e-5);
+        assert!((results[1].confidence_score - 5.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_max_sim() {
+        let document = vec![vec![1., 0.], vec![0., 2.]];
+        let query = vec![vec![1., 1.], vec![0., 1.]];
+        assert_eq!(max_sim(&query, &document, &IndexDistance::Dot), 4.);
+        assert_eq!(max_sim(&query, &[], &IndexDistance::Dot), 0.);
+        let score = max_sim(&query, &document, &IndexDistance::Cosine);
+        assert!((score - (1. / 2f32.sqrt() + 1.)).abs() < 1e-6);
+        assert_eq!(mean_vector(&document), vec![0.5, 1.]);
+    }
 }
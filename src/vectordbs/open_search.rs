@@ -1,14 +1,22 @@
+use std::collections::HashMap;
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use filter::{Expression, Filter, LabelsFilter, Operator};
+use indexify_internal_api::{ContentMetadata, SparseVector, Vector};
 use opensearch::{
     auth::Credentials,
-    cert::CertificateValidation,
-    http::transport::{SingleNodeConnectionPool, TransportBuilder},
+    cert::{Certificate, CertificateValidation},
+    http::{
+        response::Response,
+        transport::{SingleNodeConnectionPool, TransportBuilder},
+    },
     indices::IndicesCreateParts,
+    params::Refresh,
     BulkOperation,
     OpenSearch,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use url::Url;
 
@@ -18,6 +26,128 @@ use crate::{
     vectordbs::{IndexDistance, SearchResult, VectorChunk},
 };
 
+/// Source of the documents stored for every vector chunk. Metadata is
+/// indexed so it can be filtered on, content metadata is only stored. The
+/// text is analyzed for keyword searches.
+#[derive(Serialize, Deserialize)]
+struct Document {
+    #[serde(default)]
+    embedding: Vec<f32>,
+    #[serde(default)]
+    metadata: HashMap<String, Value>,
+    root_content_metadata: Option<ContentMetadata>,
+    content_metadata: ContentMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+impl Document {
+    fn into_chunk(self, content_id: String) -> VectorChunk {
+        VectorChunk {
+            content_id,
+            embedding: self.embedding,
+            vectors: HashMap::new(),
+            sparse_embedding: None,
+            metadata: self.metadata,
+            root_content_metadata: self.root_content_metadata,
+            content_metadata: self.content_metadata,
+            text: self.text,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Hit {
+    #[serde(rename = "_id")]
+    id: String,
+    #[serde(rename = "_score")]
+    score: f32,
+    #[serde(rename = "_source")]
+    source: Document,
+}
+
+/// Translates a labels filter into an OpenSearch query on the metadata
+/// fields. Like `LabelsFilter::matches`, documents without the key never
+/// match, not even for `Neq`.
+fn filter_query(filter: &LabelsFilter) -> Value {
+    let must: Vec<Value> = filter.filters().iter().map(filter_clause).collect();
+    json!({ "bool": { "must": must } })
+}
+
+fn filter_clause(filter: &Filter) -> Value {
+    let clauses = |filters: &[Filter]| filters.iter().map(filter_clause).collect::<Vec<_>>();
+    match filter {
+        Filter::Expression(expression) => expression_clause(expression),
+        Filter::And(filters) => json!({ "bool": { "must": clauses(filters) } }),
+        Filter::Or(filters) => json!({
+            "bool": { "should": clauses(filters), "minimum_should_match": 1 }
+        }),
+        Filter::Not(filter) => json!({ "bool": { "must_not": [filter_clause(filter)] } }),
+    }
+}
+
+fn expression_clause(expression: &Expression) -> Value {
+    let field = format!("metadata.{}", expression.key);
+    let field = field.as_str();
+    let value = &expression.value;
+    let exists = json!({ "exists": { "field": field } });
+    match expression.operator {
+        Operator::Eq => json!({ "term": { field: value } }),
+        Operator::Neq => json!({
+            "bool": { "must": [exists], "must_not": [{ "term": { field: value } }] }
+        }),
+        Operator::Gt => json!({ "range": { field: { "gt": value } } }),
+        Operator::Lt => json!({ "range": { field: { "lt": value } } }),
+        Operator::GtEq => json!({ "range": { field: { "gte": value } } }),
+        Operator::LtEq => json!({ "range": { field: { "lte": value } } }),
+        Operator::In => json!({ "terms": { field: value } }),
+        Operator::NotIn => json!({
+            "bool": { "must": [exists], "must_not": [{ "terms": { field: value } }] }
+        }),
+        Operator::Exists => exists,
+        Operator::StartsWith => json!({ "prefix": { field: value } }),
+        Operator::Contains => match value {
+            Value::String(s) => {
+                let escaped = s
+                    .replace('\\', "\\\\")
+                    .replace('*', "\\*")
+                    .replace('?', "\\?");
+                json!({ "wildcard": { field: { "value": format!("*{}*", escaped) } } })
+            }
+            // Terms match any element of array fields
+            value => json!({ "term": { field: value } }),
+        },
+    }
+}
+
+/// Hits of a search as search results.
+fn search_results(body: &Value) -> Result<Vec<SearchResult>> {
+    let hits: Vec<Hit> = serde_json::from_value(body["hits"]["hits"].clone())
+        .map_err(|e| anyhow!("unable to parse opensearch search response: {}", e))?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| SearchResult {
+            content_id: hit.id,
+            confidence_score: hit.score,
+            metadata: hit.source.metadata,
+            root_content_metadata: hit.source.root_content_metadata,
+            content_metadata: hit.source.content_metadata,
+        })
+        .collect())
+}
+
+async fn check_response(response: Response, action: &str) -> Result<Value> {
+    let status = response.status_code();
+    let body = response
+        .json::<Value>()
+        .await
+        .map_err(|e| anyhow!("unable to parse opensearch {} response: {}", action, e))?;
+    if !status.is_success() {
+        return Err(anyhow!("unable to {} in opensearch: '{}'", action, body));
+    }
+    Ok(body)
+}
+
 pub struct OpenSearchKnn {
     config: OpenSearchBasicConfig,
 }
@@ -32,8 +162,19 @@ impl OpenSearchKnn {
             .map_err(|e| anyhow!("unable to parse open search url: {}", e))?;
         let credentials =
             Credentials::Basic(self.config.username.clone(), self.config.password.clone());
+        let cert_validation = if self.config.insecure_skip_verify {
+            CertificateValidation::None
+        } else if let Some(path) = &self.config.ca_cert_path {
+            let pem = std::fs::read(path)
+                .map_err(|e| anyhow!("unable to read open search CA certificate: {}", e))?;
+            let cert = Certificate::from_pem(&pem)
+                .map_err(|e| anyhow!("unable to parse open search CA certificate: {}", e))?;
+            CertificateValidation::Full(cert)
+        } else {
+            CertificateValidation::Default
+        };
         let transport = TransportBuilder::new(SingleNodeConnectionPool::new(url))
-            .cert_validation(CertificateValidation::None)
+            .cert_validation(cert_validation)
             .auth(credentials)
             .build()
             .map_err(|e| anyhow!("unable to create open search transport: {}", e))?;
@@ -48,6 +189,19 @@ impl VectorDb for OpenSearchKnn {
     }
 
     async fn create_index(&self, index_params: CreateIndexParams) -> Result<()> {
+        if !index_params.vectors.is_empty() {
+            return Err(anyhow!(
+                "named vectors are not supported by {}",
+                self.name()
+            ));
+        }
+        if index_params.sparse {
+            return Err(anyhow!(
+                "sparse indexes are not supported by {}",
+                self.name()
+            ));
+        }
+        // The lucene engine supports filtering during the kNN search
         let response = self
             .create_client()?
             .indices()
@@ -58,8 +212,17 @@ impl VectorDb for OpenSearchKnn {
                         "index": { "knn": true, }
                     },
                     "mappings" : {
+                        "dynamic_templates": [
+                            {
+                                "metadata_strings": {
+                                    "path_match": "metadata.*",
+                                    "match_mapping_type": "string",
+                                    "mapping": { "type": "keyword" }
+                                }
+                            }
+                        ],
                         "properties" : {
-                            "embeddings" : {
+                            "embedding" : {
                                 "type" : "knn_vector",
                                 "dimension" : index_params.vector_dim as i32,
                                 "method": {
@@ -69,9 +232,13 @@ impl VectorDb for OpenSearchKnn {
                                         IndexDistance::Dot => "innerproduct",
                                         IndexDistance::Euclidean => "l2",
                                     },
-                                    "engine": "nmslib"
+                                    "engine": "lucene"
                                 }
-                            }
+                            },
+                            "metadata": { "type": "object" },
+                            "text": { "type": "text" },
+                            "root_content_metadata": { "type": "object", "enabled": false },
+                            "content_metadata": { "type": "object", "enabled": false }
                         }
                     }
                 }
@@ -79,74 +246,155 @@ impl VectorDb for OpenSearchKnn {
             .send()
             .await
             .map_err(|e| anyhow!("unable to create opensearch index: {}", e))?;
-        match response.error_for_status_code() {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                return Err(anyhow!("unable to create opensearch index: '{}'", e));
-            }
-        }
+        check_response(response, "create index").await?;
+        Ok(())
     }
 
     async fn add_embedding(&self, index_name: &str, vector_chunks: Vec<VectorChunk>) -> Result<()> {
         // TODO: implement smart batching to handle large chunks
-        let mut bulk_ops: Vec<opensearch::BulkOperation<Value>> = Vec::new();
+        let mut bulk_ops: Vec<BulkOperation<Value>> = Vec::new();
         for vector_chunk in vector_chunks {
-            let body = json!({
-                "embeddings": vector_chunk.embedding,
-            });
-            bulk_ops.push(BulkOperation::create(vector_chunk.content_id, body).into());
+            let document = Document {
+                embedding: vector_chunk.embedding,
+                metadata: vector_chunk.metadata,
+                root_content_metadata: vector_chunk.root_content_metadata,
+                content_metadata: vector_chunk.content_metadata,
+                text: vector_chunk.text,
+            };
+            // Indexing replaces existing documents, so adding a chunk twice is
+            // idempotent
+            bulk_ops.push(
+                BulkOperation::index(serde_json::to_value(document)?)
+                    .id(vector_chunk.content_id)
+                    .into(),
+            );
         }
 
         let response = self
             .create_client()?
             .bulk(opensearch::BulkParts::Index(index_name))
+            .refresh(Refresh::True)
             .body(bulk_ops)
             .send()
             .await
             .map_err(|e| anyhow!("unable to add opensearch embeddings: {}", e))?;
-
-        match response.error_for_status_code() {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                return Err(anyhow!("unable to add opensearch embeddings: '{}'", e));
-            }
+        let body = check_response(response, "add embeddings").await?;
+        if body["errors"].as_bool().unwrap_or_default() {
+            return Err(anyhow!(
+                "unable to add opensearch embeddings: '{}'",
+                body["items"]
+            ));
         }
+        Ok(())
     }
 
     async fn remove_embedding(&self, index_name: &str, content_id: &str) -> Result<()> {
-        let query = json!({
-            "query": {
-                "match": {
-                    "_id": content_id
-                }
-            }
-        });
+        let response = self
+            .create_client()?
+            .delete(opensearch::DeleteParts::IndexId(index_name, content_id))
+            .refresh(Refresh::True)
+            .send()
+            .await
+            .map_err(|e| anyhow!("unable to remove opensearch embeddings: {}", e))?;
+        if response.status_code().as_u16() == 404 {
+            return Ok(());
+        }
+        check_response(response, "remove embeddings").await?;
+        Ok(())
+    }
 
+    async fn get_points(&self, index: &str, ids: Vec<String>) -> Result<Vec<VectorChunk>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
         let response = self
             .create_client()?
-            .delete_by_query(opensearch::DeleteByQueryParts::Index(&[index_name]))
-            .body(query)
+            .mget(opensearch::MgetParts::Index(index))
+            .body(json!({ "ids": ids }))
             .send()
-            .await?;
+            .await
+            .map_err(|e| anyhow!("unable to get opensearch points: {}", e))?;
+        let body = check_response(response, "get points").await?;
 
-        match response.error_for_status_code() {
-            Ok(_) => Ok(()),
-            Err(e) => return Err(anyhow!("unable to remove opensearch embeddings: '{}'", e)),
+        #[derive(Deserialize)]
+        struct MgetDoc {
+            #[serde(rename = "_id")]
+            id: String,
+            found: bool,
+            #[serde(rename = "_source")]
+            source: Option<Document>,
         }
+
+        let docs: Vec<MgetDoc> = serde_json::from_value(body["docs"].clone())
+            .map_err(|e| anyhow!("unable to parse opensearch get points response: {}", e))?;
+        Ok(docs
+            .into_iter()
+            .filter(|doc| doc.found)
+            .filter_map(|doc| doc.source.map(|source| source.into_chunk(doc.id)))
+            .collect())
     }
 
-    async fn get_points(&self, _index: &str, _ids: Vec<String>) -> Result<Vec<VectorChunk>> {
-        // TODO: return empty vector for now
-        Ok(vec![])
+    async fn list_points(
+        &self,
+        index: &str,
+        after: Option<String>,
+        limit: u64,
+    ) -> Result<Vec<VectorChunk>> {
+        let mut body = json!({
+            "size": limit,
+            "query": { "match_all": {} },
+            "sort": [{ "_id": "asc" }]
+        });
+        if let Some(after) = after {
+            body["search_after"] = json!([after]);
+        }
+        let response = self
+            .create_client()?
+            .search(opensearch::SearchParts::Index(&[index]))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("unable to list opensearch points: {}", e))?;
+        let body = check_response(response, "list points").await?;
+
+        #[derive(Deserialize)]
+        struct PointHit {
+            #[serde(rename = "_id")]
+            id: String,
+            #[serde(rename = "_source")]
+            source: Document,
+        }
+
+        let hits: Vec<PointHit> = serde_json::from_value(body["hits"]["hits"].clone())
+            .map_err(|e| anyhow!("unable to parse opensearch list points response: {}", e))?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| hit.source.into_chunk(hit.id))
+            .collect())
     }
 
-    // TODO: implementation of update_metadata
     async fn update_metadata(
         &self,
-        _index: &str,
-        _content_id: String,
-        _metadata: serde_json::Value,
+        index: &str,
+        content_id: String,
+        metadata: HashMap<String, Value>,
     ) -> Result<()> {
+        // A partial document update would merge the old metadata into the
+        // new one, the script replaces it
+        let response = self
+            .create_client()?
+            .update(opensearch::UpdateParts::IndexId(index, &content_id))
+            .refresh(Refresh::True)
+            .body(json!({
+                "script": {
+                    "source": "ctx._source.metadata = params.metadata",
+                    "params": { "metadata": metadata }
+                }
+            }))
+            .send()
+            .await
+            .map_err(|e| anyhow!("unable to update opensearch metadata: {}", e))?;
+        check_response(response, "update metadata").await?;
         Ok(())
     }
 
@@ -155,108 +403,118 @@ impl VectorDb for OpenSearchKnn {
         index_name: String,
         query_embedding: Vec<f32>,
         k: u64,
-        _filters: Vec<super::Filter>,
+        filter: LabelsFilter,
     ) -> Result<Vec<SearchResult>> {
+        let mut knn = json!({
+            "vector": query_embedding,
+            "k": k
+        });
+        if !filter.is_empty() {
+            knn["filter"] = filter_query(&filter);
+        }
         let response = self
             .create_client()?
             .search(opensearch::SearchParts::Index(&[&index_name]))
             .body(json!({
+                "size": k,
+                "_source": { "excludes": ["embedding", "text"] },
                 "query": {
                     "knn": {
-                        "embeddings": {
-                            "vector": query_embedding,
-                            "k": k
-                        }
+                        "embedding": knn
                     }
                 }
             }))
             .send()
             .await
             .map_err(|e| anyhow!("unable to search opensearch embeddings: {}", e))?;
+        let body = check_response(response, "search embeddings").await?;
+        search_results(&body)
+    }
 
-        let response_body = response
-            .json::<Value>()
-            .await
-            .map_err(|e| anyhow!("unable to parse opensearch search response: {}", e))?;
-
-        let returned_hits = response_body["hits"]["hits"].as_array();
-        match returned_hits {
-            None => {
-                return Err(anyhow!(
-                    "unable to parse opensearch search response".to_string(),
-                ))
-            }
-            Some(hits) => {
-                let mut documents: Vec<SearchResult> = Vec::new();
-                for hit in hits {
-                    #[derive(Deserialize)]
-                    struct OpenSearchHit {
-                        _id: String,
-                        _score: f64,
-                    }
+    async fn search_vector(
+        &self,
+        _index: String,
+        _vector: &str,
+        _query: Vector,
+        _k: u64,
+        _filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        Err(anyhow!(
+            "named vectors are not supported by {}",
+            self.name()
+        ))
+    }
 
-                    let hit = serde_json::from_value::<OpenSearchHit>(hit.clone());
-                    match hit {
-                        Err(e) => {
-                            return Err(anyhow!(
-                                "unable to parse opensearch search response: {}",
-                                e
-                            ));
-                        }
-                        Ok(hit) => {
-                            documents.push(SearchResult {
-                                content_id: hit._id,
-                                confidence_score: hit._score as f32,
-                            });
-                        }
+    async fn search_sparse(
+        &self,
+        _index: String,
+        _query: SparseVector,
+        _k: u64,
+        _filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        Err(anyhow!(
+            "sparse indexes are not supported by {}",
+            self.name()
+        ))
+    }
+
+    async fn keyword_search(
+        &self,
+        index: String,
+        query: &str,
+        k: u64,
+        filter: LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let response = self
+            .create_client()?
+            .search(opensearch::SearchParts::Index(&[&index]))
+            .body(json!({
+                "size": k,
+                "_source": { "excludes": ["embedding", "text"] },
+                "query": {
+                    "bool": {
+                        "must": [{ "match": { "text": query } }],
+                        "filter": [filter_query(&filter)]
                     }
                 }
-                Ok(documents)
-            }
-        }
+            }))
+            .send()
+            .await
+            .map_err(|e| anyhow!("unable to search opensearch text: {}", e))?;
+        let body = check_response(response, "search text").await?;
+        search_results(&body)
     }
 
     async fn drop_index(&self, index: &str) -> Result<()> {
         let response = self
             .create_client()?
             .indices()
-            .delete(opensearch::indices::IndicesDeleteParts::Index(&[&index]))
+            .delete(opensearch::indices::IndicesDeleteParts::Index(&[index]))
             .send()
             .await
             .map_err(|e| anyhow!("unable to delete opensearch index: {}", e))?;
-
-        match response.error_for_status_code() {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                if let Some(status) = e.status_code() {
-                    if status.as_u16() == 404 {
-                        return Ok(());
-                    }
-                }
-                return Err(anyhow!("unable to delete opensearch index: '{}'", e));
-            }
+        if response.status_code().as_u16() == 404 {
+            return Ok(());
         }
+        check_response(response, "delete index").await?;
+        Ok(())
     }
 
     async fn num_vectors(&self, index: &str) -> Result<u64> {
         let response = self
             .create_client()?
-            .count(opensearch::CountParts::Index(&[&index]))
+            .count(opensearch::CountParts::Index(&[index]))
             .send()
             .await
             .map_err(|e| anyhow!("unable to count opensearch index: {}", e))?;
-
-        let response_body = response
-            .json::<Value>()
-            .await
-            .map_err(|e| anyhow!("unable to parse opensearch count response: {}", e))?;
+        let body = check_response(response, "count index").await?;
 
         #[derive(Deserialize)]
         struct OpenSearchCount {
             count: u64,
         }
 
-        let result = serde_json::from_value::<OpenSearchCount>(response_body)
+        let result = serde_json::from_value::<OpenSearchCount>(body)
             .map_err(|e| anyhow!("unable to parse opensearch count response: {}", e))?;
 
         Ok(result.count)
@@ -265,76 +523,421 @@ impl VectorDb for OpenSearchKnn {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{
+        cmp::Ordering,
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
 
-    use crate::{
-        server_config::OpenSearchBasicConfig,
-        vectordbs::{IndexDistance, VectorChunk, VectorDBTS},
+    use axum::{
+        extract::{Path, State},
+        http::StatusCode,
+        routing::{delete, post, put},
+        Json,
+        Router,
     };
-    const TEST_INDEX_NAME: &str = "test_index_name";
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::vectordbs::{
+        bm25::Bm25,
+        tests::{
+            basic_search,
+            crud_operations,
+            insertion_idempotent,
+            keyword_search,
+            list_points,
+            search_filter_expressions,
+            search_filters,
+            search_nested_filters,
+            store_metadata,
+        },
+        VectorDBTS,
+    };
+
+    /// Indexes of the mock, documents by id per index name.
+    type Indexes = Arc<Mutex<HashMap<String, HashMap<String, Value>>>>;
+
+    fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+
+    fn field<'a>(source: &'a Value, path: &str) -> Option<&'a Value> {
+        path.split('.')
+            .try_fold(source, |value, key| value.get(key))
+    }
+
+    /// The values of a field, every element for arrays.
+    fn values<'a>(source: &'a Value, path: &str) -> Vec<&'a Value> {
+        match field(source, path) {
+            Some(Value::Array(values)) => values.iter().collect(),
+            Some(value) => vec![value],
+            None => vec![],
+        }
+    }
+
+    /// Evaluates the subset of the query DSL produced by `filter_query`.
+    fn matches(query: &Value, source: &Value) -> bool {
+        let (kind, body) = query.as_object().unwrap().iter().next().unwrap();
+        match kind.as_str() {
+            "bool" => {
+                let clauses = |name: &str| body[name].as_array().cloned().unwrap_or_default();
+                let should = clauses("should");
+                clauses("must").iter().all(|q| matches(q, source)) &&
+                    !clauses("must_not").iter().any(|q| matches(q, source)) &&
+                    (should.is_empty() || should.iter().any(|q| matches(q, source)))
+            }
+            "exists" => field(source, body["field"].as_str().unwrap()).is_some(),
+            "term" | "terms" => {
+                let (path, expected) = body.as_object().unwrap().iter().next().unwrap();
+                let expected = match expected {
+                    Value::Array(expected) => expected.clone(),
+                    expected => vec![expected.clone()],
+                };
+                values(source, path).iter().any(|v| {
+                    expected
+                        .iter()
+                        .any(|e| compare(v, e) == Some(Ordering::Equal))
+                })
+            }
+            "prefix" => {
+                let (path, prefix) = body.as_object().unwrap().iter().next().unwrap();
+                values(source, path).iter().any(|v| {
+                    v.as_str()
+                        .unwrap_or_default()
+                        .starts_with(prefix.as_str().unwrap())
+                })
+            }
+            "wildcard" => {
+                let (path, pattern) = body.as_object().unwrap().iter().next().unwrap();
+                // Only the `*text*` patterns of `Contains` filters
+                let pattern = pattern["value"].as_str().unwrap();
+                let text = pattern[1..pattern.len() - 1]
+                    .replace("\\*", "*")
+                    .replace("\\?", "?")
+                    .replace("\\\\", "\\");
+                values(source, path)
+                    .iter()
+                    .any(|v| v.as_str().unwrap_or_default().contains(&text))
+            }
+            "range" => {
+                let (path, bounds) = body.as_object().unwrap().iter().next().unwrap();
+                let Some(value) = field(source, path) else {
+                    return false;
+                };
+                bounds.as_object().unwrap().iter().all(|(op, bound)| {
+                    let ordering = compare(value, bound);
+                    match op.as_str() {
+                        "gt" => ordering == Some(Ordering::Greater),
+                        "gte" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                        "lt" => ordering == Some(Ordering::Less),
+                        "lte" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                        _ => false,
+                    }
+                })
+            }
+            _ => panic!("unsupported query {}", query),
+        }
+    }
+
+    fn cosine_score(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        (1.0 + dot / (norm(a) * norm(b))) / 2.0
+    }
+
+    fn embedding(value: &Value) -> Vec<f32> {
+        serde_json::from_value(value.clone()).unwrap()
+    }
+
+    async fn create_index(
+        State(indexes): State<Indexes>,
+        Path(index): Path<String>,
+    ) -> Json<Value> {
+        indexes.lock().unwrap().entry(index).or_default();
+        Json(json!({ "acknowledged": true }))
+    }
+
+    async fn delete_index(
+        State(indexes): State<Indexes>,
+        Path(index): Path<String>,
+    ) -> (StatusCode, Json<Value>) {
+        match indexes.lock().unwrap().remove(&index) {
+            Some(_) => (StatusCode::OK, Json(json!({ "acknowledged": true }))),
+            None => (StatusCode::NOT_FOUND, Json(json!({ "status": 404 }))),
+        }
+    }
+
+    async fn bulk(
+        State(indexes): State<Indexes>,
+        Path(index): Path<String>,
+        body: String,
+    ) -> Json<Value> {
+        let mut indexes = indexes.lock().unwrap();
+        let documents = indexes.get_mut(&index).unwrap();
+        let lines: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        for pair in lines.chunks(2) {
+            let id = pair[0]["index"]["_id"].as_str().unwrap();
+            documents.insert(id.to_string(), pair[1].clone());
+        }
+        Json(json!({ "errors": false, "items": [] }))
+    }
+
+    async fn delete_doc(
+        State(indexes): State<Indexes>,
+        Path((index, id)): Path<(String, String)>,
+    ) -> (StatusCode, Json<Value>) {
+        match indexes.lock().unwrap().get_mut(&index).unwrap().remove(&id) {
+            Some(_) => (StatusCode::OK, Json(json!({ "result": "deleted" }))),
+            None => (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "result": "not_found" })),
+            ),
+        }
+    }
+
+    async fn update_doc(
+        State(indexes): State<Indexes>,
+        Path((index, id)): Path<(String, String)>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        let mut indexes = indexes.lock().unwrap();
+        let document = indexes.get_mut(&index).unwrap().get_mut(&id).unwrap();
+        assert_eq!(
+            body["script"]["source"],
+            "ctx._source.metadata = params.metadata"
+        );
+        document["metadata"] = body["script"]["params"]["metadata"].clone();
+        Json(json!({ "result": "updated" }))
+    }
+
+    async fn mget(
+        State(indexes): State<Indexes>,
+        Path(index): Path<String>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        let indexes = indexes.lock().unwrap();
+        let documents = &indexes[&index];
+        let docs: Vec<Value> = body["ids"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|id| match documents.get(id.as_str().unwrap()) {
+                Some(source) => json!({ "_id": id, "found": true, "_source": source }),
+                None => json!({ "_id": id, "found": false }),
+            })
+            .collect();
+        Json(json!({ "docs": docs }))
+    }
 
-    use super::{CreateIndexParams, OpenSearchKnn};
+    async fn search(
+        State(indexes): State<Indexes>,
+        Path(index): Path<String>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        let indexes = indexes.lock().unwrap();
+        let query = &body["query"]["bool"];
+        if !query.is_null() {
+            // Keyword searches, ranked by BM25 rather than by Lucene
+            let text = query["must"][0]["match"]["text"].as_str().unwrap();
+            let mut bm25 = Bm25::new(text);
+            for (id, source) in &indexes[&index] {
+                let Some(text) = source["text"].as_str() else {
+                    continue;
+                };
+                if matches(&query["filter"][0], source) {
+                    bm25.add((id, source), text);
+                }
+            }
+            let hits: Vec<Value> = bm25
+                .rank(body["size"].as_u64().unwrap() as usize)
+                .into_iter()
+                .map(|((id, source), score)| {
+                    json!({ "_id": id, "_score": score, "_source": source })
+                })
+                .collect();
+            return Json(json!({ "hits": { "hits": hits } }));
+        }
+        let knn = &body["query"]["knn"]["embedding"];
+        if knn.is_null() {
+            // Paging through all documents sorted by id
+            let after = body["search_after"][0].as_str();
+            let mut hits: Vec<(&String, &Value)> = indexes[&index]
+                .iter()
+                .filter(|(id, _)| after.map_or(true, |after| id.as_str() > after))
+                .collect();
+            hits.sort_by(|a, b| a.0.cmp(b.0));
+            let hits: Vec<Value> = hits
+                .into_iter()
+                .take(body["size"].as_u64().unwrap() as usize)
+                .map(|(id, source)| json!({ "_id": id, "_score": null, "_source": source }))
+                .collect();
+            return Json(json!({ "hits": { "hits": hits } }));
+        }
+        let query = embedding(&knn["vector"]);
+        let mut hits: Vec<(f32, &String, &Value)> = indexes[&index]
+            .iter()
+            .filter(|(_, source)| knn.get("filter").map_or(true, |f| matches(f, source)))
+            .map(|(id, source)| {
+                let score = cosine_score(&query, &embedding(&source["embedding"]));
+                (score, id, source)
+            })
+            .collect();
+        hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let hits: Vec<Value> = hits
+            .into_iter()
+            .take(knn["k"].as_u64().unwrap() as usize)
+            .map(|(score, id, source)| json!({ "_id": id, "_score": score, "_source": source }))
+            .collect();
+        Json(json!({ "hits": { "hits": hits } }))
+    }
+
+    async fn count(State(indexes): State<Indexes>, Path(index): Path<String>) -> Json<Value> {
+        Json(json!({ "count": indexes.lock().unwrap()[&index].len() }))
+    }
 
-    fn initialize_opensearch() -> OpenSearchKnn {
+    /// Serves the parts of the OpenSearch REST API the backend uses, so the
+    /// tests run without an OpenSearch container.
+    async fn mock_opensearch() -> OpenSearchKnn {
+        let app = Router::new()
+            .route("/:index", put(create_index).delete(delete_index))
+            .route("/:index/_bulk", post(bulk).put(bulk))
+            .route("/:index/_doc/:id", delete(delete_doc))
+            .route("/:index/_update/:id", post(update_doc))
+            .route("/:index/_mget", post(mget).get(mget))
+            .route("/:index/_search", post(search).get(search))
+            .route("/:index/_count", post(count).get(count))
+            .with_state(Indexes::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
         OpenSearchKnn::new(OpenSearchBasicConfig {
-            addr: "https://localhost:9200".into(),
+            addr: format!("http://{}", addr),
             username: "admin".into(),
             password: "admin".into(),
+            ..Default::default()
         })
     }
 
-    #[tokio::test]
-    #[tracing_test::traced_test]
-    #[ignore]
-    async fn test_search_basic() {
-        let opensearch: VectorDBTS = Arc::new(initialize_opensearch());
-        opensearch.drop_index(TEST_INDEX_NAME).await.unwrap();
+    async fn create_test_index(index_name: &str) -> VectorDBTS {
+        let opensearch: VectorDBTS = Arc::new(mock_opensearch().await);
         opensearch
             .create_index(CreateIndexParams {
-                vectordb_index_name: TEST_INDEX_NAME.into(),
+                vectordb_index_name: index_name.into(),
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
-        let chunk = VectorChunk {
-            content_id: "0".into(),
-            embedding: vec![0., 2.],
-            metadata: serde_json::Value::Null,
-        };
         opensearch
-            .add_embedding(TEST_INDEX_NAME, vec![chunk])
-            .await
-            .unwrap();
+    }
+
+    #[test]
+    fn test_filter_query() {
+        let filter = LabelsFilter(vec![
+            "tag!=value1".parse::<Filter>().unwrap(),
+            "number>=30 OR NOT tag IN (a, b)".parse::<Filter>().unwrap(),
+            "name STARTS_WITH ab AND name CONTAINS \"c*\""
+                .parse::<Filter>()
+                .unwrap(),
+        ]);
+        assert_eq!(
+            filter_query(&filter),
+            json!({
+                "bool": {
+                    "must": [
+                        {
+                            "bool": {
+                                "must": [{ "exists": { "field": "metadata.tag" } }],
+                                "must_not": [{ "term": { "metadata.tag": "value1" } }]
+                            }
+                        },
+                        {
+                            "bool": {
+                                "should": [
+                                    { "range": { "metadata.number": { "gte": 30 } } },
+                                    {
+                                        "bool": {
+                                            "must_not": [
+                                                { "terms": { "metadata.tag": ["a", "b"] } }
+                                            ]
+                                        }
+                                    }
+                                ],
+                                "minimum_should_match": 1
+                            }
+                        },
+                        {
+                            "bool": {
+                                "must": [
+                                    { "prefix": { "metadata.name": "ab" } },
+                                    { "wildcard": { "metadata.name": { "value": "*c\\**" } } }
+                                ]
+                            }
+                        }
+                    ]
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_basic() {
+        basic_search(create_test_index("hello-index").await, "hello-index").await;
+    }
 
-        // bulk api returns a response when the document is accepted for indexing, not
-        // when it is actually indexed. So we need to wait until the document is
-        // actually indexed before searching.
+    #[tokio::test]
+    async fn test_store_metadata() {
+        store_metadata(create_test_index("metadata-index").await, "metadata-index").await;
+    }
 
-        const MAX_MILLIS_TO_WAIT: u64 = 2000;
-        let mut millis_spent_waiting: u64 = 0;
-        const WAIT_MILLIS_PER_ITER: u64 = 10;
+    #[tokio::test]
+    async fn test_insertion_idempotent() {
+        insertion_idempotent(
+            create_test_index("idempotent-index").await,
+            "idempotent-index",
+        )
+        .await;
+    }
 
-        loop {
-            if millis_spent_waiting >= MAX_MILLIS_TO_WAIT {
-                panic!(
-                    "timed out waiting for document to be indexed, spent '{}' millisecs waiting",
-                    millis_spent_waiting
-                );
-            }
-            let num_elements = opensearch.num_vectors(TEST_INDEX_NAME).await.unwrap();
-            if num_elements == 1 {
-                break;
-            }
-            tokio::time::sleep(tokio::time::Duration::from_millis(WAIT_MILLIS_PER_ITER)).await;
-            millis_spent_waiting += WAIT_MILLIS_PER_ITER;
-        }
+    #[tokio::test]
+    async fn test_deletion() {
+        crud_operations(create_test_index("crud-index").await, "crud-index").await;
+    }
 
-        let results = opensearch
-            .search(TEST_INDEX_NAME.into(), vec![10., 8.], 1, vec![])
-            .await
-            .unwrap();
-        assert_eq!(results.len(), 1);
+    #[tokio::test]
+    async fn test_search_filters() {
+        search_filters(create_test_index("filters-index").await, "filters-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_search_filter_expressions() {
+        search_filter_expressions(
+            create_test_index("expressions-index").await,
+            "expressions-index",
+        )
+        .await;
+        search_nested_filters(create_test_index("nested-index").await, "nested-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_list_points() {
+        list_points(create_test_index("list-index").await, "list-index").await;
+    }
+
+    #[tokio::test]
+    async fn test_keyword_search() {
+        keyword_search(create_test_index("keyword-index").await, "keyword-index").await;
     }
 }
@@ -1,31 +1,48 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Mutex};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use filter::Operator;
-use indexify_internal_api::ContentMetadata;
+use indexify_internal_api::{ContentMetadata, SparseVector, Vector};
 use qdrant_client::{
     client::{QdrantClient, QdrantClientConfig},
     qdrant::{
+        payload_index_params::IndexParams,
         point_id::PointIdOptions::Num,
         points_selector::PointsSelectorOneOf,
         r#match::MatchValue,
         vectors::VectorsOptions,
         vectors_config::Config,
         with_payload_selector::SelectorOptions,
+        with_vectors_selector,
         Condition,
+        CountPoints,
         CreateCollection,
         Distance,
+        FieldType,
         Filter,
+        NamedVectors,
+        PayloadExcludeSelector,
+        PayloadIndexParams,
         PointId,
         PointStruct,
         PointsIdsList,
         PointsSelector,
         Range,
+        RetrievedPoint,
+        ScrollPoints,
         SearchPoints,
+        SparseIndices,
+        SparseVectorConfig,
+        SparseVectorParams,
+        TextIndexParams,
+        TokenizerType,
         VectorParams,
+        VectorParamsMap,
+        Vectors,
         VectorsConfig,
         WithPayloadSelector,
+        WithVectorsSelector,
     },
 };
 use serde::{Deserialize, Serialize};
@@ -33,9 +50,30 @@ use serde::{Deserialize, Serialize};
 use super::{CreateIndexParams, VectorDb};
 use crate::{
     server_config::QdrantConfig,
-    vectordbs::{IndexDistance, SearchResult, VectorChunk},
+    vectordbs::{bm25::Bm25, max_sim, mean_vector, IndexDistance, SearchResult, VectorChunk},
 };
 
+/// Name of the embedding in collections with named vectors
+const EMBEDDING_VECTOR: &str = "embedding";
+
+/// Name of the embedding in collections of sparse indexes
+const SPARSE_VECTOR: &str = "sparse";
+
+/// Results are read in pages of at least this many points when some filters
+/// are applied to them rather than by Qdrant
+const POST_FILTER_PAGE_SIZE: u64 = 100;
+
+/// Multi-vectors are kept in the payload to be scored with MaxSim, searches
+/// that don't score them leave them out.
+const MULTIVECTORS_PAYLOAD: &str = "indexify_payload.multivectors";
+
+/// Text stored for keyword searches, which only searches read.
+const TEXT_PAYLOAD: &str = "indexify_payload.text";
+
+/// Points read at a time when the candidates of a keyword search are
+/// scrolled through
+const KEYWORD_PAGE_SIZE: u32 = 1000;
+
 fn hex_to_u64(hex: &str) -> Result<u64, std::num::ParseIntError> {
     u64::from_str_radix(hex, 16)
 }
@@ -48,6 +86,9 @@ fn u64_to_hex(number: u64) -> String {
 #[derive(Debug)]
 pub struct QdrantDb {
     qdrant_config: QdrantConfig,
+    /// Distances of the named vectors of each collection, empty for
+    /// collections that only have the embedding
+    named_vectors: Mutex<HashMap<String, HashMap<String, IndexDistance>>>,
 }
 fn extract_metadata_from_payload(
     payload: HashMap<String, qdrant_client::qdrant::Value>,
@@ -67,6 +108,7 @@ impl QdrantDb {
     pub fn new(config: QdrantConfig) -> QdrantDb {
         Self {
             qdrant_config: config,
+            named_vectors: Mutex::new(HashMap::new()),
         }
     }
 
@@ -84,6 +126,87 @@ impl QdrantDb {
             IndexDistance::Euclidean => Distance::Euclid,
         }
     }
+
+    fn convert_from_qdrant_distance(distance: i32) -> IndexDistance {
+        match Distance::try_from(distance) {
+            Ok(Distance::Dot) => IndexDistance::Dot,
+            Ok(Distance::Euclid) => IndexDistance::Euclidean,
+            _ => IndexDistance::Cosine,
+        }
+    }
+
+    /// Looks up the named vectors of a collection, the layout of a collection
+    /// doesn't change so it is only read once.
+    async fn get_named_vectors(
+        &self,
+        client: &QdrantClient,
+        index: &str,
+    ) -> Result<HashMap<String, IndexDistance>> {
+        let cached = self.named_vectors.lock().unwrap().get(index).cloned();
+        if let Some(named_vectors) = cached {
+            return Ok(named_vectors);
+        }
+        let collection_info = client
+            .collection_info(index)
+            .await
+            .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?
+            .result
+            .ok_or(anyhow!("index not found: {}", index))?;
+        let vectors_config = collection_info
+            .config
+            .and_then(|config| config.params)
+            .and_then(|params| params.vectors_config)
+            .and_then(|vectors_config| vectors_config.config);
+        let named_vectors: HashMap<String, IndexDistance> = match vectors_config {
+            Some(Config::ParamsMap(params)) => params
+                .map
+                .into_iter()
+                .map(|(name, params)| (name, Self::convert_from_qdrant_distance(params.distance)))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        self.named_vectors
+            .lock()
+            .unwrap()
+            .insert(index.to_string(), named_vectors.clone());
+        Ok(named_vectors)
+    }
+
+    fn vectors_config(index: &CreateIndexParams) -> Result<Config> {
+        let params = |dim: u64, distance: IndexDistance| VectorParams {
+            on_disk: None,
+            size: dim,
+            distance: Self::convert_to_qdrant_distance(distance).into(),
+            hnsw_config: None,
+            quantization_config: None,
+            datatype: None,
+        };
+        if index.vectors.is_empty() {
+            return Ok(Config::Params(params(
+                index.vector_dim,
+                index.distance.clone(),
+            )));
+        }
+        for reserved in [EMBEDDING_VECTOR, SPARSE_VECTOR] {
+            if index.vectors.contains_key(reserved) {
+                return Err(anyhow!(
+                    "{} is reserved for the embedding of the index",
+                    reserved
+                ));
+            }
+        }
+        // Multi-vectors are indexed by their mean vector
+        let mut map: HashMap<String, VectorParams> = index
+            .vectors
+            .iter()
+            .map(|(name, vector)| (name.clone(), params(vector.dim, vector.distance.clone())))
+            .collect();
+        map.insert(
+            EMBEDDING_VECTOR.to_string(),
+            params(index.vector_dim, index.distance.clone()),
+        );
+        Ok(Config::ParamsMap(VectorParamsMap { map }))
+    }
 }
 
 fn content_id_from_point_id(point_id: Option<PointId>) -> Result<String> {
@@ -97,20 +220,71 @@ fn content_id_from_point_id(point_id: Option<PointId>) -> Result<String> {
     }
 }
 
+fn chunk_from_point(point: RetrievedPoint) -> Result<VectorChunk> {
+    let (metadata, indexify_payload) = extract_metadata_from_payload(point.payload)?;
+    let (embedding, mut vectors, sparse_embedding) =
+        match point.vectors.and_then(|vectors| vectors.vectors_options) {
+            Some(VectorsOptions::Vector(vector)) => (vector.data, HashMap::new(), None),
+            Some(VectorsOptions::Vectors(NamedVectors { mut vectors })) => {
+                let sparse_embedding = vectors.remove(SPARSE_VECTOR).map(|vector| SparseVector {
+                    indices: vector
+                        .indices
+                        .map(|indices| indices.data)
+                        .unwrap_or_default(),
+                    values: vector.data,
+                });
+                let embedding = match vectors.remove(EMBEDDING_VECTOR) {
+                    Some(embedding) => embedding.data,
+                    // Points of sparse indexes have no dense embedding
+                    None if sparse_embedding.is_some() => Vec::new(),
+                    None => return Err(anyhow!("point has no embedding")),
+                };
+                let vectors = vectors
+                    .into_iter()
+                    .map(|(name, vector)| (name, Vector::Dense(vector.data)))
+                    .collect();
+                (embedding, vectors, sparse_embedding)
+            }
+            _ => return Err(anyhow!("Invalid vector type")),
+        };
+    // The points only hold the mean of multi-vectors
+    for (name, multivector) in indexify_payload.multivectors {
+        vectors.insert(name, Vector::Multi(multivector));
+    }
+    Ok(VectorChunk {
+        content_id: content_id_from_point_id(point.id)?,
+        embedding,
+        vectors,
+        sparse_embedding,
+        metadata,
+        root_content_metadata: indexify_payload.root_content_metadata,
+        content_metadata: indexify_payload.content_metadata,
+        text: indexify_payload.text,
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct IndexifyPayload {
     pub content_metadata: ContentMetadata,
     pub root_content_metadata: Option<ContentMetadata>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub multivectors: HashMap<String, Vec<Vec<f32>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
 }
 
 impl IndexifyPayload {
     pub fn new(
         content_metadata: ContentMetadata,
         root_content_metadata: Option<ContentMetadata>,
+        multivectors: HashMap<String, Vec<Vec<f32>>>,
+        text: Option<String>,
     ) -> Self {
         Self {
             content_metadata,
             root_content_metadata,
+            multivectors,
+            text,
         }
     }
 }
@@ -123,42 +297,92 @@ impl VectorDb for QdrantDb {
 
     #[tracing::instrument]
     async fn create_index(&self, index: CreateIndexParams) -> Result<()> {
-        let result = self
-            .create_client()?
+        // Sparse collections only have the sparse embedding
+        let (vectors_config, sparse_vectors_config) = if index.sparse {
+            let sparse_vectors_config = SparseVectorConfig {
+                map: HashMap::from([(SPARSE_VECTOR.to_string(), SparseVectorParams::default())]),
+            };
+            (None, Some(sparse_vectors_config))
+        } else {
+            let vectors_config = VectorsConfig {
+                config: Some(Self::vectors_config(&index)?),
+            };
+            (Some(vectors_config), None)
+        };
+        let client = self.create_client()?;
+        let result = client
             .create_collection(&CreateCollection {
-                collection_name: index.vectordb_index_name,
-                vectors_config: Some(VectorsConfig {
-                    config: Some(Config::Params(VectorParams {
-                        on_disk: None,
-                        size: index.vector_dim,
-                        distance: Self::convert_to_qdrant_distance(index.distance).into(),
-                        hnsw_config: None,
-                        quantization_config: None,
-                        datatype: None,
-                    })),
-                }),
+                collection_name: index.vectordb_index_name.clone(),
+                vectors_config,
+                sparse_vectors_config,
                 ..Default::default()
             })
             .await;
+        // Collections that already exist are looked up again
+        self.named_vectors
+            .lock()
+            .unwrap()
+            .remove(&index.vectordb_index_name);
         if let Err(err) = &result {
             if err.to_string().contains("already exists") {
                 return Ok(());
             }
         }
-        result
-            .map(|_| ())
-            .map_err(|e| anyhow!("unable to create index: {}", e.to_string()))
+        result.map_err(|e| anyhow!("unable to create index: {}", e.to_string()))?;
+        // Without the full-text index, e.g. on collections created before text
+        // was stored, Qdrant matches the words of keyword searches as
+        // substrings, which only finds more candidates
+        client
+            .create_field_index_blocking(
+                &index.vectordb_index_name,
+                TEXT_PAYLOAD,
+                FieldType::Text,
+                Some(&PayloadIndexParams {
+                    index_params: Some(IndexParams::TextIndexParams(TextIndexParams {
+                        tokenizer: TokenizerType::Word.into(),
+                        lowercase: Some(true),
+                        min_token_len: None,
+                        max_token_len: None,
+                    })),
+                }),
+                None,
+            )
+            .await
+            .map_err(|e| anyhow!("unable to create text index: {}", e.to_string()))?;
+        Ok(())
     }
 
     #[tracing::instrument]
     async fn add_embedding(&self, index: &str, chunks: Vec<VectorChunk>) -> Result<()> {
+        let client = self.create_client()?;
+        let named_vectors = self.get_named_vectors(&client, index).await?;
         let mut points = Vec::<PointStruct>::new();
         for chunk in chunks {
             let chunk_id = chunk.content_id.clone();
             let mut metadata_map = chunk.metadata.clone();
+            let mut vectors = HashMap::new();
+            let mut multivectors = HashMap::new();
+            for (name, vector) in chunk.vectors {
+                if !named_vectors.contains_key(&name) {
+                    return Err(anyhow!("index {} has no vector named {}", index, name));
+                }
+                match vector {
+                    Vector::Dense(values) => {
+                        vectors.insert(name, values);
+                    }
+                    // Points hold the mean vector to find candidates, the
+                    // multi-vector is kept in the payload to score them
+                    Vector::Multi(values) => {
+                        vectors.insert(name.clone(), mean_vector(&values));
+                        multivectors.insert(name, values);
+                    }
+                }
+            }
             let indexify_payload = serde_json::to_value(IndexifyPayload::new(
                 chunk.content_metadata.clone(),
                 chunk.root_content_metadata.clone(),
+                multivectors,
+                chunk.text,
             ))
             .map_err(|e| anyhow!("unable to serialize metadata: {}", e.to_string()))?;
             metadata_map.insert("indexify_payload".to_string(), indexify_payload);
@@ -170,14 +394,33 @@ impl VectorDb for QdrantDb {
                     e.to_string()
                 )
             })?;
+            let vectors: Vectors = if let Some(sparse_embedding) = chunk.sparse_embedding {
+                let sparse_embedding: Vec<(u32, f32)> = sparse_embedding
+                    .indices
+                    .into_iter()
+                    .zip(sparse_embedding.values)
+                    .collect();
+                Vectors {
+                    vectors_options: Some(VectorsOptions::Vectors(NamedVectors {
+                        vectors: HashMap::from([(
+                            SPARSE_VECTOR.to_string(),
+                            sparse_embedding.into(),
+                        )]),
+                    })),
+                }
+            } else if named_vectors.is_empty() {
+                chunk.embedding.into()
+            } else {
+                vectors.insert(EMBEDDING_VECTOR.to_string(), chunk.embedding);
+                vectors.into()
+            };
             points.push(PointStruct::new(
                 hex_to_u64(&chunk_id).unwrap(),
-                chunk.embedding.clone(),
+                vectors,
                 metadata,
             ));
         }
-        let _result = self
-            .create_client()?
+        let _result = client
             .upsert_points(&index, None, points, None)
             .await
             .map_err(|e| anyhow!("unable to add embedding: {}", e.to_string()))?;
@@ -198,23 +441,48 @@ impl VectorDb for QdrantDb {
             .get_points(&index, None, &points, Some(true), Some(true), None)
             .await
             .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?;
-        let mut documents: Vec<VectorChunk> = Vec::new();
-        for point in result.result {
-            let (metadata, indexify_payload) = extract_metadata_from_payload(point.payload)?;
-            let vector = point.vectors.unwrap().vectors_options.unwrap(); // Unwrap the Option<VectorsOptions>
-            let embedding = match vector {
-                VectorsOptions::Vector(vector) => vector,
-                _ => return Err(anyhow!("Invalid vector type")),
-            };
-            documents.push(VectorChunk {
-                content_id: content_id_from_point_id(point.id)?,
-                embedding: embedding.data,
-                metadata,
-                root_content_metadata: indexify_payload.root_content_metadata,
-                content_metadata: indexify_payload.content_metadata,
-            });
-        }
-        Ok(documents)
+        result.result.into_iter().map(chunk_from_point).collect()
+    }
+
+    async fn list_points(
+        &self,
+        index: &str,
+        after: Option<String>,
+        limit: u64,
+    ) -> Result<Vec<VectorChunk>> {
+        // Points are scrolled in the order of their ids and the offset is
+        // inclusive, so one more point is read to skip `after`
+        let offset = after
+            .as_deref()
+            .map(hex_to_u64)
+            .transpose()
+            .map_err(|e| anyhow!("invalid content id: {}", e))?;
+        let result = self
+            .create_client()?
+            .scroll(&ScrollPoints {
+                collection_name: index.to_string(),
+                offset: offset.map(PointId::from),
+                limit: Some(limit as u32 + 1),
+                with_payload: Some(WithPayloadSelector {
+                    selector_options: Some(SelectorOptions::Enable(true)),
+                }),
+                with_vectors: Some(WithVectorsSelector {
+                    selector_options: Some(with_vectors_selector::SelectorOptions::Enable(true)),
+                }),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?;
+        result
+            .result
+            .into_iter()
+            .map(chunk_from_point)
+            .filter(|chunk| match (chunk, &after) {
+                (Ok(chunk), Some(after)) => &chunk.content_id != after,
+                _ => true,
+            })
+            .take(limit as usize)
+            .collect()
     }
 
     async fn update_metadata(
@@ -267,42 +535,207 @@ impl VectorDb for QdrantDb {
         k: u64,
         filter: filter::LabelsFilter,
     ) -> Result<Vec<SearchResult>> {
-        let filter = if !filter.is_empty() {
-            Some(get_filters(filter.0)?)
-        } else {
+        let client = self.create_client()?;
+        let vector_name = if self.get_named_vectors(&client, &index).await?.is_empty() {
             None
+        } else {
+            Some(EMBEDDING_VECTOR.to_string())
         };
-        let result = self
-            .create_client()?
-            .search_points(&SearchPoints {
-                collection_name: index,
-                vector: query_embedding,
-                limit: k,
-                with_payload: Some(WithPayloadSelector {
-                    selector_options: Some(SelectorOptions::Enable(true)),
-                }),
-                filter,
+        let request = SearchPoints {
+            collection_name: index,
+            vector: query_embedding,
+            vector_name,
+            limit: k,
+            ..Default::default()
+        };
+        let documents = search_points(&client, request, &filter, false)
+            .await?
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect();
+        Ok(documents)
+    }
+
+    #[tracing::instrument]
+    async fn search_vector(
+        &self,
+        index: String,
+        vector: &str,
+        query: Vector,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let client = self.create_client()?;
+        let distance = self
+            .get_named_vectors(&client, &index)
+            .await?
+            .remove(vector)
+            .ok_or(anyhow!("index {} has no vector named {}", index, vector))?;
+        let vector_name = Some(vector.to_string());
+        let query = match query {
+            Vector::Dense(query) => {
+                let request = SearchPoints {
+                    collection_name: index,
+                    vector: query,
+                    vector_name,
+                    limit: k,
+                    ..Default::default()
+                };
+                return Ok(search_points(&client, request, &filter, false)
+                    .await?
+                    .into_iter()
+                    .map(|(result, _)| result)
+                    .collect());
+            }
+            Vector::Multi(query) => query,
+        };
+        // Candidates are found with the mean vectors and scored with MaxSim.
+        // Points whose mean vector isn't among the candidates are missed even
+        // if their MaxSim score is higher.
+        let request = SearchPoints {
+            collection_name: index,
+            vector: mean_vector(&query),
+            vector_name,
+            limit: k * self.qdrant_config.multivector_candidates_factor.max(1),
+            ..Default::default()
+        };
+        let candidates = search_points(&client, request, &filter, true).await?;
+        let mut documents: Vec<SearchResult> = candidates
+            .into_iter()
+            .map(|(mut result, mut multivectors)| {
+                let document = multivectors.remove(vector).unwrap_or_default();
+                result.confidence_score = max_sim(&query, &document, &distance);
+                result
+            })
+            .collect();
+        documents.sort_by(|a, b| b.confidence_score.total_cmp(&a.confidence_score));
+        documents.truncate(k as usize);
+        Ok(documents)
+    }
+
+    #[tracing::instrument]
+    async fn search_sparse(
+        &self,
+        index: String,
+        query: SparseVector,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let client = self.create_client()?;
+        let request = SearchPoints {
+            collection_name: index,
+            vector: query.values,
+            sparse_indices: Some(SparseIndices {
+                data: query.indices,
+            }),
+            vector_name: Some(SPARSE_VECTOR.to_string()),
+            limit: k,
+            ..Default::default()
+        };
+        let documents = search_points(&client, request, &filter, false)
+            .await?
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect();
+        Ok(documents)
+    }
+
+    // Candidates containing any of the query words are found with the
+    // full-text index of the text and ranked by BM25. The average length of
+    // the candidates stands in for the average length of all documents.
+    #[tracing::instrument]
+    async fn keyword_search(
+        &self,
+        index: String,
+        query: &str,
+        k: u64,
+        filter: filter::LabelsFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let mut bm25 = Bm25::new(query);
+        let words: Vec<Condition> = bm25
+            .words()
+            .map(|word| Condition::matches(TEXT_PAYLOAD, MatchValue::Text(word.to_string())))
+            .collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let client = self.create_client()?;
+        let (filter, post_filter) = split_filters(&filter)?;
+        let mut searched = Filter::must_not([Condition::is_empty(TEXT_PAYLOAD)]);
+        if let Some(filter) = filter {
+            searched.must.push(filter.into());
+        }
+        let num_docs = client
+            .count(&CountPoints {
+                collection_name: index.clone(),
+                filter: Some(searched.clone()),
+                exact: Some(true),
                 ..Default::default()
             })
             .await
-            .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?;
-        let mut documents: Vec<SearchResult> = Vec::new();
-        for point in result.result {
-            let (metadata, indexify_payload) = extract_metadata_from_payload(point.payload)?;
-            // TODO similarity score
-            documents.push(SearchResult {
-                confidence_score: point.score,
-                content_id: content_id_from_point_id(point.id)?,
-                metadata,
-                content_metadata: indexify_payload.content_metadata.clone(),
-                root_content_metadata: indexify_payload.root_content_metadata.clone(),
-            });
+            .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?
+            .result
+            .map(|result| result.count)
+            .unwrap_or_default();
+        bm25.set_num_docs(num_docs as usize);
+
+        let candidates = Filter {
+            should: words,
+            ..searched
+        };
+        let mut offset = None;
+        loop {
+            let page = client
+                .scroll(&ScrollPoints {
+                    collection_name: index.clone(),
+                    filter: Some(candidates.clone()),
+                    offset,
+                    limit: Some(KEYWORD_PAGE_SIZE),
+                    with_payload: Some(WithPayloadSelector {
+                        selector_options: Some(SelectorOptions::Exclude(PayloadExcludeSelector {
+                            fields: vec![MULTIVECTORS_PAYLOAD.to_string()],
+                        })),
+                    }),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?;
+            for point in page.result {
+                let content_id = content_id_from_point_id(point.id)?;
+                let (metadata, indexify_payload) = extract_metadata_from_payload(point.payload)?;
+                let Some(text) = indexify_payload.text else {
+                    continue;
+                };
+                if !post_filter.matches(&metadata) {
+                    continue;
+                }
+                let result = SearchResult {
+                    content_id,
+                    confidence_score: 0.0,
+                    metadata,
+                    root_content_metadata: indexify_payload.root_content_metadata,
+                    content_metadata: indexify_payload.content_metadata,
+                };
+                bm25.add(result, &text);
+            }
+            offset = page.next_page_offset;
+            if offset.is_none() {
+                break;
+            }
         }
-        Ok(documents)
+        Ok(bm25
+            .rank(k as usize)
+            .into_iter()
+            .map(|(result, confidence_score)| SearchResult {
+                confidence_score,
+                ..result
+            })
+            .collect())
     }
 
     #[tracing::instrument]
     async fn drop_index(&self, index: &str) -> Result<()> {
+        self.named_vectors.lock().unwrap().remove(index);
         let result = self.create_client()?.delete_collection(index).await;
         if let Err(err) = result {
             if err.to_string().contains("doesn't exist") {
@@ -329,74 +762,192 @@ impl VectorDb for QdrantDb {
     }
 }
 
-/// Convert Indexify Filters to Qdrant Filters.
-fn get_filters(filters: Vec<super::Expression>) -> Result<Filter> {
-    let mut must = Vec::new();
-    let mut must_not = Vec::new();
-
-    for f in filters {
-        match f.operator {
-            Operator::Eq => {
-                let value = get_match_value_from_json_value(f.value)?;
-                must.push(Condition::matches(f.key, value))
-            }
-            Operator::Neq => {
-                let value = get_match_value_from_json_value(f.value)?;
-                must_not.push(Condition::matches(f.key, value))
-            }
-            Operator::Lt => {
-                let value = get_f64_from_json_value(f.value)?;
-                must.push(Condition::range(
-                    f.key,
-                    Range {
-                        lt: Some(value),
-                        ..Default::default()
-                    },
-                ));
-            }
-            Operator::Gt => {
-                let value = get_f64_from_json_value(f.value)?;
-                must.push(Condition::range(
-                    f.key,
-                    Range {
-                        gt: Some(value),
-                        ..Default::default()
-                    },
-                ));
-            }
-            Operator::LtEq => {
-                let value = get_f64_from_json_value(f.value)?;
-                must.push(Condition::range(
-                    f.key,
-                    Range {
-                        lte: Some(value),
-                        ..Default::default()
-                    },
-                ));
-            }
-            Operator::GtEq => {
-                let value = get_f64_from_json_value(f.value)?;
-                must.push(Condition::range(
-                    f.key,
-                    Range {
-                        gte: Some(value),
-                        ..Default::default()
-                    },
-                ));
+/// Runs a search of the embedding or a named vector of a collection with the
+/// labels filter, returning the results along with the multi-vectors of the
+/// points if `with_multivectors` is set.
+///
+/// Filters Qdrant can't evaluate are applied to the results, which are read
+/// in pages until `limit` of them match.
+async fn search_points(
+    client: &QdrantClient,
+    request: SearchPoints,
+    filter: &filter::LabelsFilter,
+    with_multivectors: bool,
+) -> Result<Vec<(SearchResult, HashMap<String, Vec<Vec<f32>>>)>> {
+    let (filter, post_filter) = split_filters(filter)?;
+    let mut excluded = vec![TEXT_PAYLOAD.to_string()];
+    if !with_multivectors {
+        excluded.push(MULTIVECTORS_PAYLOAD.to_string());
+    }
+    let selector_options = SelectorOptions::Exclude(PayloadExcludeSelector { fields: excluded });
+    let limit = request.limit;
+    let page_size = if post_filter.is_empty() {
+        limit
+    } else {
+        limit.max(POST_FILTER_PAGE_SIZE)
+    };
+    let mut documents = Vec::new();
+    let mut offset = 0;
+    loop {
+        let result = client
+            .search_points(&SearchPoints {
+                with_payload: Some(WithPayloadSelector {
+                    selector_options: Some(selector_options.clone()),
+                }),
+                filter: filter.clone(),
+                limit: page_size,
+                offset: Some(offset),
+                ..request.clone()
+            })
+            .await
+            .map_err(|e| anyhow!("unable to read index: {}", e.to_string()))?;
+        let num_points = result.result.len() as u64;
+        for point in result.result {
+            let (metadata, indexify_payload) = extract_metadata_from_payload(point.payload)?;
+            if !post_filter.matches(&metadata) {
+                continue;
             }
+            // TODO similarity score
+            documents.push((
+                SearchResult {
+                    confidence_score: point.score,
+                    content_id: content_id_from_point_id(point.id)?,
+                    metadata,
+                    content_metadata: indexify_payload.content_metadata,
+                    root_content_metadata: indexify_payload.root_content_metadata,
+                },
+                indexify_payload.multivectors,
+            ));
+        }
+        if documents.len() as u64 >= limit || num_points < page_size {
+            break;
         }
+        offset += page_size;
     }
+    documents.truncate(limit as usize);
+    Ok(documents)
+}
+
+/// Splits the labels filter into the Qdrant filter and the filters applied to
+/// the results.
+fn split_filters(filter: &filter::LabelsFilter) -> Result<(Option<Filter>, filter::LabelsFilter)> {
+    let (filters, post_filters): (Vec<_>, Vec<_>) = filter
+        .filters()
+        .iter()
+        .cloned()
+        .partition(|filter| !uses_starts_with(filter));
+    let filter = if !filters.is_empty() {
+        Some(get_filters(&filter::LabelsFilter(filters))?)
+    } else {
+        None
+    };
+    Ok((filter, filter::LabelsFilter(post_filters)))
+}
+
+/// Qdrant has no prefix match on keywords, filters with `StartsWith` are
+/// applied to the search results instead.
+fn uses_starts_with(filter: &filter::Filter) -> bool {
+    match filter {
+        filter::Filter::Expression(expression) => expression.operator == Operator::StartsWith,
+        filter::Filter::And(filters) | filter::Filter::Or(filters) => {
+            filters.iter().any(uses_starts_with)
+        }
+        filter::Filter::Not(filter) => uses_starts_with(filter),
+    }
+}
+
+/// Convert Indexify Filters to Qdrant Filters.
+fn get_filters(filters: &filter::LabelsFilter) -> Result<Filter> {
+    Ok(Filter::must(
+        filters
+            .filters()
+            .iter()
+            .map(get_condition)
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
 
-    Ok(Filter {
-        must,
-        must_not,
-        ..Default::default()
+fn get_condition(filter: &filter::Filter) -> Result<Condition> {
+    let conditions = |filters: &[filter::Filter]| -> Result<Vec<Condition>> {
+        filters.iter().map(get_condition).collect()
+    };
+    Ok(match filter {
+        filter::Filter::Expression(expression) => get_expression_condition(expression)?,
+        filter::Filter::And(filters) => Filter::must(conditions(filters)?).into(),
+        filter::Filter::Or(filters) => Filter::should(conditions(filters)?).into(),
+        filter::Filter::Not(filter) => Filter::must_not([get_condition(filter)?]).into(),
     })
 }
 
+fn get_expression_condition(f: &filter::Expression) -> Result<Condition> {
+    let key = f.key.as_str();
+    // Payloads without the key never match, as with the in-memory filters
+    let exists = || Condition::from(Filter::must_not([Condition::is_empty(key)]));
+    let range = |range: Range| Condition::range(key, range);
+    let any_of = |values: &serde_json::Value| -> Result<Condition> {
+        let values = values
+            .as_array()
+            .ok_or(anyhow!("Expected a list of values for {}", f.operator))?;
+        let matches = values
+            .iter()
+            .map(|v| {
+                Ok(Condition::matches(
+                    key,
+                    get_match_value_from_json_value(v.clone())?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Filter::should(matches).into())
+    };
+    let condition = match f.operator {
+        Operator::Eq => Condition::matches(key, get_match_value_from_json_value(f.value.clone())?),
+        Operator::Neq => {
+            let value = get_match_value_from_json_value(f.value.clone())?;
+            Filter::must([
+                exists(),
+                Filter::must_not([Condition::matches(key, value)]).into(),
+            ])
+            .into()
+        }
+        Operator::Lt => range(Range {
+            lt: Some(get_f64_from_json_value(f.value.clone())?),
+            ..Default::default()
+        }),
+        Operator::Gt => range(Range {
+            gt: Some(get_f64_from_json_value(f.value.clone())?),
+            ..Default::default()
+        }),
+        Operator::LtEq => range(Range {
+            lte: Some(get_f64_from_json_value(f.value.clone())?),
+            ..Default::default()
+        }),
+        Operator::GtEq => range(Range {
+            gte: Some(get_f64_from_json_value(f.value.clone())?),
+            ..Default::default()
+        }),
+        Operator::In => any_of(&f.value)?,
+        Operator::NotIn => {
+            Filter::must([exists(), Filter::must_not([any_of(&f.value)?]).into()]).into()
+        }
+        Operator::Exists => exists(),
+        Operator::Contains => match &f.value {
+            serde_json::Value::String(s) => Condition::matches(key, MatchValue::Text(s.clone())),
+            // Match values are compared against every element of array payloads
+            value => Condition::matches(key, get_match_value_from_json_value(value.clone())?),
+        },
+        Operator::StartsWith => {
+            return Err(anyhow!(
+                "{} filters can't be evaluated by qdrant",
+                f.operator
+            ))
+        }
+    };
+    Ok(condition)
+}
+
 fn get_match_value_from_json_value(value: serde_json::Value) -> Result<MatchValue> {
     match value {
-        serde_json::Value::String(s) => Ok(MatchValue::Text(s)),
+        serde_json::Value::String(s) => Ok(MatchValue::Keyword(s)),
         serde_json::Value::Bool(b) => Ok(MatchValue::Boolean(b)),
         serde_json::Value::Number(n) => {
             if n.is_i64() {
@@ -418,13 +969,24 @@ fn get_f64_from_json_value(value: serde_json::Value) -> Result<f64> {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{collections::HashMap, sync::Arc};
 
     use super::{CreateIndexParams, QdrantDb};
     use crate::{
         server_config::QdrantConfig,
         vectordbs::{
-            tests::{basic_search, insertion_idempotent, search_filters, store_metadata},
+            tests::{
+                basic_search,
+                insertion_idempotent,
+                keyword_search,
+                list_points,
+                named_vectors,
+                search_filter_expressions,
+                search_filters,
+                search_nested_filters,
+                sparse_embeddings,
+                store_metadata,
+            },
             IndexDistance,
             VectorDBTS,
         },
@@ -435,6 +997,7 @@ mod tests {
     async fn test_search_basic() {
         let qdrant: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         qdrant.drop_index("hello-index").await.unwrap();
         qdrant
@@ -443,6 +1006,8 @@ mod tests {
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -453,6 +1018,7 @@ mod tests {
     async fn test_store_metadata() {
         let qdrant: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         qdrant.drop_index("metadata-index").await.unwrap();
         qdrant
@@ -461,6 +1027,8 @@ mod tests {
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -474,6 +1042,7 @@ mod tests {
         let hash_on = vec!["user_id".to_string(), "url".to_string()];
         let qdrant: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         qdrant.drop_index(index_name).await.unwrap();
         qdrant
@@ -482,6 +1051,8 @@ mod tests {
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: Some(hash_on.clone()),
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
@@ -493,6 +1064,7 @@ mod tests {
         let index_name = "metadata-index";
         let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
             addr: "http://localhost:6334".into(),
+            ..Default::default()
         }));
         vector_db.drop_index("metadata-index").await.unwrap();
         vector_db
@@ -501,9 +1073,121 @@ mod tests {
                 vector_dim: 2,
                 distance: IndexDistance::Cosine,
                 unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
             })
             .await
             .unwrap();
         search_filters(vector_db, index_name).await;
     }
+
+    #[tokio::test]
+    async fn test_search_nested_filters() {
+        let index_name = "nested-index";
+        let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
+            addr: "http://localhost:6334".into(),
+            ..Default::default()
+        }));
+        vector_db.drop_index(index_name).await.unwrap();
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index_name.into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        search_nested_filters(vector_db, index_name).await;
+    }
+
+    #[tokio::test]
+    async fn test_search_filter_expressions() {
+        let index_name = "expressions-index";
+        let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
+            addr: "http://localhost:6334".into(),
+            ..Default::default()
+        }));
+        vector_db.drop_index(index_name).await.unwrap();
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index_name.into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        search_filter_expressions(vector_db, index_name).await;
+    }
+
+    #[tokio::test]
+    async fn test_list_points() {
+        let index_name = "list-index";
+        let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
+            addr: "http://localhost:6334".into(),
+            ..Default::default()
+        }));
+        vector_db.drop_index(index_name).await.unwrap();
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index_name.into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        list_points(vector_db, index_name).await;
+    }
+
+    #[tokio::test]
+    async fn test_keyword_search() {
+        let index_name = "keyword-index";
+        let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
+            addr: "http://localhost:6334".into(),
+            ..Default::default()
+        }));
+        vector_db.drop_index(index_name).await.unwrap();
+        vector_db
+            .create_index(CreateIndexParams {
+                vectordb_index_name: index_name.into(),
+                vector_dim: 2,
+                distance: IndexDistance::Cosine,
+                unique_params: None,
+                vectors: HashMap::new(),
+                sparse: false,
+            })
+            .await
+            .unwrap();
+        keyword_search(vector_db, index_name).await;
+    }
+
+    #[tokio::test]
+    async fn test_named_vectors() {
+        let index_name = "named-vectors-index";
+        let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
+            addr: "http://localhost:6334".into(),
+            ..Default::default()
+        }));
+        vector_db.drop_index(index_name).await.unwrap();
+        named_vectors(vector_db, index_name).await;
+    }
+
+    #[tokio::test]
+    async fn test_sparse_embeddings() {
+        let index_name = "sparse-index";
+        let vector_db: VectorDBTS = Arc::new(QdrantDb::new(QdrantConfig {
+            addr: "http://localhost:6334".into(),
+            ..Default::default()
+        }));
+        vector_db.drop_index(index_name).await.unwrap();
+        sparse_embeddings(vector_db, index_name).await;
+    }
 }